@@ -58,6 +58,9 @@ pub(crate) struct Config {
     /// The configuration for the Bitcoin ZMQ client.
     pub btc_zmq: BtcZmqConfig,
 
+    /// The configuration for the fee estimation.
+    pub fee_estimation: FeeEstimationConfig,
+
     /// Nag interval for the contract manager in the duty tracker.
     pub nag_interval: Duration,
 }
@@ -119,6 +122,48 @@ pub(crate) struct OperatorWalletConfig {
     pub stake_funding_pool_size: usize,
 }
 
+/// The source of the fee estimates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FeeEstimationBackend {
+    /// Bitcoin Core's `estimatesmartfee` RPC.
+    BitcoinCore,
+
+    /// The fee rates paid by the transactions in the mempool, read periodically with
+    /// `getrawmempool true`.
+    Mempool,
+
+    /// Always use the fallback fee rate.
+    Static,
+}
+
+/// Fee estimation configuration.
+///
+/// All fee rates are in sat/vB.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FeeEstimationConfig {
+    /// The source of the fee estimates.
+    ///
+    /// The fallback fee rate is used whenever it cannot produce an estimate.
+    pub backend: FeeEstimationBackend,
+
+    /// The number of blocks within which transactions that have a protocol deadline should be
+    /// confirmed.
+    pub deadline_critical_target: u16,
+
+    /// The number of blocks within which transactions without a deadline should be confirmed.
+    pub background_target: u16,
+
+    /// The minimum fee rate to pay.
+    pub min_fee_rate: u64,
+
+    /// The maximum fee rate to pay.
+    pub max_fee_rate: u64,
+
+    /// The fee rate to pay when no estimate is available.
+    pub fallback_fee_rate: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +214,14 @@ mod tests {
             rawblock_connection_string = "tcp://127.0.0.1:28334"
             rawtx_connection_string = "tcp://127.0.0.1:28335"
             sequence_connection_string = "tcp://127.0.0.1:28336"
+
+            [fee_estimation]
+            backend = "bitcoin_core"
+            deadline_critical_target = 2
+            background_target = 144
+            min_fee_rate = 1
+            max_fee_rate = 1_000
+            fallback_fee_rate = 10
         "#;
 
         let config = toml::from_str::<Config>(config);
@@ -9,22 +9,27 @@ use std::{
 use anyhow::anyhow;
 use bdk_bitcoind_rpc::bitcoincore_rpc;
 use bitcoin::{
-    hashes::Hash,
-    secp256k1::SecretKey,
-    sighash::{Prevouts, SighashCache, TapSighashType},
-    FeeRate, OutPoint, TxOut, XOnlyPublicKey,
+    secp256k1::SecretKey, sighash::TapSighashType, FeeRate, OutPoint, TxOut, XOnlyPublicKey,
 };
-use btc_notify::client::BtcZmqClient;
+use btc_notify::client::{BtcZmqClient, StartBlock};
 use duty_tracker::{
     contract_manager::ContractManager, contract_persister::ContractPersister,
-    stake_chain_persister::StakeChainPersister, tx_driver::TxDriver,
+    duty_persister::DutyPersister, stake_chain_persister::StakeChainPersister, tx_driver::TxDriver,
+    tx_driver_persister::TxDriverPersister,
 };
 use libp2p::{
     identity::{secp256k1::PublicKey as LibP2pSecpPublicKey, PublicKey as LibP2pPublicKey},
     PeerId,
 };
 use musig2::KeyAggContext;
-use operator_wallet::{sync::Backend, OperatorWallet, OperatorWalletConfig};
+use operator_wallet::{
+    fee_estimator::{
+        BitcoinCoreFeeEstimator, FeeEstimator, FeeOracle, FeeOracleConfig, FeeUrgency,
+        MempoolFeeEstimator, StaticFeeEstimator,
+    },
+    sync::Backend,
+    OperatorWallet, OperatorWalletConfig,
+};
 use secp256k1::{Parity, SECP256K1};
 use secret_service_client::{
     rustls::{
@@ -33,7 +38,10 @@ use secret_service_client::{
     },
     SecretServiceClient,
 };
-use secret_service_proto::v1::traits::{Musig2Signer, P2PSigner, SecretService, WalletSigner};
+use secret_service_proto::{
+    v1::traits::{Musig2Signer, P2PSigner, SecretService, WalletSigner},
+    v2::traits::{SigningRequest, SpendPath, TxSigner},
+};
 use sqlx::{
     migrate::Migrator,
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
@@ -52,15 +60,18 @@ use strata_btcio::rpc::{
 };
 use strata_p2p::swarm::handle::P2PHandle;
 use strata_p2p_types::{P2POperatorPubKey, StakeChainId};
-use tokio::{spawn, task::JoinHandle, try_join};
-use tracing::{debug, info};
+use tokio::{spawn, sync::RwLock, task::JoinHandle, try_join};
+use tracing::{debug, info, warn};
 
 use crate::{
-    config::{Config, P2PConfig, SecretServiceConfig},
+    config::{Config, FeeEstimationBackend, P2PConfig, SecretServiceConfig},
     params::Params,
     rpc_server::{start_rpc, BridgeRpc},
 };
 
+/// How often the mempool based fee estimator takes a snapshot of the mempool.
+const MEMPOOL_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Bootstraps the bridge client in Operator mode by hooking up all the required auxiliary services
 /// including database, rpc server, etc.
 pub(crate) async fn bootstrap(params: Params, config: Config) -> anyhow::Result<()> {
@@ -107,9 +118,38 @@ pub(crate) async fn bootstrap(params: Params, config: Config) -> anyhow::Result<
         config.btc_client.retry_count,
         config.btc_client.retry_interval,
     )?;
+    ensure_txindex(&bitcoin_rpc_client).await?;
+
+    // proving is only needed to defend a challenged claim so the node can run without a prover, the
+    // assert chain duties fail until one is configured.
+    if !std::env::var("SP1_PROVER").is_ok_and(|prover| !prover.is_empty()) {
+        warn!("SP1_PROVER is not set, challenged claims cannot be defended with the assert chain");
+    }
+
+    // The blocks mined since the last block processed by the contract manager are replayed by the
+    // ZMQ client once the contract manager is ready for them.
+    info!("initializing the contract persister");
+    let contract_persister = ContractPersister::new(db.pool().clone()).await?;
+    let start_block = contract_persister
+        .load_cursor()
+        .await?
+        .map(|cursor| StartBlock::Height(cursor + 1));
+
+    let zmq_config = config.btc_zmq.clone().with_rpc_connection(
+        &config.btc_client.url,
+        &config.btc_client.user,
+        &config.btc_client.pass,
+    );
+    let zmq_client = BtcZmqClient::connect(&zmq_config, start_block)
+        .await
+        .expect("should be able to connect to zmq");
+
+    // Initialize the fee oracle.
+    let fee_oracle = init_fee_oracle(&config, bitcoin_rpc_client.clone());
 
     // Initialize the operator wallet.
-    let mut operator_wallet = init_operator_wallet(&config, &params, s2_client.clone()).await?;
+    let mut operator_wallet =
+        init_operator_wallet(&config, &params, s2_client.clone(), fee_oracle).await?;
 
     // Get the operator's key index.
     let my_index = params
@@ -137,9 +177,6 @@ pub(crate) async fn bootstrap(params: Params, config: Config) -> anyhow::Result<
 
     // Initialize the duty tracker.
     info!("initializing the duty tracker with the contract manager");
-    let zmq_client = BtcZmqClient::connect(&config.btc_zmq)
-        .await
-        .expect("should be able to connect to zmq");
     let contract_manager_task = init_duty_tracker(
         &params,
         &config,
@@ -148,6 +185,7 @@ pub(crate) async fn bootstrap(params: Params, config: Config) -> anyhow::Result<
         s2_client,
         message_handler,
         operator_wallet,
+        contract_persister,
         db,
     )
     .await?;
@@ -165,6 +203,26 @@ pub(crate) async fn bootstrap(params: Params, config: Config) -> anyhow::Result<
     Ok(())
 }
 
+/// Ensures that the bitcoin node maintains a transaction index.
+///
+/// The duty tracker looks up confirmed transactions by txid to avoid publishing (and paying fees
+/// for) the same transaction twice, which only works with `txindex=1`. This is checked by looking
+/// up the coinbase of the first block which, unlike the genesis coinbase, is indexed.
+async fn ensure_txindex(rpc_client: &BitcoinClient) -> anyhow::Result<()> {
+    if rpc_client.get_block_count().await? < 1 {
+        return Ok(());
+    }
+
+    let block = rpc_client.get_block_at(1).await?;
+    let coinbase_txid = block.txdata[0].compute_txid();
+    rpc_client
+        .get_raw_transaction_verbosity_zero(&coinbase_txid)
+        .await
+        .map_err(|e| anyhow!("bitcoin node must run with txindex=1: {e}"))?;
+
+    Ok(())
+}
+
 async fn init_secret_service_client(config: &SecretServiceConfig) -> SecretServiceClient {
     let key = fs::read(&config.key).expect("readable key");
     let key = if config.key.extension().is_some_and(|x| x == "der") {
@@ -338,6 +396,7 @@ async fn init_duty_tracker(
     s2_client: SecretServiceClient,
     message_handler: MessageHandler,
     operator_wallet: OperatorWallet,
+    contract_persister: ContractPersister,
     db: SqliteDb,
 ) -> anyhow::Result<JoinHandle<()>> {
     let network = params.network;
@@ -366,13 +425,26 @@ async fn init_duty_tracker(
         .expect("should be able to find my index");
     let operator_table =
         OperatorTable::new(operator_table_entries, my_idx as u32).expect("my index exists");
-    let tx_driver = TxDriver::new(zmq_client.clone(), rpc_client.clone()).await;
+    let fee_oracle = operator_wallet.fee_oracle().clone();
+    let operator_wallet = Arc::new(RwLock::new(operator_wallet));
+    info!("initializing the tx driver persister");
+    let tx_driver_persister = TxDriverPersister::new(db.pool().clone()).await?;
+    let tx_driver = TxDriver::new(
+        zmq_client.clone(),
+        rpc_client.clone(),
+        network,
+        operator_wallet.clone(),
+        s2_client.clone(),
+        fee_oracle,
+        tx_driver_persister,
+    )
+    .await;
     info!("initializing the p2p handle");
     let p2p_handle = message_handler.handle.clone();
-    info!("initializing the contract persister");
-    let contract_persister = ContractPersister::new(db.pool().clone()).await?;
     info!("initializing the stake chain persister");
     let stake_chain_persister = StakeChainPersister::new(db.clone()).await?;
+    info!("initializing the duty persister");
+    let duty_persister = DutyPersister::new(db.pool().clone()).await?;
 
     Ok(ContractManager::new(
         network,
@@ -388,6 +460,7 @@ async fn init_duty_tracker(
         p2p_handle,
         contract_persister,
         stake_chain_persister,
+        duty_persister,
         s2_client,
         operator_wallet,
         db,
@@ -409,11 +482,60 @@ async fn start_rpc_server(
     Ok(handle)
 }
 
+/// Initializes the fee oracle with the configured fee estimation backend, falling back to the
+/// static fee rate.
+fn init_fee_oracle(config: &Config, bitcoin_rpc_client: BitcoinClient) -> FeeOracle {
+    let fee_config = &config.fee_estimation;
+    let fee_rate = |sat_per_vb: u64| {
+        FeeRate::from_sat_per_vb(sat_per_vb).expect("should be able to create a fee rate")
+    };
+
+    let fallback: Arc<dyn FeeEstimator> = Arc::new(StaticFeeEstimator::new(fee_rate(
+        fee_config.fallback_fee_rate,
+    )));
+    let estimators = match fee_config.backend {
+        FeeEstimationBackend::BitcoinCore => vec![
+            Arc::new(BitcoinCoreFeeEstimator::new(bitcoin_rpc_client)) as Arc<dyn FeeEstimator>,
+            fallback,
+        ],
+        FeeEstimationBackend::Mempool => {
+            let auth = bitcoincore_rpc::Auth::UserPass(
+                config.btc_client.user.to_string(),
+                config.btc_client.pass.to_string(),
+            );
+            let mempool_rpc_client = Arc::new(
+                bitcoincore_rpc::Client::new(config.btc_client.url.as_str(), auth)
+                    .expect("should be able to create bitcoin client"),
+            );
+            vec![
+                Arc::new(MempoolFeeEstimator::new(
+                    mempool_rpc_client,
+                    MEMPOOL_SNAPSHOT_INTERVAL,
+                )) as Arc<dyn FeeEstimator>,
+                fallback,
+            ]
+        }
+        FeeEstimationBackend::Static => vec![fallback],
+    };
+    info!(?estimators, "fee estimators");
+
+    FeeOracle::new(
+        estimators,
+        FeeOracleConfig {
+            deadline_critical_target: fee_config.deadline_critical_target,
+            background_target: fee_config.background_target,
+            min_fee_rate: fee_rate(fee_config.min_fee_rate),
+            max_fee_rate: fee_rate(fee_config.max_fee_rate),
+        },
+    )
+}
+
 /// Initializes the operator wallet
 async fn init_operator_wallet(
     config: &Config,
     params: &Params,
     s2_client: SecretServiceClient,
+    fee_oracle: FeeOracle,
 ) -> anyhow::Result<OperatorWallet> {
     // BitcoinD RPC client for the Operator Wallet.
     let auth = bitcoincore_rpc::Auth::UserPass(
@@ -446,6 +568,7 @@ async fn init_operator_wallet(
         stakechain_key,
         operator_wallet_config,
         sync_backend,
+        fee_oracle,
     );
     info!(?operator_wallet, "created operator wallet");
 
@@ -478,15 +601,8 @@ async fn handle_stakechain_genesis(
         // This means that we don't have a pre-stake tx in the database.
         // We need to create a pre-stake tx, sign it, broadcast it and save it to the database.
         info!("no pre-stake tx in the database, creating one");
-        let fee_rate = bitcoin_rpc_client
-            .estimate_smart_fee(1)
-            .await
-            .expect("should be able to get the fee rate estimate");
-
-        let fee_rate =
-            FeeRate::from_sat_per_vb(fee_rate).expect("should be able to create a fee rate");
-
-        info!(%fee_rate, "fetched fee rate from bitcoin client");
+        let fee_rate = operator_wallet.fee_rate(FeeUrgency::Background).await;
+        info!(%fee_rate, "fetched fee rate from the fee oracle");
 
         // We need to sync the wallet.
         info!("syncing the operator wallet");
@@ -519,27 +635,30 @@ async fn handle_stakechain_genesis(
                     .txout
             })
             .collect::<Vec<TxOut>>();
-        let prevouts = Prevouts::All(&txins_as_outs);
-        let mut sighasher = SighashCache::new(pre_stake_tx);
         // Sign all the inputs.
-        for input in 0..txins_as_outs.len() {
-            let sighash = sighasher
-                .taproot_key_spend_signature_hash(input, &prevouts, TapSighashType::Default)
-                .expect("must be able to compute the sighash");
+        let mut signatures = Vec::with_capacity(txins_as_outs.len());
+        for input_index in 0..txins_as_outs.len() {
+            let request = SigningRequest {
+                tx: pre_stake_tx.clone(),
+                prevouts: txins_as_outs.clone(),
+                input_index,
+                spend_path: SpendPath::KeySpend { merkle_root: None },
+                sighash_type: TapSighashType::Default,
+            };
 
             // Sign the pre-stake tx.
             let signature = s2_client
                 .general_wallet_signer()
-                .sign(&sighash.to_byte_array(), None)
+                .sign_tx(request)
                 .await
-                .expect("should be able to sign the pre-stake tx");
-
-            sighasher
-                .witness_mut(input)
-                .expect("must be able to get the witness")
-                .push(signature.serialize());
+                .expect("should be able to sign the pre-stake tx")
+                .expect("the pre-stake tx must comply with the signing policy");
+            signatures.push(signature);
+        }
+        let mut signed_pre_stake_tx = pre_stake_tx;
+        for (txin, signature) in signed_pre_stake_tx.input.iter_mut().zip(signatures) {
+            txin.witness.push(signature.to_vec());
         }
-        let signed_pre_stake_tx = sighasher.into_transaction();
         info!(%pre_stake_txid, "signed the pre-stake tx");
 
         // Broadcast the pre-stake tx.
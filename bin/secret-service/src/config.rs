@@ -2,7 +2,7 @@
 
 use std::{net::SocketAddr, path::PathBuf};
 
-use bitcoin::Network;
+use bitcoin::{Network, ScriptBuf};
 use serde::Deserialize;
 
 /// Configuration for the Secret Service.
@@ -17,8 +17,47 @@ pub struct Config {
     /// A file path to a 32-byte seed file.
     pub seed: Option<PathBuf>,
 
+    /// A file path to the ledger of the messages signed by the WOTS keys.
+    ///
+    /// Defaults to a `wots_ledger` file next to the seed file. It must persist as long as the
+    /// seed does, otherwise WOTS keys could end up signing two different messages.
+    pub wots_ledger: Option<PathBuf>,
+
     /// Which bitcoin network to use
     pub network: Option<Network>,
+
+    /// Policy the transactions signed through the v2 signing API must comply with.
+    #[serde(default)]
+    pub policy: PolicyConfig,
+}
+
+/// Configuration for the signing policy.
+///
+/// Transactions of the peg-out graphs are registered at runtime and thus aren't configured here.
+/// Registering them only lets the N-of-N key sign them, the wallet signers still check the
+/// allowed scripts.
+#[derive(Debug, Default, Deserialize)]
+pub struct PolicyConfig {
+    /// Hex-encoded scripts the signed transactions are allowed to pay to.
+    ///
+    /// If unset, the signed transactions may pay to any script.
+    pub allowed_scripts: Option<Vec<ScriptBuf>>,
+
+    /// Maximum fee a signed transaction may pay, in sats.
+    pub max_fee: Option<u64>,
+
+    /// Maximum fee rate a signed transaction may pay, in sat/vB.
+    pub max_fee_rate: Option<u64>,
+
+    /// Maximum number of peg-out graph transactions that can be registered at once.
+    ///
+    /// If unset, defaults to
+    /// [`DEFAULT_MAX_REGISTERED_TXIDS`](secret_service_server::policy::DEFAULT_MAX_REGISTERED_TXIDS).
+    pub max_registered_txids: Option<usize>,
+
+    /// Whether to reject the v1 requests signing raw digests, which bypass the policy.
+    #[serde(default)]
+    pub reject_raw_digests: bool,
 }
 
 /// Configuration for the transport layer.
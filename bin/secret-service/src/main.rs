@@ -9,13 +9,18 @@ pub mod seeded_impl;
 mod tests;
 mod tls;
 
-use std::{env::args, path::PathBuf, str::FromStr, sync::LazyLock};
+use std::{
+    env::args,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
 
-use bitcoin::Network;
+use bitcoin::{Amount, FeeRate, Network};
 use colored::Colorize;
 use config::Config;
-use secret_service_server::{run_server, Config as ServerConfig};
-use seeded_impl::Service;
+use secret_service_server::{policy::SigningPolicy, run_server, Config as ServerConfig};
+use seeded_impl::{wots_ledger::WotsLedger, Service};
 use tls::load_tls;
 use tracing::{info, warn, Level};
 
@@ -38,17 +43,45 @@ async fn main() {
     let conf: Config = toml::from_str(&text).expect("valid toml");
     let tls = load_tls(conf.tls).await;
 
+    let mut signing_policy = SigningPolicy::default();
+    if let Some(allowed_scripts) = conf.policy.allowed_scripts {
+        signing_policy = signing_policy.with_allowed_scripts(allowed_scripts);
+    }
+    if let Some(max_fee) = conf.policy.max_fee {
+        signing_policy = signing_policy.with_max_fee(Amount::from_sat(max_fee));
+    }
+    if let Some(max_fee_rate) = conf.policy.max_fee_rate {
+        signing_policy = signing_policy
+            .with_max_fee_rate(FeeRate::from_sat_per_vb(max_fee_rate).expect("valid max fee rate"));
+    }
+    if let Some(max_registered_txids) = conf.policy.max_registered_txids {
+        signing_policy = signing_policy.with_max_registered_txids(max_registered_txids);
+    }
+    if conf.policy.reject_raw_digests {
+        signing_policy = signing_policy.rejecting_raw_digests();
+    }
+
     let config = ServerConfig {
         addr: conf.transport.addr,
         tls_config: tls,
         connection_limit: conf.transport.conn_limit,
+        signing_policy: Arc::new(signing_policy),
     };
 
+    let seed_path = conf
+        .seed
+        .unwrap_or(PathBuf::from_str("seed").expect("valid path"));
+    let wots_ledger_path = conf
+        .wots_ledger
+        .unwrap_or_else(|| seed_path.with_file_name("wots_ledger"));
+    let wots_ledger = WotsLedger::open(&wots_ledger_path)
+        .await
+        .expect("good wots ledger");
+
     let service = Service::load_from_seed(
-        &conf
-            .seed
-            .unwrap_or(PathBuf::from_str("seed").expect("valid path")),
+        &seed_path,
         conf.network.unwrap_or(Network::Signet),
+        wots_ledger,
     )
     .await
     .expect("good service");
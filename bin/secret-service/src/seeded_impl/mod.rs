@@ -1,6 +1,6 @@
 //! Basic, seeded implementation of a secret service
 
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 use bitcoin::{bip32::Xpriv, Network};
 use colored::Colorize;
@@ -14,6 +14,7 @@ use tokio::{fs, io};
 use tracing::info;
 use wallet::{GeneralWalletSigner, StakechainWalletSigner};
 use wots::SeededWotsSigner;
+use wots_ledger::WotsLedger;
 
 pub mod musig2;
 pub mod p2p;
@@ -21,17 +22,25 @@ pub mod paths;
 pub mod stakechain;
 pub mod wallet;
 pub mod wots;
+pub mod wots_ledger;
 
 /// Secret data for the Secret Service.
 #[derive(Debug)]
 pub struct Service {
     /// Operator's keys.
     keys: OperatorKeys,
+
+    /// Ledger of the messages signed by the WOTS keys.
+    wots_ledger: Arc<WotsLedger>,
 }
 
 impl Service {
     /// Loads the operator's keys from a seed file.
-    pub async fn load_from_seed(seed_path: &Path, network: Network) -> io::Result<Self> {
+    pub async fn load_from_seed(
+        seed_path: &Path,
+        network: Network,
+        wots_ledger: WotsLedger,
+    ) -> io::Result<Self> {
         let mut seed = [0; 32];
 
         if let Some(parent) = seed_path.parent() {
@@ -58,18 +67,21 @@ impl Service {
             Err(e) => return Err(e),
         };
 
-        Ok(Self::new_with_seed(seed, network))
+        Ok(Self::new_with_seed(seed, network, wots_ledger))
     }
 
     /// Deterministically creates a new service using a given seed
-    pub fn new_with_seed(seed: [u8; 32], network: Network) -> Self {
+    pub fn new_with_seed(seed: [u8; 32], network: Network, wots_ledger: WotsLedger) -> Self {
         let keys = OperatorKeys::new(&Xpriv::new_master(network, &seed).expect("valid xpriv"))
             .expect("valid keychain");
         info!(
             "Master fingerprint: {}",
             keys.master_xpub().fingerprint().to_string().bold()
         );
-        Self { keys }
+        Self {
+            keys,
+            wots_ledger: Arc::new(wots_ledger),
+        }
     }
 }
 
@@ -103,7 +115,7 @@ impl SecretService<Server, ServerFirstRound, ServerSecondRound> for Service {
     }
 
     fn wots_signer(&self) -> Self::WotsSigner {
-        SeededWotsSigner::new(self.keys.base_xpriv())
+        SeededWotsSigner::new(self.keys.base_xpriv(), self.wots_ledger.clone())
     }
 
     fn stake_chain_preimages(&self) -> Self::StakeChainPreimages {
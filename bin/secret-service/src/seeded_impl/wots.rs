@@ -1,5 +1,7 @@
 //! In-memory persistence for the Winternitz One-Time Signature (WOTS) keys.
 
+use std::sync::Arc;
+
 use bitcoin::{
     bip32::Xpriv,
     hashes::{hash160, Hash},
@@ -9,10 +11,13 @@ use bitvm::signatures::wots_api::{wots256, wots_hash};
 use hkdf::Hkdf;
 use make_buf::make_buf;
 use musig2::secp256k1::SECP256K1;
-use secret_service_proto::v1::traits::{Server, WotsSigner};
+use secret_service_proto::v1::traits::{Server, WotsSignError, WotsSigner};
 use sha2::Sha256;
 
-use super::paths::{WOTS_IKM_128_PATH, WOTS_IKM_256_PATH};
+use super::{
+    paths::{WOTS_IKM_128_PATH, WOTS_IKM_256_PATH},
+    wots_ledger::{WotsKeyId, WotsLedger},
+};
 
 // Changing this number may cause certain code to break as the signing code is not designed to
 // handle cases where the digit width doesn't divide byte width without residue.
@@ -26,11 +31,14 @@ pub struct SeededWotsSigner {
     ikm_128: [u8; 32],
     /// Initial key material for 256-bit WOTS keys.
     ikm_256: [u8; 32],
+    /// Ledger of the messages signed by the WOTS keys.
+    ledger: Arc<WotsLedger>,
 }
 
 impl SeededWotsSigner {
-    /// Creates a new WOTS signer from an operator's base private key (m/20000').
-    pub fn new(base: &Xpriv) -> Self {
+    /// Creates a new WOTS signer from an operator's base private key (m/20000') that records the
+    /// messages it signs in the given ledger.
+    pub fn new(base: &Xpriv, ledger: Arc<WotsLedger>) -> Self {
         Self {
             ikm_128: base
                 .derive_priv(SECP256K1, &WOTS_IKM_128_PATH)
@@ -42,6 +50,7 @@ impl SeededWotsSigner {
                 .unwrap()
                 .private_key
                 .secret_bytes(),
+            ledger,
         }
     }
 }
@@ -112,9 +121,16 @@ impl WotsSigner<Server> for SeededWotsSigner {
         vout: u32,
         index: u32,
         msg: &[u8; 16],
-    ) -> [u8; 20 * key_width(128, WINTERNITZ_DIGIT_WIDTH)] {
+    ) -> Result<[u8; 20 * key_width(128, WINTERNITZ_DIGIT_WIDTH)], WotsSignError> {
+        let key = WotsKeyId {
+            msg_len: 16,
+            prestake_txid: txid,
+            prestake_vout: vout,
+            index,
+        };
+        self.ledger.record(key, msg).await?;
         let sk = self.get_128_secret_key(txid, vout, index).await;
-        wots_sign_128_bitvm(msg, &sk)
+        Ok(wots_sign_128_bitvm(msg, &sk))
     }
 
     #[expect(refining_impl_trait)]
@@ -124,9 +140,16 @@ impl WotsSigner<Server> for SeededWotsSigner {
         vout: u32,
         index: u32,
         msg: &[u8; 32],
-    ) -> [u8; 20 * key_width(256, WINTERNITZ_DIGIT_WIDTH)] {
+    ) -> Result<[u8; 20 * key_width(256, WINTERNITZ_DIGIT_WIDTH)], WotsSignError> {
+        let key = WotsKeyId {
+            msg_len: 32,
+            prestake_txid: txid,
+            prestake_vout: vout,
+            index,
+        };
+        self.ledger.record(key, msg).await?;
         let sk = self.get_256_secret_key(txid, vout, index).await;
-        wots_sign_256_bitvm(msg, &sk)
+        Ok(wots_sign_256_bitvm(msg, &sk))
     }
 }
 
@@ -0,0 +1,210 @@
+//! Durable record of the messages signed by the Winternitz One-Time Signature (WOTS) keys.
+
+use std::{collections::BTreeMap, path::Path};
+
+use bitcoin::{
+    hashes::{sha256, Hash},
+    Txid,
+};
+use make_buf::make_buf;
+use secret_service_proto::v1::traits::WotsSignError;
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{self, AsyncReadExt, AsyncWriteExt},
+    sync::Mutex,
+};
+use tracing::{error, info, warn};
+
+/// Length of a ledger record: the key's message length, prestake txid, prestake vout and WOTS
+/// index, followed by the hash of the message it signed.
+const RECORD_LEN: usize = 1 + 32 + 4 + 4 + 32;
+
+/// Identifies a WOTS key derived by the secret service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct WotsKeyId {
+    /// Length, in bytes, of the messages signed by the key.
+    pub(super) msg_len: u8,
+
+    /// Prestake transaction ID the key is derived from.
+    pub(super) prestake_txid: Txid,
+
+    /// Prestake vout the key is derived from.
+    pub(super) prestake_vout: u32,
+
+    /// WOTS index the key is derived from.
+    pub(super) index: u32,
+}
+
+/// Append-only ledger of the first message signed by every WOTS key.
+///
+/// Signing two different messages with a WOTS key lets anyone forge signatures with it, so the
+/// use of a key is synced to disk before the signature is produced. Signing the same message
+/// again is harmless and allowed.
+#[derive(Debug)]
+pub struct WotsLedger {
+    /// The ledger's file and its contents.
+    inner: Mutex<LedgerInner>,
+}
+
+/// State of the [`WotsLedger`] guarded by its lock.
+#[derive(Debug)]
+struct LedgerInner {
+    /// The ledger's file, opened in append mode.
+    file: File,
+
+    /// Hash of the message signed by every used key.
+    signed: BTreeMap<WotsKeyId, sha256::Hash>,
+
+    /// Whether appending to the file failed, in which case it may end with a partial record and
+    /// nothing can be recorded until the ledger is reopened.
+    broken: bool,
+}
+
+impl WotsLedger {
+    /// Opens the ledger at the given path, creating it if it doesn't exist.
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+
+        let complete_len = contents.len() - contents.len() % RECORD_LEN;
+        if complete_len != contents.len() {
+            // the last record was not fully written, so the signature it guarded was never
+            // produced.
+            warn!("Discarding a partial record at the end of the WOTS ledger");
+            file.set_len(complete_len as u64).await?;
+            file.sync_data().await?;
+        }
+
+        let signed = contents[..complete_len]
+            .chunks_exact(RECORD_LEN)
+            .map(decode_record)
+            .collect::<BTreeMap<_, _>>();
+        info!(
+            "Loaded {} used WOTS keys from {}",
+            signed.len(),
+            path.display()
+        );
+
+        Ok(Self {
+            inner: Mutex::new(LedgerInner {
+                file,
+                signed,
+                broken: false,
+            }),
+        })
+    }
+
+    /// Durably records that the `key` signs `msg`.
+    ///
+    /// Fails if the key already signed a different message or if the record couldn't be synced
+    /// to disk, in which case the signature must not be produced.
+    pub(super) async fn record(&self, key: WotsKeyId, msg: &[u8]) -> Result<(), WotsSignError> {
+        let msg_hash = sha256::Hash::hash(msg);
+        let mut inner = self.inner.lock().await;
+
+        match inner.signed.get(&key) {
+            Some(signed) if *signed == msg_hash => return Ok(()),
+            Some(_) => {
+                warn!("refusing to sign a second message with WOTS key {key:?}");
+                return Err(WotsSignError::KeyAlreadyUsed);
+            }
+            None => {}
+        }
+
+        if inner.broken {
+            return Err(WotsSignError::RecordFailed);
+        }
+
+        let record = make_buf! {
+            (&[key.msg_len], 1),
+            (key.prestake_txid.as_raw_hash().as_byte_array(), 32),
+            (&key.prestake_vout.to_le_bytes(), 4),
+            (&key.index.to_le_bytes(), 4),
+            (msg_hash.as_byte_array(), 32),
+        };
+        if let Err(e) = append(&mut inner.file, &record).await {
+            error!("failed to record the use of WOTS key {key:?}: {e:?}");
+            inner.broken = true;
+            return Err(WotsSignError::RecordFailed);
+        }
+
+        inner.signed.insert(key, msg_hash);
+        Ok(())
+    }
+}
+
+/// Appends a record to the ledger's file and syncs it to disk.
+async fn append(file: &mut File, record: &[u8]) -> io::Result<()> {
+    file.write_all(record).await?;
+    file.flush().await?;
+    file.sync_data().await
+}
+
+/// Decodes a ledger record into the key and the hash of the message it signed.
+fn decode_record(record: &[u8]) -> (WotsKeyId, sha256::Hash) {
+    let key = WotsKeyId {
+        msg_len: record[0],
+        prestake_txid: Txid::from_slice(&record[1..33]).expect("correct length"),
+        prestake_vout: u32::from_le_bytes(record[33..37].try_into().expect("correct length")),
+        index: u32::from_le_bytes(record[37..41].try_into().expect("correct length")),
+    };
+    let msg_hash = sha256::Hash::from_slice(&record[41..]).expect("correct length");
+    (key, msg_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_second_message_across_restarts() {
+        let path = std::env::temp_dir().join(format!("wots-ledger-{}", thread_rng().gen::<u64>()));
+        let key = WotsKeyId {
+            msg_len: 32,
+            prestake_txid: Txid::from_byte_array(thread_rng().gen()),
+            prestake_vout: 0,
+            index: 0,
+        };
+        let other_key = WotsKeyId { index: 1, ..key };
+
+        let ledger = WotsLedger::open(&path).await.expect("open ledger");
+        assert_eq!(ledger.record(key, &[1; 32]).await, Ok(()));
+        assert_eq!(ledger.record(key, &[1; 32]).await, Ok(()));
+        assert_eq!(
+            ledger.record(key, &[2; 32]).await,
+            Err(WotsSignError::KeyAlreadyUsed)
+        );
+        assert_eq!(ledger.record(other_key, &[2; 32]).await, Ok(()));
+        drop(ledger);
+
+        // simulate a crash in the middle of appending a record.
+        let mut contents = std::fs::read(&path).expect("read ledger");
+        contents.extend_from_slice(&[0xff; RECORD_LEN / 2]);
+        std::fs::write(&path, contents).expect("write ledger");
+
+        let ledger = WotsLedger::open(&path).await.expect("reopen ledger");
+        assert_eq!(ledger.record(key, &[1; 32]).await, Ok(()));
+        assert_eq!(
+            ledger.record(key, &[2; 32]).await,
+            Err(WotsSignError::KeyAlreadyUsed)
+        );
+        assert_eq!(
+            ledger.record(other_key, &[1; 32]).await,
+            Err(WotsSignError::KeyAlreadyUsed)
+        );
+
+        std::fs::remove_file(&path).expect("remove ledger");
+    }
+}
@@ -7,9 +7,11 @@ use std::{
 };
 
 use bitcoin::{
+    absolute,
     hashes::Hash,
-    key::{Parity, Secp256k1, TapTweak},
-    Network, Txid, XOnlyPublicKey,
+    key::{Parity, Secp256k1, TapTweak, TweakedPublicKey},
+    transaction, Amount, Network, OutPoint, ScriptBuf, Sequence, TapSighashType, Transaction, TxIn,
+    TxOut, Txid, Witness, XOnlyPublicKey,
 };
 use musig2::{
     secp256k1::{Message, SecretKey, SECP256K1},
@@ -17,8 +19,15 @@ use musig2::{
 };
 use rand::{thread_rng, Rng};
 use secret_service_client::SecretServiceClient;
-use secret_service_proto::v1::traits::*;
+use secret_service_proto::{
+    v1::{traits::*, wire::ServerMessage},
+    v2::traits::{
+        Musig2FinalizeTxError, Musig2TxSignerFirstRound, PolicyRegistry, PolicyViolation,
+        SigningRequest, SpendPath, TxSigner, TxidSigner,
+    },
+};
 use secret_service_server::{
+    policy::SigningPolicy,
     run_server,
     rustls::{
         self,
@@ -28,11 +37,11 @@ use secret_service_server::{
 };
 use strata_bridge_primitives::{scripts::taproot::TaprootWitness, secp::EvenSecretKey};
 
-use crate::seeded_impl::Service;
+use crate::seeded_impl::{wots_ledger::WotsLedger, Service};
 
-#[tokio::test]
-async fn e2e() {
-    let server_addr: SocketAddr = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 20_000).into();
+/// Runs a secret service on the given port and connects a client to it.
+async fn start(port: u16, signing_policy: SigningPolicy) -> SecretServiceClient {
+    let server_addr: SocketAddr = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port).into();
     let server_host = "localhost".to_string();
 
     let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
@@ -46,8 +55,14 @@ async fn e2e() {
         addr: server_addr,
         tls_config: server_tls_config,
         connection_limit: None,
+        signing_policy: Arc::new(signing_policy),
     };
-    let service = Service::new_with_seed([0u8; 32], Network::Signet);
+    let wots_ledger = WotsLedger::open(
+        &std::env::temp_dir().join(format!("wots-ledger-{}", thread_rng().gen::<u64>())),
+    )
+    .await
+    .expect("good wots ledger");
+    let service = Service::new_with_seed([0u8; 32], Network::Signet, wots_ledger);
 
     tokio::spawn(async move {
         run_server(config, service.into()).await.unwrap();
@@ -65,9 +80,14 @@ async fn e2e() {
         timeout: Duration::from_secs(1),
     };
 
-    let client = SecretServiceClient::new(client_config)
+    SecretServiceClient::new(client_config)
         .await
-        .expect("good conn");
+        .expect("good conn")
+}
+
+#[tokio::test]
+async fn e2e() {
+    let client = start(20_000, SigningPolicy::default()).await;
 
     // wallet signers
     let general_wallet_signer = client.general_wallet_signer();
@@ -157,6 +177,24 @@ async fn e2e() {
     wots.get_256_public_key(txid, 0, 0)
         .await
         .expect("good response");
+    let sig = wots
+        .get_256_signature(txid, 0, 0, &[1; 32])
+        .await
+        .expect("good response")
+        .expect("unused key");
+    assert_eq!(
+        wots.get_256_signature(txid, 0, 0, &[1; 32])
+            .await
+            .expect("good response"),
+        Ok(sig),
+        "re-signing the same message must be allowed"
+    );
+    assert_eq!(
+        wots.get_256_signature(txid, 0, 0, &[2; 32])
+            .await
+            .expect("good response"),
+        Err(WotsSignError::KeyAlreadyUsed)
+    );
 
     // Musig2
     let ms2_signer = client.musig2_signer();
@@ -321,6 +359,327 @@ async fn e2e() {
         .is_ok());
 }
 
+#[tokio::test]
+async fn policy_checked_signing() {
+    let destination = ScriptBuf::new_p2tr(
+        SECP256K1,
+        SecretKey::new(&mut thread_rng())
+            .x_only_public_key(SECP256K1)
+            .0,
+        None,
+    );
+    let policy = SigningPolicy::default()
+        .with_allowed_scripts([destination.clone()])
+        .with_max_fee(Amount::from_sat(10_000));
+    let client = start(20_001, policy).await;
+
+    let signer = client.general_wallet_signer();
+    let pubkey = signer.pubkey().await.expect("good response");
+    let (tweaked_pubkey, _) = pubkey.tap_tweak(SECP256K1, None);
+
+    let request = |output: TxOut| SigningRequest {
+        tx: Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array(thread_rng().gen()), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![output],
+        },
+        prevouts: vec![TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2tr(SECP256K1, pubkey, None),
+        }],
+        input_index: 0,
+        spend_path: SpendPath::KeySpend { merkle_root: None },
+        sighash_type: TapSighashType::Default,
+    };
+
+    // pays to an allowed script within the fee cap
+    let allowed = request(TxOut {
+        value: Amount::from_sat(95_000),
+        script_pubkey: destination.clone(),
+    });
+    let sighash = allowed.sighash().expect("valid request");
+    let sig = signer
+        .sign_tx(allowed)
+        .await
+        .expect("good response")
+        .expect("request complies with the policy");
+    assert!(SECP256K1
+        .verify_schnorr(
+            &sig.signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &tweaked_pubkey.to_inner()
+        )
+        .is_ok());
+
+    // pays to a script that isn't allowed
+    let disallowed = request(TxOut {
+        value: Amount::from_sat(95_000),
+        script_pubkey: ScriptBuf::new_p2tr(SECP256K1, pubkey, None),
+    });
+    assert_eq!(
+        signer
+            .sign_tx(disallowed.clone())
+            .await
+            .expect("good response"),
+        Err(PolicyViolation::DisallowedOutput { vout: 0 })
+    );
+
+    // registering it as a transaction of a peg-out graph doesn't get it past the allowed scripts.
+    client
+        .policy_registry()
+        .allow_txids(
+            Txid::from_byte_array(thread_rng().gen()),
+            vec![disallowed.tx.compute_txid()],
+        )
+        .await
+        .expect("good response")
+        .expect("within the cap");
+    assert_eq!(
+        signer.sign_tx(disallowed).await.expect("good response"),
+        Err(PolicyViolation::DisallowedOutput { vout: 0 })
+    );
+
+    // pays too much fees
+    let expensive = request(TxOut {
+        value: Amount::from_sat(50_000),
+        script_pubkey: destination.clone(),
+    });
+    assert_eq!(
+        signer.sign_tx(expensive).await.expect("good response"),
+        Err(PolicyViolation::FeeTooHigh {
+            fee: 50_000,
+            max_fee: 10_000
+        })
+    );
+
+    // with `ANYONECANPAY`, only the amount of the signed input counts toward the fee, whatever
+    // the client claims about the other prevouts.
+    let anyone_can_pay = |other_prevout_value: Amount| {
+        let mut request = request(TxOut {
+            value: Amount::from_sat(95_000),
+            script_pubkey: destination.clone(),
+        });
+        request.tx.input.push(TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array(thread_rng().gen()), 0),
+            ..request.tx.input[0].clone()
+        });
+        request.prevouts.push(TxOut {
+            value: other_prevout_value,
+            script_pubkey: destination.clone(),
+        });
+        request.sighash_type = TapSighashType::AllPlusAnyoneCanPay;
+        request
+    };
+    for other_prevout_value in [Amount::ZERO, Amount::from_sat(1_000_000)] {
+        let request = anyone_can_pay(other_prevout_value);
+        assert_eq!(request.fee(), Some(Amount::from_sat(5_000)));
+        assert!(signer
+            .sign_tx(request)
+            .await
+            .expect("good response")
+            .is_ok());
+    }
+    let mut expensive = anyone_can_pay(Amount::from_sat(1_000_000));
+    expensive.tx.output[0].value = Amount::from_sat(50_000);
+    assert_eq!(
+        signer.sign_tx(expensive).await.expect("good response"),
+        Err(PolicyViolation::FeeTooHigh {
+            fee: 50_000,
+            max_fee: 10_000
+        })
+    );
+}
+
+#[tokio::test]
+async fn graph_registration() {
+    let client = start(
+        20_002,
+        SigningPolicy::default().with_max_registered_txids(1),
+    )
+    .await;
+    let registry = client.policy_registry();
+    let ms2_signer = client.musig2_signer();
+    let pubkey = ms2_signer.pubkey().await.expect("good response");
+
+    // like the N-of-N inputs of the challenge transaction, this is signed with `SINGLE` so the
+    // fee caps don't apply, but the transaction must be registered.
+    let request = SigningRequest {
+        tx: Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array(thread_rng().gen()), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2tr(SECP256K1, pubkey, None),
+            }],
+        },
+        prevouts: vec![TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2tr(SECP256K1, pubkey, None),
+        }],
+        input_index: 0,
+        spend_path: SpendPath::KeySpend { merkle_root: None },
+        sighash_type: TapSighashType::SinglePlusAnyoneCanPay,
+    };
+    let deposit_txid = Txid::from_byte_array(thread_rng().gen());
+    let txid = request.tx.compute_txid();
+    let prevout = request.tx.input[0].previous_output;
+
+    let ms2_signer = &ms2_signer;
+    let finalize = |request: SigningRequest| async move {
+        let first_round = ms2_signer
+            .new_session(
+                vec![pubkey],
+                TaprootWitness::Key,
+                prevout.txid,
+                prevout.vout,
+            )
+            .await
+            .expect("good response")
+            .expect("valid keys");
+        first_round
+            .finalize_tx(request)
+            .await
+            .expect("good response")
+    };
+
+    assert!(matches!(
+        finalize(request.clone()).await,
+        Err(Musig2FinalizeTxError::PolicyViolation(
+            PolicyViolation::UnknownTransaction
+        ))
+    ));
+
+    registry
+        .allow_txids(deposit_txid, vec![txid])
+        .await
+        .expect("good response")
+        .expect("within the cap");
+
+    // registering it again doesn't count toward the cap, unlike the transactions of another
+    // contract.
+    registry
+        .allow_txids(deposit_txid, vec![txid])
+        .await
+        .expect("good response")
+        .expect("already registered");
+    assert_eq!(
+        registry
+            .allow_txids(
+                Txid::from_byte_array(thread_rng().gen()),
+                vec![Txid::from_byte_array(thread_rng().gen())]
+            )
+            .await
+            .expect("good response"),
+        Err(PolicyViolation::TooManyTxids { max_txids: 1 })
+    );
+
+    let second_round = finalize(request.clone())
+        .await
+        .expect("registered transaction is signed");
+    second_round
+        .finalize()
+        .await
+        .expect("good response")
+        .expect("single signer session is complete");
+
+    // once the contract is over, its transactions can no longer be signed.
+    registry
+        .forget_deposit(deposit_txid)
+        .await
+        .expect("good response");
+    assert!(matches!(
+        finalize(request).await,
+        Err(Musig2FinalizeTxError::PolicyViolation(
+            PolicyViolation::UnknownTransaction
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn raw_digest_rejection() {
+    let client = start(20_003, SigningPolicy::default().rejecting_raw_digests()).await;
+    let general_signer = client.general_wallet_signer();
+    let stakechain_signer = client.stakechain_wallet_signer();
+    let pubkey = general_signer.pubkey().await.expect("good response");
+
+    // raw digests can't be checked against the policy.
+    assert!(matches!(
+        general_signer.sign(&thread_rng().gen(), None).await,
+        Err(ClientError::WrongMessage(msg)) if matches!(*msg, ServerMessage::RawDigestSigningDisabled)
+    ));
+    assert!(matches!(
+        stakechain_signer.sign_no_tweak(&thread_rng().gen()).await,
+        Err(ClientError::WrongMessage(msg)) if matches!(*msg, ServerMessage::RawDigestSigningDisabled)
+    ));
+
+    // transactions are still signed through the v2 API, like the CPFP children spending outputs
+    // locked with the untweaked key.
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array(thread_rng().gen()), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(90_000),
+            script_pubkey: ScriptBuf::new_p2tr(SECP256K1, pubkey, None),
+        }],
+    };
+    let request = SigningRequest {
+        tx: tx.clone(),
+        prevouts: vec![TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+                pubkey,
+            )),
+        }],
+        input_index: 0,
+        spend_path: SpendPath::UntweakedKeySpend,
+        sighash_type: TapSighashType::Default,
+    };
+    let sighash = request.sighash().expect("valid request");
+    let sig = general_signer
+        .sign_tx(request)
+        .await
+        .expect("good response")
+        .expect("request complies with the policy");
+    assert!(SECP256K1
+        .verify_schnorr(
+            &sig.signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &pubkey
+        )
+        .is_ok());
+
+    // as are the txids, which the bridge proofs commit to.
+    let sig = general_signer
+        .sign_txid(tx.clone())
+        .await
+        .expect("good response");
+    assert!(SECP256K1
+        .verify_schnorr(
+            &sig,
+            &Message::from_digest(tx.compute_txid().to_byte_array()),
+            &pubkey
+        )
+        .is_ok());
+}
+
 /// Dummy certificate verifier that treats any certificate as valid.
 /// NOTE, such verification is vulnerable to MITM attacks, but convenient for testing.
 #[derive(Debug)]
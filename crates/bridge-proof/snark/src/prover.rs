@@ -20,10 +20,13 @@ pub fn sp1_prove(
 ) -> anyhow::Result<(Proof<Bn254>, [Fr; 1], BridgeProofPublicOutput)> {
     info!(action = "simulating proof in native mode");
     let native_host = get_native_host();
-    let _ = BridgeProgram::prove(input, &native_host).expect("failed to assert proof statements");
+    let _ = BridgeProgram::prove(input, &native_host)
+        .map_err(|e| anyhow::anyhow!("failed to assert proof statements: {e:?}"))?;
 
-    if std::env::var("SP1_PROVER").is_err() {
-        panic!("Only network prover is supported");
+    // the prover is selected through the environment.
+    match std::env::var("SP1_PROVER") {
+        Ok(prover) if !prover.is_empty() => {}
+        _ => anyhow::bail!("SP1_PROVER must be set, only the network prover is supported"),
     }
 
     info!(action = "generating proof");
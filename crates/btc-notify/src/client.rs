@@ -1,40 +1,112 @@
 //! This module contains the top level BtcZmqClient implementation.
 //!
 //! Once the client is initialized, consumers of this API will create [`Subscription`]s with
-//! [`BtcZmqClient::subscribe_blocks`] or [`BtcZmqClient::subscribe_transactions`]. These
+//! [`BtcZmqClient::subscribe_blocks`], [`BtcZmqClient::subscribe_block_disconnects`],
+//! [`BtcZmqClient::subscribe_chain_events`] or one of the transaction subscription methods. These
 //! subscription objects can be primarily worked with via their [`futures::Stream`] trait API.
-use std::{error::Error, sync::Arc, time::Duration};
+//!
+//! Transactions can be selected by the outpoints they spend ([`BtcZmqClient::subscribe_spend`]),
+//! by txid ([`BtcZmqClient::subscribe_txid`]) or by the scripts they pay to
+//! ([`BtcZmqClient::subscribe_script`]). These are matched through indices and should be preferred
+//! over arbitrary predicates ([`BtcZmqClient::subscribe_transactions`]), which have to be evaluated
+//! against every transaction one by one.
+//!
+//! Missed ZMQ notifications are detected and made up for over RPC, see
+//! [`BtcZmqClient::health`].
+//!
+//! New transaction subscriptions immediately receive the current status of the transactions they
+//! select among the unburied blocks and the known mempool transactions. With
+//! [`BtcZmqConfig::with_mempool_tracking`], the whole mempool is known, including the transactions
+//! that entered it before the client connected.
+//!
+//! For tests, [`BtcZmqClient::connect_simulated`] connects a client to a
+//! [`crate::simulation::SimulatedChain`] instead of `bitcoind`.
+//!
+//! A client that is connected with a [`StartBlock`] first replays the blocks that were mined since
+//! that block over RPC and only then starts processing the live notifications. The replay starts
+//! once [`BtcZmqClient::catch_up`] is called so that consumers can subscribe beforehand.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    sync::Arc,
+    time::Duration,
+};
 
-use bitcoin::{Block, Transaction};
-use bitcoincore_zmq::{subscribe_async_wait_handshake, Message, SocketMessage};
+use bitcoin::{Block, BlockHash, OutPoint, ScriptBuf, Transaction, Txid};
+use bitcoincore_zmq::{subscribe_async_wait_handshake, Message, SequenceMessage, SocketMessage};
 use futures::StreamExt;
 use tokio::{
-    sync::{mpsc, Mutex},
+    select,
+    sync::{mpsc, watch, Mutex, Notify},
     task::{self, JoinHandle},
+    time::MissedTickBehavior,
 };
-use tracing::{error, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 pub use crate::{
-    config::BtcZmqConfig,
-    event::{TxEvent, TxStatus},
-    state_machine::TxPredicate,
+    config::{BtcNotifyBackend, BtcZmqConfig},
+    event::{ChainEvent, TxEvent, TxStatus},
+    filter::TxPredicate,
+    health::NotifierHealth,
+};
+use crate::{
+    constants::CATCH_UP_RETRY_INTERVAL,
+    filter::{TxFilter, TxFilterIndex},
+    health::SequenceTracker,
+    polling::RpcPoller,
+    rpc::{BtcRpcClient, BtcRpcError},
+    simulation::SimulatedChain,
+    state_machine::BtcZmqSM,
+    subscription::Subscription,
 };
-use crate::{state_machine::BtcZmqSM, subscription::Subscription};
 
+#[derive(Debug)]
 struct TxSubscriptionDetails {
-    predicate: TxPredicate,
+    filter: TxFilter,
     outbox: mpsc::UnboundedSender<TxEvent>,
 }
 
-// Coverage is disabled because when tests pass, most Debug impls will never be invoked.
-#[cfg_attr(coverage_nightly, coverage(off))]
-impl std::fmt::Debug for TxSubscriptionDetails {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("TxSubscriptionDetails")
-            .field("predicate", &format!("{:?}", Arc::as_ptr(&self.predicate)))
-            .field("outbox", &self.outbox)
-            .finish()
+/// The active transaction subscriptions, indexed by the transactions that they select.
+#[derive(Debug, Default)]
+struct TxSubscriptions {
+    next_id: u64,
+    details: BTreeMap<u64, TxSubscriptionDetails>,
+    index: TxFilterIndex<u64>,
+}
+
+impl TxSubscriptions {
+    fn insert(&mut self, details: TxSubscriptionDetails) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.index.insert(id, &details.filter);
+        self.details.insert(id, details);
+
+        id
     }
+
+    fn remove(&mut self, id: u64) -> Option<TxSubscriptionDetails> {
+        let details = self.details.remove(&id)?;
+        self.index.remove(id, &details.filter);
+
+        Some(details)
+    }
+
+    fn len(&self) -> usize {
+        self.details.len()
+    }
+}
+
+/// The first block that a [`BtcZmqClient`] should deliver to its subscribers.
+///
+/// The block must be part of the main chain when the client connects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartBlock {
+    /// The block at the given height.
+    Height(u64),
+
+    /// The block with the given hash.
+    Hash(BlockHash),
 }
 
 /// Main structure responsible for processing ZMQ notifications and feeding the appropriate events
@@ -44,166 +116,438 @@ impl std::fmt::Debug for TxSubscriptionDetails {
 /// Dropping this object will abort the monitoring thread.
 #[derive(Debug, Clone)]
 pub struct BtcZmqClient {
+    bury_depth: usize,
     block_subs: Arc<Mutex<Vec<mpsc::UnboundedSender<Block>>>>,
-    tx_subs: Arc<Mutex<Vec<TxSubscriptionDetails>>>,
+    block_disconnect_subs: Arc<Mutex<Vec<mpsc::UnboundedSender<Block>>>>,
+    chain_event_subs: Arc<Mutex<Vec<mpsc::UnboundedSender<ChainEvent>>>>,
+    tx_subs: Arc<Mutex<TxSubscriptions>>,
     state_machine: Arc<Mutex<BtcZmqSM>>,
+    catch_up_gate: Arc<Notify>,
+    unsubscribes: mpsc::UnboundedSender<u64>,
+    health: watch::Receiver<NotifierHealth>,
     thread_handle: Arc<JoinHandle<()>>,
+    unsubscribe_handle: Arc<JoinHandle<()>>,
 }
 
 impl Drop for BtcZmqClient {
     fn drop(&mut self) {
         self.thread_handle.abort();
+        self.unsubscribe_handle.abort();
     }
 }
 
 impl BtcZmqClient {
     /// Primary constructor for [`BtcZmqClient`].
     ///
-    /// It takes a [`BtcZmqConfig`] and uses that information to connect to `bitcoind`.
-    pub async fn connect(cfg: &BtcZmqConfig) -> Result<Self, Box<dyn Error>> {
+    /// It takes a [`BtcZmqConfig`] and uses that information to connect to `bitcoind`, either to
+    /// its ZMQ interface or to its RPC interface depending on the configured
+    /// [`BtcNotifyBackend`]. Both backends deliver the same events to the subscribers.
+    ///
+    /// If a [`StartBlock`] is supplied, the blocks from that block up to the chain tip are fetched
+    /// over the RPC interface configured with [`BtcZmqConfig::with_rpc_connection`] and delivered
+    /// to the subscribers, in order, before any of the live notifications. The blocks that are
+    /// delivered during this replay are not delivered again when their live notification arrives.
+    /// The replay only starts once [`BtcZmqClient::catch_up`] is called and the live notifications
+    /// are held back until then.
+    ///
+    /// The `bury_depth` blocks preceding the [`StartBlock`] are loaded without being delivered so
+    /// that reorgs of the blocks right before the [`StartBlock`] are noticed as well.
+    pub async fn connect(
+        cfg: &BtcZmqConfig,
+        start: Option<StartBlock>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::connect_to(cfg, start, None).await
+    }
+
+    /// Constructs a [`BtcZmqClient`] that receives its notifications from a [`SimulatedChain`]
+    /// rather than from `bitcoind`.
+    ///
+    /// The connection strings, the RPC connection and the backend of the [`BtcZmqConfig`] are
+    /// ignored. If mempool tracking is enabled, the snapshot of the mempool is taken from the
+    /// simulated chain.
+    pub async fn connect_simulated(cfg: &BtcZmqConfig, chain: &SimulatedChain) -> Self {
+        Self::connect_to(cfg, None, Some(chain))
+            .await
+            .expect("connecting to a simulated chain does not fail")
+    }
+
+    async fn connect_to(
+        cfg: &BtcZmqConfig,
+        start: Option<StartBlock>,
+        simulated: Option<&SimulatedChain>,
+    ) -> Result<Self, Box<dyn Error>> {
         trace!(?cfg, "subscribing to bitcoind");
-        let state_machine = Arc::new(Mutex::new(BtcZmqSM::init(cfg.bury_depth)));
-
-        let sockets = cfg
-            .hashblock_connection_string
-            .iter()
-            .chain(cfg.hashtx_connection_string.iter())
-            .chain(cfg.rawblock_connection_string.iter())
-            .chain(cfg.rawtx_connection_string.iter())
-            .chain(cfg.sequence_connection_string.iter())
-            .map(String::as_str)
-            .collect::<Vec<&str>>();
-
-        let mut stream = match tokio::time::timeout(
-            Duration::from_millis(2000),
-            subscribe_async_wait_handshake(&sockets),
-        )
-        .await
-        {
-            Ok(Ok(stream)) => {
-                // Ok(Ok(_)), ok from both functions.
-                stream
+        let state_machine = Arc::new(Mutex::new(
+            BtcZmqSM::init(cfg.bury_depth).with_mempool_tracking(cfg.track_mempool),
+        ));
+
+        let rpc_client = match simulated {
+            Some(_) => None,
+            None => cfg.rpc.as_ref().map(BtcRpcClient::new).transpose()?,
+        };
+
+        // We subscribe to the live notifications before looking up the chain tip so that no block
+        // is missed between the end of the replay and the start of the live notifications.
+        let source = match (simulated, cfg.backend) {
+            // The simulated chain publishes the same messages as the ZMQ interface.
+            (Some(chain), _) => MessageSource::Zmq(
+                chain
+                    .subscribe()
+                    .map(|msg| Ok::<_, bitcoincore_zmq::Error>(SocketMessage::Message(msg)))
+                    .boxed(),
+            ),
+            (None, BtcNotifyBackend::Zmq) => {
+                let sockets = cfg
+                    .hashblock_connection_string
+                    .iter()
+                    .chain(cfg.hashtx_connection_string.iter())
+                    .chain(cfg.rawblock_connection_string.iter())
+                    .chain(cfg.rawtx_connection_string.iter())
+                    .chain(cfg.sequence_connection_string.iter())
+                    .map(String::as_str)
+                    .collect::<Vec<&str>>();
+
+                let stream = match tokio::time::timeout(
+                    Duration::from_millis(2000),
+                    subscribe_async_wait_handshake(&sockets),
+                )
+                .await
+                {
+                    Ok(Ok(stream)) => {
+                        // Ok(Ok(_)), ok from both functions.
+                        stream
+                    }
+                    Ok(Err(err)) => {
+                        // Ok(Err(_)), ok from `timeout` but an error from the subscribe function.
+                        panic!("subscribe error: {err}");
+                    }
+                    Err(_) => {
+                        // Err(_), err from `timeout` means that it timed out.
+                        panic!("bitcoin-core zmq subscription handshake timed out");
+                    }
+                };
+
+                MessageSource::Zmq(stream.boxed())
             }
-            Ok(Err(err)) => {
-                // Ok(Err(_)), ok from `timeout` but an error from the subscribe function.
-                panic!("subscribe error: {err}");
+            (None, BtcNotifyBackend::RpcPolling { poll_interval }) => {
+                let rpc_client = rpc_client
+                    .clone()
+                    .ok_or("an rpc connection is required to poll bitcoind")?;
+                let poller = RpcPoller::new(rpc_client, cfg.bury_depth).await?;
+
+                MessageSource::RpcPolling {
+                    poller,
+                    poll_interval,
+                }
             }
-            Err(_) => {
-                // Err(_), err from `timeout` means that it timed out.
-                panic!("bitcoin-core zmq subscription handshake timed out");
+        };
+
+        // The chain tip and the mempool as of the subscription to the ZMQ interface are the
+        // baseline against which the client resynchronizes if it misses notifications before it
+        // receives any block.
+        let mut tip_at_connect = None;
+        let mut mempool = BTreeSet::new();
+        if let (MessageSource::Zmq(_), Some(rpc_client)) = (&source, &rpc_client) {
+            let tip_height = rpc_client.get_block_count().await?;
+            tip_at_connect = Some((tip_height, rpc_client.get_block_hash(tip_height).await?));
+            mempool.extend(rpc_client.get_raw_mempool().await?.txids);
+        }
+
+        // The transactions that entered the mempool before the subscription are only known to
+        // bitcoind.
+        let mut mempool_snapshot = Vec::new();
+        if cfg.track_mempool {
+            match (simulated, &rpc_client) {
+                (Some(chain), _) => mempool_snapshot = chain.mempool(),
+                (None, Some(rpc_client)) => {
+                    for txid in rpc_client.get_raw_mempool().await?.txids {
+                        match rpc_client.get_raw_transaction(&txid).await {
+                            Ok(tx) => mempool_snapshot.push(tx),
+                            // The transaction may have left the mempool in the meantime.
+                            Err(e) => debug!(%txid, %e, "could not fetch mempool transaction"),
+                        }
+                    }
+                }
+                (None, None) => {
+                    return Err("an rpc connection is required to track the mempool".into())
+                }
+            }
+            info!(num_txs=%mempool_snapshot.len(), "took a snapshot of the mempool");
+        }
+
+        let catch_up = match start {
+            Some(start) => {
+                let rpc_client = rpc_client
+                    .clone()
+                    .ok_or("an rpc connection is required to start from a past block")?;
+
+                let start_height = match start {
+                    StartBlock::Height(height) => height,
+                    StartBlock::Hash(blockhash) => rpc_client.get_block_height(&blockhash).await?,
+                };
+
+                // There are no filters yet so loading these blocks does not produce any events.
+                let mut sm = state_machine.lock().await;
+                for height in start_height.saturating_sub(cfg.bury_depth as u64)..start_height {
+                    trace!(%height, "loading block preceding the start block");
+                    let diff = sm.process_block(rpc_client.get_block_at(height).await?);
+                    debug_assert!(diff.is_empty(), "loaded blocks must not produce events");
+                }
+                drop(sm);
+
+                info!(%start_height, "will catch up from the start block");
+                Some((rpc_client, start_height))
             }
+            None => None,
         };
 
         let block_subs = Arc::new(Mutex::new(Vec::<mpsc::UnboundedSender<Block>>::new()));
         let block_subs_thread = block_subs.clone();
-        let tx_subs = Arc::new(Mutex::new(Vec::<TxSubscriptionDetails>::new()));
+        let block_disconnect_subs =
+            Arc::new(Mutex::new(Vec::<mpsc::UnboundedSender<Block>>::new()));
+        let block_disconnect_subs_thread = block_disconnect_subs.clone();
+        let chain_event_subs =
+            Arc::new(Mutex::new(Vec::<mpsc::UnboundedSender<ChainEvent>>::new()));
+        let chain_event_subs_thread = chain_event_subs.clone();
+        let tx_subs = Arc::new(Mutex::new(TxSubscriptions::default()));
         let tx_subs_thread = tx_subs.clone();
         let state_machine_thread = state_machine.clone();
+        let catch_up_gate = Arc::new(Notify::new());
+        let catch_up_gate_thread = catch_up_gate.clone();
+        let (health_sender, health) = watch::channel(NotifierHealth::Healthy);
+        let bury_depth = cfg.bury_depth;
+        let heartbeat_timeout = cfg.heartbeat_timeout;
         let thread_handle = Arc::new(task::spawn(async move {
-            loop {
-                // This loop has no break condition. It is only aborted when the BtcZmqClient is
-                // dropped.
-                info!("listening for ZMQ events");
-
-                while let Some(res) = stream.next().await {
-                    let mut sm = state_machine_thread.lock().await;
-                    let diff = match res {
-                        Ok(SocketMessage::Message(msg)) => {
-                            let topic = msg.topic_str();
-                            match msg {
-                                Message::HashBlock(_, _) => {
-                                    trace!(%topic, "received event");
-                                    Vec::new()
-                                }
-                                Message::HashTx(_, _) => {
-                                    trace!(%topic, "received event");
-                                    Vec::new()
-                                }
-                                Message::Block(block, _) => {
-                                    trace!(%topic, "received event");
-                                    // First send the block to the block subscribers.
-                                    block_subs_thread
-                                        .lock()
-                                        .await
-                                        .retain(|sub| sub.send(block.clone()).is_ok());
-
-                                    // Now we process the block to understand what the relevant
-                                    // transaction diff is.
-                                    trace!(?block, "processing block");
-                                    info!(block_hash=%block.block_hash(), "processing block");
-                                    sm.process_block(block)
-                                }
-                                Message::Tx(tx, _) => {
-                                    trace!(%topic, "received event");
-                                    info!(txid=%tx.compute_txid(), "processing transaction");
-                                    sm.process_tx(tx)
+            let mut processor = MessageProcessor {
+                state_machine: state_machine_thread,
+                block_subs: block_subs_thread,
+                block_disconnect_subs: block_disconnect_subs_thread,
+                chain_event_subs: chain_event_subs_thread,
+                tx_subs: tx_subs_thread,
+                replayed: BTreeSet::new(),
+                reorg_depth: 0,
+                rpc_client,
+                bury_depth,
+                tip_at_connect,
+                mempool,
+                sequences: SequenceTracker::default(),
+                resync_pending: false,
+                health: health_sender,
+            };
+
+            if catch_up.is_some() {
+                catch_up_gate_thread.notified().await;
+            }
+            // The replayed blocks may include some of the snapshot transactions, so the snapshot is
+            // processed first.
+            if !mempool_snapshot.is_empty() {
+                processor.seed_mempool(mempool_snapshot).await;
+            }
+            if let Some((rpc_client, start_height)) = catch_up {
+                processor.replay(&rpc_client, start_height).await;
+            }
+
+            match source {
+                MessageSource::Zmq(mut stream) => {
+                    let mut heartbeat = tokio::time::interval(heartbeat_timeout);
+                    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    // The first tick completes immediately.
+                    heartbeat.tick().await;
+                    let mut idle = true;
+
+                    // This loop has no break condition. It is only aborted when the BtcZmqClient
+                    // is dropped.
+                    loop {
+                        info!("listening for ZMQ events");
+
+                        loop {
+                            select! {
+                                res = stream.next() => {
+                                    let Some(res) = res else {
+                                        break;
+                                    };
+                                    idle = false;
+
+                                    match res {
+                                        Ok(SocketMessage::Message(msg)) => {
+                                            let topic = msg.topic_str();
+                                            let missed =
+                                                processor.sequences.observe(topic, msg.sequence());
+                                            if missed > 0 {
+                                                warn!(%topic, %missed, "missed ZMQ messages");
+                                                processor.resync().await;
+                                            }
+                                            processor.process(msg).await;
+                                        }
+                                        Ok(monitoring_msg) => {
+                                            // The socket may have been disconnected in which
+                                            // case messages were lost. This is checked at the
+                                            // next heartbeat, once the socket had a chance to
+                                            // reconnect.
+                                            warn!(
+                                                ?monitoring_msg,
+                                                "received monitoring message, scheduling a resync"
+                                            );
+                                            processor.resync_pending |=
+                                                processor.rpc_client.is_some();
+                                        }
+                                        Err(e) => {
+                                            error!(%e, "Error processing ZMQ message");
+                                        }
+                                    }
                                 }
-                                Message::Sequence(seq, _) => {
-                                    trace!(%topic, "received event");
-                                    info!(%seq, "processing sequence");
-                                    sm.process_sequence(seq)
+                                _ = heartbeat.tick() => {
+                                    processor.heartbeat(idle).await;
+                                    idle = true;
                                 }
                             }
                         }
-                        Ok(monitoring_msg) => {
-                            warn!(?monitoring_msg, "ignoring monitoring message");
-                            Vec::new()
-                        }
-                        Err(e) => {
-                            error!(%e, "Error processing ZMQ message");
-                            Vec::new()
-                        }
-                    };
-
-                    info!("applying filtering predicates on the btc chain state diff");
-                    tx_subs_thread.lock().await.retain(|sub| {
-                        for msg in diff.iter().filter(|event| (sub.predicate)(&event.rawtx)) {
-                            // Now we send the diff to the relevant subscribers.
-                            // If we ever encounter a send error,
-                            // it means the receiver has been dropped.
-                            if sub.outbox.send(msg.clone()).is_err() {
-                                sm.rm_filter(&sub.predicate);
-                                return false;
+                    }
+                }
+                MessageSource::RpcPolling {
+                    mut poller,
+                    poll_interval,
+                } => {
+                    info!(?poll_interval, "polling bitcoind for events");
+
+                    let mut interval = tokio::time::interval(poll_interval);
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    // This loop has no break condition. It is only aborted when the BtcZmqClient
+                    // is dropped.
+                    loop {
+                        interval.tick().await;
+                        match poller.poll().await {
+                            Ok(messages) => {
+                                processor.health.send_replace(NotifierHealth::Healthy);
+                                for msg in messages {
+                                    processor.process(msg).await;
+                                }
+                            }
+                            Err(e) => {
+                                error!(%e, "could not poll bitcoind, retrying at the next interval");
+                                processor.health.send_replace(NotifierHealth::Degraded);
                             }
                         }
-                        true
-                    });
+                    }
                 }
             }
         }));
 
+        // Dropped transaction subscriptions are released in the background so that their filters
+        // stop being evaluated right away rather than on the next matching event.
+        let (unsubscribes, mut unsubscribed) = mpsc::unbounded_channel::<u64>();
+        let unsubscribe_state_machine = state_machine.clone();
+        let unsubscribe_tx_subs = tx_subs.clone();
+        let unsubscribe_handle = Arc::new(task::spawn(async move {
+            while let Some(id) = unsubscribed.recv().await {
+                remove_tx_subscription(&unsubscribe_state_machine, &unsubscribe_tx_subs, id).await;
+            }
+        }));
+
         info!("subscribed to bitcoind");
 
         Ok(BtcZmqClient {
+            bury_depth: cfg.bury_depth,
             block_subs,
+            block_disconnect_subs,
+            chain_event_subs,
             tx_subs,
             state_machine,
+            catch_up_gate,
+            unsubscribes,
+            health,
             thread_handle,
+            unsubscribe_handle,
         })
     }
 
+    /// Starts replaying the blocks since the [`StartBlock`] supplied to
+    /// [`BtcZmqClient::connect`], after which the live notifications are processed.
+    ///
+    /// This should be called once all the initial subscriptions are in place since the replayed
+    /// events are only delivered to the subscriptions that exist at the time. It has no effect on
+    /// clients that were connected without a [`StartBlock`].
+    pub fn catch_up(&self) {
+        self.catch_up_gate.notify_one();
+    }
+
     /// Creates a new [`Subscription`] that emits new [`bitcoin::Transaction`] and [`TxStatus`]
     /// every time a transaction's status changes due to block or mempool events.
+    ///
+    /// The predicate is evaluated against every transaction that the client observes. Prefer
+    /// [`BtcZmqClient::subscribe_spend`], [`BtcZmqClient::subscribe_txid`] or
+    /// [`BtcZmqClient::subscribe_script`] whenever they can express the selection.
     pub async fn subscribe_transactions(
         &self,
         f: impl Fn(&Transaction) -> bool + Sync + Send + 'static,
     ) -> Subscription<TxEvent> {
+        self.subscribe_filter(TxFilter::Predicate(Arc::new(f)))
+            .await
+    }
+
+    /// Creates a new [`Subscription`] that emits a [`TxEvent`] every time the status of a
+    /// transaction that spends the given outpoint changes.
+    ///
+    /// Every conflicting spend of the outpoint is reported, so the events may concern several
+    /// distinct transactions. The subscription is closed once one of them is buried since the
+    /// outpoint can no longer be spent by any other transaction.
+    pub async fn subscribe_spend(&self, outpoint: OutPoint) -> Subscription<TxEvent> {
+        self.subscribe_filter(TxFilter::Spend(outpoint)).await
+    }
+
+    /// Creates a new [`Subscription`] that emits a [`TxEvent`] every time the status of the
+    /// transaction with the given txid changes.
+    ///
+    /// The subscription is closed once the transaction is buried.
+    pub async fn subscribe_txid(&self, txid: Txid) -> Subscription<TxEvent> {
+        self.subscribe_filter(TxFilter::Txid(txid)).await
+    }
+
+    /// Creates a new [`Subscription`] that emits a [`TxEvent`] every time the status of a
+    /// transaction that has an output locked by the given script changes.
+    pub async fn subscribe_script(&self, script_pubkey: ScriptBuf) -> Subscription<TxEvent> {
+        self.subscribe_filter(TxFilter::Script(script_pubkey)).await
+    }
+
+    async fn subscribe_filter(&self, filter: TxFilter) -> Subscription<TxEvent> {
         let (send, recv) = mpsc::unbounded_channel();
 
         let details = TxSubscriptionDetails {
-            predicate: Arc::new(f),
+            filter,
             outbox: send,
         };
         trace!(?details, "subscribing to transactions");
 
-        let mut subs = self.tx_subs.lock().await;
+        // The state machine is always locked before the subscriptions to avoid deadlocks.
         let mut sm = self.state_machine.lock().await;
-        sm.add_filter(details.predicate.clone());
+        let mut subs = self.tx_subs.lock().await;
+        let current = sm.add_filter(details.filter.clone());
         drop(sm); // dropped eagerly to allow other threads to progress immediately.
-        subs.push(details);
+
+        // The subscriber learns about the current status of the transactions it selects before any
+        // later change since the subscriptions are still locked.
+        for event in current {
+            trace!(
+                ?event,
+                "sending current transaction status to new subscriber"
+            );
+            // The receiver is returned below so it can't have been dropped yet.
+            let _ = details.outbox.send(event);
+        }
+        let id = subs.insert(details);
         drop(subs); // dropped eagerly to allow other threads to progress immediately.
 
-        Subscription::from_receiver(recv)
+        Subscription::with_unsubscribe(recv, id, self.unsubscribes.clone())
+    }
+
+    /// Removes a transaction subscription right away.
+    ///
+    /// Dropping a [`Subscription`] has the same effect except that the subscription is removed in
+    /// the background. Subscriptions that do not deliver transaction events are left untouched.
+    pub async fn unsubscribe(&self, subscription: Subscription<TxEvent>) {
+        if let Some(id) = subscription.id() {
+            remove_tx_subscription(&self.state_machine, &self.tx_subs, id).await;
+        }
     }
 
     /// Creates a new [`Subscription`] that emits new [`bitcoin::Block`] every time a new block is
@@ -218,8 +562,57 @@ impl BtcZmqClient {
         Subscription::from_receiver(recv)
     }
 
-    /// Returns the number of active transaction subscriptions created with
-    /// [`BtcZmqClient::subscribe_transactions`].
+    /// Creates a new [`Subscription`] that emits a [`bitcoin::Block`] every time it is disconnected
+    /// from the main Bitcoin blockchain due to a reorg.
+    ///
+    /// Disconnects are emitted in reverse chronological order, i.e. the tip is disconnected first.
+    /// Only unburied blocks that were observed by this client are reported.
+    pub async fn subscribe_block_disconnects(&self) -> Subscription<Block> {
+        let (send, recv) = mpsc::unbounded_channel();
+
+        trace!("subscribing to block disconnects");
+
+        self.block_disconnect_subs.lock().await.push(send);
+
+        Subscription::from_receiver(recv)
+    }
+
+    /// Creates a new [`Subscription`] that emits a [`ChainEvent`] every time a block is connected
+    /// to or disconnected from the main Bitcoin blockchain.
+    ///
+    /// Unlike [`BtcZmqClient::subscribe_blocks`] and [`BtcZmqClient::subscribe_block_disconnects`],
+    /// connects and disconnects are delivered over the same subscription, in the order in which
+    /// they happened, so that consumers can follow the chain tip as it moves backwards during
+    /// reorgs. Only unburied blocks that were observed by this client are reported as
+    /// disconnected.
+    pub async fn subscribe_chain_events(&self) -> Subscription<ChainEvent> {
+        let (send, recv) = mpsc::unbounded_channel();
+
+        trace!("subscribing to chain events");
+
+        self.chain_event_subs.lock().await.push(send);
+
+        Subscription::from_receiver(recv)
+    }
+
+    /// Returns the number of blocks that must be built on top of a block before it is considered
+    /// buried by this client.
+    pub fn bury_depth(&self) -> usize {
+        self.bury_depth
+    }
+
+    /// Returns whether the notifications received from `bitcoind` are known to be complete.
+    ///
+    /// The client detects dropped ZMQ messages from their sequence numbers and stalled sockets
+    /// from the absence of notifications for the configured heartbeat timeout. It then
+    /// resynchronizes over the RPC connection configured with
+    /// [`BtcZmqConfig::with_rpc_connection`].
+    pub fn health(&self) -> NotifierHealth {
+        *self.health.borrow()
+    }
+
+    /// Returns the number of active transaction subscriptions, irrespective of how they select
+    /// transactions.
     pub async fn num_tx_subscriptions(&self) -> usize {
         self.tx_subs.lock().await.len()
     }
@@ -229,6 +622,370 @@ impl BtcZmqClient {
     pub async fn num_block_subscriptions(&self) -> usize {
         self.block_subs.lock().await.len()
     }
+
+    /// Returns the number of active block disconnect subscriptions created with
+    /// [`BtcZmqClient::subscribe_block_disconnects`].
+    pub async fn num_block_disconnect_subscriptions(&self) -> usize {
+        self.block_disconnect_subs.lock().await.len()
+    }
+
+    /// Returns the number of active chain event subscriptions created with
+    /// [`BtcZmqClient::subscribe_chain_events`].
+    pub async fn num_chain_event_subscriptions(&self) -> usize {
+        self.chain_event_subs.lock().await.len()
+    }
+}
+
+/// The source of the live notifications processed by a [`BtcZmqClient`], where `S` is the type of
+/// the stream of ZMQ messages.
+enum MessageSource<S> {
+    /// The ZMQ interface of `bitcoind`.
+    Zmq(S),
+
+    /// The RPC interface of `bitcoind`, polled at a fixed interval.
+    RpcPolling {
+        poller: RpcPoller,
+        poll_interval: Duration,
+    },
+}
+
+/// Feeds the notifications to the state machine and distributes the resulting events to the
+/// subscribers.
+struct MessageProcessor {
+    state_machine: Arc<Mutex<BtcZmqSM>>,
+    block_subs: Arc<Mutex<Vec<mpsc::UnboundedSender<Block>>>>,
+    block_disconnect_subs: Arc<Mutex<Vec<mpsc::UnboundedSender<Block>>>>,
+    chain_event_subs: Arc<Mutex<Vec<mpsc::UnboundedSender<ChainEvent>>>>,
+    tx_subs: Arc<Mutex<TxSubscriptions>>,
+
+    /// The hashes of the replayed blocks whose live notifications have yet to be skipped.
+    replayed: BTreeSet<BlockHash>,
+
+    /// The number of blocks that were disconnected since the last connected block.
+    reorg_depth: usize,
+
+    /// The RPC interface used to resynchronize after missed notifications, if configured.
+    rpc_client: Option<BtcRpcClient>,
+
+    /// The number of blocks that must be built on top of a block before it is considered buried.
+    bury_depth: usize,
+
+    /// The chain tip at the time the client subscribed to the ZMQ interface, if an RPC connection
+    /// is configured.
+    tip_at_connect: Option<(u64, BlockHash)>,
+
+    /// The txids of the transactions that are in the mempool according to the notifications.
+    mempool: BTreeSet<Txid>,
+
+    /// The sequence numbers of the last ZMQ message of every topic.
+    sequences: SequenceTracker,
+
+    /// Whether notifications may have been missed without the client having resynchronized yet.
+    resync_pending: bool,
+
+    /// Publishes the health of the notifications to the [`BtcZmqClient`].
+    health: watch::Sender<NotifierHealth>,
+}
+
+impl MessageProcessor {
+    /// Replays the blocks from the given height up to the chain tip.
+    async fn replay(&mut self, rpc_client: &BtcRpcClient, start_height: u64) {
+        info!(%start_height, "catching up with the chain tip");
+
+        let mut next = start_height;
+        loop {
+            // The tip is looked up again after each batch so that the blocks that are mined during
+            // the replay are replayed as well.
+            let tip = match rpc_client.get_block_count().await {
+                Ok(tip) => tip,
+                Err(e) => {
+                    error!(%e, "could not fetch the chain tip, retrying");
+                    tokio::time::sleep(CATCH_UP_RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+            if next > tip {
+                break;
+            }
+
+            while next <= tip {
+                let block = match rpc_client.get_block_at(next).await {
+                    Ok(block) => block,
+                    Err(e) => {
+                        error!(%e, height=%next, "could not fetch block, retrying");
+                        tokio::time::sleep(CATCH_UP_RETRY_INTERVAL).await;
+                        continue;
+                    }
+                };
+                self.replayed.insert(block.block_hash());
+
+                let state_machine = self.state_machine.clone();
+                let mut sm = state_machine.lock().await;
+                let diff = self.connect_block(&mut sm, block).await;
+                self.dispatch_tx_events(&mut sm, diff).await;
+                next += 1;
+            }
+        }
+
+        info!(tip=%next.saturating_sub(1), "caught up with the chain tip");
+    }
+
+    /// Reports the transactions of a mempool snapshot that the subscriptions select.
+    async fn seed_mempool(&mut self, txs: Vec<Transaction>) {
+        let state_machine = self.state_machine.clone();
+        let mut sm = state_machine.lock().await;
+        let diff = sm.process_mempool(txs);
+        self.dispatch_tx_events(&mut sm, diff).await;
+    }
+
+    /// Called periodically while listening for ZMQ notifications, with `idle` set if none were
+    /// received since the previous call.
+    ///
+    /// Pending resyncs are retried and, if the client was idle, the chain tip is checked over RPC
+    /// in case the socket stalled.
+    async fn heartbeat(&mut self, idle: bool) {
+        if self.resync_pending {
+            self.resync().await;
+            return;
+        }
+
+        let Some(rpc_client) = self.rpc_client.clone() else {
+            return;
+        };
+        if !idle {
+            return;
+        }
+
+        let state_machine = self.state_machine.clone();
+        let tip = state_machine
+            .lock()
+            .await
+            .unburied_chain()
+            .last_key_value()
+            .map(|(_, hash)| *hash)
+            .or(self.tip_at_connect.map(|(_, hash)| hash));
+        match rpc_client.get_best_block_hash().await {
+            Ok(best_hash) if Some(best_hash) != tip => {
+                warn!(%best_hash, ?tip, "no ZMQ notifications received for the latest blocks");
+                self.resync().await;
+            }
+            Ok(_) => trace!("chain tip is up to date"),
+            Err(e) => warn!(%e, "could not check the chain tip"),
+        }
+    }
+
+    /// Resynchronizes the unburied blocks and the mempool transactions with `bitcoind` after
+    /// notifications were missed, updating the health of the client accordingly.
+    async fn resync(&mut self) {
+        let Some(rpc_client) = self.rpc_client.clone() else {
+            warn!("cannot resynchronize without an rpc connection, events may be missing");
+            self.health.send_replace(NotifierHealth::Degraded);
+            return;
+        };
+
+        info!("resynchronizing with bitcoind");
+        self.health.send_replace(NotifierHealth::Resyncing);
+        match self.try_resync(&rpc_client).await {
+            Ok(()) => {
+                info!("resynchronized with bitcoind");
+                self.resync_pending = false;
+                self.health.send_replace(NotifierHealth::Healthy);
+            }
+            Err(e) => {
+                error!(%e, "could not resynchronize with bitcoind, retrying at the next heartbeat");
+                self.resync_pending = true;
+                self.health.send_replace(NotifierHealth::Degraded);
+            }
+        }
+    }
+
+    async fn try_resync(&mut self, rpc_client: &BtcRpcClient) -> Result<(), BtcRpcError> {
+        let state_machine = self.state_machine.clone();
+        let mut sm = state_machine.lock().await;
+        let mut chain = sm.unburied_chain();
+        if chain.is_empty() {
+            chain.extend(self.tip_at_connect);
+        }
+
+        // The rawtx events of these transactions may have been missed so they are fetched again.
+        for txid in sm.drop_placeholders() {
+            self.mempool.remove(&txid);
+        }
+        drop(sm);
+
+        // The poller reports the differences between our view and that of bitcoind as the
+        // notifications that we would have received.
+        let mut poller = RpcPoller::resume(
+            rpc_client.clone(),
+            self.bury_depth,
+            chain,
+            self.mempool.clone(),
+        );
+        let messages = poller.poll().await?;
+        debug!(num_messages=%messages.len(), "replaying missed notifications");
+        for msg in messages {
+            self.process(msg).await;
+        }
+
+        Ok(())
+    }
+
+    /// Processes a single notification.
+    async fn process(&mut self, msg: Message) {
+        let state_machine = self.state_machine.clone();
+        let mut sm = state_machine.lock().await;
+        let topic = msg.topic_str();
+        let diff = match msg {
+            Message::HashBlock(_, _) => {
+                trace!(%topic, "received event");
+                Vec::new()
+            }
+            Message::HashTx(_, _) => {
+                trace!(%topic, "received event");
+                Vec::new()
+            }
+            Message::Block(block, _) => {
+                trace!(%topic, "received event");
+                // Notifications are delivered in order so once a block that was not replayed
+                // arrives, none of the following ones were replayed either.
+                if self.replayed.remove(&block.block_hash()) {
+                    info!(block_hash=%block.block_hash(), "skipping block that was already replayed");
+                    return;
+                }
+                self.replayed.clear();
+
+                // The notifications of the blocks that were fetched during a resync may still
+                // arrive afterwards.
+                if sm.unburied_block(&block.block_hash()).is_some() {
+                    info!(block_hash=%block.block_hash(), "skipping block that is already connected");
+                    return;
+                }
+
+                self.connect_block(&mut sm, block).await
+            }
+            Message::Tx(tx, _) => {
+                trace!(%topic, "received event");
+                info!(txid=%tx.compute_txid(), "processing transaction");
+                sm.process_tx(tx)
+            }
+            Message::Sequence(seq, _) => {
+                trace!(%topic, "received event");
+                info!(%seq, "processing sequence");
+                match seq {
+                    // The state machine forgets the block once it processes the disconnect so we
+                    // have to look it up beforehand.
+                    SequenceMessage::BlockDisconnect { blockhash } => {
+                        match sm.unburied_block(&blockhash).cloned() {
+                            Some(block) => {
+                                self.reorg_depth += 1;
+                                let event = ChainEvent::Disconnected {
+                                    hash: blockhash,
+                                    height: block.bip34_block_height().unwrap_or(0),
+                                    reorg_depth: self.reorg_depth,
+                                };
+                                self.chain_event_subs
+                                    .lock()
+                                    .await
+                                    .retain(|sub| sub.send(event.clone()).is_ok());
+
+                                self.block_disconnect_subs
+                                    .lock()
+                                    .await
+                                    .retain(|sub| sub.send(block.clone()).is_ok());
+                            }
+                            None => {
+                                warn!(%blockhash, "disconnected block is not tracked, not notifying subscribers");
+                            }
+                        }
+                    }
+                    SequenceMessage::MempoolAcceptance { txid, .. } => {
+                        self.mempool.insert(txid);
+                    }
+                    SequenceMessage::MempoolRemoval { txid, .. } => {
+                        self.mempool.remove(&txid);
+                    }
+                    SequenceMessage::BlockConnect { .. } => {}
+                }
+                sm.process_sequence(seq)
+            }
+        };
+
+        self.dispatch_tx_events(&mut sm, diff).await;
+    }
+
+    /// Sends a newly connected block to the block subscribers and processes it with the state
+    /// machine, returning the resulting transaction events.
+    async fn connect_block(&mut self, sm: &mut BtcZmqSM, block: Block) -> Vec<TxEvent> {
+        self.reorg_depth = 0;
+        for tx in &block.txdata {
+            self.mempool.remove(&tx.compute_txid());
+        }
+
+        // First send the block to the block subscribers.
+        self.chain_event_subs
+            .lock()
+            .await
+            .retain(|sub| sub.send(ChainEvent::Connected(block.clone())).is_ok());
+        self.block_subs
+            .lock()
+            .await
+            .retain(|sub| sub.send(block.clone()).is_ok());
+
+        // Now we process the block to understand what the relevant transaction diff is.
+        trace!(?block, "processing block");
+        info!(block_hash=%block.block_hash(), "processing block");
+        sm.process_block(block)
+    }
+
+    /// Sends the transaction events to the subscribers whose filters match them.
+    ///
+    /// Subscriptions to a single transaction or outpoint are closed once the transaction is buried
+    /// since nothing can happen to it anymore.
+    async fn dispatch_tx_events(&self, sm: &mut BtcZmqSM, diff: Vec<TxEvent>) {
+        info!("applying filters on the btc chain state diff");
+        let mut subs = self.tx_subs.lock().await;
+        let mut closed = BTreeSet::new();
+        for msg in diff {
+            let buried = matches!(msg.status, TxStatus::Buried { .. });
+            for id in subs.index.matching(&msg.rawtx) {
+                if closed.contains(&id) {
+                    continue;
+                }
+                let details = &subs.details[&id];
+
+                // Now we send the diff to the relevant subscribers.
+                // If we ever encounter a send error,
+                // it means the receiver has been dropped.
+                let dropped = details.outbox.send(msg.clone()).is_err();
+                let done =
+                    buried && matches!(details.filter, TxFilter::Txid(_) | TxFilter::Spend(_));
+                if dropped || done {
+                    closed.insert(id);
+                }
+            }
+        }
+
+        for id in closed {
+            if let Some(details) = subs.remove(id) {
+                trace!(?details, "closing transaction subscription");
+                sm.rm_filter(&details.filter);
+            }
+        }
+    }
+}
+
+/// Removes the transaction subscription with the given id along with its filter.
+async fn remove_tx_subscription(
+    state_machine: &Mutex<BtcZmqSM>,
+    tx_subs: &Mutex<TxSubscriptions>,
+    id: u64,
+) {
+    // The state machine is always locked before the subscriptions to avoid deadlocks.
+    let mut sm = state_machine.lock().await;
+    if let Some(details) = tx_subs.lock().await.remove(id) {
+        trace!(?details, "unsubscribing from transactions");
+        sm.rm_filter(&details.filter);
+    }
 }
 
 #[cfg(test)]
@@ -264,7 +1021,7 @@ mod e2e_tests {
             .with_rawtx_connection_string("tcp://127.0.0.1:23885")
             .with_sequence_connection_string("tcp://127.0.0.1:23886");
 
-        let client = BtcZmqClient::connect(&cfg).await?;
+        let client = BtcZmqClient::connect(&cfg, None).await?;
 
         Ok((client, bitcoind))
     }
@@ -293,9 +1050,9 @@ mod e2e_tests {
             .with_sequence_connection_string("tcp://127.0.0.1:23886");
 
         info!("connecting to bitcoind with client 1");
-        let client_1 = BtcZmqClient::connect(&cfg).await?;
+        let client_1 = BtcZmqClient::connect(&cfg, None).await?;
         info!("connecting to bitcoind with client 2");
-        let client_2 = BtcZmqClient::connect(&cfg).await?;
+        let client_2 = BtcZmqClient::connect(&cfg, None).await?;
 
         Ok((client_1, client_2, bitcoind))
     }
@@ -348,6 +1105,107 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn basic_subscribe_block_disconnects_functionality(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        // Set up new bitcoind and zmq client instance.
+        let (client, bitcoind) = setup().await?;
+
+        // Subscribe to new blocks and block disconnects
+        let mut block_sub = client.subscribe_blocks().await;
+        let mut disconnect_sub = client.subscribe_block_disconnects().await;
+
+        // Mine a new block and wait for it so that the client is tracking it.
+        let newly_mined = bitcoind
+            .client
+            .generate_to_address(1, &bitcoind.client.new_address()?)?
+            .into_model()?;
+        let blk = block_sub.next().await.map(|b| b.block_hash());
+        assert_eq!(newly_mined.0.first(), blk.as_ref());
+
+        // Reorg the block out of the main chain.
+        bitcoind
+            .client
+            .call::<()>("invalidateblock", &[json!(blk.unwrap().to_string())])?;
+
+        // Wait for the disconnected block to be delivered over the subscription
+        let disconnected = disconnect_sub.next().await.map(|b| b.block_hash());
+
+        // Assert that the disconnected block is the one we mined
+        assert_eq!(newly_mined.0.first(), disconnected.as_ref());
+
+        // Explicitly drop the client here to prevent rustc from "optimizing" the code and dropping
+        // it earlier, aborting the producer thread
+        drop(client);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn chain_events_follow_reorgs_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        // Set up new bitcoind and zmq client instance.
+        let (client, bitcoind) = setup().await?;
+        let new_address = bitcoind.client.new_address()?;
+
+        let mut chain_sub = client.subscribe_chain_events().await;
+
+        // Mine two blocks and wait for them so that the client is tracking them.
+        let old_branch = bitcoind
+            .client
+            .generate_to_address(2, &new_address)?
+            .into_model()?
+            .0;
+        for blockhash in &old_branch {
+            match chain_sub.next().await {
+                Some(ChainEvent::Connected(block)) => assert_eq!(&block.block_hash(), blockhash),
+                other => panic!("expected a connected block, got {other:?}"),
+            }
+        }
+
+        // Reorg both blocks out of the main chain and replace them with a longer branch.
+        bitcoind
+            .client
+            .call::<()>("invalidateblock", &[json!(old_branch[0].to_string())])?;
+        let new_branch = bitcoind
+            .client
+            .generate_to_address(3, &new_address)?
+            .into_model()?
+            .0;
+
+        // The old branch is disconnected from the tip down with increasing reorg depths.
+        for (expected_depth, blockhash) in old_branch.iter().rev().enumerate() {
+            match chain_sub.next().await {
+                Some(ChainEvent::Disconnected {
+                    hash, reorg_depth, ..
+                }) => {
+                    assert_eq!(&hash, blockhash);
+                    assert_eq!(reorg_depth, expected_depth + 1);
+                }
+                other => panic!("expected a disconnected block, got {other:?}"),
+            }
+        }
+
+        // Then the new branch is connected.
+        for blockhash in &new_branch {
+            match chain_sub.next().await {
+                Some(ChainEvent::Connected(block)) => assert_eq!(&block.block_hash(), blockhash),
+                other => panic!("expected a connected block, got {other:?}"),
+            }
+        }
+
+        // Explicitly drop the client here to prevent rustc from "optimizing" the code and dropping
+        // it earlier, aborting the producer thread
+        drop(client);
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn multiple_subscribers_receive_same_events() -> Result<(), Box<dyn std::error::Error>> {
@@ -530,6 +1388,83 @@ mod e2e_tests {
         Ok(())
     }
 
+    // Indexed subscriptions only deliver the transactions that they select.
+    #[tokio::test]
+    #[serial]
+    async fn indexed_subscriptions_deliver_selected_transactions(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        // Set up new bitcoind and zmq client instance.
+        let (client, bitcoind) = setup().await?;
+
+        // Mine 101 blocks so that the coins in the first block are spendable.
+        let mining_address = bitcoind.client.new_address()?;
+        let _ = bitcoind
+            .client
+            .generate_to_address(101, &mining_address)?
+            .into_model()?;
+        wait_for_height(&bitcoind, 101).await?;
+
+        // Subscribe to the transactions paying to a fresh address.
+        let watched_address = bitcoind.client.new_address()?;
+        let mut script_sub = client
+            .subscribe_script(watched_address.script_pubkey())
+            .await;
+
+        // Pay to another address first so that the watched transaction is not the first one to
+        // enter the mempool.
+        let other_address = bitcoind.client.new_address()?;
+        bitcoind
+            .client
+            .send_to_address(&other_address, bitcoin::Amount::ONE_BTC)?;
+        let txid = bitcoind
+            .client
+            .send_to_address(&watched_address, bitcoin::Amount::ONE_BTC)?
+            .txid()?;
+
+        let observed = script_sub.next().await.unwrap();
+        assert_eq!(observed.rawtx.compute_txid(), txid);
+        assert_eq!(observed.status, TxStatus::Mempool);
+
+        // Subscribe to the same transaction by txid and by one of the outpoints it spends.
+        let mut txid_sub = client.subscribe_txid(txid).await;
+        let mut spend_sub = client
+            .subscribe_spend(observed.rawtx.input[0].previous_output)
+            .await;
+        assert_eq!(client.num_tx_subscriptions().await, 3);
+
+        // The late subscriptions learn that the transaction is in the mempool right away.
+        for sub in [&mut txid_sub, &mut spend_sub] {
+            let observed = sub.next().await.unwrap();
+            assert_eq!(observed.rawtx.compute_txid(), txid);
+            assert_eq!(observed.status, TxStatus::Mempool);
+        }
+
+        // Mining the transaction is reported to all three subscriptions.
+        let blockhash = bitcoind
+            .client
+            .generate_to_address(1, &mining_address)?
+            .into_model()?
+            .0
+            .remove(0);
+        let mined = TxStatus::Mined {
+            blockhash,
+            height: 102,
+        };
+        for sub in [&mut script_sub, &mut txid_sub, &mut spend_sub] {
+            let observed = sub.next().await.unwrap();
+            assert_eq!(observed.rawtx.compute_txid(), txid);
+            assert_eq!(observed.status, mined);
+        }
+
+        // Explicitly drop the client here to prevent rustc from "optimizing" the code and dropping
+        // it earlier, aborting the producer thread.
+        drop(client);
+
+        Ok(())
+    }
+
     // Exactly one Mined status is delivered per (transaction, block) pair (Uniqueness)
     #[tokio::test]
     #[serial]
@@ -763,20 +1698,10 @@ mod e2e_tests {
             }
         }
 
-        // Drop the subscription to trigger its removal from the BtcZmqClient.
+        // Drop the subscription to trigger its removal from the BtcZmqClient. This happens in the
+        // background without waiting for another matching event.
         drop(tx_sub);
 
-        // Assert that we still have an active subscription because we haven't yet processed an
-        // an event that would cause it prune the subscription.
-        assert_eq!(client.num_tx_subscriptions().await, 1);
-
-        // Mine a block, triggering a nominal Mined event for our active subscription, this should
-        // cause the subscription to be pruned.
-        bitcoind
-            .client
-            .generate_to_address(1, &new_address)
-            .unwrap();
-
         // Wait for our active subscription count to report 0.
         tokio::time::timeout(std::time::Duration::from_secs(10), async {
             loop {
@@ -791,6 +1716,152 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn buried_tx_subscriptions_closed() -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        // Set up new bitcoind and zmq client instance.
+        let (client, bitcoind) = setup().await?;
+
+        // Create a new address that will serve as the recipient of new transactions.
+        let new_address = bitcoind.client.new_address()?;
+
+        // Mine 101 blocks so that the coins in the first block are spendable.
+        let _ = bitcoind
+            .client
+            .generate_to_address(101, &new_address)?
+            .into_model()?;
+        wait_for_height(&bitcoind, 101).await?;
+
+        // Explicitly unsubscribing removes the subscription right away.
+        let tx_sub = client.subscribe_transactions(|_| true).await;
+        assert_eq!(client.num_tx_subscriptions().await, 1);
+        client.unsubscribe(tx_sub).await;
+        assert_eq!(client.num_tx_subscriptions().await, 0);
+
+        // Subscribe to a transaction that has yet to be mined.
+        let txid = bitcoind
+            .client
+            .send_to_address(&new_address, bitcoin::Amount::ONE_BTC)?
+            .txid()?;
+        let mut txid_sub = client.subscribe_txid(txid).await;
+        assert_eq!(client.num_tx_subscriptions().await, 1);
+
+        // Mine the transaction and bury it.
+        bitcoind
+            .client
+            .generate_to_address(1 + client.bury_depth(), &new_address)?;
+
+        // The Mempool event is only delivered if the transaction was accepted after subscribing.
+        let mut observed = txid_sub.next().await.unwrap();
+        if observed.status == TxStatus::Mempool {
+            observed = txid_sub.next().await.unwrap();
+        }
+        assert_eq!(observed.rawtx.compute_txid(), txid);
+        assert!(matches!(observed.status, TxStatus::Mined { .. }));
+
+        let observed = txid_sub.next().await.unwrap();
+        assert_eq!(observed.rawtx.compute_txid(), txid);
+        assert!(matches!(observed.status, TxStatus::Buried { .. }));
+
+        // Nothing can happen to the transaction anymore so the subscription is closed.
+        assert!(txid_sub.next().await.is_none());
+        assert_eq!(client.num_tx_subscriptions().await, 0);
+
+        // Explicitly drop the client here to prevent rustc from "optimizing" the code and dropping
+        // it earlier, aborting the producer thread.
+        drop(client);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn late_subscriptions_receive_current_status() -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        let mut bitcoin_conf = corepc_node::Conf::default();
+        bitcoin_conf.enable_zmq = true;
+        bitcoin_conf.args.extend(vec![
+            "-zmqpubrawblock=tcp://127.0.0.1:23884",
+            "-zmqpubrawtx=tcp://127.0.0.1:23885",
+            "-zmqpubsequence=tcp://127.0.0.1:23886",
+            "-debug=zmq",
+        ]);
+        let bitcoind = corepc_node::Node::from_downloaded_with_conf(&bitcoin_conf)?;
+        let new_address = bitcoind.client.new_address()?;
+
+        // Mine 101 blocks so that the coins in the first block are spendable.
+        let _ = bitcoind
+            .client
+            .generate_to_address(101, &new_address)?
+            .into_model()?;
+        wait_for_height(&bitcoind, 101).await?;
+
+        // This transaction enters the mempool before the client connects.
+        let early_txid = bitcoind
+            .client
+            .send_to_address(&new_address, bitcoin::Amount::ONE_BTC)?
+            .txid()?;
+
+        let cookie = bitcoind
+            .params
+            .get_cookie_values()?
+            .expect("bitcoind must use cookie authentication");
+        let cfg = BtcZmqConfig::default()
+            .with_bury_depth(DEFAULT_BURY_DEPTH)
+            .with_rawblock_connection_string("tcp://127.0.0.1:23884")
+            .with_rawtx_connection_string("tcp://127.0.0.1:23885")
+            .with_sequence_connection_string("tcp://127.0.0.1:23886")
+            .with_rpc_connection(&bitcoind.rpc_url(), &cookie.user, &cookie.password)
+            .with_mempool_tracking(true);
+        let client = BtcZmqClient::connect(&cfg, None).await?;
+
+        // This transaction enters the mempool while no subscription selects it.
+        let late_txid = bitcoind
+            .client
+            .send_to_address(&new_address, bitcoin::Amount::ONE_BTC)?
+            .txid()?;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        for txid in [early_txid, late_txid] {
+            let mut txid_sub = client.subscribe_txid(txid).await;
+            let observed = txid_sub.next().await.unwrap();
+            assert_eq!(observed.rawtx.compute_txid(), txid);
+            assert_eq!(observed.status, TxStatus::Mempool);
+        }
+
+        // Once mined, the transactions are reported as such to the new subscriptions.
+        let blockhash = bitcoind
+            .client
+            .generate_to_address(1, &new_address)?
+            .into_model()?
+            .0
+            .remove(0);
+        wait_for_height(&bitcoind, 102).await?;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        for txid in [early_txid, late_txid] {
+            let mut txid_sub = client.subscribe_txid(txid).await;
+            let observed = txid_sub.next().await.unwrap();
+            assert_eq!(observed.rawtx.compute_txid(), txid);
+            assert_eq!(
+                observed.status,
+                TxStatus::Mined {
+                    blockhash,
+                    height: 102
+                }
+            );
+        }
+
+        // Explicitly drop the client here to prevent rustc from "optimizing" the code and dropping
+        // it earlier, aborting the producer thread.
+        drop(client);
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn dropped_block_subscriptions_pruned() -> Result<(), Box<dyn std::error::Error>> {
@@ -847,4 +1918,113 @@ mod e2e_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn catch_up_replays_missed_blocks_once() -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        let mut bitcoin_conf = corepc_node::Conf::default();
+        bitcoin_conf.enable_zmq = true;
+        bitcoin_conf.args.extend(vec![
+            "-zmqpubrawblock=tcp://127.0.0.1:23884",
+            "-zmqpubrawtx=tcp://127.0.0.1:23885",
+            "-zmqpubsequence=tcp://127.0.0.1:23886",
+            "-debug=zmq",
+        ]);
+        let bitcoind = corepc_node::Node::from_downloaded_with_conf(&bitcoin_conf)?;
+        let new_address = bitcoind.client.new_address()?;
+
+        // Mine some blocks before the client connects.
+        let missed = bitcoind
+            .client
+            .generate_to_address(10, &new_address)?
+            .into_model()?;
+        wait_for_height(&bitcoind, 10).await?;
+
+        let cookie = bitcoind
+            .params
+            .get_cookie_values()?
+            .expect("bitcoind must use cookie authentication");
+        let cfg = BtcZmqConfig::default()
+            .with_bury_depth(DEFAULT_BURY_DEPTH)
+            .with_rawblock_connection_string("tcp://127.0.0.1:23884")
+            .with_rawtx_connection_string("tcp://127.0.0.1:23885")
+            .with_sequence_connection_string("tcp://127.0.0.1:23886")
+            .with_rpc_connection(&bitcoind.rpc_url(), &cookie.user, &cookie.password);
+
+        // Start from the third of the missed blocks.
+        let client = BtcZmqClient::connect(&cfg, Some(StartBlock::Height(3))).await?;
+        let mut block_sub = client.subscribe_blocks().await;
+
+        // Blocks mined after connecting but before catching up must be delivered exactly once as
+        // well.
+        let live = bitcoind
+            .client
+            .generate_to_address(2, &new_address)?
+            .into_model()?;
+        client.catch_up();
+
+        for expected in missed.0.iter().skip(2).chain(live.0.iter()) {
+            let blk = tokio::time::timeout(std::time::Duration::from_secs(10), block_sub.next())
+                .await?
+                .map(|b| b.block_hash());
+            assert_eq!(Some(expected), blk.as_ref());
+        }
+
+        // The ZMQ notifications of the replayed blocks must have been skipped.
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert!(matches!(futures::poll!(block_sub.next()), Poll::Pending));
+
+        drop(client);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn missed_blocks_are_resynchronized() -> Result<(), Box<dyn std::error::Error>> {
+        logging::init(LoggerConfig::new("btc-notify".to_string()));
+
+        let mut bitcoin_conf = corepc_node::Conf::default();
+        bitcoin_conf.enable_zmq = true;
+        bitcoin_conf
+            .args
+            .extend(vec!["-zmqpubrawtx=tcp://127.0.0.1:23885", "-debug=zmq"]);
+        let bitcoind = corepc_node::Node::from_downloaded_with_conf(&bitcoin_conf)?;
+        let new_address = bitcoind.client.new_address()?;
+
+        let cookie = bitcoind
+            .params
+            .get_cookie_values()?
+            .expect("bitcoind must use cookie authentication");
+        // No block notifications are published, so blocks can only be learned about through the
+        // heartbeat.
+        let cfg = BtcZmqConfig::default()
+            .with_bury_depth(DEFAULT_BURY_DEPTH)
+            .with_rawtx_connection_string("tcp://127.0.0.1:23885")
+            .with_rpc_connection(&bitcoind.rpc_url(), &cookie.user, &cookie.password)
+            .with_heartbeat_timeout(std::time::Duration::from_secs(1));
+
+        let client = BtcZmqClient::connect(&cfg, None).await?;
+        let mut block_sub = client.subscribe_blocks().await;
+        assert_eq!(client.health(), NotifierHealth::Healthy);
+
+        let mined = bitcoind
+            .client
+            .generate_to_address(3, &new_address)?
+            .into_model()?;
+
+        for expected in mined.0.iter() {
+            let blk = tokio::time::timeout(std::time::Duration::from_secs(10), block_sub.next())
+                .await?
+                .map(|b| b.block_hash());
+            assert_eq!(Some(expected), blk.as_ref());
+        }
+        assert_eq!(client.health(), NotifierHealth::Healthy);
+
+        drop(client);
+
+        Ok(())
+    }
 }
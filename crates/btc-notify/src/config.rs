@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
-use crate::constants::DEFAULT_BURY_DEPTH;
+use crate::constants::{DEFAULT_BURY_DEPTH, DEFAULT_HEARTBEAT_TIMEOUT};
 
 /// Main configuration type used to establish the connection with the ZMQ interface of Bitcoin.
 ///
@@ -9,6 +11,10 @@ use crate::constants::DEFAULT_BURY_DEPTH;
 /// streams going unmonitored. In the limit, this means that the default [`BtcZmqConfig`] will
 /// result in a [`crate::client::BtcZmqClient`] that does absolutely nothing (NOOP).
 ///
+/// Alternatively, the [`BtcNotifyBackend::RpcPolling`] backend can be selected for nodes that do
+/// not expose their ZMQ interface, in which case the connection strings are ignored and only the
+/// RPC connection is used.
+///
 /// You should construct a [`BtcZmqConfig`] with [`Default::default`] and modify it with the member
 /// methods on this struct.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -30,6 +36,56 @@ pub struct BtcZmqConfig {
 
     /// Connection string used in `bitcoin.conf => zmqpubsequence`.
     pub(crate) sequence_connection_string: Option<String>,
+
+    /// Connection details of the RPC interface used to catch up with the chain tip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rpc: Option<BtcRpcConfig>,
+
+    /// The source of the chain notifications, defaults to [`BtcNotifyBackend::Zmq`].
+    #[serde(default)]
+    pub(crate) backend: BtcNotifyBackend,
+
+    /// The time without any ZMQ notification after which the chain tip is checked over RPC,
+    /// defaults to [`DEFAULT_HEARTBEAT_TIMEOUT`].
+    #[serde(default = "default_heartbeat_timeout")]
+    pub(crate) heartbeat_timeout: Duration,
+
+    /// Whether the mempool is looked up over RPC when connecting and kept in memory afterwards,
+    /// defaults to `false`.
+    #[serde(default)]
+    pub(crate) track_mempool: bool,
+}
+
+fn default_heartbeat_timeout() -> Duration {
+    DEFAULT_HEARTBEAT_TIMEOUT
+}
+
+/// The source of the chain notifications of a [`crate::client::BtcZmqClient`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BtcNotifyBackend {
+    /// Notifications are received from the ZMQ interface of `bitcoind`.
+    #[default]
+    Zmq,
+
+    /// The RPC interface of `bitcoind` is polled for new blocks and mempool transactions.
+    RpcPolling {
+        /// The time between two consecutive polls.
+        poll_interval: Duration,
+    },
+}
+
+/// Connection details of the RPC interface of `bitcoind`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct BtcRpcConfig {
+    /// The URL of the RPC interface.
+    pub(crate) url: String,
+
+    /// The RPC username.
+    pub(crate) user: String,
+
+    /// The RPC password.
+    pub(crate) pass: String,
 }
 
 impl BtcZmqConfig {
@@ -90,6 +146,60 @@ impl BtcZmqConfig {
         self.bury_depth = n;
         self
     }
+
+    /// Updates the [`BtcZmqConfig`] with the connection details of the RPC interface of
+    /// `bitcoind` and returns the updated config.
+    ///
+    /// Useful for a builder pattern with dotchaining.
+    ///
+    /// This is required to start a [`crate::client::BtcZmqClient`] from a past block or to use the
+    /// [`BtcNotifyBackend::RpcPolling`] backend.
+    pub fn with_rpc_connection(mut self, url: &str, user: &str, pass: &str) -> Self {
+        self.rpc = Some(BtcRpcConfig {
+            url: url.to_string(),
+            user: user.to_string(),
+            pass: pass.to_string(),
+        });
+        self
+    }
+
+    /// Updates the [`BtcZmqConfig`] to use the [`BtcNotifyBackend::RpcPolling`] backend with the
+    /// given poll interval and returns the updated config.
+    ///
+    /// Useful for a builder pattern with dotchaining.
+    ///
+    /// The RPC connection must be set with [`BtcZmqConfig::with_rpc_connection`] as well.
+    pub fn with_rpc_polling(mut self, poll_interval: Duration) -> Self {
+        self.backend = BtcNotifyBackend::RpcPolling { poll_interval };
+        self
+    }
+
+    /// Updates the [`BtcZmqConfig`] with a new heartbeat timeout and returns the updated config.
+    ///
+    /// Useful for a builder pattern with dotchaining.
+    ///
+    /// When no ZMQ notification is received for this long, the chain tip is checked over the RPC
+    /// connection and the client resynchronizes if it fell behind. This has no effect without an
+    /// RPC connection.
+    pub fn with_heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// Updates the [`BtcZmqConfig`] to enable or disable mempool tracking and returns the updated
+    /// config.
+    ///
+    /// Useful for a builder pattern with dotchaining.
+    ///
+    /// When enabled, the client takes a snapshot of the mempool over the RPC connection when it
+    /// connects so that the transactions that entered the mempool beforehand are reported as well.
+    /// It also keeps the mempool transactions that no subscription selects, so that subscriptions
+    /// added later learn about the ones they select. The RPC connection must be set with
+    /// [`BtcZmqConfig::with_rpc_connection`] as well.
+    pub fn with_mempool_tracking(mut self, track_mempool: bool) -> Self {
+        self.track_mempool = track_mempool;
+        self
+    }
 }
 
 impl Default for BtcZmqConfig {
@@ -101,6 +211,10 @@ impl Default for BtcZmqConfig {
             rawblock_connection_string: None,
             rawtx_connection_string: None,
             sequence_connection_string: None,
+            rpc: None,
+            backend: BtcNotifyBackend::Zmq,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            track_mempool: false,
         }
     }
 }
@@ -1,8 +1,20 @@
 //! This module provides the constant values used throughout the crate.
 
+use std::time::Duration;
+
 /// Default depth at which a block is considered "buried".
 ///
 /// This can be overridden everywhere it is used.
 // TODO(proofofkeags), TODO(Rajil1213): Use different default finality depths depending on the
 // network we are on?
 pub(crate) const DEFAULT_BURY_DEPTH: usize = 6;
+
+/// Time to wait before retrying a failed RPC request while catching up with the chain tip.
+pub(crate) const CATCH_UP_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default time without any ZMQ notification after which the client checks with `bitcoind` that
+/// it did not miss any block.
+pub(crate) const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Height of the first block of a [`crate::simulation::SimulatedChain`].
+pub(crate) const SIMULATED_START_HEIGHT: u64 = 100;
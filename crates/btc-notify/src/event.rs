@@ -1,4 +1,4 @@
-use bitcoin::{BlockHash, Transaction};
+use bitcoin::{Block, BlockHash, Transaction};
 
 /// TxStatus is the primary output of this API via the subscription.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -57,3 +57,31 @@ pub struct TxEvent {
     /// The new [`TxStatus`] that this event is reporting for the transaction.
     pub status: TxStatus,
 }
+
+/// Type that is emitted to Subscriptions created with
+/// [`crate::client::BtcZmqClient::subscribe_chain_events`].
+///
+/// It describes a single movement of the chain tip. Events are emitted in the order in which they
+/// happened so a reorg shows up as a series of [`ChainEvent::Disconnected`] events, from the old
+/// tip down to the fork point, followed by the [`ChainEvent::Connected`] events of the new branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// The block was connected on top of the chain tip.
+    Connected(Block),
+
+    /// The block at the chain tip was disconnected due to a reorg.
+    Disconnected {
+        /// The hash of the disconnected block.
+        hash: BlockHash,
+
+        /// The height of the disconnected block.
+        height: u64,
+
+        /// The number of blocks that have been disconnected since the last connected block,
+        /// including this one.
+        ///
+        /// This is 1 for the first block that is disconnected in a reorg and equals the depth of
+        /// the reorg for the last one.
+        reorg_depth: usize,
+    },
+}
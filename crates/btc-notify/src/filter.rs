@@ -0,0 +1,420 @@
+//! This module contains the filters that select the transactions that subscribers are interested
+//! in.
+//!
+//! Spends, txids and scripts are matched through ordered indices so that the cost of matching a
+//! transaction grows with its number of inputs and outputs rather than with the number of
+//! subscriptions. Only the [`TxPredicate`]s have to be evaluated one by one.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::Arc,
+};
+
+use bitcoin::{OutPoint, ScriptBuf, Transaction, Txid};
+
+/// Type synonym to capture predicates of the following form: Transaction -> bool.
+///
+/// The choice of using an arc here is intentional so that we can directly compare these predicates
+/// (via [`Arc::ptr_eq`]) when managing the active subscription set.
+pub type TxPredicate = Arc<dyn Fn(&Transaction) -> bool + Sync + Send>;
+
+/// Selects the transactions that a subscription is interested in.
+#[derive(Clone)]
+pub(crate) enum TxFilter {
+    /// Transactions that spend the given outpoint.
+    Spend(OutPoint),
+
+    /// The transaction with the given txid.
+    Txid(Txid),
+
+    /// Transactions that have an output locked by the given script.
+    Script(ScriptBuf),
+
+    /// Transactions for which the predicate holds.
+    Predicate(TxPredicate),
+}
+
+// Coverage is disabled because when tests pass, most Debug impls will never be invoked.
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl fmt::Debug for TxFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxFilter::Spend(outpoint) => f.debug_tuple("Spend").field(outpoint).finish(),
+            TxFilter::Txid(txid) => f.debug_tuple("Txid").field(txid).finish(),
+            TxFilter::Script(script) => f.debug_tuple("Script").field(script).finish(),
+            TxFilter::Predicate(pred) => f
+                .debug_tuple("Predicate")
+                .field(&format!("{:?}", Arc::as_ptr(pred)))
+                .finish(),
+        }
+    }
+}
+
+impl TxFilter {
+    /// Returns whether the transaction is selected by this filter.
+    pub(crate) fn matches(&self, tx: &Transaction) -> bool {
+        match self {
+            TxFilter::Spend(outpoint) => tx
+                .input
+                .iter()
+                .any(|txin| txin.previous_output == *outpoint),
+            TxFilter::Txid(txid) => tx.compute_txid() == *txid,
+            TxFilter::Script(script) => {
+                tx.output.iter().any(|txout| txout.script_pubkey == *script)
+            }
+            TxFilter::Predicate(pred) => pred(tx),
+        }
+    }
+}
+
+/// The disjunction of a multiset of [`TxFilter`]s.
+///
+/// The same filter may be added several times, in which case it has to be removed as many times
+/// before it stops matching.
+#[derive(Clone, Default)]
+pub(crate) struct TxFilterSet {
+    spends: BTreeMap<OutPoint, usize>,
+    txids: BTreeMap<Txid, usize>,
+    scripts: BTreeMap<ScriptBuf, usize>,
+    predicates: Vec<TxPredicate>,
+}
+
+// Coverage is disabled because when tests pass, most Debug impls will never be invoked.
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl fmt::Debug for TxFilterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TxFilterSet")
+            .field("spends", &self.spends)
+            .field("txids", &self.txids)
+            .field("scripts", &self.scripts)
+            .field(
+                "predicates",
+                &self
+                    .predicates
+                    .iter()
+                    .map(|p| format!("{:?}", Arc::as_ptr(p)))
+                    .collect::<Vec<String>>(),
+            )
+            .finish()
+    }
+}
+
+impl PartialEq for TxFilterSet {
+    fn eq(&self, other: &Self) -> bool {
+        let predicates_eq = self.predicates.len() == other.predicates.len()
+            && self
+                .predicates
+                .iter()
+                .zip(other.predicates.iter())
+                .all(|(a, b)| Arc::ptr_eq(a, b));
+
+        predicates_eq
+            && self.spends == other.spends
+            && self.txids == other.txids
+            && self.scripts == other.scripts
+    }
+}
+
+impl Eq for TxFilterSet {}
+
+impl TxFilterSet {
+    /// Adds a filter to the set.
+    pub(crate) fn insert(&mut self, filter: TxFilter) {
+        match filter {
+            TxFilter::Spend(outpoint) => *self.spends.entry(outpoint).or_default() += 1,
+            TxFilter::Txid(txid) => *self.txids.entry(txid).or_default() += 1,
+            TxFilter::Script(script) => *self.scripts.entry(script).or_default() += 1,
+            TxFilter::Predicate(pred) => self.predicates.push(pred),
+        }
+    }
+
+    /// Removes one occurrence of a filter that was previously added via [`TxFilterSet::insert`].
+    pub(crate) fn remove(&mut self, filter: &TxFilter) {
+        match filter {
+            TxFilter::Spend(outpoint) => decrement(&mut self.spends, outpoint),
+            TxFilter::Txid(txid) => decrement(&mut self.txids, txid),
+            TxFilter::Script(script) => decrement(&mut self.scripts, script),
+            TxFilter::Predicate(pred) => {
+                if let Some(idx) = self.predicates.iter().position(|p| Arc::ptr_eq(p, pred)) {
+                    self.predicates.swap_remove(idx);
+                }
+            }
+        }
+    }
+
+    /// Returns whether the transaction is selected by any of the filters in the set.
+    pub(crate) fn matches(&self, tx: &Transaction) -> bool {
+        (!self.txids.is_empty() && self.txids.contains_key(&tx.compute_txid()))
+            || (!self.spends.is_empty()
+                && tx
+                    .input
+                    .iter()
+                    .any(|txin| self.spends.contains_key(&txin.previous_output)))
+            || (!self.scripts.is_empty()
+                && tx
+                    .output
+                    .iter()
+                    .any(|txout| self.scripts.contains_key(&txout.script_pubkey)))
+            || self.predicates.iter().any(|pred| pred(tx))
+    }
+}
+
+/// Decrements the count of a key, removing it once it reaches zero.
+fn decrement<K: Ord>(counts: &mut BTreeMap<K, usize>, key: &K) {
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+/// Maps the transactions to the subscriptions, identified by keys of type `K`, whose
+/// [`TxFilter`]s select them.
+#[derive(Clone)]
+pub(crate) struct TxFilterIndex<K> {
+    spends: BTreeMap<OutPoint, BTreeSet<K>>,
+    txids: BTreeMap<Txid, BTreeSet<K>>,
+    scripts: BTreeMap<ScriptBuf, BTreeSet<K>>,
+    predicates: BTreeMap<K, TxPredicate>,
+}
+
+// Coverage is disabled because when tests pass, most Debug impls will never be invoked.
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl<K: fmt::Debug> fmt::Debug for TxFilterIndex<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TxFilterIndex")
+            .field("spends", &self.spends)
+            .field("txids", &self.txids)
+            .field("scripts", &self.scripts)
+            .field(
+                "predicates",
+                &self
+                    .predicates
+                    .iter()
+                    .map(|(k, p)| (k, format!("{:?}", Arc::as_ptr(p))))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<K> Default for TxFilterIndex<K> {
+    fn default() -> Self {
+        Self {
+            spends: BTreeMap::new(),
+            txids: BTreeMap::new(),
+            scripts: BTreeMap::new(),
+            predicates: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Ord + Copy> TxFilterIndex<K> {
+    /// Registers the filter of the subscription with the given key.
+    pub(crate) fn insert(&mut self, key: K, filter: &TxFilter) {
+        match filter {
+            TxFilter::Spend(outpoint) => {
+                self.spends.entry(*outpoint).or_default().insert(key);
+            }
+            TxFilter::Txid(txid) => {
+                self.txids.entry(*txid).or_default().insert(key);
+            }
+            TxFilter::Script(script) => {
+                self.scripts.entry(script.clone()).or_default().insert(key);
+            }
+            TxFilter::Predicate(pred) => {
+                self.predicates.insert(key, pred.clone());
+            }
+        }
+    }
+
+    /// Unregisters the filter of the subscription with the given key.
+    pub(crate) fn remove(&mut self, key: K, filter: &TxFilter) {
+        match filter {
+            TxFilter::Spend(outpoint) => remove_key(&mut self.spends, outpoint, &key),
+            TxFilter::Txid(txid) => remove_key(&mut self.txids, txid, &key),
+            TxFilter::Script(script) => remove_key(&mut self.scripts, script, &key),
+            TxFilter::Predicate(_) => {
+                self.predicates.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the keys of the subscriptions whose filters select the transaction.
+    pub(crate) fn matching(&self, tx: &Transaction) -> BTreeSet<K> {
+        let mut keys = BTreeSet::new();
+
+        if !self.txids.is_empty() {
+            if let Some(subs) = self.txids.get(&tx.compute_txid()) {
+                keys.extend(subs.iter().copied());
+            }
+        }
+
+        if !self.spends.is_empty() {
+            for txin in &tx.input {
+                if let Some(subs) = self.spends.get(&txin.previous_output) {
+                    keys.extend(subs.iter().copied());
+                }
+            }
+        }
+
+        if !self.scripts.is_empty() {
+            for txout in &tx.output {
+                if let Some(subs) = self.scripts.get(&txout.script_pubkey) {
+                    keys.extend(subs.iter().copied());
+                }
+            }
+        }
+
+        keys.extend(
+            self.predicates
+                .iter()
+                .filter(|(_, pred)| pred(tx))
+                .map(|(key, _)| *key),
+        );
+
+        keys
+    }
+}
+
+/// Removes a key from the set stored under `index_key`, dropping the set once it is empty.
+fn remove_key<I: Ord, K: Ord>(index: &mut BTreeMap<I, BTreeSet<K>>, index_key: &I, key: &K) {
+    if let Some(keys) = index.get_mut(index_key) {
+        keys.remove(key);
+        if keys.is_empty() {
+            index.remove(index_key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod prop_tests {
+    use std::sync::Arc;
+
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction, Amount, OutPoint, ScriptBuf, Sequence,
+        Transaction, TxIn, TxOut, Txid, Witness,
+    };
+    use proptest::prelude::*;
+
+    use super::{TxFilter, TxFilterIndex, TxFilterSet};
+
+    // Generates a transaction that spends and pays to a handful of outpoints and scripts drawn
+    // from small pools so that the filters below have a fair chance of matching it.
+    prop_compose! {
+        fn arb_transaction()(
+            spends in prop::collection::vec((0..8u8, 0..4u32), 1..4),
+            scripts in prop::collection::vec(0..8u8, 1..4),
+        ) -> Transaction {
+            Transaction {
+                version: transaction::Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: spends
+                    .into_iter()
+                    .map(|(txid, vout)| TxIn {
+                        previous_output: OutPoint {
+                            txid: Txid::from_byte_array([txid; 32]),
+                            vout,
+                        },
+                        script_sig: ScriptBuf::new(),
+                        sequence: Sequence::MAX,
+                        witness: Witness::new(),
+                    })
+                    .collect(),
+                output: scripts
+                    .into_iter()
+                    .map(|script| TxOut {
+                        value: Amount::from_sat(1_000),
+                        script_pubkey: ScriptBuf::from_bytes(vec![script]),
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    // Generates a filter of any kind, drawing from the same pools as the transactions.
+    fn arb_filter(txs: Vec<Transaction>) -> BoxedStrategy<TxFilter> {
+        prop_oneof![
+            (0..8u8, 0..4u32).prop_map(|(txid, vout)| TxFilter::Spend(OutPoint {
+                txid: Txid::from_byte_array([txid; 32]),
+                vout,
+            })),
+            (0..8u8).prop_map(|script| TxFilter::Script(ScriptBuf::from_bytes(vec![script]))),
+            prop::sample::select(txs).prop_map(|tx| TxFilter::Txid(tx.compute_txid())),
+            (1..4usize).prop_map(|modsize| TxFilter::Predicate(Arc::new(
+                move |tx: &Transaction| { tx.output.len() % modsize == 0 }
+            ))),
+        ]
+        .boxed()
+    }
+
+    fn arb_filters_and_txs() -> BoxedStrategy<(Vec<TxFilter>, Vec<Transaction>)> {
+        prop::collection::vec(arb_transaction(), 1..8)
+            .prop_flat_map(|txs| {
+                (
+                    prop::collection::vec(arb_filter(txs.clone()), 0..16),
+                    Just(txs),
+                )
+            })
+            .boxed()
+    }
+
+    proptest! {
+        // Ensures that the indexed filter set selects exactly the transactions that are selected by
+        // at least one of its filters.
+        #[test]
+        fn filter_set_matches_disjunction((filters, txs) in arb_filters_and_txs()) {
+            let mut set = TxFilterSet::default();
+            for filter in filters.iter() {
+                set.insert(filter.clone());
+            }
+
+            for tx in txs.iter() {
+                prop_assert_eq!(set.matches(tx), filters.iter().any(|f| f.matches(tx)));
+            }
+        }
+
+        // Ensures that the index returns exactly the subscriptions whose filters select the
+        // transaction.
+        #[test]
+        fn filter_index_matches_subscriptions((filters, txs) in arb_filters_and_txs()) {
+            let mut index = TxFilterIndex::default();
+            for (key, filter) in filters.iter().enumerate() {
+                index.insert(key, filter);
+            }
+
+            for tx in txs.iter() {
+                let expected = filters
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| f.matches(tx))
+                    .map(|(key, _)| key)
+                    .collect();
+                prop_assert_eq!(index.matching(tx), expected);
+            }
+        }
+
+        // Ensures that removing every filter after adding it results in an empty filter set and
+        // index. (filter Invertibility)
+        #[test]
+        fn filter_removal_inverts_insertion((filters, _txs) in arb_filters_and_txs()) {
+            let mut set = TxFilterSet::default();
+            let mut index = TxFilterIndex::default();
+            for (key, filter) in filters.iter().enumerate() {
+                set.insert(filter.clone());
+                index.insert(key, filter);
+            }
+            for (key, filter) in filters.iter().enumerate() {
+                set.remove(filter);
+                index.remove(key, filter);
+            }
+
+            prop_assert_eq!(set, TxFilterSet::default());
+            prop_assert!(index.spends.is_empty());
+            prop_assert!(index.txids.is_empty());
+            prop_assert!(index.scripts.is_empty());
+            prop_assert!(index.predicates.is_empty());
+        }
+    }
+}
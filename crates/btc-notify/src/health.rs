@@ -0,0 +1,70 @@
+//! This module contains the types used to monitor whether the notifications received from
+//! `bitcoind` are complete.
+use std::collections::BTreeMap;
+
+/// The health of the notifications that a [`crate::client::BtcZmqClient`] receives from
+/// `bitcoind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifierHealth {
+    /// No notifications are known to be missing.
+    Healthy,
+
+    /// Notifications were missed and the client is resynchronizing with `bitcoind` over RPC.
+    Resyncing,
+
+    /// Notifications were missed and the client could not resynchronize, either because no RPC
+    /// connection is configured or because `bitcoind` could not be reached. Events may be missing
+    /// until the client manages to resynchronize.
+    Degraded,
+}
+
+/// Keeps track of the sequence numbers of the ZMQ messages of every topic in order to detect the
+/// messages that were dropped.
+///
+/// `bitcoind` numbers the messages of each topic consecutively, so a message whose sequence number
+/// doesn't follow the previous one of the same topic means that messages were lost in between.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceTracker {
+    last: BTreeMap<String, u32>,
+}
+
+impl SequenceTracker {
+    /// Records the sequence number of a message and returns the number of messages of the same
+    /// topic that were missed since the previous one.
+    pub(crate) fn observe(&mut self, topic: &str, sequence: u32) -> u32 {
+        match self.last.get_mut(topic) {
+            Some(last) => {
+                let missed = sequence.wrapping_sub(*last).wrapping_sub(1);
+                *last = sequence;
+                missed
+            }
+            None => {
+                self.last.insert(topic.to_string(), sequence);
+                0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SequenceTracker;
+
+    #[test]
+    fn detects_gaps_per_topic() {
+        let mut tracker = SequenceTracker::default();
+
+        // The first message of each topic sets the baseline.
+        assert_eq!(tracker.observe("rawblock", 5), 0);
+        assert_eq!(tracker.observe("rawtx", 0), 0);
+
+        assert_eq!(tracker.observe("rawblock", 6), 0);
+        assert_eq!(tracker.observe("rawtx", 3), 2);
+        assert_eq!(tracker.observe("rawblock", 7), 0);
+
+        // Sequence numbers wrap around.
+        assert_eq!(tracker.observe("sequence", u32::MAX), 0);
+        assert_eq!(tracker.observe("sequence", 0), 0);
+        assert_eq!(tracker.observe("sequence", 2), 1);
+    }
+}
@@ -11,5 +11,10 @@ pub mod client;
 mod config;
 mod constants;
 mod event;
+mod filter;
+mod health;
+mod polling;
+mod rpc;
+pub mod simulation;
 mod state_machine;
 pub mod subscription;
@@ -0,0 +1,505 @@
+//! This module contains the [`RpcPoller`] which is used in place of the ZMQ interface of `bitcoind`
+//! when it is not available.
+//!
+//! The poller compares the chain tip and the mempool against what it saw in the previous poll and
+//! translates the differences into the same [`Message`]s that the ZMQ interface would have
+//! published so that they can be fed to the same state machine.
+use std::collections::{BTreeMap, BTreeSet};
+
+use bitcoin::{BlockHash, Txid};
+use bitcoincore_zmq::{Message, SequenceMessage};
+use tracing::{debug, info, trace, warn};
+
+use crate::rpc::{BtcRpcClient, BtcRpcError};
+
+/// Polls the RPC interface of `bitcoind` for new blocks and mempool transactions.
+///
+/// The messages produced by the poller do not carry ZMQ sequence numbers, they are always zero.
+#[derive(Debug)]
+pub(crate) struct RpcPoller {
+    rpc_client: BtcRpcClient,
+
+    /// The number of blocks below the tip that are tracked in order to find the fork point when a
+    /// reorg happens.
+    max_reorg_depth: usize,
+
+    /// The hashes of the most recent blocks in the main chain, indexed by height.
+    chain: BTreeMap<u64, BlockHash>,
+
+    /// The txids of the transactions that were in the mempool as of the last poll.
+    mempool: BTreeSet<Txid>,
+
+    /// The mempool sequence number as of the last poll, if any.
+    mempool_sequence: Option<u64>,
+}
+
+impl RpcPoller {
+    /// Creates a new [`RpcPoller`] that reports the blocks and transactions that appear after this
+    /// call, same as a fresh ZMQ subscription.
+    pub(crate) async fn new(
+        rpc_client: BtcRpcClient,
+        max_reorg_depth: usize,
+    ) -> Result<Self, BtcRpcError> {
+        let tip_height = rpc_client.get_block_count().await?;
+        let tip_hash = rpc_client.get_block_hash(tip_height).await?;
+        let snapshot = rpc_client.get_raw_mempool().await?;
+        info!(%tip_height, %tip_hash, mempool_sequence=%snapshot.mempool_sequence, "initialized rpc poller");
+
+        Ok(RpcPoller {
+            rpc_client,
+            max_reorg_depth,
+            chain: BTreeMap::from([(tip_height, tip_hash)]),
+            mempool: snapshot.txids.into_iter().collect(),
+            mempool_sequence: Some(snapshot.mempool_sequence),
+        })
+    }
+
+    /// Creates a new [`RpcPoller`] whose first poll reports the changes with respect to the given
+    /// blocks and mempool transactions.
+    ///
+    /// This is used to resynchronize a client that missed notifications.
+    pub(crate) fn resume(
+        rpc_client: BtcRpcClient,
+        max_reorg_depth: usize,
+        chain: BTreeMap<u64, BlockHash>,
+        mempool: BTreeSet<Txid>,
+    ) -> Self {
+        RpcPoller {
+            rpc_client,
+            max_reorg_depth,
+            chain,
+            mempool,
+            mempool_sequence: None,
+        }
+    }
+
+    /// Returns the messages that describe the changes to the chain and the mempool since the
+    /// previous poll, in the order in which they should be processed.
+    ///
+    /// Disconnected blocks are reported from the newest to the oldest and connected blocks from the
+    /// oldest to the newest, followed by the mempool acceptances and removals.
+    pub(crate) async fn poll(&mut self) -> Result<Vec<Message>, BtcRpcError> {
+        // The mempool is looked up before the chain so that every transaction that left the mempool
+        // because it was mined is included in one of the blocks that are fetched below.
+        let snapshot = self.rpc_client.get_raw_mempool().await?;
+
+        let mut messages = Vec::new();
+        let mut mined = BTreeSet::new();
+
+        let best_hash = self.rpc_client.get_best_block_hash().await?;
+        if self.chain.last_key_value().map(|(_, hash)| *hash) != Some(best_hash) {
+            let tip_height = self.rpc_client.get_block_height(&best_hash).await?;
+
+            let mut fork_height = None;
+            for (&height, &hash) in self.chain.iter().rev() {
+                if height <= tip_height && self.rpc_client.get_block_hash(height).await? == hash {
+                    fork_height = Some(height);
+                    break;
+                }
+            }
+            let fork_height = fork_height.unwrap_or_else(|| {
+                let oldest = self.chain.first_key_value().map(|(height, _)| *height);
+                warn!(
+                    ?oldest,
+                    "reorg is deeper than the tracked blocks, disconnecting all of them"
+                );
+                oldest.unwrap_or(tip_height).saturating_sub(1)
+            });
+
+            for (height, blockhash) in self.chain.split_off(&(fork_height + 1)).into_iter().rev() {
+                debug!(%height, %blockhash, "block disconnected");
+                messages.push(Message::Sequence(
+                    SequenceMessage::BlockDisconnect { blockhash },
+                    0,
+                ));
+            }
+
+            for height in fork_height + 1..=tip_height {
+                let blockhash = self.rpc_client.get_block_hash(height).await?;
+                let block = self.rpc_client.get_block(&blockhash).await?;
+                debug!(%height, %blockhash, "block connected");
+
+                mined.extend(block.txdata.iter().map(|tx| tx.compute_txid()));
+                self.chain.insert(height, blockhash);
+                messages.push(Message::Block(block, 0));
+            }
+
+            while self.chain.len() > self.max_reorg_depth + 1 {
+                self.chain.pop_first();
+            }
+
+            // Transactions that were mined are reported by their blocks.
+            self.mempool.retain(|txid| !mined.contains(txid));
+        }
+
+        if Some(snapshot.mempool_sequence) != self.mempool_sequence {
+            let mempool_sequence = snapshot.mempool_sequence;
+            let mempool = snapshot
+                .txids
+                .into_iter()
+                .filter(|txid| !mined.contains(txid))
+                .collect::<BTreeSet<Txid>>();
+
+            for txid in mempool.difference(&self.mempool) {
+                match self.rpc_client.get_raw_transaction(txid).await {
+                    Ok(tx) => {
+                        trace!(%txid, "transaction entered the mempool");
+                        messages.push(Message::Tx(tx, 0));
+                        messages.push(Message::Sequence(
+                            SequenceMessage::MempoolAcceptance {
+                                txid: *txid,
+                                mempool_sequence,
+                            },
+                            0,
+                        ));
+                    }
+                    Err(e) => {
+                        debug!(%txid, %e, "transaction left the mempool before it could be fetched");
+                    }
+                }
+            }
+
+            for txid in self.mempool.difference(&mempool) {
+                trace!(%txid, "transaction left the mempool");
+                messages.push(Message::Sequence(
+                    SequenceMessage::MempoolRemoval {
+                        txid: *txid,
+                        mempool_sequence,
+                    },
+                    0,
+                ));
+            }
+
+            self.mempool = mempool;
+            self.mempool_sequence = Some(mempool_sequence);
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bitcoin::{
+        absolute::LockTime,
+        block::{Header, Version},
+        consensus::encode::serialize_hex,
+        hashes::Hash,
+        transaction, Amount, Block, CompactTarget, OutPoint, ScriptBuf, Sequence, Transaction,
+        TxIn, TxMerkleNode, TxOut, Witness,
+    };
+    use corepc_node::serde_json::json;
+    use jsonrpsee::{
+        server::{Server, ServerHandle},
+        types::ErrorObjectOwned,
+        RpcModule,
+    };
+
+    use super::*;
+    use crate::config::BtcRpcConfig;
+
+    /// The state of the node served by the mock RPC server.
+    #[derive(Debug, Default)]
+    struct MockNode {
+        chain: Vec<Block>,
+        mempool: Vec<Transaction>,
+        mempool_sequence: u64,
+    }
+
+    impl MockNode {
+        fn accept(&mut self, tx: Transaction) {
+            self.mempool.push(tx);
+            self.mempool_sequence += 1;
+        }
+
+        fn evict(&mut self, txid: Txid) {
+            self.mempool.retain(|tx| tx.compute_txid() != txid);
+            self.mempool_sequence += 1;
+        }
+
+        /// Mines a block on top of the block at the given height with the given nonce, discarding
+        /// the blocks above it, and returns its hash.
+        fn mine_at(&mut self, height: usize, nonce: u32, txdata: Vec<Transaction>) -> BlockHash {
+            self.chain.truncate(height);
+            let txids = txdata
+                .iter()
+                .map(Transaction::compute_txid)
+                .collect::<Vec<_>>();
+            self.mempool
+                .retain(|tx| !txids.contains(&tx.compute_txid()));
+            self.mempool_sequence += 1;
+
+            let prev_blockhash = self
+                .chain
+                .last()
+                .map(Block::block_hash)
+                .unwrap_or(BlockHash::all_zeros());
+            let block = Block {
+                header: Header {
+                    version: Version::TWO,
+                    prev_blockhash,
+                    merkle_root: TxMerkleNode::all_zeros(),
+                    time: height as u32,
+                    bits: CompactTarget::from_consensus(0x207fffff),
+                    nonce,
+                },
+                txdata,
+            };
+            let blockhash = block.block_hash();
+            self.chain.push(block);
+
+            blockhash
+        }
+    }
+
+    fn tx(nonce: u32) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: LockTime::from_consensus(nonce),
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    fn not_found() -> ErrorObjectOwned {
+        ErrorObjectOwned::owned(-5, "not found", None::<()>)
+    }
+
+    /// Serves the subset of the RPC interface of `bitcoind` that the poller uses.
+    async fn serve(
+        node: Arc<Mutex<MockNode>>,
+    ) -> Result<(BtcRpcClient, ServerHandle), Box<dyn std::error::Error>> {
+        let mut module = RpcModule::new(node);
+        module.register_method("getblockcount", |_, node, _| {
+            node.lock().unwrap().chain.len() as u64 - 1
+        })?;
+        module.register_method("getbestblockhash", |_, node, _| {
+            node.lock().unwrap().chain.last().map(Block::block_hash)
+        })?;
+        module.register_method("getblockhash", |params, node, _| {
+            let (height,) = params.parse::<(usize,)>()?;
+            node.lock()
+                .unwrap()
+                .chain
+                .get(height)
+                .map(Block::block_hash)
+                .ok_or_else(not_found)
+        })?;
+        module.register_method("getblockheader", |params, node, _| {
+            let (hash, _) = params.parse::<(BlockHash, bool)>()?;
+            node.lock()
+                .unwrap()
+                .chain
+                .iter()
+                .position(|block| block.block_hash() == hash)
+                .map(|height| json!({ "height": height }))
+                .ok_or_else(not_found)
+        })?;
+        module.register_method("getblock", |params, node, _| {
+            let (hash, _) = params.parse::<(BlockHash, u8)>()?;
+            node.lock()
+                .unwrap()
+                .chain
+                .iter()
+                .find(|block| block.block_hash() == hash)
+                .map(serialize_hex)
+                .ok_or_else(not_found)
+        })?;
+        module.register_method("getrawmempool", |_, node, _| {
+            let node = node.lock().unwrap();
+            json!({
+                "txids": node.mempool.iter().map(Transaction::compute_txid).collect::<Vec<_>>(),
+                "mempool_sequence": node.mempool_sequence,
+            })
+        })?;
+        module.register_method("getrawtransaction", |params, node, _| {
+            let (txid, _) = params.parse::<(Txid, bool)>()?;
+            node.lock()
+                .unwrap()
+                .mempool
+                .iter()
+                .find(|tx| tx.compute_txid() == txid)
+                .map(serialize_hex)
+                .ok_or_else(not_found)
+        })?;
+
+        let server = Server::builder().build("127.0.0.1:0").await?;
+        let cfg = BtcRpcConfig {
+            url: format!("http://{}", server.local_addr()?),
+            user: "user".to_string(),
+            pass: "pass".to_string(),
+        };
+
+        Ok((BtcRpcClient::new(&cfg)?, server.start(module)))
+    }
+
+    /// Summarizes the messages so that they can be compared in assertions.
+    fn summarize(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|msg| match msg {
+                Message::Block(block, _) => format!("connect {}", block.block_hash()),
+                Message::Tx(tx, _) => format!("tx {}", tx.compute_txid()),
+                Message::Sequence(SequenceMessage::BlockDisconnect { blockhash }, _) => {
+                    format!("disconnect {blockhash}")
+                }
+                Message::Sequence(SequenceMessage::MempoolAcceptance { txid, .. }, _) => {
+                    format!("accept {txid}")
+                }
+                Message::Sequence(SequenceMessage::MempoolRemoval { txid, .. }, _) => {
+                    format!("remove {txid}")
+                }
+                other => panic!("unexpected message: {other:?}"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reports_blocks_and_mempool_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let node = Arc::new(Mutex::new(MockNode::default()));
+        node.lock().unwrap().mine_at(0, 0, vec![tx(0)]);
+        let (rpc_client, _server) = serve(node.clone()).await?;
+
+        let mut poller = RpcPoller::new(rpc_client, 6).await?;
+        assert!(poller.poll().await?.is_empty());
+
+        // A transaction enters the mempool.
+        let mined_tx = tx(1);
+        let mined_txid = mined_tx.compute_txid();
+        node.lock().unwrap().accept(mined_tx.clone());
+        assert_eq!(
+            summarize(&poller.poll().await?),
+            vec![format!("tx {mined_txid}"), format!("accept {mined_txid}")]
+        );
+
+        // The transaction is mined, which must not be reported as a removal from the mempool.
+        let blockhash = node.lock().unwrap().mine_at(1, 0, vec![tx(2), mined_tx]);
+        assert_eq!(
+            summarize(&poller.poll().await?),
+            vec![format!("connect {blockhash}")]
+        );
+
+        // Another transaction enters the mempool and is evicted.
+        let evicted_tx = tx(3);
+        let evicted_txid = evicted_tx.compute_txid();
+        node.lock().unwrap().accept(evicted_tx);
+        assert_eq!(
+            summarize(&poller.poll().await?),
+            vec![
+                format!("tx {evicted_txid}"),
+                format!("accept {evicted_txid}")
+            ]
+        );
+        node.lock().unwrap().evict(evicted_txid);
+        assert_eq!(
+            summarize(&poller.poll().await?),
+            vec![format!("remove {evicted_txid}")]
+        );
+
+        assert!(poller.poll().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_reorgs_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        let node = Arc::new(Mutex::new(MockNode::default()));
+        node.lock().unwrap().mine_at(0, 0, vec![tx(0)]);
+        let (rpc_client, _server) = serve(node.clone()).await?;
+
+        let mut poller = RpcPoller::new(rpc_client, 6).await?;
+
+        // Several blocks are mined between two polls.
+        let old_chain = {
+            let mut node = node.lock().unwrap();
+            (1..4)
+                .map(|height| node.mine_at(height, 0, vec![tx(height as u32)]))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summarize(&poller.poll().await?),
+            old_chain
+                .iter()
+                .map(|hash| format!("connect {hash}"))
+                .collect::<Vec<_>>()
+        );
+
+        // Replace all the blocks above the first one with a longer fork.
+        let new_chain = {
+            let mut node = node.lock().unwrap();
+            (1..5)
+                .map(|height| node.mine_at(height, 1, vec![tx(10 + height as u32)]))
+                .collect::<Vec<_>>()
+        };
+
+        let expected = old_chain
+            .iter()
+            .rev()
+            .map(|hash| format!("disconnect {hash}"))
+            .chain(new_chain.iter().map(|hash| format!("connect {hash}")))
+            .collect::<Vec<_>>();
+        assert_eq!(summarize(&poller.poll().await?), expected);
+
+        Ok(())
+    }
+    #[tokio::test]
+    async fn resumed_poller_reports_missed_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let node = Arc::new(Mutex::new(MockNode::default()));
+        let (genesis, stale) = {
+            let mut node = node.lock().unwrap();
+            (
+                node.mine_at(0, 0, vec![tx(0)]),
+                node.mine_at(1, 0, vec![tx(1)]),
+            )
+        };
+        let (rpc_client, _server) = serve(node.clone()).await?;
+
+        // The stale block is replaced and transactions enter the mempool while notifications are
+        // missed.
+        let (seen_tx, missed_tx) = (tx(20), tx(21));
+        let new_chain = {
+            let mut node = node.lock().unwrap();
+            let new_chain = (1..3)
+                .map(|height| node.mine_at(height, 1, vec![tx(10 + height as u32)]))
+                .collect::<Vec<_>>();
+            node.accept(seen_tx.clone());
+            node.accept(missed_tx.clone());
+            new_chain
+        };
+
+        // The view of the client includes the stale block, one of the new transactions and a
+        // transaction that has since left the mempool.
+        let evicted_txid = tx(99).compute_txid();
+        let mut poller = RpcPoller::resume(
+            rpc_client,
+            6,
+            BTreeMap::from([(0, genesis), (1, stale)]),
+            BTreeSet::from([seen_tx.compute_txid(), evicted_txid]),
+        );
+
+        let missed_txid = missed_tx.compute_txid();
+        let expected = std::iter::once(format!("disconnect {stale}"))
+            .chain(new_chain.iter().map(|hash| format!("connect {hash}")))
+            .chain([
+                format!("tx {missed_txid}"),
+                format!("accept {missed_txid}"),
+                format!("remove {evicted_txid}"),
+            ])
+            .collect::<Vec<_>>();
+        assert_eq!(summarize(&poller.poll().await?), expected);
+
+        assert!(poller.poll().await?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! This module contains a minimal client for the JSON-RPC interface of `bitcoind`.
+//!
+//! It only implements the handful of read-only calls that are needed to catch up with the chain tip
+//! and to poll for new blocks and mempool transactions.
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bitcoin::{consensus::encode::deserialize_hex, Block, BlockHash, Transaction, Txid};
+use jsonrpsee::{
+    core::{client::ClientT, ClientError},
+    http_client::{HeaderMap, HeaderValue, HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::config::BtcRpcConfig;
+
+/// Error type for the [`BtcRpcClient`] methods.
+#[derive(Debug, Error)]
+pub(crate) enum BtcRpcError {
+    /// The request could not be sent or was rejected by `bitcoind`.
+    #[error("rpc request failed: {0}")]
+    Request(#[from] ClientError),
+
+    /// The response could not be decoded.
+    #[error("could not decode rpc response: {0}")]
+    Decode(String),
+}
+
+/// The transactions in the mempool along with the mempool sequence number at the time of the
+/// snapshot, as returned by `getrawmempool false true`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct MempoolSnapshot {
+    /// The txids of the transactions in the mempool.
+    pub(crate) txids: Vec<Txid>,
+
+    /// The sequence number of the mempool, incremented on every acceptance and removal.
+    pub(crate) mempool_sequence: u64,
+}
+
+/// The subset of the `getblockheader` response that we care about.
+#[derive(Debug, Deserialize)]
+struct BlockHeaderInfo {
+    height: u64,
+}
+
+/// Minimal client for the JSON-RPC interface of `bitcoind`.
+#[derive(Debug, Clone)]
+pub(crate) struct BtcRpcClient {
+    client: HttpClient,
+}
+
+impl BtcRpcClient {
+    /// Creates a new [`BtcRpcClient`] that authenticates with the credentials in the config.
+    pub(crate) fn new(cfg: &BtcRpcConfig) -> Result<Self, BtcRpcError> {
+        let credentials = STANDARD.encode(format!("{}:{}", cfg.user, cfg.pass));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Basic {credentials}"))
+                .expect("base64 encoded credentials must be a valid header value"),
+        );
+
+        let client = HttpClientBuilder::default()
+            .set_headers(headers)
+            .build(&cfg.url)?;
+
+        Ok(BtcRpcClient { client })
+    }
+
+    /// Returns the height of the chain tip.
+    pub(crate) async fn get_block_count(&self) -> Result<u64, BtcRpcError> {
+        Ok(self.client.request("getblockcount", rpc_params![]).await?)
+    }
+
+    /// Returns the hash of the chain tip.
+    pub(crate) async fn get_best_block_hash(&self) -> Result<BlockHash, BtcRpcError> {
+        Ok(self
+            .client
+            .request("getbestblockhash", rpc_params![])
+            .await?)
+    }
+
+    /// Returns the hash of the block at the given height in the main chain.
+    pub(crate) async fn get_block_hash(&self, height: u64) -> Result<BlockHash, BtcRpcError> {
+        Ok(self
+            .client
+            .request("getblockhash", rpc_params![height])
+            .await?)
+    }
+
+    /// Returns the height of the block with the given hash.
+    pub(crate) async fn get_block_height(&self, hash: &BlockHash) -> Result<u64, BtcRpcError> {
+        let header: BlockHeaderInfo = self
+            .client
+            .request("getblockheader", rpc_params![hash, true])
+            .await?;
+
+        Ok(header.height)
+    }
+
+    /// Returns the block with the given hash.
+    pub(crate) async fn get_block(&self, hash: &BlockHash) -> Result<Block, BtcRpcError> {
+        let hex: String = self
+            .client
+            .request("getblock", rpc_params![hash, 0])
+            .await?;
+
+        deserialize_hex(&hex).map_err(|e| BtcRpcError::Decode(e.to_string()))
+    }
+
+    /// Returns the block at the given height in the main chain.
+    pub(crate) async fn get_block_at(&self, height: u64) -> Result<Block, BtcRpcError> {
+        let hash = self.get_block_hash(height).await?;
+
+        self.get_block(&hash).await
+    }
+
+    /// Returns the txids of the transactions in the mempool along with the mempool sequence
+    /// number.
+    pub(crate) async fn get_raw_mempool(&self) -> Result<MempoolSnapshot, BtcRpcError> {
+        Ok(self
+            .client
+            .request("getrawmempool", rpc_params![false, true])
+            .await?)
+    }
+
+    /// Returns the transaction with the given txid.
+    ///
+    /// Transactions that are not in the mempool can only be looked up if `bitcoind` maintains a
+    /// transaction index.
+    pub(crate) async fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+    ) -> Result<Transaction, BtcRpcError> {
+        let hex: String = self
+            .client
+            .request("getrawtransaction", rpc_params![txid, false])
+            .await?;
+
+        deserialize_hex(&hex).map_err(|e| BtcRpcError::Decode(e.to_string()))
+    }
+}
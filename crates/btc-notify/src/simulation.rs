@@ -0,0 +1,603 @@
+//! This module contains the [`SimulatedChain`], an in-process model of a Bitcoin node that stands
+//! in for `bitcoind` when testing the consumers of this crate.
+//!
+//! The chain is driven programmatically: transactions are submitted to and evicted from the
+//! mempool, blocks are mined and disconnected on demand. Every change is published as the same
+//! [`Message`]s that the ZMQ interface of `bitcoind` would have published, so a
+//! [`crate::client::BtcZmqClient`] connected with
+//! [`crate::client::BtcZmqClient::connect_simulated`] delivers the same events as one connected to
+//! `bitcoind`, without any external process.
+//!
+//! Transactions are not validated in any way. Submitting a transaction that spends the same
+//! outpoint as a mempool transaction replaces the latter though, as does mining it.
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+use bitcoin::{
+    absolute::LockTime,
+    block::{Header, Version},
+    hashes::Hash,
+    script::Builder,
+    transaction, Amount, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence,
+    Transaction, TxIn, TxMerkleNode, TxOut, Txid, Witness,
+};
+use bitcoincore_zmq::{Message, SequenceMessage};
+use tokio::sync::mpsc;
+use tracing::{debug, info, trace};
+
+use crate::{constants::SIMULATED_START_HEIGHT, subscription::Subscription};
+
+/// An in-process Bitcoin chain and mempool whose changes are published as ZMQ notifications.
+///
+/// Clones share the same chain, so a test can keep one handle to drive the chain while the clients
+/// under test are connected to it.
+#[derive(Debug, Clone)]
+pub struct SimulatedChain {
+    state: Arc<Mutex<ChainState>>,
+}
+
+#[derive(Debug)]
+struct ChainState {
+    /// The blocks of the main chain from the oldest to the newest. The first block is at
+    /// [`SIMULATED_START_HEIGHT`] and is never disconnected.
+    blocks: Vec<Block>,
+
+    /// The transactions in the mempool in the order in which they were accepted.
+    mempool: Vec<Transaction>,
+
+    /// The mempool sequence number, incremented with every acceptance and removal.
+    mempool_sequence: u64,
+
+    /// The number of blocks that were built so far, used to tell apart the blocks that are mined
+    /// at the same height.
+    num_built: u32,
+
+    /// The sequence number of the next `rawblock` message.
+    rawblock_sequence: u32,
+
+    /// The sequence number of the next `rawtx` message.
+    rawtx_sequence: u32,
+
+    /// The sequence number of the next `sequence` message.
+    sequence_sequence: u32,
+
+    /// The clients listening to the notifications.
+    listeners: Vec<mpsc::UnboundedSender<Message>>,
+}
+
+impl Default for SimulatedChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedChain {
+    /// Creates a new [`SimulatedChain`] made of a single block at height 100 and with an empty
+    /// mempool.
+    ///
+    /// The chain doesn't start at the genesis block since rust-bitcoin can't extract heights below
+    /// 17 from coinbase transactions.
+    pub fn new() -> Self {
+        let mut state = ChainState {
+            blocks: Vec::new(),
+            mempool: Vec::new(),
+            mempool_sequence: 0,
+            num_built: 0,
+            rawblock_sequence: 0,
+            rawtx_sequence: 0,
+            sequence_sequence: 0,
+            listeners: Vec::new(),
+        };
+        let first = state.build_block(SIMULATED_START_HEIGHT, BlockHash::all_zeros(), Vec::new());
+        info!(blockhash=%first.block_hash(), height=%SIMULATED_START_HEIGHT, "created simulated chain");
+        state.blocks.push(first);
+
+        SimulatedChain {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Returns a stream of the notifications that are published from now on.
+    pub(crate) fn subscribe(&self) -> Subscription<Message> {
+        let (send, recv) = mpsc::unbounded_channel();
+        self.lock().listeners.push(send);
+
+        Subscription::from_receiver(recv)
+    }
+
+    /// Returns the height of the chain tip.
+    pub fn tip_height(&self) -> u64 {
+        self.lock().tip_height()
+    }
+
+    /// Returns the hash of the chain tip.
+    pub fn tip_hash(&self) -> BlockHash {
+        self.lock().tip().block_hash()
+    }
+
+    /// Returns the main chain block at the given height, if any.
+    pub fn block_at(&self, height: u64) -> Option<Block> {
+        let index = height.checked_sub(SIMULATED_START_HEIGHT)?;
+        self.lock().blocks.get(index as usize).cloned()
+    }
+
+    /// Returns the transactions in the mempool in the order in which they were accepted.
+    pub fn mempool(&self) -> Vec<Transaction> {
+        self.lock().mempool.clone()
+    }
+
+    /// Accepts a transaction to the mempool, replacing the mempool transactions that spend any of
+    /// the same outpoints along with their descendants.
+    ///
+    /// Nothing happens if the transaction is already in the mempool.
+    pub fn submit_tx(&self, tx: Transaction) -> Txid {
+        self.lock().accept(tx)
+    }
+
+    /// Evicts a transaction from the mempool along with its descendants, returning the txids of
+    /// the evicted transactions.
+    pub fn evict_tx(&self, txid: Txid) -> Vec<Txid> {
+        self.lock().evict(BTreeSet::from([txid]))
+    }
+
+    /// Mines a block that includes all the mempool transactions and returns its hash.
+    pub fn mine_block(&self) -> BlockHash {
+        let mut state = self.lock();
+        let txdata = std::mem::take(&mut state.mempool);
+        state.connect(txdata)
+    }
+
+    /// Mines the given number of blocks, the first of which includes all the mempool transactions,
+    /// and returns their hashes.
+    pub fn mine_blocks(&self, num_blocks: usize) -> Vec<BlockHash> {
+        (0..num_blocks).map(|_| self.mine_block()).collect()
+    }
+
+    /// Mines a block that includes exactly the given transactions and returns its hash.
+    ///
+    /// The transactions do not need to be in the mempool. The ones that are leave it, and the
+    /// mempool transactions that conflict with them are evicted.
+    pub fn mine_block_with(&self, txdata: Vec<Transaction>) -> BlockHash {
+        let mut state = self.lock();
+        let included = txdata
+            .iter()
+            .map(Transaction::compute_txid)
+            .collect::<BTreeSet<_>>();
+        state
+            .mempool
+            .retain(|tx| !included.contains(&tx.compute_txid()));
+        let conflicts = state.conflicts(&txdata);
+        state.evict(conflicts);
+
+        state.connect(txdata)
+    }
+
+    /// Disconnects the given number of blocks from the tip and returns them, newest first.
+    ///
+    /// The transactions of the disconnected blocks return to the mempool, as with `bitcoind`.
+    ///
+    /// # Panics
+    ///
+    /// If `depth` isn't smaller than the number of blocks in the chain, since the first block can't
+    /// be disconnected.
+    pub fn disconnect_blocks(&self, depth: usize) -> Vec<Block> {
+        let mut state = self.lock();
+        let disconnected = state.disconnect(depth);
+
+        // The oldest transactions are accepted first so that parents precede their children.
+        for block in disconnected.iter().rev() {
+            for tx in block.txdata.iter().skip(1) {
+                state.accept(tx.clone());
+            }
+        }
+
+        disconnected
+    }
+
+    /// Replaces the given number of blocks at the tip with one more block than that, making the
+    /// new chain the main chain, and returns the hashes of the new blocks.
+    ///
+    /// The transactions of the disconnected blocks are included in the first new block and the
+    /// mempool is left untouched.
+    ///
+    /// # Panics
+    ///
+    /// If `depth` isn't smaller than the number of blocks in the chain, since the first block can't
+    /// be disconnected.
+    pub fn reorg(&self, depth: usize) -> Vec<BlockHash> {
+        let mut state = self.lock();
+        let disconnected = state.disconnect(depth);
+        let txdata = disconnected
+            .into_iter()
+            .rev()
+            .flat_map(|block| block.txdata.into_iter().skip(1))
+            .collect();
+
+        let mut new_blocks = vec![state.connect(txdata)];
+        for _ in 0..depth {
+            new_blocks.push(state.connect(Vec::new()));
+        }
+
+        new_blocks
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ChainState> {
+        self.state.lock().expect("simulated chain lock poisoned")
+    }
+}
+
+impl ChainState {
+    fn tip(&self) -> &Block {
+        self.blocks
+            .last()
+            .expect("the first block is never disconnected")
+    }
+
+    fn tip_height(&self) -> u64 {
+        SIMULATED_START_HEIGHT + self.blocks.len() as u64 - 1
+    }
+
+    /// Builds a block at the given height whose coinbase transaction encodes that height as per
+    /// BIP34.
+    fn build_block(
+        &mut self,
+        height: u64,
+        prev_blockhash: BlockHash,
+        txdata: Vec<Transaction>,
+    ) -> Block {
+        // The number of blocks built so far makes the coinbase transactions of competing blocks
+        // distinct.
+        let nonce = self.num_built;
+        self.num_built += 1;
+
+        let coinbase = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new()
+                    .push_int(height as i64)
+                    .push_int(nonce as i64)
+                    .into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_int_btc(50),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+
+        let mut block = Block {
+            header: Header {
+                version: Version::TWO,
+                prev_blockhash,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: height as u32,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce,
+            },
+            txdata: std::iter::once(coinbase).chain(txdata).collect(),
+        };
+        block.header.merkle_root = block
+            .compute_merkle_root()
+            .expect("the block has a coinbase transaction");
+
+        block
+    }
+
+    /// Builds a block with the given transactions on top of the tip and publishes it.
+    fn connect(&mut self, txdata: Vec<Transaction>) -> BlockHash {
+        let height = self.tip_height() + 1;
+        let block = self.build_block(height, self.tip().block_hash(), txdata);
+        let blockhash = block.block_hash();
+        debug!(%blockhash, %height, num_txs=%block.txdata.len(), "mined simulated block");
+
+        for tx in &block.txdata {
+            self.publish_tx(tx.clone());
+        }
+        self.publish_sequence(SequenceMessage::BlockConnect { blockhash });
+        let sequence = next_sequence(&mut self.rawblock_sequence);
+        self.publish(Message::Block(block.clone(), sequence));
+        self.blocks.push(block);
+
+        blockhash
+    }
+
+    /// Disconnects the given number of blocks from the tip and returns them, newest first.
+    fn disconnect(&mut self, depth: usize) -> Vec<Block> {
+        assert!(
+            depth < self.blocks.len(),
+            "the first block of a simulated chain can't be disconnected"
+        );
+
+        let mut disconnected = Vec::with_capacity(depth);
+        for _ in 0..depth {
+            let block = self.blocks.pop().expect("depth was checked");
+            let blockhash = block.block_hash();
+            debug!(%blockhash, "disconnected simulated block");
+            self.publish_sequence(SequenceMessage::BlockDisconnect { blockhash });
+            disconnected.push(block);
+        }
+
+        disconnected
+    }
+
+    /// Accepts a transaction to the mempool after evicting the transactions it conflicts with.
+    fn accept(&mut self, tx: Transaction) -> Txid {
+        let txid = tx.compute_txid();
+        if self.mempool.iter().any(|m| m.compute_txid() == txid) {
+            trace!(%txid, "transaction already in the simulated mempool");
+            return txid;
+        }
+
+        let conflicts = self.conflicts(std::slice::from_ref(&tx));
+        self.evict(conflicts);
+
+        debug!(%txid, "accepted transaction to the simulated mempool");
+        self.publish_tx(tx.clone());
+        self.mempool_sequence += 1;
+        self.publish_sequence(SequenceMessage::MempoolAcceptance {
+            txid,
+            mempool_sequence: self.mempool_sequence,
+        });
+        self.mempool.push(tx);
+
+        txid
+    }
+
+    /// Returns the txids of the mempool transactions, other than the given ones, that spend any of
+    /// the outpoints that the given transactions spend.
+    fn conflicts(&self, txs: &[Transaction]) -> BTreeSet<Txid> {
+        let txids = txs
+            .iter()
+            .map(Transaction::compute_txid)
+            .collect::<BTreeSet<_>>();
+        let spent = txs
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+            .collect::<BTreeSet<_>>();
+
+        self.mempool
+            .iter()
+            .map(|tx| (tx.compute_txid(), tx))
+            .filter(|(txid, tx)| {
+                !txids.contains(txid)
+                    && tx
+                        .input
+                        .iter()
+                        .any(|input| spent.contains(&input.previous_output))
+            })
+            .map(|(txid, _)| txid)
+            .collect()
+    }
+
+    /// Evicts the given transactions along with their descendants from the mempool and returns
+    /// their txids.
+    fn evict(&mut self, mut evicted: BTreeSet<Txid>) -> Vec<Txid> {
+        let mut removed = Vec::new();
+        loop {
+            let (gone, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.mempool)
+                .into_iter()
+                .partition(|tx| {
+                    evicted.contains(&tx.compute_txid())
+                        || tx
+                            .input
+                            .iter()
+                            .any(|input| evicted.contains(&input.previous_output.txid))
+                });
+            self.mempool = kept;
+            if gone.is_empty() {
+                break;
+            }
+
+            for tx in gone {
+                let txid = tx.compute_txid();
+                debug!(%txid, "evicted transaction from the simulated mempool");
+                evicted.insert(txid);
+                self.mempool_sequence += 1;
+                self.publish_sequence(SequenceMessage::MempoolRemoval {
+                    txid,
+                    mempool_sequence: self.mempool_sequence,
+                });
+                removed.push(txid);
+            }
+        }
+
+        removed
+    }
+
+    fn publish_tx(&mut self, tx: Transaction) {
+        let sequence = next_sequence(&mut self.rawtx_sequence);
+        self.publish(Message::Tx(tx, sequence));
+    }
+
+    fn publish_sequence(&mut self, msg: SequenceMessage) {
+        let sequence = next_sequence(&mut self.sequence_sequence);
+        self.publish(Message::Sequence(msg, sequence));
+    }
+
+    /// Sends a notification to the listeners, forgetting the ones that were dropped.
+    fn publish(&mut self, msg: Message) {
+        trace!(?msg, "publishing simulated notification");
+        self.listeners
+            .retain(|listener| listener.send(msg.clone()).is_ok());
+    }
+}
+
+/// Returns the given sequence number and increments it, as `bitcoind` numbers the messages of
+/// each topic.
+fn next_sequence(sequence: &mut u32) -> u32 {
+    let current = *sequence;
+    *sequence = sequence.wrapping_add(1);
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::{
+        client::{BtcZmqClient, BtcZmqConfig, ChainEvent, TxStatus},
+        constants::DEFAULT_BURY_DEPTH,
+    };
+
+    fn tx(previous_output: OutPoint, value: u64) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    async fn setup() -> (SimulatedChain, BtcZmqClient) {
+        let chain = SimulatedChain::new();
+        let cfg = BtcZmqConfig::default().with_bury_depth(DEFAULT_BURY_DEPTH);
+        let client = BtcZmqClient::connect_simulated(&cfg, &chain).await;
+
+        (chain, client)
+    }
+
+    #[tokio::test]
+    async fn transactions_go_through_their_lifecycle() {
+        let (chain, client) = setup().await;
+        let tx = tx(OutPoint::new(Txid::all_zeros(), 0), 1000);
+        let mut txid_sub = client.subscribe_txid(tx.compute_txid()).await;
+
+        chain.submit_tx(tx.clone());
+        let observed = txid_sub.next().await.unwrap();
+        assert_eq!(observed.rawtx, tx);
+        assert_eq!(observed.status, TxStatus::Mempool);
+
+        let blockhash = chain.mine_block();
+        let mined = TxStatus::Mined {
+            blockhash,
+            height: SIMULATED_START_HEIGHT + 1,
+        };
+        assert_eq!(txid_sub.next().await.unwrap().status, mined);
+
+        chain.mine_blocks(client.bury_depth());
+        let buried = TxStatus::Buried {
+            blockhash,
+            height: SIMULATED_START_HEIGHT + 1,
+        };
+        assert_eq!(txid_sub.next().await.unwrap().status, buried);
+        assert!(txid_sub.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn reorgs_unwind_transactions() {
+        let (chain, client) = setup().await;
+        let tx = tx(OutPoint::new(Txid::all_zeros(), 0), 1000);
+        let mut txid_sub = client.subscribe_txid(tx.compute_txid()).await;
+        let mut chain_events = client.subscribe_chain_events().await;
+
+        let first = chain.mine_block_with(vec![tx.clone()]);
+        let second = chain.mine_block();
+        assert!(matches!(
+            txid_sub.next().await.unwrap().status,
+            TxStatus::Mined { blockhash, .. } if blockhash == first
+        ));
+
+        // The transaction returns to the mempool when its block is disconnected.
+        chain.disconnect_blocks(2);
+        assert_eq!(txid_sub.next().await.unwrap().status, TxStatus::Unknown);
+        assert_eq!(txid_sub.next().await.unwrap().status, TxStatus::Mempool);
+
+        let third = chain.mine_block();
+        assert!(matches!(
+            txid_sub.next().await.unwrap().status,
+            TxStatus::Mined { blockhash, .. } if blockhash == third
+        ));
+
+        // The transactions of the replaced blocks are included in the first block of the new
+        // chain.
+        let new_blocks = chain.reorg(1);
+        assert_eq!(txid_sub.next().await.unwrap().status, TxStatus::Unknown);
+        assert!(matches!(
+            txid_sub.next().await.unwrap().status,
+            TxStatus::Mined { blockhash, .. } if blockhash == new_blocks[0]
+        ));
+
+        let mut observed = Vec::new();
+        for _ in 0..8 {
+            observed.push(match chain_events.next().await.unwrap() {
+                ChainEvent::Connected(block) => (block.block_hash(), 0),
+                ChainEvent::Disconnected {
+                    hash, reorg_depth, ..
+                } => (hash, reorg_depth),
+            });
+        }
+        assert_eq!(
+            observed,
+            vec![
+                (first, 0),
+                (second, 0),
+                (second, 1),
+                (first, 2),
+                (third, 0),
+                (third, 1),
+                (new_blocks[0], 0),
+                (new_blocks[1], 0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replacements_and_evictions_are_reported() {
+        let (chain, client) = setup().await;
+        let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+        let mut spend_sub = client.subscribe_spend(outpoint).await;
+
+        let original = tx(outpoint, 1000);
+        let child = tx(OutPoint::new(original.compute_txid(), 0), 900);
+        let replacement = tx(outpoint, 800);
+
+        chain.submit_tx(original.clone());
+        let mut child_sub = client.subscribe_txid(child.compute_txid()).await;
+        chain.submit_tx(child.clone());
+        let observed = spend_sub.next().await.unwrap();
+        assert_eq!(
+            (observed.rawtx, observed.status),
+            (original.clone(), TxStatus::Mempool)
+        );
+        assert_eq!(child_sub.next().await.unwrap().status, TxStatus::Mempool);
+
+        // Replacing a transaction evicts it along with its descendants.
+        chain.submit_tx(replacement.clone());
+        let observed = spend_sub.next().await.unwrap();
+        assert_eq!(
+            (observed.rawtx, observed.status),
+            (original, TxStatus::Unknown)
+        );
+        assert_eq!(child_sub.next().await.unwrap().status, TxStatus::Unknown);
+        let observed = spend_sub.next().await.unwrap();
+        assert_eq!(
+            (observed.rawtx, observed.status),
+            (replacement.clone(), TxStatus::Mempool)
+        );
+
+        assert_eq!(
+            chain.evict_tx(replacement.compute_txid()),
+            vec![replacement.compute_txid()]
+        );
+        let observed = spend_sub.next().await.unwrap();
+        assert_eq!(
+            (observed.rawtx, observed.status),
+            (replacement, TxStatus::Unknown)
+        );
+        assert!(chain.mempool().is_empty());
+    }
+}
@@ -1,20 +1,16 @@
 use std::{
     collections::{BTreeMap, VecDeque},
     fmt,
-    sync::Arc,
 };
 
 use bitcoin::{Block, BlockHash, Transaction, Txid};
 use bitcoincore_zmq::SequenceMessage;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::event::{TxEvent, TxStatus};
-
-/// Type synonym to capture predicates of the following form: Transaction -> bool.
-///
-/// The choice of using an arc here is intentional so that we can directly compare these predicates
-/// (via [`Arc::ptr_eq`]) when managing the active subscription set.
-pub type TxPredicate = Arc<dyn Fn(&Transaction) -> bool + Sync + Send>;
+use crate::{
+    event::{TxEvent, TxStatus},
+    filter::{TxFilter, TxFilterSet},
+};
 
 /// Keeps track of distinct messages coming in on parallel streams that are all triggered by the
 /// same underlying event.
@@ -38,9 +34,9 @@ pub(crate) struct BtcZmqSM {
     /// block to be considered "buried": the transactions will never be reversed.
     bury_depth: usize,
 
-    /// The set of predicates that are selecting for transactions, the disjunction of which
+    /// The set of filters that are selecting for transactions, the disjunction of which
     /// we care about.
-    tx_filters: Vec<TxPredicate>,
+    tx_filters: TxFilterSet,
 
     /// The core data structure that holds [`TxLifecycles`] indexed by txid. The encoding
     /// should be understood as follows: If the entry is in the map but the value is None, then
@@ -52,6 +48,20 @@ pub(crate) struct BtcZmqSM {
     // The list of unburied blocks in a queue where the front is the newest block and the
     // back is the oldest "unburied" block
     unburied_blocks: VecDeque<Block>,
+
+    /// Whether the transactions that no filter selects are kept while they are in the mempool so
+    /// that the filters added later can be checked against them.
+    track_mempool: bool,
+
+    /// The transactions in the mempool that none of the filters select. This is only populated if
+    /// the mempool is tracked.
+    mempool: BTreeMap<Txid, Transaction>,
+
+    /// The transactions that none of the filters select and for which only the rawtx event was
+    /// received. These are either waiting for their MempoolAcceptance event or were included in a
+    /// block, so they are forgotten once the next block is processed. This is only populated if
+    /// the mempool is tracked.
+    unaccepted: BTreeMap<Txid, Transaction>,
 }
 
 // Coverage is disabled because when tests pass, most Debug impls will never be invoked.
@@ -60,32 +70,24 @@ impl fmt::Debug for BtcZmqSM {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("BtcZmqSM")
             .field("bury_depth", &self.bury_depth)
-            .field(
-                "tx_filters",
-                &self
-                    .tx_filters
-                    .iter()
-                    .map(|f| format!("{:?}", Arc::as_ptr(f)))
-                    .collect::<Vec<String>>(),
-            )
+            .field("tx_filters", &self.tx_filters)
             .field("tx_lifecycles", &self.tx_lifecycles)
             .field("unburied_blocks", &self.unburied_blocks)
+            .field("track_mempool", &self.track_mempool)
+            .field("mempool", &self.mempool)
+            .field("unaccepted", &self.unaccepted)
             .finish()
     }
 }
 impl PartialEq for BtcZmqSM {
     fn eq(&self, other: &Self) -> bool {
-        let filter_eq = self.tx_filters.len() == other.tx_filters.len()
-            && self
-                .tx_filters
-                .iter()
-                .zip(other.tx_filters.iter())
-                .all(|(a, b)| Arc::ptr_eq(a, b));
-
-        filter_eq
+        self.tx_filters == other.tx_filters
             && self.bury_depth == other.bury_depth
             && self.tx_lifecycles == other.tx_lifecycles
             && self.unburied_blocks == other.unburied_blocks
+            && self.track_mempool == other.track_mempool
+            && self.mempool == other.mempool
+            && self.unaccepted == other.unaccepted
     }
 }
 
@@ -99,27 +101,170 @@ impl BtcZmqSM {
         info!(%bury_depth, "initializing a ZMQ state machine");
         BtcZmqSM {
             bury_depth,
-            tx_filters: Vec::new(),
+            tx_filters: TxFilterSet::default(),
             tx_lifecycles: BTreeMap::new(),
             unburied_blocks: VecDeque::new(),
+            track_mempool: false,
+            mempool: BTreeMap::new(),
+            unaccepted: BTreeMap::new(),
         }
     }
 
-    /// Takes a [`TxPredicate`] and adds it to the state machine.
+    /// Sets whether the state machine keeps the mempool transactions that no filter selects, so
+    /// that the filters added later can be checked against the whole mempool rather than just the
+    /// transactions that were already tracked.
+    pub(crate) fn with_mempool_tracking(mut self, track_mempool: bool) -> Self {
+        self.track_mempool = track_mempool;
+        self
+    }
+
+    /// Takes a [`TxFilter`] and adds it to the state machine.
     ///
-    /// The state machine will track any transaction that matches the disjunction of predicates
-    /// added.
-    pub(crate) fn add_filter(&mut self, pred: TxPredicate) {
-        trace!("adding a predicate filter to a ZMQ state machine");
-        self.tx_filters.push(pred);
+    /// The state machine will track any transaction that matches the disjunction of filters
+    /// added. The returned events describe the current status of the transactions that the new
+    /// filter selects among the unburied blocks and the known mempool transactions, so that they
+    /// can be reported to a late subscriber.
+    pub(crate) fn add_filter(&mut self, filter: TxFilter) -> Vec<TxEvent> {
+        trace!(?filter, "adding a filter to a ZMQ state machine");
+        let mut diff = Vec::new();
+
+        // The blocks are visited from the oldest to the newest so that the events are in
+        // chronological order.
+        for block in self.unburied_blocks.iter().rev() {
+            let blockhash = block.block_hash();
+            let height = block.bip34_block_height().unwrap_or(0);
+            for matched_tx in block.txdata.iter().filter(|tx| filter.matches(tx)) {
+                let txid = matched_tx.compute_txid();
+                debug!(%txid, %blockhash, %height, "new filter selects an unburied transaction");
+                let lifecycle = TxLifecycle {
+                    raw: matched_tx.clone(),
+                    block: Some((height, blockhash)),
+                };
+                self.tx_lifecycles.insert(txid, Some(lifecycle));
+                diff.push(TxEvent {
+                    rawtx: matched_tx.clone(),
+                    status: TxStatus::Mined { blockhash, height },
+                });
+            }
+        }
+
+        for lifecycle in self.tx_lifecycles.values().flatten() {
+            if lifecycle.block.is_none() && filter.matches(&lifecycle.raw) {
+                debug!(txid=%lifecycle.raw.compute_txid(), "new filter selects a tracked mempool transaction");
+                diff.push(TxEvent {
+                    rawtx: lifecycle.raw.clone(),
+                    status: TxStatus::Mempool,
+                });
+            }
+        }
+
+        // The transactions that the new filter selects are tracked from now on.
+        let selected = self
+            .mempool
+            .iter()
+            .filter(|(_, tx)| filter.matches(tx))
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+        for txid in selected {
+            let Some(tx) = self.mempool.remove(&txid) else {
+                continue;
+            };
+            debug!(%txid, "new filter selects a mempool transaction");
+            let lifecycle = TxLifecycle {
+                raw: tx.clone(),
+                block: None,
+            };
+            self.tx_lifecycles.insert(txid, Some(lifecycle));
+            diff.push(TxEvent {
+                rawtx: tx,
+                status: TxStatus::Mempool,
+            });
+        }
+
+        // The status of these isn't known yet, their MempoolAcceptance event will report them.
+        let selected = self
+            .unaccepted
+            .iter()
+            .filter(|(_, tx)| filter.matches(tx))
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+        for txid in selected {
+            if let Some(tx) = self.unaccepted.remove(&txid) {
+                let lifecycle = TxLifecycle {
+                    raw: tx,
+                    block: None,
+                };
+                self.tx_lifecycles.insert(txid, Some(lifecycle));
+            }
+        }
+
+        self.tx_filters.insert(filter);
+
+        diff
+    }
+
+    /// Takes a [`TxFilter`] that was previously added via [`BtcZmqSM::add_filter`].
+    ///
+    /// The transactions that none of the remaining filters select are no longer tracked.
+    pub(crate) fn rm_filter(&mut self, filter: &TxFilter) {
+        trace!(?filter, "Removing a filter from a ZMQ state machine");
+        self.tx_filters.remove(filter);
+
+        let tx_filters = &self.tx_filters;
+        let track_mempool = self.track_mempool;
+        let mempool = &mut self.mempool;
+        self.tx_lifecycles.retain(|txid, v| match v {
+            Some(lifecycle) => {
+                let keep = tx_filters.matches(&lifecycle.raw);
+                if !keep {
+                    trace!(%txid, "transaction no longer selected by any filter, pruning it");
+                    if track_mempool && lifecycle.block.is_none() {
+                        mempool.insert(*txid, lifecycle.raw.clone());
+                    }
+                }
+                keep
+            }
+            // We can't tell whether a placeholder is selected until its rawtx event arrives.
+            None => true,
+        });
+    }
+
+    /// Returns the unburied block with the given [`BlockHash`], if the state machine is still
+    /// tracking it.
+    pub(crate) fn unburied_block(&self, blockhash: &BlockHash) -> Option<&Block> {
+        self.unburied_blocks
+            .iter()
+            .find(|block| block.block_hash() == *blockhash)
+    }
+
+    /// Returns the hashes of the unburied blocks indexed by height.
+    pub(crate) fn unburied_chain(&self) -> BTreeMap<u64, BlockHash> {
+        self.unburied_blocks
+            .iter()
+            .map(|block| (block.bip34_block_height().unwrap_or(0), block.block_hash()))
+            .collect()
     }
 
-    /// Takes a [`TxPredicate`] that was previously added via [`BtcZmqSM::add_filter`].
-    pub(crate) fn rm_filter(&mut self, pred: &TxPredicate) {
-        trace!("Removing a predicate filter from a ZMQ state machine");
-        if let Some(idx) = self.tx_filters.iter().position(|p| Arc::ptr_eq(p, pred)) {
-            self.tx_filters.swap_remove(idx);
+    /// Forgets the transactions for which only a MempoolAcceptance event was received, returning
+    /// their txids.
+    ///
+    /// This is used when resynchronizing after missed notifications since the rawtx events of
+    /// these transactions may never arrive.
+    pub(crate) fn drop_placeholders(&mut self) -> Vec<Txid> {
+        let placeholders = self
+            .tx_lifecycles
+            .iter()
+            .filter(|(_, v)| v.is_none())
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+
+        for txid in &placeholders {
+            trace!(%txid, "dropping placeholder");
+            self.tx_lifecycles.remove(txid);
         }
+        self.unaccepted.clear();
+
+        placeholders
     }
 
     /// One of the three primary state transition functions of the [`BtcZmqSM`], updating internal
@@ -144,11 +289,9 @@ impl BtcZmqSM {
                     warn!(block_hash=%block.block_hash(), prev_block_hash=%tip.block_hash(), "block's previous block hash does not match the tip, possible reorg detected");
                 }
             }
-            // TODO(proofofkeags): fix the problem where we can't notice reorgs close to startup
-            // time This is due to the fact that we don't have a full bury depth of
-            // history. Fixing this requires us to backfill the block history at startup
-            // using the RPC interface, or accepting the blocks newer than the
-            // bury depth as an argument to the constructor.
+            // This only happens for the first block. Reorgs of the blocks preceding it can't be
+            // noticed unless the client is started from a past block, in which case it loads a
+            // full bury depth of history before processing it.
             None => {
                 trace!(?block, "no tip found, adding block to unburied blocks");
                 self.unburied_blocks.push_front(block);
@@ -156,6 +299,15 @@ impl BtcZmqSM {
         }
         let block = self.unburied_blocks.front().unwrap();
 
+        // The rawtx events that weren't followed by a MempoolAcceptance event by now were those of
+        // block transactions.
+        self.unaccepted.clear();
+        if !self.mempool.is_empty() {
+            for tx in block.txdata.iter() {
+                self.mempool.remove(&tx.compute_txid());
+            }
+        }
+
         // Now we allocate a Vec will collect the net-new transaction states that need to be
         // distributed.
         let mut diff = Vec::new();
@@ -166,11 +318,7 @@ impl BtcZmqSM {
         // 1. Unknown -> Mined
         // 2. Mempool -> Mined
         // 3. Mined -> Buried
-        for matched_tx in block
-            .txdata
-            .iter()
-            .filter(|tx| self.tx_filters.iter().any(|f| f(tx)))
-        {
+        for matched_tx in block.txdata.iter().filter(|tx| self.tx_filters.matches(tx)) {
             trace!(?matched_tx, "processing transactions in the block");
             match self.tx_lifecycles.get_mut(&matched_tx.compute_txid()) {
                 // This is either the scenario where we haven't yet seen the transaction in any
@@ -238,7 +386,7 @@ impl BtcZmqSM {
                     debug!(%buried_txid, %blockhash, %height, "handled all mined transactions, starting to process buried transactions");
 
                     self.tx_lifecycles.remove(&buried_txid);
-                    if self.tx_filters.iter().any(|f| f(&buried_tx)) {
+                    if self.tx_filters.matches(&buried_tx) {
                         diff.push(TxEvent {
                             rawtx: buried_tx,
                             status: TxStatus::Buried { blockhash, height },
@@ -259,7 +407,19 @@ impl BtcZmqSM {
     pub(crate) fn process_tx(&mut self, tx: Transaction) -> Vec<TxEvent> {
         let txid = tx.compute_txid();
         trace!(?tx, %txid, "filtering transactions");
-        if !self.tx_filters.iter().any(|f| f(&tx)) {
+        if !self.tx_filters.matches(&tx) {
+            // A placeholder left behind by a MempoolAcceptance event is of no use once we know that
+            // the transaction isn't selected.
+            if let Some(None) = self.tx_lifecycles.get(&txid) {
+                trace!(%txid, "pruning placeholder of unmatched transaction");
+                self.tx_lifecycles.remove(&txid);
+                if self.track_mempool {
+                    self.mempool.insert(txid, tx);
+                }
+            } else if self.track_mempool && !self.mempool.contains_key(&txid) {
+                trace!(%txid, "keeping unmatched transaction until it is accepted to the mempool");
+                self.unaccepted.insert(txid, tx);
+            }
             return Vec::new();
         }
 
@@ -405,6 +565,16 @@ impl BtcZmqSM {
                     Some(None) => {
                         panic!("invariant violated: MempoolAcceptance received before rawtx")
                     }
+                    // In this case we already know that no filter selects this transaction.
+                    None if self.mempool.contains_key(&txid) => {
+                        trace!(?seq, %txid, "duplicate MempoolAcceptance of unmatched transaction");
+                    }
+                    None if self.unaccepted.contains_key(&txid) => {
+                        trace!(?seq, %txid, "unmatched transaction accepted to the mempool");
+                        if let Some(tx) = self.unaccepted.remove(&txid) {
+                            self.mempool.insert(txid, tx);
+                        }
+                    }
                     // In this case we know nothing of this transaction yet.
                     None => {
                         // We insert a placeholder because we expect the rawtx event to fill in the
@@ -415,13 +585,16 @@ impl BtcZmqSM {
                         // memory until we clear out these placeholders. However, for every
                         // MempoolAcceptance event we are guaranteed to have a corresponding rawtx
                         // event. So this shouldn't cause a memory leak
-                        // unless we miss ZMQ events entirely.
+                        // unless we miss ZMQ events entirely. The placeholder is pruned when that
+                        // rawtx event arrives if the transaction isn't selected by any filter.
                         trace!(?diff, ?seq, %txid, "saw dangling transaction in mempool");
                         self.tx_lifecycles.insert(txid, None);
                     }
                 }
             }
             SequenceMessage::MempoolRemoval { txid, .. } => {
+                self.mempool.remove(&txid);
+                self.unaccepted.remove(&txid);
                 match self.tx_lifecycles.remove(&txid) {
                     // This will happen if we've seen the rawtx event for a txid irrespective of its
                     // MempoolAcceptance.
@@ -461,6 +634,48 @@ impl BtcZmqSM {
 
         diff
     }
+
+    /// Processes a snapshot of the mempool, reporting the transactions that the filters select
+    /// and that weren't known to be in the mempool already.
+    ///
+    /// This is used to learn about the transactions that entered the mempool before the
+    /// notifications were subscribed to.
+    pub(crate) fn process_mempool(&mut self, txs: Vec<Transaction>) -> Vec<TxEvent> {
+        info!(num_txs=%txs.len(), "processing mempool snapshot");
+        let mut diff = Vec::new();
+        for tx in txs {
+            let txid = tx.compute_txid();
+            self.unaccepted.remove(&txid);
+            if !self.tx_filters.matches(&tx) {
+                if let Some(None) = self.tx_lifecycles.get(&txid) {
+                    self.tx_lifecycles.remove(&txid);
+                }
+                if self.track_mempool {
+                    self.mempool.insert(txid, tx);
+                }
+                continue;
+            }
+
+            match self.tx_lifecycles.get(&txid) {
+                // The transaction was included in a block since the snapshot was taken.
+                Some(Some(lifecycle)) if lifecycle.block.is_some() => continue,
+                _ => {
+                    debug!(%txid, "found selected transaction in the mempool snapshot");
+                    let lifecycle = TxLifecycle {
+                        raw: tx.clone(),
+                        block: None,
+                    };
+                    self.tx_lifecycles.insert(txid, Some(lifecycle));
+                    diff.push(TxEvent {
+                        rawtx: tx,
+                        status: TxStatus::Mempool,
+                    });
+                }
+            }
+        }
+
+        diff
+    }
 }
 
 #[cfg(test)]
@@ -484,10 +699,10 @@ mod prop_tests {
     use prop::array::uniform16;
     use proptest::prelude::*;
 
-    use super::TxPredicate;
     use crate::{
         constants::DEFAULT_BURY_DEPTH,
         event::{TxEvent, TxStatus},
+        filter::{TxFilter, TxPredicate},
         state_machine::BtcZmqSM,
     };
 
@@ -687,7 +902,7 @@ mod prop_tests {
         #[test]
         fn only_matched_transactions_in_diffs(pred in arb_predicate(), block in arb_block(17, Hash::all_zeros())) {
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm.add_filter(pred.pred.clone());
+            sm.add_filter(TxFilter::Predicate(pred.pred.clone()));
             let diff = sm.process_block(block);
             for event in diff.iter() {
                 prop_assert!((pred.pred)(&event.rawtx))
@@ -699,7 +914,7 @@ mod prop_tests {
         #[test]
         fn all_matched_transactions_in_diffs(pred in arb_predicate(), block in arb_block(17, Hash::all_zeros())) {
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm.add_filter(pred.pred.clone());
+            sm.add_filter(TxFilter::Predicate(pred.pred.clone()));
             let diff = sm.process_block(block.clone());
             prop_assert_eq!(diff.len(), block.txdata.iter().filter(|tx| (pred.pred)(tx)).count())
         }
@@ -710,7 +925,7 @@ mod prop_tests {
         #[test]
         fn lone_process_tx_yields_empty_diff(tx in arb_transaction()) {
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm.add_filter(std::sync::Arc::new(|_|true));
+            sm.add_filter(TxFilter::Predicate(std::sync::Arc::new(|_|true)));
             let diff = sm.process_tx(tx);
             prop_assert_eq!(diff, Vec::new());
         }
@@ -724,7 +939,7 @@ mod prop_tests {
             let mempool_sequence = 0u64;
 
             let mut sm1 = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm1.add_filter(std::sync::Arc::new(|_|true));
+            sm1.add_filter(TxFilter::Predicate(std::sync::Arc::new(|_|true)));
 
             let diff_tx_1 = sm1.process_tx(tx.clone());
             let diff_seq_1 = sm1.process_sequence(SequenceMessage::MempoolAcceptance{ txid, mempool_sequence });
@@ -734,7 +949,7 @@ mod prop_tests {
             let diff_1 = diff_tx_1_set.union(&diff_seq_1_set).cloned().collect::<BTreeSet<TxEvent>>();
 
             let mut sm2 = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm2.add_filter(std::sync::Arc::new(|_|true));
+            sm2.add_filter(TxFilter::Predicate(std::sync::Arc::new(|_|true)));
 
             let diff_seq_2 = sm2.process_sequence(SequenceMessage::MempoolAcceptance{ txid, mempool_sequence });
             let diff_tx_2 = sm2.process_tx(tx);
@@ -756,7 +971,7 @@ mod prop_tests {
             let blockhash = block.block_hash();
 
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm.add_filter(pred.pred);
+            sm.add_filter(TxFilter::Predicate(pred.pred));
             let diff_mined = sm.process_block(block);
             let is_mined = |s: &TxStatus| matches!(s, TxStatus::Mined{..});
             prop_assert!(diff_mined.iter().map(|event| &event.status).all(is_mined));
@@ -770,7 +985,7 @@ mod prop_tests {
         #[test]
         fn transactions_eventually_buried(mut chain in arb_chain(17, Hash::all_zeros(), 7)) {
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm.add_filter(std::sync::Arc::new(|_|true));
+            sm.add_filter(TxFilter::Predicate(std::sync::Arc::new(|_|true)));
 
             let oldest = chain.pop_back().unwrap();
             let diff = sm.process_block(oldest);
@@ -796,7 +1011,7 @@ mod prop_tests {
         fn seq_and_tx_make_mempool(tx in arb_transaction()) {
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
 
-            sm.add_filter(Arc::new(|_|true));
+            sm.add_filter(TxFilter::Predicate(Arc::new(|_|true)));
 
             let diff = sm.process_sequence(SequenceMessage::MempoolAcceptance { txid: tx.compute_txid(), mempool_sequence: 0 });
             prop_assert!(diff.is_empty());
@@ -812,18 +1027,123 @@ mod prop_tests {
             let sm_ref = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
             let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
 
-            sm.add_filter(pred.pred.clone());
-            sm.rm_filter(&pred.pred);
+            let filter = TxFilter::Predicate(pred.pred);
+            sm.add_filter(filter.clone());
+            sm.rm_filter(&filter);
 
             prop_assert_eq!(sm, sm_ref);
         }
 
+        // Ensures that the transactions that were only tracked for a filter are forgotten once
+        // that filter is removed.
+        #[test]
+        fn filter_rm_prunes_lifecycles(block in arb_block(17, Hash::all_zeros())) {
+            let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
+            let filter = TxFilter::Predicate(Arc::new(|_| true));
+            sm.add_filter(filter.clone());
+            sm.process_block(block);
+            prop_assert!(!sm.tx_lifecycles.is_empty());
+
+            sm.rm_filter(&filter);
+            prop_assert!(sm.tx_lifecycles.is_empty());
+        }
+
+        // Ensures that the placeholder left by a MempoolAcceptance is pruned once the rawtx event
+        // shows that the transaction isn't selected.
+        #[test]
+        fn unmatched_tx_prunes_placeholder(tx in arb_transaction()) {
+            let sm_ref = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
+            let mut sm = sm_ref.clone();
+
+            sm.process_sequence(SequenceMessage::MempoolAcceptance { txid: tx.compute_txid(), mempool_sequence: 0 });
+            let diff = sm.process_tx(tx);
+
+            prop_assert!(diff.is_empty());
+            prop_assert_eq!(sm, sm_ref);
+        }
+
+        // Ensures that a filter added after a block was processed reports the transactions of the
+        // block that it selects. (late filter Completeness)
+        #[test]
+        fn late_filter_reports_unburied_transactions(pred in arb_predicate(), block in arb_block(17, Hash::all_zeros())) {
+            let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
+            prop_assert!(sm.process_block(block.clone()).is_empty());
+
+            let diff = sm.add_filter(TxFilter::Predicate(pred.pred.clone()));
+            let expected = block
+                .txdata
+                .iter()
+                .filter(|tx| (pred.pred)(tx))
+                .map(|tx| TxEvent {
+                    rawtx: tx.clone(),
+                    status: TxStatus::Mined {
+                        blockhash: block.block_hash(),
+                        height: 18,
+                    },
+                })
+                .collect::<Vec<_>>();
+            prop_assert_eq!(diff, expected);
+        }
+
+        // Ensures that a filter added after a transaction entered the mempool reports it if the
+        // mempool is tracked, irrespective of the order of the rawtx and MempoolAcceptance events.
+        #[test]
+        fn late_filter_reports_mempool_transactions(tx in arb_transaction(), raw_first in any::<bool>()) {
+            let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH).with_mempool_tracking(true);
+
+            let txid = tx.compute_txid();
+            if raw_first {
+                sm.process_tx(tx.clone());
+            }
+            sm.process_sequence(SequenceMessage::MempoolAcceptance { txid, mempool_sequence: 0 });
+            if !raw_first {
+                sm.process_tx(tx.clone());
+            }
+
+            let diff = sm.add_filter(TxFilter::Txid(txid));
+            prop_assert_eq!(diff, vec![TxEvent { rawtx: tx, status: TxStatus::Mempool }]);
+        }
+
+        // Ensures that the transactions that are no longer in the mempool aren't reported to a
+        // late filter.
+        #[test]
+        fn late_filter_ignores_removed_transactions(tx in arb_transaction()) {
+            let sm_ref = BtcZmqSM::init(DEFAULT_BURY_DEPTH).with_mempool_tracking(true);
+            let mut sm = sm_ref.clone();
+
+            let txid = tx.compute_txid();
+            sm.process_tx(tx);
+            sm.process_sequence(SequenceMessage::MempoolAcceptance { txid, mempool_sequence: 0 });
+            sm.process_sequence(SequenceMessage::MempoolRemoval { txid, mempool_sequence: 1 });
+            prop_assert_eq!(&sm, &sm_ref);
+
+            let diff = sm.add_filter(TxFilter::Txid(txid));
+            prop_assert!(diff.is_empty());
+        }
+
+        // Ensures that a mempool snapshot reports exactly the transactions that the filters
+        // select. (snapshot Consistency and Completeness)
+        #[test]
+        fn mempool_snapshot_reports_matched_transactions(pred in arb_predicate(), block in arb_block(17, Hash::all_zeros())) {
+            let mut sm = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
+            sm.add_filter(TxFilter::Predicate(pred.pred.clone()));
+
+            let diff = sm.process_mempool(block.txdata.clone());
+            let expected = block
+                .txdata
+                .into_iter()
+                .filter(|tx| (pred.pred)(tx))
+                .map(|rawtx| TxEvent { rawtx, status: TxStatus::Mempool })
+                .collect::<Vec<_>>();
+            prop_assert_eq!(diff, expected);
+        }
+
         // Ensures that a processing of a MempoolRemoval inverts the processing of a
         // MempoolAcceptance, even if there is an interceding `rawtx` event. (Mempool Invertibility)
         #[test]
         fn mempool_removal_inverts_acceptance(tx in arb_transaction(), include_raw in any::<bool>()) {
             let mut sm_ref = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm_ref.add_filter(Arc::new(|_|true));
+            sm_ref.add_filter(TxFilter::Predicate(Arc::new(|_|true)));
             let mut sm = sm_ref.clone();
 
             let txid = tx.compute_txid();
@@ -845,7 +1165,7 @@ mod prop_tests {
             mut chain in arb_chain(17, Hash::all_zeros(), 2),
         ) {
             let mut sm_ref = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm_ref.add_filter(Arc::new(|_|true));
+            sm_ref.add_filter(TxFilter::Predicate(Arc::new(|_|true)));
 
             // To ensure that we have a more interesting state machine than just the block we want
             // to process we include transactions that aren't included in any block.
@@ -880,7 +1200,7 @@ mod prop_tests {
         #[test]
         fn tx_after_block_idempotence(block in arb_block(17, Hash::all_zeros())) {
             let mut sm_ref = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm_ref.add_filter(Arc::new(|_|true));
+            sm_ref.add_filter(TxFilter::Predicate(Arc::new(|_|true)));
             sm_ref.process_block(block.clone());
             let mut sm = sm_ref.clone();
 
@@ -895,7 +1215,7 @@ mod prop_tests {
         #[test]
         fn tx_block_commutativity(block in arb_block(17, Hash::all_zeros())) {
             let mut sm_base = BtcZmqSM::init(DEFAULT_BURY_DEPTH);
-            sm_base.add_filter(Arc::new(|_|true));
+            sm_base.add_filter(TxFilter::Predicate(Arc::new(|_|true)));
             let mut sm_block_first = sm_base.clone();
             let mut sm_tx_first = sm_base;
 
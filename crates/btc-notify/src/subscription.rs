@@ -9,15 +9,53 @@ use tokio::sync::mpsc;
 
 /// The primary type that consumers of this API will handle. It is created via one of the calls to
 /// `BtcZmqClient::subscribe_*`. From there you should use it via it's [`futures::Stream`] API.
+///
+/// Dropping a transaction subscription unsubscribes it from the client that created it.
 #[derive(Debug)]
 pub struct Subscription<T> {
     receiver: mpsc::UnboundedReceiver<T>,
+    unsubscribe: Option<Unsubscribe>,
 }
 
 impl<T> Subscription<T> {
     /// Intentionally left private so as not to leak implementation details to consuming APIs.
     pub(crate) fn from_receiver(receiver: mpsc::UnboundedReceiver<T>) -> Subscription<T> {
-        Subscription { receiver }
+        Subscription {
+            receiver,
+            unsubscribe: None,
+        }
+    }
+
+    /// Creates a [`Subscription`] that sends its id over the supplied channel once it is dropped
+    /// so that the client can release the resources associated with it.
+    pub(crate) fn with_unsubscribe(
+        receiver: mpsc::UnboundedReceiver<T>,
+        id: u64,
+        unsubscribes: mpsc::UnboundedSender<u64>,
+    ) -> Subscription<T> {
+        Subscription {
+            receiver,
+            unsubscribe: Some(Unsubscribe { id, unsubscribes }),
+        }
+    }
+
+    /// Returns the id under which the client tracks this subscription, if it has one.
+    pub(crate) fn id(&self) -> Option<u64> {
+        self.unsubscribe.as_ref().map(|unsubscribe| unsubscribe.id)
+    }
+}
+
+/// Notifies the client that a subscription was dropped.
+#[derive(Debug)]
+struct Unsubscribe {
+    id: u64,
+    unsubscribes: mpsc::UnboundedSender<u64>,
+}
+
+impl Drop for Unsubscribe {
+    fn drop(&mut self) {
+        // The client may be gone already in which case there is nothing left to release.
+        let _ = self.unsubscribes.send(self.id);
     }
 }
 
@@ -10,7 +10,7 @@ use async_trait::async_trait;
 use bitcoin::{Network, OutPoint, PublicKey, Transaction, TxOut, Txid};
 use musig2::{PartialSignature, PubNonce, SecNonce};
 use secp256k1::schnorr::Signature;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 use strata_bridge_primitives::{
     bitcoin::BitcoinAddress,
     constants::NUM_ASSERT_DATA_TX,
@@ -71,6 +71,36 @@ impl SqliteDb {
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
+
+    /// Adds the stake data for the given operator and stake index using the supplied connection.
+    ///
+    /// Unlike [`PublicDb::add_stake_data`], this does not open (or commit) a transaction of its own
+    /// so that callers can batch it with other writes in a transaction they control.
+    pub async fn add_stake_data_with(
+        conn: &mut SqliteConnection,
+        operator_id: OperatorIdx,
+        stake_index: u32,
+        stake_data: StakeTxData,
+    ) -> DbResult<()> {
+        let stake_data = DbStakeTxData::from(stake_data);
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO operator_stake_data
+                (operator_id, deposit_id, funding_txid, funding_vout, hash, withdrawal_fulfillment_pk)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            operator_id,
+            stake_index,
+            stake_data.funding_txid,
+            stake_data.funding_vout,
+            stake_data.hash,
+            stake_data.withdrawal_fulfillment_pk,
+        )
+        .execute(conn)
+        .await
+        .map_err(StorageError::from)?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -405,28 +435,15 @@ impl PublicDb for SqliteDb {
             let pool = self.pool.to_owned();
             async move {
                 let mut tx = pool.begin().await.map_err(StorageError::from)?;
-                let stake_data = DbStakeTxData::from(stake_data);
 
-                sqlx::query!(
-                    "INSERT OR REPLACE INTO operator_stake_data
-                        (operator_id, deposit_id, funding_txid, funding_vout, hash, withdrawal_fulfillment_pk)
-                        VALUES ($1, $2, $3, $4, $5, $6)",
-                    operator_id,
-                    stake_index,
-                    stake_data.funding_txid,
-                    stake_data.funding_vout,
-                    stake_data.hash,
-                    stake_data.withdrawal_fulfillment_pk,
-                )
-                .execute(&mut *tx)
-                .await
-                .map_err(StorageError::from)?;
+                Self::add_stake_data_with(&mut tx, operator_id, stake_index, stake_data).await?;
 
                 tx.commit().await.map_err(StorageError::from)?;
 
                 Ok(())
             }
-        }).await
+        })
+        .await
     }
 
     async fn get_stake_data(
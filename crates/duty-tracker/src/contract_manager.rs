@@ -11,25 +11,71 @@ use alpen_bridge_params::prelude::{ConnectorParams, PegOutGraphParams, StakeChai
 use bdk_wallet::{miniscript::ToPublicKey, Wallet};
 use bitcoin::{
     hashes::{sha256, sha256d, Hash as _},
-    sighash::{Prevouts, SighashCache},
-    Block, FeeRate, Network, OutPoint, Psbt, TapSighashType, Transaction, Txid,
+    secp256k1::schnorr,
+    taproot, Amount, Block, Network, OutPoint, Psbt, TapSighashType, Transaction, TxOut, Txid,
+    Witness, XOnlyPublicKey,
 };
-use bitvm::chunk::api::{NUM_HASH, NUM_PUBS, NUM_U256};
-use btc_notify::client::BtcZmqClient;
+use bitcoin_bosd::Descriptor;
+use bitvm::{
+    chunk::api::{
+        api_generate_full_tapscripts, generate_assertions, validate_assertions,
+        Signatures as g16Signatures, NUM_HASH, NUM_PUBS, NUM_U256,
+    },
+    signatures::wots_api::wots256,
+};
+use btc_notify::client::{BtcZmqClient, ChainEvent};
 use futures::{
-    future::{join3, join_all},
+    future::{join3, join_all, try_join_all},
     StreamExt,
 };
-use operator_wallet::{FundingUtxo, OperatorWallet};
-use secret_service_client::SecretServiceClient;
-use secret_service_proto::v1::traits::*;
-use strata_bridge_connectors::prelude::ConnectorStake;
+use musig2::{PartialSignature, PubNonce};
+use operator_wallet::{fee_estimator::FeeUrgency, FundingUtxo, OperatorWallet};
+use secret_service_client::{musig2::Musig2FirstRound, SecretServiceClient};
+use secret_service_proto::{
+    v1::traits::*,
+    v2::traits::{
+        Musig2TxSignerFirstRound, PolicyRegistry, SigningRequest, SpendPath, TxSigner, TxidSigner,
+    },
+};
+use serde::{Deserialize, Serialize};
+use sp1_verifier::hash_public_inputs;
+use strata_bridge_connectors::{
+    partial_verification_scripts::PARTIAL_VERIFIER_SCRIPTS,
+    prelude::{
+        ConnectorA3Leaf, ConnectorC1Path, ConnectorCpfp, ConnectorStake,
+        DisprovePublicInputsCommitmentWitness, StakeSpendPath,
+    },
+};
 use strata_bridge_db::{persistent::sqlite::SqliteDb, public::PublicDb};
 use strata_bridge_p2p_service::MessageHandler;
-use strata_bridge_primitives::{build_context::BuildContext, operator_table::OperatorTable};
+use strata_bridge_primitives::{
+    build_context::BuildContext,
+    constants::NUM_ASSERT_DATA_TX,
+    operator_table::OperatorTable,
+    scripts::{
+        taproot::TaprootWitness,
+        transform::{wots_from_compact, wots_to_byte_array},
+    },
+    types::BitcoinBlockHeight,
+    wots::{self, Assertions, Groth16Signatures, Signatures, Wots256Signature},
+};
+use strata_bridge_proof_primitives::L1TxWithProofBundle;
+use strata_bridge_proof_protocol::{
+    BridgeProofInput, BridgeProofPublicOutput,
+    REQUIRED_NUM_OF_HEADERS_AFTER_WITHDRAWAL_FULFILLMENT_TX,
+};
+use strata_bridge_proof_snark::{bridge_vk, prover};
 use strata_bridge_stake_chain::{
     prelude::StakeTx, stake_chain::StakeChainInputs, transactions::stake::StakeTxData,
 };
+use strata_bridge_tx_graph::{
+    errors::TxGraphError,
+    peg_out_graph::{MusigInput, PegOutGraph, PegOutGraphInput},
+    transactions::{
+        claim::ClaimTx,
+        prelude::{AssertChain, AssertDataTxBatch, CovenantTx, WithdrawalMetadata},
+    },
+};
 use strata_btcio::rpc::{traits::ReaderRpc, BitcoinClient};
 use strata_p2p::{
     self,
@@ -45,22 +91,24 @@ use strata_p2p_wire::p2p::v1::{GetMessageRequest, GossipsubMsg, UnsignedGossipsu
 use strata_primitives::params::RollupParams;
 use strata_state::{bridge_state::DepositState, chain_state::Chainstate};
 use tokio::{
-    sync::RwLock,
-    task::{self, JoinHandle},
+    sync::{Mutex, RwLock},
+    task::{self, AbortHandle, JoinHandle},
     time,
 };
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
-    contract_persister::ContractPersister,
+    contract_persister::{ContractPersistErr, ContractPersister},
     contract_state_machine::{
-        ContractEvent, ContractSM, DepositSetup, FulfillerDuty, OperatorDuty,
+        ContractEvent, ContractSM, DepositSetup, FulfillerDuty, MachineState, OperatorDuty,
+        TransitionErr, VerifierDuty,
     },
+    duty_persister::{DutyId, DutyPersister},
     errors::{ContractManagerErr, StakeChainErr},
     predicates::{deposit_request_info, parse_strata_checkpoint},
     stake_chain_persister::StakeChainPersister,
     stake_chain_state_machine::StakeChainSM,
-    tx_driver::TxDriver,
+    tx_driver::{CpfpAnchor, DriveErr, DriveOptions, DriveUpdate, FinalityPolicy, TxDriver},
 };
 
 /// System that handles all of the chain and p2p events and forwards them to their respective
@@ -90,8 +138,9 @@ impl ContractManager {
         mut p2p_handle: P2PHandle,
         contract_persister: ContractPersister,
         stake_chain_persister: StakeChainPersister,
+        duty_persister: DutyPersister,
         s2_client: SecretServiceClient,
-        wallet: OperatorWallet,
+        wallet: Arc<RwLock<OperatorWallet>>,
         db: SqliteDb,
     ) -> JoinHandle<()> {
         task::spawn(async move {
@@ -162,24 +211,49 @@ impl ContractManager {
                 }
             };
 
-            // It's extremely unlikely that these will ever differ at all but it's possible for
-            // them to differ by at most 1 in the scenario where we crash mid-batch when committing
-            // contract state to disk.
-            let mut cursor = active_contracts
-                .iter()
-                .min_by(|(_, sm1), (_, sm2)| {
-                    sm1.state().block_height.cmp(&sm2.state().block_height)
+            // The cursor is committed in the same transaction as the contract states so it always
+            // reflects the last block whose effects were persisted. The ZMQ client is expected to
+            // be connected from the block following it so it replays the missed blocks for us.
+            // Databases that predate the cursor fall back to the least advanced contract and catch
+            // up over RPC here.
+            let persisted_cursor = match contract_persister.load_cursor().await {
+                Ok(cursor) => cursor,
+                Err(e) => {
+                    crash(e.into());
+                    return;
+                }
+            };
+            let mut cursor = persisted_cursor
+                .or_else(|| {
+                    active_contracts
+                        .iter()
+                        .min_by(|(_, sm1), (_, sm2)| {
+                            sm1.state().block_height.cmp(&sm2.state().block_height)
+                        })
+                        .map(|(_, sm)| sm.state().block_height)
                 })
-                .map(|(_, sm)| sm.state().block_height)
                 .unwrap_or(current);
 
+            // undo information is persisted along with each block so that blocks processed before
+            // the last shutdown can still be disconnected.
+            let block_undos = match contract_persister.load_undos().await {
+                Ok(block_undos) => block_undos,
+                Err(e) => {
+                    crash(e.into());
+                    return;
+                }
+            };
+
             let msg_handler = MessageHandler::new(p2p_handle.clone());
             let output_handles = Arc::new(OutputHandles {
-                wallet: RwLock::new(wallet),
+                wallet,
                 msg_handler,
                 s2_client,
                 tx_driver,
+                rpc_client,
                 db,
+                duty_persister,
+                in_flight_duties: Mutex::new(BTreeMap::new()),
             });
             let cfg = ExecutionConfig {
                 network,
@@ -196,16 +270,34 @@ impl ContractManager {
             let state = ExecutionState {
                 active_contracts,
                 stake_chains,
+                block_undos,
             };
             let mut ctx = ContractManagerCtx {
                 cfg: cfg.clone(),
+                bury_depth: zmq_client.bury_depth(),
                 state,
                 state_handles,
             };
 
-            while cursor < current {
+            // duties that were not executed successfully before the last shutdown are resumed
+            // before any new block is processed.
+            match output_handles.duty_persister.load_pending().await {
+                Ok(pending_duties) => {
+                    for (duty_id, duty) in pending_duties {
+                        info!(%duty_id, ?duty, "resuming pending duty");
+                        spawn_duty(&cfg, &output_handles, duty_id, duty).await;
+                    }
+                }
+                Err(e) => {
+                    crash(e.into());
+                    return;
+                }
+            }
+            cancel_stale_duties(&ctx.state.active_contracts, &output_handles).await;
+
+            while persisted_cursor.is_none() && cursor < current {
                 let next = cursor + 1;
-                let block = match rpc_client.get_block_at(next).await {
+                let block = match output_handles.rpc_client.get_block_at(next).await {
                     Ok(a) => a,
                     Err(e) => {
                         crash(e.into());
@@ -215,53 +307,66 @@ impl ContractManager {
                 let blockhash = block.block_hash();
                 match ctx.process_block(block).await {
                     Ok(duties) => {
-                        duties.into_iter().for_each(|duty| {
+                        for duty in duties {
                             info!(?duty, "starting duty execution from lagging blocks");
-                            let cfg = cfg.clone();
-                            let output_handles = output_handles.clone();
-                            tokio::task::spawn(async move {
-                                if let Err(e) = execute_duty(cfg, output_handles, duty).await {
-                                    error!(%e, "failed to execute duty");
-                                }
-                            });
-                        });
+                            dispatch_duty(&cfg, &output_handles, duty).await;
+                        }
+                        cancel_stale_duties(&ctx.state.active_contracts, &output_handles).await;
                     }
                     Err(e) => {
                         error!(%blockhash, %cursor, %e, "failed to process block");
-                        break;
+                        crash(e);
+                        return;
                     }
                 }
 
                 cursor = next;
             }
 
-            let mut block_sub = zmq_client.subscribe_blocks().await;
+            // Connects and disconnects are consumed from a single subscription so that they are
+            // processed in the order in which they happened.
+            let mut chain_event_sub = zmq_client.subscribe_chain_events().await;
+            zmq_client.catch_up();
             let mut interval = time::interval(nag_interval);
             let pov_key = ctx.cfg.operator_table.pov_op_key().clone();
             loop {
                 let mut duties = vec![];
                 tokio::select! {
-                    Some(block) = block_sub.next() => {
-                        let blockhash = block.block_hash();
-                        let block_height = block.bip34_block_height().expect("must have valid height");
-                        info!(%blockhash, %block_height, "processing block");
-                        match ctx.process_block(block).await {
-                            Ok(block_duties) => {
-                                let num_duties = block_duties.len();
-                                info!(%blockhash, %block_height, %num_duties, "queueing duties generated by the block event for execution");
-                                duties.extend(block_duties.into_iter());
-                            },
-                            Err(e) => {
-                                error!(%blockhash, %block_height, ?e, "failed to process block");
-                                break;
+                    Some(event) = chain_event_sub.next() => match event {
+                        ChainEvent::Connected(block) => {
+                            let blockhash = block.block_hash();
+                            let block_height = block.bip34_block_height().expect("must have valid height");
+                            info!(%blockhash, %block_height, "processing block");
+                            match ctx.process_block(block).await {
+                                Ok(block_duties) => {
+                                    let num_duties = block_duties.len();
+                                    info!(%blockhash, %block_height, %num_duties, "queueing duties generated by the block event for execution");
+                                    duties.extend(block_duties.into_iter());
+                                },
+                                Err(e) => {
+                                    // the block has not been persisted so the manager cannot move
+                                    // past it, it is replayed from the cursor on restart.
+                                    error!(%blockhash, %block_height, ?e, "failed to process block");
+                                    crash(e);
+                                    return;
+                                }
                             }
-                        }
+                        },
+                        ChainEvent::Disconnected { hash: blockhash, height: block_height, reorg_depth } => {
+                            warn!(%blockhash, %block_height, %reorg_depth, "rolling back contracts to before the disconnected block");
+                            if let Err(e) = ctx.process_block_disconnect(block_height).await {
+                                error!(%blockhash, %block_height, ?e, "failed to process block disconnect");
+                                crash(e);
+                                return;
+                            }
+                        },
                     },
                     Some(event) = p2p_handle.next() => match event {
                         Ok(Event::ReceivedMessage(msg)) => {
+                            // a p2p message only concerns its sender so failing to process it must
+                            // not stop the manager.
                             if let Err(e) = ctx.process_p2p_message(msg.clone()).await {
                                 error!(%e, "failed to process p2p msg");
-                                break;
                             }
                         },
                         Ok(Event::ReceivedRequest(req)) => match req {
@@ -306,14 +411,18 @@ impl ContractManager {
                                     *sha256d::Hash::from_bytes_ref(session_id.as_ref())
                                 );
 
-                                if ctx.state.active_contracts.contains_key(&session_id_as_txid) {
-                                    duties.push(OperatorDuty::PublishGraphNonces);
-                                } else if ctx.state.active_contracts
+                                if let Some(contract) = ctx.state.active_contracts.get(&session_id_as_txid) {
+                                    match contract.graph_nonces_duty() {
+                                        Ok(duty) => duties.push(duty),
+                                        Err(e) => warn!(%e, "cannot publish graph nonces yet"),
+                                    }
+                                } else if let Some(contract) = ctx.state.active_contracts
                                     .values()
-                                    .map(|sm| sm.deposit_request_txid())
-                                    .any(|txid| txid == session_id_as_txid) {
-
-                                    duties.push(OperatorDuty::PublishRootNonce);
+                                    .find(|sm| sm.deposit_request_txid() == session_id_as_txid) {
+                                    match contract.root_nonce_duty() {
+                                        Ok(duty) => duties.push(duty),
+                                        Err(e) => warn!(%e, "cannot publish root nonce yet"),
+                                    }
                                 } else {
                                     // otherwise ignore this message.
                                     warn!(txid=%session_id_as_txid, "received a musig2 nonces exchange for an unknown session");
@@ -322,14 +431,18 @@ impl ContractManager {
                             GetMessageRequest::Musig2SignaturesExchange { session_id, .. } => {
                                 let session_id_as_txid = Txid::from_raw_hash(*sha256d::Hash::from_bytes_ref(session_id.as_ref()));
 
-                                if ctx.state.active_contracts.contains_key(&session_id_as_txid) {
-                                    duties.push(OperatorDuty::PublishGraphSignatures);
-                                } else if ctx.state.active_contracts
+                                if let Some(contract) = ctx.state.active_contracts.get(&session_id_as_txid) {
+                                    match contract.graph_sigs_duty() {
+                                        Ok(duty) => duties.push(duty),
+                                        Err(e) => warn!(%e, "cannot publish graph signatures yet"),
+                                    }
+                                } else if let Some(contract) = ctx.state.active_contracts
                                     .values()
-                                    .map(|sm| sm.deposit_request_txid())
-                                    .any(|txid| txid == session_id_as_txid) {
-
-                                    duties.push(OperatorDuty::PublishRootSignature);
+                                    .find(|sm| sm.deposit_request_txid() == session_id_as_txid) {
+                                    match contract.root_sig_duty() {
+                                        Ok(duty) => duties.push(duty),
+                                        Err(e) => warn!(%e, "cannot publish root signature yet"),
+                                    }
                                 } else {
                                     // otherwise ignore this message.
                                     warn!(txid=%session_id_as_txid, "received a musig2 signatures exchange for an unknown session");
@@ -348,17 +461,11 @@ impl ContractManager {
                     }
                 }
 
-                duties.into_iter().for_each(|duty| {
+                for duty in duties {
                     info!(?duty, "starting duty execution from new blocks");
-
-                    let cfg = cfg.clone();
-                    let output_handles = output_handles.clone();
-                    tokio::task::spawn(async move {
-                        if let Err(e) = execute_duty(cfg, output_handles, duty).await {
-                            error!(%e, "failed to execute duty");
-                        }
-                    });
-                });
+                    dispatch_duty(&cfg, &output_handles, duty).await;
+                }
+                cancel_stale_duties(&ctx.state.active_contracts, &output_handles).await;
             }
         })
     }
@@ -371,11 +478,20 @@ impl Drop for ContractManager {
 
 /// The handles required by the duty tracker to execute duties.
 struct OutputHandles {
-    wallet: RwLock<OperatorWallet>,
+    wallet: Arc<RwLock<OperatorWallet>>,
     msg_handler: MessageHandler,
     s2_client: SecretServiceClient,
     tx_driver: TxDriver,
+    rpc_client: BitcoinClient,
     db: SqliteDb,
+    duty_persister: DutyPersister,
+    in_flight_duties: Mutex<BTreeMap<DutyId, InFlightDuty>>,
+}
+
+/// A duty whose execution is underway.
+struct InFlightDuty {
+    duty: OperatorDuty,
+    handle: AbortHandle,
 }
 
 /// The actual state that is being tracked by the [`ContractManager`].
@@ -383,6 +499,34 @@ struct OutputHandles {
 struct ExecutionState {
     active_contracts: BTreeMap<Txid, ContractSM>,
     stake_chains: StakeChainSM,
+    block_undos: BTreeMap<BitcoinBlockHeight, BlockUndo>,
+}
+
+/// The information required to undo the effects of a block on the active contracts in case it is
+/// disconnected from the chain tip.
+///
+/// This is persisted along with the effects of the block so that it survives restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BlockUndo {
+    /// The states of the contracts that processed transactions in the block, captured before the
+    /// first of them was applied.
+    checkpoints: BTreeMap<Txid, MachineState>,
+
+    /// The deposit txids of the contracts that were created by the block.
+    new_contracts: Vec<Txid>,
+
+    /// The state of the stake chains before the block, if the block changed it.
+    stake_chains: Option<BTreeMap<P2POperatorPubKey, StakeChainInputs>>,
+}
+
+impl BlockUndo {
+    /// Records the current state of the contract unless it has already been recorded for this
+    /// block.
+    fn checkpoint(&mut self, contract: &ContractSM) {
+        self.checkpoints
+            .entry(contract.deposit_txid())
+            .or_insert_with(|| contract.state().clone());
+    }
 }
 
 /// The proxy for the state being tracked by the [`ContractManager`].
@@ -405,19 +549,71 @@ struct ExecutionConfig {
 
 struct ContractManagerCtx {
     cfg: ExecutionConfig,
+    /// The number of blocks for which undo information is retained.
+    bury_depth: usize,
     state_handles: StateHandles,
     state: ExecutionState,
 }
 
 impl ContractManagerCtx {
+    /// Processes all the events in the block and persists the resulting states atomically.
+    ///
+    /// If any of the state transitions fail or the states cannot be persisted, the block is
+    /// rejected as a whole and the in-memory states are reverted to what they were before the
+    /// block.
     async fn process_block(
         &mut self,
         block: Block,
     ) -> Result<Vec<OperatorDuty>, ContractManagerErr> {
         let height = block.bip34_block_height().unwrap_or(0);
-        // TODO(proofofkeags): persist entire block worth of states at once. Ensure all the state
-        // transitions succeed before committing them to disk.
+        let mut undo = BlockUndo::default();
+
+        let res = match self.apply_block(height, block, &mut undo).await {
+            Ok((duties, stake_chains_updated)) => self
+                .persist_block(height, &undo, stake_chains_updated)
+                .await
+                .map(|_| duties),
+            Err(e) => Err(e),
+        };
+
+        match res {
+            Ok(duties) => {
+                // Blocks that are buried can no longer be disconnected so there is no need to keep
+                // their undo information around.
+                self.state.block_undos.insert(height, undo);
+                let bury_depth = self.bury_depth as u64;
+                self.state
+                    .block_undos
+                    .retain(|undo_height, _| undo_height + bury_depth > height);
+
+                Ok(duties)
+            }
+            Err(e) => {
+                error!(%height, %e, "rejecting block, reverting contract states");
+                self.undo_block(height, undo)?;
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Applies all the events in the block to the in-memory contract states, recording what is
+    /// needed to undo them in `undo`.
+    ///
+    /// Returns the duties generated by the block and whether the stake chain state needs to be
+    /// persisted.
+    async fn apply_block(
+        &mut self,
+        height: BitcoinBlockHeight,
+        block: Block,
+        undo: &mut BlockUndo,
+    ) -> Result<(Vec<OperatorDuty>, bool), ContractManagerErr> {
         let mut duties = Vec::new();
+        let mut stake_chains_updated = false;
+        // the stake chain state is recorded up front so that it is restored if any of the
+        // transitions below fail after it was updated.
+        let stake_chains_before = self.state.stake_chains.state().clone();
+        undo.stake_chains = Some(stake_chains_before.clone());
         // this is to aggregate and commit new contracts separately so that the block event that
         // advances the cursor does not advance the cursor on the newly created contracts.
         let mut new_contracts = Vec::new();
@@ -432,7 +628,8 @@ impl ContractManagerCtx {
         let stake_index = self.state.active_contracts.len() as u32;
 
         for tx in block.txdata {
-            let assignment_duties = self.process_assignments(&tx).await?;
+            let assignment_duties = self.process_assignments(&tx, undo)?;
+            stake_chains_updated |= !assignment_duties.is_empty();
             duties.extend(assignment_duties.into_iter());
 
             let txid = tx.compute_txid();
@@ -444,12 +641,12 @@ impl ContractManagerCtx {
                 stake_index,
             ) {
                 let deposit_request_txid = txid;
-                let deposit_tx = match deposit_info.construct_signing_data(
+                let deposit_signing_data = match deposit_info.construct_signing_data(
                     &self.cfg.operator_table.tx_build_context(self.cfg.network),
                     &self.cfg.pegout_graph_params,
                     &self.cfg.sidesystem_params,
                 ) {
-                    Ok(data) => data.psbt.unsigned_tx,
+                    Ok(data) => data,
                     Err(err) => {
                         error!(
                             ?deposit_info,
@@ -463,7 +660,7 @@ impl ContractManagerCtx {
                 if self
                     .state
                     .active_contracts
-                    .contains_key(&deposit_tx.compute_txid())
+                    .contains_key(&deposit_signing_data.psbt.unsigned_tx.compute_txid())
                 {
                     // We already processed this. Do not create another contract attached to this
                     // deposit txid.
@@ -487,7 +684,7 @@ impl ContractManagerCtx {
                     height + self.cfg.pegout_graph_params.refund_delay as u64,
                     stake_index,
                     deposit_request_txid,
-                    deposit_tx,
+                    deposit_signing_data,
                     stake_chain_inputs,
                 );
 
@@ -504,22 +701,27 @@ impl ContractManagerCtx {
                     continue;
                 }
 
+                undo.checkpoint(contract);
                 match contract.process_contract_event(ContractEvent::DepositConfirmation(tx)) {
                     Ok(Some(duty)) => duties.push(duty),
                     Ok(None) => trace!("this is fine"),
-                    Err(e) => error!(%e, "failed to process deposit confirmation"),
+                    Err(e) => {
+                        error!(deposit_txid=%txid, %e, "failed to process deposit confirmation");
+                        return Err(e)?;
+                    }
                 }
 
                 continue;
             }
 
-            for (_deposit_txid, contract) in self.state.active_contracts.iter_mut() {
+            for (deposit_txid, contract) in self.state.active_contracts.iter_mut() {
                 if contract.state().block_height >= height {
                     // Don't process events if we've already processed them.
                     continue;
                 }
 
                 if contract.transaction_filter(&tx) {
+                    undo.checkpoint(contract);
                     match contract.process_contract_event(ContractEvent::PegOutGraphConfirmation(
                         tx.clone(),
                         height,
@@ -529,7 +731,7 @@ impl ContractManagerCtx {
                             trace!(txid=%tx.compute_txid(), "no duty emitted when processing this transaction...this is fine 🔥")
                         }
                         Err(e) => {
-                            error!(%e, "failed to process pegout graph confirmation");
+                            error!(%deposit_txid, %e, "failed to process pegout graph confirmation");
                             return Err(e)?;
                         }
                     }
@@ -539,38 +741,201 @@ impl ContractManagerCtx {
 
         // Now that we've handled all the transaction level events, we should inform all the
         // CSMs that a new block has arrived
-        for (_, contract) in self.state.active_contracts.iter_mut() {
-            if let Some(duty) = contract.process_contract_event(ContractEvent::Block(height))? {
-                duties.push(duty);
+        for (deposit_txid, contract) in self.state.active_contracts.iter_mut() {
+            if contract.state().block_height >= height {
+                // Don't process events if we've already processed them.
+                continue;
             }
-        }
 
-        self.state_handles
-            .contract_persister
-            .commit_all(self.state.active_contracts.iter())
-            .await?;
+            match contract.process_contract_event(ContractEvent::Block(height)) {
+                Ok(Some(duty)) => duties.push(duty),
+                Ok(None) => {}
+                Err(e) => {
+                    error!(%deposit_txid, %height, %e, "failed to process new block");
+                    return Err(e)?;
+                }
+            }
+        }
 
         // Now that we've processed all the events related to the old contracts and dispatched the
         // corresponding events to them, we can add the new contracts which will receive relevant
         // events from subsequent blocks.
         for sm in new_contracts {
+            undo.new_contracts.push(sm.deposit_txid());
+            self.state.active_contracts.insert(sm.deposit_txid(), sm);
+        }
+
+        if self.state.stake_chains.state() != &stake_chains_before {
+            stake_chains_updated = true;
+        } else {
+            undo.stake_chains = None;
+        }
+
+        Ok((duties, stake_chains_updated))
+    }
+
+    /// Persists the contract states, the new contracts, the stake chain state (if updated), the
+    /// block cursor and the undo information for the block in a single database transaction.
+    async fn persist_block(
+        &self,
+        height: BitcoinBlockHeight,
+        undo: &BlockUndo,
+        stake_chains_updated: bool,
+    ) -> Result<(), ContractManagerErr> {
+        let contract_persister = &self.state_handles.contract_persister;
+        let mut db_tx = contract_persister.begin().await?;
+
+        contract_persister
+            .commit_all(
+                &mut db_tx,
+                self.state
+                    .active_contracts
+                    .iter()
+                    .filter(|(txid, _)| !undo.new_contracts.contains(txid)),
+            )
+            .await?;
+
+        for deposit_txid in &undo.new_contracts {
+            let sm = &self.state.active_contracts[deposit_txid];
+            contract_persister
+                .init(&mut db_tx, sm.cfg(), sm.state())
+                .await?;
+        }
+
+        if stake_chains_updated {
+            info!("committing stake chain state");
             self.state_handles
-                .contract_persister
-                .init(sm.cfg(), sm.state())
+                .stake_chain_persister
+                .commit_stake_data_with(
+                    &mut db_tx,
+                    &self.cfg.operator_table,
+                    self.state.stake_chains.state().clone(),
+                )
                 .await?;
+        }
 
-            self.state.active_contracts.insert(sm.deposit_txid(), sm);
+        contract_persister.commit_cursor(&mut db_tx, height).await?;
+
+        contract_persister
+            .commit_undo(&mut db_tx, height, undo)
+            .await?;
+        if let Some(buried_height) = height.checked_sub(self.bury_depth as u64) {
+            contract_persister
+                .prune_undos(&mut db_tx, buried_height)
+                .await?;
         }
 
-        Ok(duties)
+        db_tx
+            .commit()
+            .await
+            .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Reverts the in-memory effects of the block at the given height on the active contracts and
+    /// the stake chains.
+    ///
+    /// Contracts that were created by the block are dropped altogether.
+    fn undo_block(
+        &mut self,
+        height: BitcoinBlockHeight,
+        undo: BlockUndo,
+    ) -> Result<(), ContractManagerErr> {
+        if let Some(stake_chains) = undo.stake_chains {
+            info!(%height, "restoring stake chain state from before the block");
+            self.state.stake_chains = StakeChainSM::restore(
+                self.cfg.network,
+                self.cfg.operator_table.clone(),
+                self.cfg.stake_chain_params,
+                stake_chains,
+            )?;
+        }
+
+        for deposit_txid in &undo.new_contracts {
+            info!(%deposit_txid, %height, "dropping contract created by the block");
+            self.state.active_contracts.remove(deposit_txid);
+        }
+
+        let mut checkpoints = undo.checkpoints;
+        for (deposit_txid, contract) in self.state.active_contracts.iter_mut() {
+            let checkpoint = checkpoints.remove(deposit_txid).map(Box::new);
+            if checkpoint.is_none() && contract.state().block_height < height {
+                // This contract was not affected by the block.
+                continue;
+            }
+
+            if checkpoint.is_some() {
+                info!(%deposit_txid, %height, "restoring contract state from before the block");
+            }
+
+            contract
+                .process_contract_event(ContractEvent::BlockDisconnected { height, checkpoint })?;
+        }
+
+        Ok(())
+    }
+
+    /// Rolls back every active contract to its last state that is consistent with the chain tip
+    /// preceding the disconnected block at the given height.
+    ///
+    /// Contracts that were created by the disconnected block are dropped altogether.
+    async fn process_block_disconnect(
+        &mut self,
+        height: BitcoinBlockHeight,
+    ) -> Result<(), ContractManagerErr> {
+        let Some(undo) = self.state.block_undos.remove(&height) else {
+            return Err(ContractManagerErr::FatalErr(
+                format!("no undo information retained for block at height {height}").into(),
+            ));
+        };
+
+        let new_contracts = undo.new_contracts.clone();
+        let stake_chains_restored = undo.stake_chains.is_some();
+        self.undo_block(height, undo)?;
+
+        let contract_persister = &self.state_handles.contract_persister;
+        let mut db_tx = contract_persister.begin().await?;
+
+        for deposit_txid in &new_contracts {
+            contract_persister.delete(&mut db_tx, deposit_txid).await?;
+        }
+
+        contract_persister
+            .commit_all(&mut db_tx, self.state.active_contracts.iter())
+            .await?;
+
+        if stake_chains_restored {
+            self.state_handles
+                .stake_chain_persister
+                .commit_stake_data_with(
+                    &mut db_tx,
+                    &self.cfg.operator_table,
+                    self.state.stake_chains.state().clone(),
+                )
+                .await?;
+        }
+
+        contract_persister
+            .commit_cursor(&mut db_tx, height - 1)
+            .await?;
+        contract_persister.delete_undo(&mut db_tx, height).await?;
+
+        db_tx
+            .commit()
+            .await
+            .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+        Ok(())
     }
 
     /// This function validates whether a transaction is a valid Strata checkpoint transaction,
     /// extracts any valid assigned deposit entries and produces the `Assignment` [`ContractEvent`]
     /// so that it can be processed further.
-    async fn process_assignments(
+    fn process_assignments(
         &mut self,
         tx: &Transaction,
+        undo: &mut BlockUndo,
     ) -> Result<Vec<OperatorDuty>, ContractManagerErr> {
         let mut duties = Vec::new();
 
@@ -603,26 +968,18 @@ impl ContractManagerCtx {
                         continue;
                     };
 
+                    undo.checkpoint(sm);
                     match sm
                         .process_contract_event(ContractEvent::Assignment(entry.clone(), stake_tx))
                     {
                         Ok(Some(duty)) => {
-                            info!("committing stake chain state");
-                            self.state_handles
-                                .stake_chain_persister
-                                .commit_stake_data(
-                                    &self.cfg.operator_table,
-                                    self.state.stake_chains.state().clone(),
-                                )
-                                .await?;
-
                             duties.push(duty);
                         }
                         Ok(None) => {
                             info!(?entry, "no duty generated for assignment");
                         }
                         Err(e) => {
-                            error!(%e, "could not generate duty for assignment event");
+                            error!(%deposit_txid, %e, "could not generate duty for assignment event");
                             return Err(e)?;
                         }
                     }
@@ -939,9 +1296,189 @@ impl ContractManagerCtx {
     }
 }
 
+/// The delay before the first retry of a failed duty.
+const DUTY_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The maximum delay between two consecutive attempts at executing a duty.
+const DUTY_RETRY_MAX_DELAY: Duration = Duration::from_secs(600);
+
+/// The number of attempts at executing a duty after which it is given up on.
+///
+/// With the backoff above, the 29 waits between attempts add up to about 3.5 hours: 1023 seconds
+/// while the delay doubles up to 512 seconds and 19 more waits of 600 seconds.
+const DUTY_MAX_ATTEMPTS: u32 = 30;
+
+/// Adds the duty to the persistent queue and starts executing it.
+///
+/// If the duty cannot be persisted, it is still executed but will not be resumed after a restart.
+async fn dispatch_duty(
+    cfg: &ExecutionConfig,
+    output_handles: &Arc<OutputHandles>,
+    duty: OperatorDuty,
+) {
+    // duties that are emitted repeatedly, such as the assert chain, are deduplicated by their id
+    // so the ones that were given up on are not started over.
+    if let Ok(duty_id) = DutyId::of(&duty) {
+        match output_handles.duty_persister.is_dead(&duty_id).await {
+            Ok(true) => {
+                debug!(%duty_id, "duty was given up on, not executing it again");
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => warn!(%duty_id, %e, "could not check whether the duty was given up on"),
+        }
+    }
+
+    let duty_id = match output_handles.duty_persister.enqueue(&duty).await {
+        Ok(duty_id) => duty_id,
+        Err(e) => {
+            error!(%e, ?duty, "could not persist duty, executing it regardless");
+            match DutyId::of(&duty) {
+                Ok(duty_id) => duty_id,
+                Err(e) => {
+                    error!(%e, ?duty, "could not compute duty id, dropping duty");
+                    return;
+                }
+            }
+        }
+    };
+
+    spawn_duty(cfg, output_handles, duty_id, duty).await;
+}
+
+/// Spawns a task that executes the duty until it succeeds, unless the same duty is already being
+/// executed.
+async fn spawn_duty(
+    cfg: &ExecutionConfig,
+    output_handles: &Arc<OutputHandles>,
+    duty_id: DutyId,
+    duty: OperatorDuty,
+) {
+    // the lock is held until the task is registered so that it cannot deregister itself before.
+    let mut in_flight_duties = output_handles.in_flight_duties.lock().await;
+    if in_flight_duties.contains_key(&duty_id) {
+        debug!(%duty_id, "duty is already being executed");
+        return;
+    }
+
+    let task_cfg = cfg.clone();
+    let task_output_handles = output_handles.clone();
+    let task_duty = duty.clone();
+    let handle = tokio::task::spawn(async move {
+        execute_duty_with_retries(task_cfg, task_output_handles, duty_id, task_duty).await;
+    })
+    .abort_handle();
+    in_flight_duties.insert(duty_id, InFlightDuty { duty, handle });
+}
+
+/// Aborts the execution of the duties that the active contracts have moved past and removes them
+/// from the queue.
+///
+/// Duties emitted on behalf of contracts that are no longer active are cancelled as well.
+async fn cancel_stale_duties(
+    active_contracts: &BTreeMap<Txid, ContractSM>,
+    output_handles: &OutputHandles,
+) {
+    let mut in_flight_duties = output_handles.in_flight_duties.lock().await;
+    let stale_duties = in_flight_duties
+        .iter()
+        .filter(|(_, in_flight)| {
+            in_flight.duty.is_contract_bound()
+                && active_contracts
+                    .values()
+                    .find(|sm| sm.emitted(&in_flight.duty))
+                    .is_none_or(|sm| sm.has_moved_past(&in_flight.duty))
+        })
+        .map(|(duty_id, _)| *duty_id)
+        .collect::<Vec<_>>();
+
+    for duty_id in stale_duties {
+        if let Some(in_flight) = in_flight_duties.remove(&duty_id) {
+            info!(%duty_id, duty=?in_flight.duty, "cancelling duty that the contract has moved past");
+            in_flight.handle.abort();
+        }
+
+        if let Err(e) = output_handles.duty_persister.cancel(&duty_id).await {
+            // the duty will be resumed on restart and cancelled again then.
+            error!(%duty_id, %e, "could not remove cancelled duty from the queue");
+        }
+    }
+}
+
+/// Executes the duty, retrying with an exponential backoff until it succeeds.
+///
+/// The duty is only removed from the queue once it has been executed successfully so that it is
+/// resumed if the node crashes in the meantime. This means that a duty may be executed more than
+/// once and so, every duty handler must be idempotent.
+///
+/// Duties that fail with a permanent error or that still fail after [`DUTY_MAX_ATTEMPTS`]
+/// attempts are moved to the dead letter table instead.
+async fn execute_duty_with_retries(
+    cfg: ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    duty_id: DutyId,
+    duty: OperatorDuty,
+) {
+    let mut delay = DUTY_RETRY_BASE_DELAY;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match output_handles.duty_persister.record_attempt(&duty_id).await {
+            // attempts made before a restart count as well.
+            Ok(persisted_attempt) => attempt = persisted_attempt,
+            Err(e) => warn!(%duty_id, %e, "could not record duty execution attempt"),
+        }
+        info!(%duty_id, %attempt, "executing duty");
+
+        match execute_duty(cfg.clone(), output_handles.clone(), duty_id, duty.clone()).await {
+            Ok(()) => {
+                if let Err(e) = output_handles.duty_persister.complete(&duty_id).await {
+                    // the duty will be executed once more on restart which is fine since duties
+                    // are idempotent.
+                    error!(%duty_id, %e, "could not mark duty as complete");
+                }
+                break;
+            }
+            Err(ContractManagerErr::TxDriverErr(DriveErr::InputsSpent { conflicting_txid })) => {
+                // retrying cannot help as the transaction can never be confirmed. The state machine
+                // reacts to the conflicting transaction which is already confirmed.
+                warn!(%duty_id, %conflicting_txid, "inputs of the duty's tx have been spent, abandoning duty");
+                if let Err(e) = output_handles.duty_persister.complete(&duty_id).await {
+                    error!(%duty_id, %e, "could not mark duty as complete");
+                }
+                break;
+            }
+            Err(e) if e.is_permanent() || attempt >= DUTY_MAX_ATTEMPTS => {
+                error!(%duty_id, %e, %attempt, ?duty, "giving up on duty");
+                let reason = format!("failed after {attempt} attempts: {e}");
+                if let Err(e) = output_handles
+                    .duty_persister
+                    .dead_letter(&duty_id, &reason)
+                    .await
+                {
+                    error!(%duty_id, %e, "could not move duty to the dead letter table");
+                }
+                break;
+            }
+            Err(e) => {
+                error!(%duty_id, %e, ?delay, "failed to execute duty, retrying");
+                time::sleep(delay).await;
+                delay = (delay * 2).min(DUTY_RETRY_MAX_DELAY);
+            }
+        }
+    }
+
+    output_handles
+        .in_flight_duties
+        .lock()
+        .await
+        .remove(&duty_id);
+}
+
 async fn execute_duty(
     cfg: ExecutionConfig,
     output_handles: Arc<OutputHandles>,
+    duty_id: DutyId,
     duty: OperatorDuty,
 ) -> Result<(), ContractManagerErr> {
     let OutputHandles {
@@ -950,6 +1487,7 @@ async fn execute_duty(
         s2_client,
         tx_driver,
         db,
+        ..
     } = &*output_handles;
 
     match duty {
@@ -963,25 +1501,22 @@ async fn execute_duty(
             let operator_pk = s2_client.general_wallet_signer().pubkey().await?;
 
             let wots_client = s2_client.wots_signer();
-            /// VOUT is static because irrelevant so we're just gonna use 0
-            const VOUT: u32 = 0;
-            // withdrawal_fulfillment uses index 0
             let withdrawal_fulfillment = Wots256PublicKey::from_flattened_bytes(
                 &wots_client
-                    .get_256_public_key(deposit_txid, VOUT, 0)
+                    .get_256_public_key(deposit_txid, WOTS_VOUT, WITHDRAWAL_FULFILLMENT_WOTS_INDEX)
                     .await?,
             );
             const NUM_FQS: usize = NUM_U256;
             const NUM_PUB_INPUTS: usize = NUM_PUBS;
             const NUM_HASHES: usize = NUM_HASH;
             let public_inputs_ftrs: [_; NUM_PUB_INPUTS] = std::array::from_fn(|i| {
-                wots_client.get_256_public_key(deposit_txid, VOUT, i as u32)
+                wots_client.get_256_public_key(deposit_txid, WOTS_VOUT, public_input_wots_index(i))
             });
             let fqs_ftrs: [_; NUM_FQS] = std::array::from_fn(|i| {
-                wots_client.get_256_public_key(deposit_txid, VOUT, (i + NUM_PUB_INPUTS) as u32)
+                wots_client.get_256_public_key(deposit_txid, WOTS_VOUT, fq_wots_index(i))
             });
             let hashes_ftrs: [_; NUM_HASHES] = std::array::from_fn(|i| {
-                wots_client.get_128_public_key(deposit_txid, VOUT, i as u32)
+                wots_client.get_128_public_key(deposit_txid, WOTS_VOUT, hash_wots_index(i))
             });
 
             let (public_inputs, fqs, hashes) = join3(
@@ -1075,7 +1610,8 @@ async fn execute_duty(
                 FundingUtxo::ShouldRefill { op, left } => {
                     info!("refilling stakechain funding utxos, have {left} left");
 
-                    let psbt = wallet.refill_claim_funding_utxos(FeeRate::BROADCAST_MIN)?;
+                    let fee_rate = wallet.fee_rate(FeeUrgency::Background).await;
+                    let psbt = wallet.refill_claim_funding_utxos(fee_rate)?;
                     finalize_claim_funding_tx(s2_client, tx_driver, wallet.general_wallet(), psbt)
                         .await?;
 
@@ -1087,7 +1623,8 @@ async fn execute_duty(
                     //
                     // For every case afterwards, we should receive a `ShouldRefill` message before
                     // the wallet is actually empty.
-                    let psbt = wallet.refill_claim_funding_utxos(FeeRate::BROADCAST_MIN)?;
+                    let fee_rate = wallet.fee_rate(FeeUrgency::Background).await;
+                    let psbt = wallet.refill_claim_funding_utxos(fee_rate)?;
                     finalize_claim_funding_tx(s2_client, tx_driver, wallet.general_wallet(), psbt)
                         .await?;
 
@@ -1131,10 +1668,250 @@ async fn execute_duty(
 
             Ok(())
         }
+        OperatorDuty::PublishGraphNonces {
+            deposit_txid,
+            graph_inputs,
+        } => {
+            handle_publish_graph_nonces(&cfg, output_handles.clone(), deposit_txid, graph_inputs)
+                .await
+        }
+        OperatorDuty::PublishGraphSignatures {
+            deposit_txid,
+            graph_inputs,
+            graph_nonces,
+        } => {
+            handle_publish_graph_sigs(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_inputs,
+                graph_nonces,
+            )
+            .await
+        }
+        OperatorDuty::PublishRootNonce {
+            deposit_request_txid,
+            deposit_txid,
+            witness,
+        } => {
+            handle_publish_root_nonce(
+                &cfg,
+                output_handles.clone(),
+                deposit_request_txid,
+                deposit_txid,
+                witness,
+            )
+            .await
+        }
+        OperatorDuty::PublishRootSignature {
+            deposit_request_txid,
+            deposit_txid,
+            witness,
+            deposit_tx,
+            deposit_request_prevout,
+            root_nonces,
+        } => {
+            handle_publish_root_signature(
+                &cfg,
+                output_handles.clone(),
+                deposit_request_txid,
+                deposit_txid,
+                witness,
+                deposit_tx,
+                deposit_request_prevout,
+                root_nonces,
+            )
+            .await
+        }
+        OperatorDuty::PublishDeposit {
+            deposit_tx,
+            witness,
+            deposit_request_prevout,
+            root_nonces,
+            root_sigs,
+        } => {
+            handle_publish_deposit(
+                &cfg,
+                output_handles.clone(),
+                deposit_tx,
+                witness,
+                deposit_request_prevout,
+                root_nonces,
+                root_sigs,
+            )
+            .await
+        }
         OperatorDuty::FulfillerDuty(FulfillerDuty::AdvanceStakeChain {
             stake_index,
             stake_tx,
         }) => handle_advance_stake_chain(&cfg, output_handles.clone(), stake_index, stake_tx).await,
+        OperatorDuty::FulfillerDuty(FulfillerDuty::PublishFulfillment {
+            withdrawal_metadata,
+            user_descriptor,
+        }) => {
+            handle_publish_fulfillment(
+                &cfg,
+                output_handles.clone(),
+                duty_id,
+                withdrawal_metadata,
+                user_descriptor,
+            )
+            .await
+        }
+        OperatorDuty::FulfillerDuty(FulfillerDuty::PublishClaim {
+            deposit_txid,
+            graph_input,
+            withdrawal_fulfillment_txid,
+        }) => {
+            handle_publish_claim(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_input,
+                withdrawal_fulfillment_txid,
+            )
+            .await
+        }
+        OperatorDuty::FulfillerDuty(FulfillerDuty::PublishPayoutOptimistic {
+            deposit_txid,
+            graph_input,
+            graph_sigs,
+            deadline,
+        }) => {
+            handle_publish_payout_optimistic(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_input,
+                graph_sigs,
+                deadline,
+            )
+            .await
+        }
+        OperatorDuty::FulfillerDuty(FulfillerDuty::PublishAssertChain {
+            deposit_txid,
+            deposit_idx,
+            deposit_request_height,
+            graph_input,
+            graph_sigs,
+            withdrawal_fulfillment_txid,
+            withdrawal_fulfillment_height,
+        }) => {
+            handle_publish_assert_chain(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                deposit_idx,
+                deposit_request_height,
+                graph_input,
+                graph_sigs,
+                withdrawal_fulfillment_txid,
+                withdrawal_fulfillment_height,
+            )
+            .await
+        }
+        OperatorDuty::FulfillerDuty(FulfillerDuty::PublishPayout {
+            deposit_txid,
+            graph_input,
+            graph_sigs,
+            deadline,
+        }) => {
+            handle_publish_payout(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_input,
+                graph_sigs,
+                deadline,
+            )
+            .await
+        }
+        OperatorDuty::VerifierDuty(VerifierDuty::VerifyClaim {
+            deposit_txid,
+            claim_tx,
+            graph_input,
+            graph_sigs,
+            withdrawal_fulfillment_txid,
+            challenge_deadline,
+        }) => {
+            handle_verify_claim(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                claim_tx,
+                graph_input,
+                graph_sigs,
+                withdrawal_fulfillment_txid,
+                challenge_deadline,
+            )
+            .await
+        }
+        OperatorDuty::VerifierDuty(VerifierDuty::PublishChallenge {
+            deposit_txid,
+            graph_input,
+            graph_sigs,
+            deadline,
+        }) => {
+            handle_publish_challenge(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_input,
+                graph_sigs,
+                deadline,
+            )
+            .await
+        }
+        OperatorDuty::VerifierDuty(VerifierDuty::VerifyAssertion {
+            deposit_txid,
+            graph_input,
+            graph_sigs,
+            claim_height,
+            post_assert_height,
+            disprove_deadline,
+        }) => {
+            handle_verify_assertion(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_input,
+                graph_sigs,
+                claim_height,
+                post_assert_height,
+                disprove_deadline,
+            )
+            .await
+        }
+        OperatorDuty::VerifierDuty(VerifierDuty::PublishDisprove {
+            deposit_txid,
+            graph_input,
+            graph_sigs,
+            claim_height,
+            post_assert_height,
+            deadline,
+        }) => {
+            // the disprove witness is not part of the duty so we have to find it again.
+            handle_verify_assertion(
+                &cfg,
+                output_handles.clone(),
+                deposit_txid,
+                graph_input,
+                graph_sigs,
+                claim_height,
+                post_assert_height,
+                deadline,
+            )
+            .await
+        }
+        OperatorDuty::ForgetGraphs { deposit_txid } => {
+            info!(%deposit_txid, "forgetting the graph transactions registered with the secret service");
+            s2_client
+                .policy_registry()
+                .forget_deposit(deposit_txid)
+                .await?;
+
+            Ok(())
+        }
         ignored_duty => {
             warn!(?ignored_duty, "ignoring duty");
             Ok(())
@@ -1148,49 +1925,82 @@ async fn finalize_claim_funding_tx(
     general_wallet: &Wallet,
     psbt: Psbt,
 ) -> Result<(), ContractManagerErr> {
-    let mut tx = psbt.unsigned_tx;
-    let txins_as_outs = tx
-        .input
-        .iter()
-        .map(|txin| {
-            general_wallet
+    let tx = sign_general_wallet_tx(s2_client, general_wallet, psbt).await?;
+
+    info!(
+        txid = %tx.compute_txid(),
+        "submitting claim funding tx to the tx driver"
+    );
+    tx_driver.drive(tx, None, FinalityPolicy::Buried).await?;
+
+    Ok(())
+}
+
+/// Signs all the inputs of a transaction funded by the general wallet with the general wallet
+/// signer.
+async fn sign_general_wallet_tx(
+    s2_client: &SecretServiceClient,
+    general_wallet: &Wallet,
+    psbt: Psbt,
+) -> Result<Transaction, ContractManagerErr> {
+    let mut tx = psbt.unsigned_tx;
+    let txins_as_outs = tx
+        .input
+        .iter()
+        .map(|txin| {
+            general_wallet
                 .get_utxo(txin.previous_output)
                 .expect("always have this output because the wallet selected it in the first place")
                 .txout
         })
         .collect::<Vec<_>>();
-    let mut sighasher = SighashCache::new(&mut tx);
-    let sighash_type = TapSighashType::All;
-    let prevouts = Prevouts::All(&txins_as_outs);
+    let signer = s2_client.general_wallet_signer();
+    let mut signatures = Vec::with_capacity(txins_as_outs.len());
     for input_index in 0..txins_as_outs.len() {
-        let sighash = sighasher
-            .taproot_key_spend_signature_hash(input_index, &prevouts, sighash_type)
-            .expect("failed to construct sighash");
-        let signature = s2_client
-            .general_wallet_signer()
-            .sign(&sighash.to_byte_array(), None)
-            .await?;
-
-        let signature = bitcoin::taproot::Signature {
-            signature,
-            sighash_type,
+        let request = SigningRequest {
+            tx: tx.clone(),
+            prevouts: txins_as_outs.clone(),
+            input_index,
+            spend_path: SpendPath::KeySpend { merkle_root: None },
+            sighash_type: TapSighashType::All,
         };
-        sighasher
-            .witness_mut(input_index)
-            .expect("an input here")
-            .push(signature.to_vec());
+        signatures.push(signer.sign_tx(request).await??);
     }
 
-    info!(
-        txid = %tx.compute_txid(),
-        "submitting claim funding tx to the tx driver"
-    );
-    tx_driver
-        .drive(tx, 0)
-        .await
-        .map_err(|e| ContractManagerErr::FatalErr(Box::new(e)))?;
+    for (txin, signature) in tx.input.iter_mut().zip(signatures) {
+        txin.witness.push(signature.to_vec());
+    }
 
-    Ok(())
+    Ok(tx)
+}
+
+/// Builds the request to sign the given input of a stake transaction.
+fn stake_tx_signing_request(stake_tx: &StakeTx, input_index: usize) -> SigningRequest {
+    let prevouts = stake_tx
+        .psbt
+        .inputs
+        .iter()
+        .map(|input| {
+            input
+                .witness_utxo
+                .clone()
+                .expect("must have a witness utxo")
+        })
+        .collect();
+    let sighash_type = stake_tx.psbt.inputs[input_index]
+        .sighash_type
+        .map_or(Ok(TapSighashType::Default), |sighash_type| {
+            sighash_type.taproot_hash_ty()
+        })
+        .expect("stake transactions must use taproot sighash types");
+
+    SigningRequest {
+        tx: stake_tx.psbt.unsigned_tx.clone(),
+        prevouts,
+        input_index,
+        spend_path: SpendPath::from(&stake_tx.witnesses()[input_index]),
+        sighash_type,
+    }
 }
 
 async fn handle_advance_stake_chain(
@@ -1208,12 +2018,12 @@ async fn handle_advance_stake_chain(
         .await?
         .ok_or(StakeChainErr::StakeSetupDataNotFound(op_p2p_key.clone()))?;
 
-    let messages = stake_tx.sighashes();
     let funds_signature = output_handles
         .s2_client
         .general_wallet_signer()
-        .sign(messages[0].as_ref(), None)
-        .await?;
+        .sign_tx(stake_tx_signing_request(&stake_tx, 0))
+        .await??
+        .signature;
 
     let signed_stake_tx = if stake_index == 0 {
         // the first stake transaction spends the pre-stake which is locked by the key in the
@@ -1221,8 +2031,9 @@ async fn handle_advance_stake_chain(
         let stake_signature = output_handles
             .s2_client
             .general_wallet_signer()
-            .sign(messages[1].as_ref(), None)
-            .await?;
+            .sign_tx(stake_tx_signing_request(&stake_tx, 1))
+            .await??
+            .signature;
 
         stake_tx.finalize_initial(funds_signature, stake_signature)
     } else {
@@ -1266,8 +2077,9 @@ async fn handle_advance_stake_chain(
         let stake_signature = output_handles
             .s2_client
             .stakechain_wallet_signer()
-            .sign_no_tweak(messages[1].as_ref())
-            .await?;
+            .sign_tx(stake_tx_signing_request(&stake_tx, 1))
+            .await??
+            .signature;
 
         stake_tx.finalize(
             &prev_preimage,
@@ -1277,13 +2089,1438 @@ async fn handle_advance_stake_chain(
         )
     };
 
-    let confirm_by = 1;
-    // FIXME: (@Rajil1213) change this to the current block height
-    // once the tx driver's deadline handling is implemented
     output_handles
         .tx_driver
-        .drive(signed_stake_tx, confirm_by)
+        .drive(signed_stake_tx, None, FinalityPolicy::Buried)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_publish_fulfillment(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    duty_id: DutyId,
+    withdrawal_metadata: WithdrawalMetadata,
+    user_descriptor: Descriptor,
+) -> Result<(), ContractManagerErr> {
+    let deposit_txid = withdrawal_metadata.deposit_txid;
+    let deposit_idx = withdrawal_metadata.deposit_idx;
+
+    // the fulfillment pays the user out of the operator's own funds so a retry must never build a
+    // second one. Instead, the transaction from the previous attempt is resubmitted.
+    if let Some(signed_tx) = output_handles.duty_persister.load_tx(&duty_id).await? {
+        let withdrawal_fulfillment_txid = signed_tx.compute_txid();
+        info!(%deposit_txid, %withdrawal_fulfillment_txid, "resubmitting withdrawal fulfillment tx to the tx driver");
+        output_handles
+            .tx_driver
+            .drive(signed_tx, None, FinalityPolicy::Buried)
+            .await?;
+
+        return Ok(());
+    }
+
+    let user_address = user_descriptor.to_address(cfg.network).map_err(|e| {
+        TransitionErr(format!(
+            "cannot front withdrawal to user descriptor ({user_descriptor}): {e}"
+        ))
+    })?;
+    let PegOutGraphParams {
+        deposit_amount,
+        operator_fee,
+        ..
+    } = cfg.pegout_graph_params;
+    let amount = deposit_amount - operator_fee;
+
+    info!(%deposit_txid, %deposit_idx, %amount, "fronting withdrawal");
+    let mut wallet = output_handles.wallet.write().await;
+    info!("syncing wallet before fronting the withdrawal");
+    match wallet.sync().await {
+        Ok(()) => info!("synced wallet successfully"),
+        Err(e) => error!(?e, "could not sync wallet but proceeding regardless"),
+    }
+
+    let fee_rate = wallet.fee_rate(FeeUrgency::DeadlineCritical).await;
+    let psbt = wallet.front_withdrawal(
+        fee_rate,
+        user_address,
+        amount,
+        &withdrawal_metadata.op_return_data(),
+    )?;
+    let signed_tx =
+        sign_general_wallet_tx(&output_handles.s2_client, wallet.general_wallet(), psbt).await?;
+    // the transaction is persisted before it is broadcasted so that it is reused if this duty is
+    // retried.
+    output_handles
+        .duty_persister
+        .save_tx(&duty_id, &signed_tx)
+        .await?;
+    // release the wallet while the transaction is being driven.
+    drop(wallet);
+
+    // the contract state machine records this txid once the fulfillment confirms so that it can
+    // be committed to in the claim transaction.
+    let withdrawal_fulfillment_txid = signed_tx.compute_txid();
+    info!(%deposit_txid, %withdrawal_fulfillment_txid, "submitting withdrawal fulfillment tx to the tx driver");
+    output_handles
+        .tx_driver
+        .drive(signed_tx, None, FinalityPolicy::Buried)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_publish_claim(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_input: PegOutGraphInput,
+    withdrawal_fulfillment_txid: Txid,
+) -> Result<(), ContractManagerErr> {
+    let (graph, connectors) = PegOutGraph::generate(
+        graph_input,
+        &cfg.operator_table.tx_build_context(cfg.network),
+        deposit_txid,
+        cfg.pegout_graph_params.clone(),
+        cfg.connector_params,
+        cfg.stake_chain_params,
+        Vec::new(),
+    )?;
+    let claim_tx = graph.claim_tx;
+    let cpfp_vout = claim_tx.cpfp_vout();
+    let input_amount = parent_input_amount(claim_tx.psbt());
+
+    let message = withdrawal_fulfillment_txid.as_byte_array();
+    let compact_signature = output_handles
+        .s2_client
+        .wots_signer()
+        .get_256_signature(
+            deposit_txid,
+            WOTS_VOUT,
+            WITHDRAWAL_FULFILLMENT_WOTS_INDEX,
+            message,
+        )
+        .await??;
+    let signature = wots_from_compact(message, wots_digits(&compact_signature));
+
+    let signed_claim = claim_tx.finalize(signature, connectors.kickoff);
+
+    info!(%deposit_txid, %withdrawal_fulfillment_txid, claim_txid=%signed_claim.compute_txid(), "submitting claim tx to the tx driver");
+    drive_with_cpfp(
+        &output_handles,
+        signed_claim,
+        input_amount,
+        cpfp_vout,
+        connectors.connector_cpfp,
+        None,
+    )
+    .await
+}
+
+async fn handle_publish_payout_optimistic(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_input: PegOutGraphInput,
+    graph_sigs: Vec<schnorr::Signature>,
+    deadline: BitcoinBlockHeight,
+) -> Result<(), ContractManagerErr> {
+    let (graph, connectors) = PegOutGraph::generate(
+        graph_input,
+        &cfg.operator_table.tx_build_context(cfg.network),
+        deposit_txid,
+        cfg.pegout_graph_params.clone(),
+        cfg.connector_params,
+        cfg.stake_chain_params,
+        Vec::new(),
+    )?;
+    let [deposit_sig, c0_sig, c1_sig, c2_sig, p_sig] =
+        graph_tx_sigs(&graph, &graph_sigs, graph.payout_optimistic.compute_txid())?;
+    let payout_optimistic = graph.payout_optimistic;
+    let cpfp_vout = payout_optimistic.cpfp_vout();
+    let input_amount = parent_input_amount(payout_optimistic.psbt());
+
+    let signed_payout_optimistic = payout_optimistic.finalize(
+        deposit_sig,
+        c0_sig,
+        c1_sig,
+        c2_sig,
+        p_sig,
+        connectors.claim_out_0,
+        connectors.claim_out_1,
+        connectors.n_of_n,
+        connectors.hashlock_payout,
+    );
+
+    info!(%deposit_txid, payout_optimistic_txid=%signed_payout_optimistic.compute_txid(), "submitting payout optimistic tx to the tx driver");
+    drive_with_cpfp(
+        &output_handles,
+        signed_payout_optimistic,
+        input_amount,
+        cpfp_vout,
+        connectors.connector_cpfp,
+        Some(deadline),
+    )
+    .await
+}
+
+#[expect(clippy::too_many_arguments)]
+async fn handle_publish_assert_chain(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    deposit_idx: u32,
+    deposit_request_height: BitcoinBlockHeight,
+    graph_input: PegOutGraphInput,
+    graph_sigs: Vec<schnorr::Signature>,
+    withdrawal_fulfillment_txid: Txid,
+    withdrawal_fulfillment_height: BitcoinBlockHeight,
+) -> Result<(), ContractManagerErr> {
+    let (graph, connectors) = PegOutGraph::generate(
+        graph_input,
+        &cfg.operator_table.tx_build_context(cfg.network),
+        deposit_txid,
+        cfg.pegout_graph_params.clone(),
+        cfg.connector_params,
+        cfg.stake_chain_params,
+        Vec::new(),
+    )?;
+    let [pre_assert_sig] = graph_tx_sigs(
+        &graph,
+        &graph_sigs,
+        graph.assert_chain.pre_assert.compute_txid(),
+    )?;
+    let post_assert_sigs = graph_tx_sigs::<NUM_ASSERT_DATA_TX>(
+        &graph,
+        &graph_sigs,
+        graph.assert_chain.post_assert.compute_txid(),
+    )?;
+    let AssertChain {
+        pre_assert,
+        assert_data,
+        post_assert,
+    } = graph.assert_chain;
+
+    // generate the proof before publishing anything so that we never start the assert chain
+    // without being able to complete it.
+    info!(%deposit_txid, %withdrawal_fulfillment_txid, "generating proof for the assert chain");
+    let assertions = prove_and_generate_assertions(
+        cfg,
+        &output_handles,
+        deposit_idx,
+        deposit_request_height,
+        withdrawal_fulfillment_txid,
+        withdrawal_fulfillment_height,
+    )
+    .await?;
+    let wots_signatures =
+        sign_assertions(&output_handles.s2_client, deposit_txid, assertions).await?;
+
+    let pre_assert_input_amount = parent_input_amount(pre_assert.psbt());
+    let pre_assert_cpfp_vout = pre_assert.cpfp_vout();
+    let signed_pre_assert = pre_assert.finalize(connectors.claim_out_0, pre_assert_sig);
+
+    info!(%deposit_txid, pre_assert_txid=%signed_pre_assert.compute_txid(), "submitting pre-assert tx to the tx driver");
+    drive_with_cpfp(
+        &output_handles,
+        signed_pre_assert,
+        pre_assert_input_amount,
+        pre_assert_cpfp_vout,
+        connectors.connector_cpfp,
+        None,
+    )
+    .await?;
+
+    let assert_data_cpfp_vout = assert_data.cpfp_vout();
+    let assert_data_input_amounts: [Amount; NUM_ASSERT_DATA_TX] = std::array::from_fn(|i| {
+        assert_data
+            .total_input_amount(i)
+            .expect("must have an assert data tx at every index in the batch")
+    });
+    let signed_assert_data_txs = assert_data.finalize(
+        connectors.assert_data_hash_factory,
+        connectors.assert_data256_factory,
+        wots_signatures,
+    );
+
+    // the assert data txs only depend on the pre-assert tx so they are driven concurrently, one
+    // after the other they could take long enough to run past the timelocks.
+    let assert_data_drives = signed_assert_data_txs
+        .into_iter()
+        .zip(assert_data_input_amounts)
+        .enumerate()
+        .map(|(index, (signed_assert_data_tx, input_amount))| {
+            info!(%deposit_txid, %index, assert_data_txid=%signed_assert_data_tx.compute_txid(), "submitting assert data tx to the tx driver");
+            drive_with_cpfp(
+                &output_handles,
+                signed_assert_data_tx,
+                input_amount,
+                assert_data_cpfp_vout,
+                connectors.connector_cpfp,
+                None,
+            )
+        });
+    try_join_all(assert_data_drives).await?;
+
+    let post_assert_input_amount = parent_input_amount(post_assert.psbt());
+    let post_assert_cpfp_vout = post_assert.cpfp_vout();
+    let signed_post_assert = post_assert.finalize(&post_assert_sigs);
+
+    info!(%deposit_txid, post_assert_txid=%signed_post_assert.compute_txid(), "submitting post-assert tx to the tx driver");
+    drive_with_cpfp(
+        &output_handles,
+        signed_post_assert,
+        post_assert_input_amount,
+        post_assert_cpfp_vout,
+        connectors.connector_cpfp,
+        None,
+    )
+    .await
+}
+
+async fn handle_publish_payout(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_input: PegOutGraphInput,
+    graph_sigs: Vec<schnorr::Signature>,
+    deadline: BitcoinBlockHeight,
+) -> Result<(), ContractManagerErr> {
+    let (graph, connectors) = PegOutGraph::generate(
+        graph_input,
+        &cfg.operator_table.tx_build_context(cfg.network),
+        deposit_txid,
+        cfg.pegout_graph_params.clone(),
+        cfg.connector_params,
+        cfg.stake_chain_params,
+        Vec::new(),
+    )?;
+    let [deposit_sig, a3_sig, c2_sig, p_sig] =
+        graph_tx_sigs(&graph, &graph_sigs, graph.payout_tx.compute_txid())?;
+    let payout_tx = graph.payout_tx;
+    let cpfp_vout = payout_tx.cpfp_vout();
+    let input_amount = parent_input_amount(payout_tx.psbt());
+
+    let signed_payout = payout_tx.finalize(
+        deposit_sig,
+        a3_sig,
+        c2_sig,
+        p_sig,
+        connectors.post_assert_out_0,
+        connectors.n_of_n,
+        connectors.hashlock_payout,
+    );
+
+    info!(%deposit_txid, payout_txid=%signed_payout.compute_txid(), "submitting payout tx to the tx driver");
+    drive_with_cpfp(
+        &output_handles,
+        signed_payout,
+        input_amount,
+        cpfp_vout,
+        connectors.connector_cpfp,
+        Some(deadline),
+    )
+    .await
+}
+
+/// Collects the data required to prove the withdrawal fulfillment, generates the proof and turns
+/// it into the assertions that are committed to in the assert chain.
+///
+/// The proof commits to the most recent strata checkpoint before the withdrawal fulfillment that
+/// dispatches the deposit, and to the headers starting from the last L1 block verified in that
+/// checkpoint up to the required number of blocks after the withdrawal fulfillment. The
+/// checkpoint is searched for no further back than the deposit request.
+async fn prove_and_generate_assertions(
+    cfg: &ExecutionConfig,
+    output_handles: &OutputHandles,
+    deposit_idx: u32,
+    deposit_request_height: BitcoinBlockHeight,
+    withdrawal_fulfillment_txid: Txid,
+    withdrawal_fulfillment_height: BitcoinBlockHeight,
+) -> Result<Assertions, ContractManagerErr> {
+    let rpc_client = &output_handles.rpc_client;
+
+    let mut height = withdrawal_fulfillment_height;
+    let (checkpoint_block, checkpoint_tx_idx, chain_state) = loop {
+        let block = rpc_client.get_block_at(height).await?;
+        let checkpoint = block.txdata.iter().enumerate().find_map(|(idx, tx)| {
+            let checkpoint = parse_strata_checkpoint(tx, &cfg.sidesystem_params)?;
+            let chain_state =
+                borsh::from_slice::<Chainstate>(checkpoint.sidecar().chainstate()).ok()?;
+            let is_dispatched = chain_state
+                .deposits_table()
+                .get_deposit(deposit_idx)
+                .is_some_and(|entry| matches!(entry.deposit_state(), DepositState::Dispatched(_)));
+
+            is_dispatched.then_some((idx, chain_state))
+        });
+
+        if let Some((idx, chain_state)) = checkpoint {
+            break (block, idx, chain_state);
+        }
+
+        if height <= deposit_request_height {
+            return Err(TransitionErr(format!(
+                "no checkpoint dispatching deposit {deposit_idx} found between the deposit request (at {deposit_request_height}) and the withdrawal fulfillment ({withdrawal_fulfillment_txid})"
+            ))
+            .into());
+        }
+        height -= 1;
+    };
+    let checkpoint_height = height;
+
+    let start_height = chain_state
+        .l1_view()
+        .header_vs()
+        .last_verified_block
+        .height()
+        + 1;
+    let end_height = withdrawal_fulfillment_height
+        + REQUIRED_NUM_OF_HEADERS_AFTER_WITHDRAWAL_FULFILLMENT_TX as u64;
+    info!(%deposit_idx, %withdrawal_fulfillment_txid, %checkpoint_height, %start_height, %end_height, "collecting headers for the bridge proof");
+
+    let mut headers = Vec::new();
+    let mut withdrawal_fulfillment_tx = None;
+    for height in start_height..=end_height {
+        let block = if height == checkpoint_height {
+            checkpoint_block.clone()
+        } else {
+            rpc_client.get_block_at(height).await?
+        };
+
+        if height == withdrawal_fulfillment_height {
+            let idx = block
+                .txdata
+                .iter()
+                .position(|tx| tx.compute_txid() == withdrawal_fulfillment_txid)
+                .ok_or(TransitionErr(format!(
+                    "withdrawal fulfillment ({withdrawal_fulfillment_txid}) not found at height {height}"
+                )))?;
+            withdrawal_fulfillment_tx = Some((
+                L1TxWithProofBundle::generate(&block.txdata, idx as u32),
+                (height - start_height) as usize,
+            ));
+        }
+
+        headers.push(block.header);
+    }
+
+    let strata_checkpoint_tx = (
+        L1TxWithProofBundle::generate(&checkpoint_block.txdata, checkpoint_tx_idx as u32),
+        (checkpoint_height - start_height) as usize,
+    );
+    let withdrawal_fulfillment_tx = withdrawal_fulfillment_tx.ok_or(TransitionErr(format!(
+        "withdrawal fulfillment ({withdrawal_fulfillment_txid}) is not in the header chain"
+    )))?;
+
+    let op_signature = output_handles
+        .s2_client
+        .general_wallet_signer()
+        .sign_txid(withdrawal_fulfillment_tx.0.transaction().clone())
+        .await?;
+
+    let input = BridgeProofInput {
+        rollup_params: cfg.sidesystem_params.clone(),
+        pegout_graph_params: cfg.pegout_graph_params.clone(),
+        headers,
+        deposit_idx,
+        strata_checkpoint_tx,
+        withdrawal_fulfillment_tx,
+        op_signature: op_signature.into(),
+    };
+
+    // proving is CPU-bound so it must not block the runtime.
+    let (proof, public_inputs, public_output) =
+        task::spawn_blocking(move || prover::sp1_prove(&input))
+            .await
+            .map_err(|e| ContractManagerErr::FatalErr(Box::new(e)))?
+            .map_err(|e| ContractManagerErr::ProverErr(format!("{e:#}")))?;
+
+    let groth16 = generate_assertions(
+        proof,
+        public_inputs.to_vec(),
+        &bridge_vk::GROTH16_VERIFICATION_KEY,
+    )
+    .map_err(|e| TransitionErr(format!("could not generate assertions from proof: {e:?}")))?;
+
+    Ok(Assertions {
+        withdrawal_fulfillment: public_output.withdrawal_fulfillment_txid.0,
+        groth16,
+    })
+}
+
+/// Prestake vout every WOTS key of a deposit is derived with.
+///
+/// The vout is irrelevant since the keys are already scoped by the deposit txid, so 0 is used.
+const WOTS_VOUT: u32 = 0;
+
+/// WOTS index of the 256-bit key committing to the withdrawal fulfillment txid.
+///
+/// This key is used both by the claim and by the assertions, always to sign the same txid.
+const WITHDRAWAL_FULFILLMENT_WOTS_INDEX: u32 = 0;
+
+/// WOTS index of the 256-bit key committing to the `i`-th groth16 public input.
+///
+/// The public inputs come right after the withdrawal fulfillment, so that no key signs two
+/// different messages.
+const fn public_input_wots_index(i: usize) -> u32 {
+    WITHDRAWAL_FULFILLMENT_WOTS_INDEX + 1 + i as u32
+}
+
+/// WOTS index of the 256-bit key committing to the `i`-th groth16 field element.
+const fn fq_wots_index(i: usize) -> u32 {
+    public_input_wots_index(NUM_PUBS + i)
+}
+
+/// WOTS index of the 128-bit key committing to the `i`-th groth16 hash.
+///
+/// 128-bit keys are derived from different key material than 256-bit keys, so they have an index
+/// space of their own.
+const fn hash_wots_index(i: usize) -> u32 {
+    i as u32
+}
+
+/// WOTS-signs every element of the assertions with the keys that were shared during the deposit
+/// setup.
+async fn sign_assertions(
+    s2_client: &SecretServiceClient,
+    deposit_txid: Txid,
+    assertions: Assertions,
+) -> Result<Signatures, ContractManagerErr> {
+    let wots_client = s2_client.wots_signer();
+    let (public_inputs, fqs, hashes) = assertions.groth16;
+
+    // this is the key the claim committed to the withdrawal fulfillment with, so it signs the same
+    // message again.
+    let withdrawal_fulfillment = wots_client
+        .get_256_signature(
+            deposit_txid,
+            WOTS_VOUT,
+            WITHDRAWAL_FULFILLMENT_WOTS_INDEX,
+            &assertions.withdrawal_fulfillment,
+        )
+        .await??;
+    let withdrawal_fulfillment = Wots256Signature(wots_from_compact(
+        &assertions.withdrawal_fulfillment,
+        wots_digits(&withdrawal_fulfillment),
+    ));
+
+    let public_inputs_ftrs = public_inputs.iter().enumerate().map(|(i, msg)| {
+        wots_client.get_256_signature(deposit_txid, WOTS_VOUT, public_input_wots_index(i), msg)
+    });
+    let fqs_ftrs = fqs.iter().enumerate().map(|(i, msg)| {
+        wots_client.get_256_signature(deposit_txid, WOTS_VOUT, fq_wots_index(i), msg)
+    });
+    let hashes_ftrs = hashes.iter().enumerate().map(|(i, msg)| {
+        wots_client.get_128_signature(deposit_txid, WOTS_VOUT, hash_wots_index(i), msg)
+    });
+
+    let (public_inputs_sigs, fqs_sigs, hashes_sigs) = join3(
+        join_all(public_inputs_ftrs),
+        join_all(fqs_ftrs),
+        join_all(hashes_ftrs),
+    )
+    .await;
+
+    let public_inputs_sigs = public_inputs_sigs
+        .into_iter()
+        .map(|sig| Ok(sig??))
+        .collect::<Result<Vec<_>, ContractManagerErr>>()?;
+    let fqs_sigs = fqs_sigs
+        .into_iter()
+        .map(|sig| Ok(sig??))
+        .collect::<Result<Vec<_>, ContractManagerErr>>()?;
+    let hashes_sigs = hashes_sigs
+        .into_iter()
+        .map(|sig| Ok(sig??))
+        .collect::<Result<Vec<_>, ContractManagerErr>>()?;
+
+    let groth16 = Groth16Signatures((
+        Box::new(std::array::from_fn(|i| {
+            wots_from_compact(&public_inputs[i], wots_digits(&public_inputs_sigs[i]))
+        })),
+        Box::new(std::array::from_fn(|i| {
+            wots_from_compact(&fqs[i], wots_digits(&fqs_sigs[i]))
+        })),
+        Box::new(std::array::from_fn(|i| {
+            wots_from_compact(&hashes[i], wots_digits(&hashes_sigs[i]))
+        })),
+    ));
+
+    Ok(Signatures {
+        withdrawal_fulfillment,
+        groth16,
+    })
+}
+
+/// Checks that the claim commits to the withdrawal fulfillment that was observed on chain for the
+/// withdrawal assigned to the claimer in the strata checkpoint, and challenges it if it does not.
+#[expect(clippy::too_many_arguments)]
+async fn handle_verify_claim(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    claim_tx: Transaction,
+    graph_input: PegOutGraphInput,
+    graph_sigs: Vec<schnorr::Signature>,
+    withdrawal_fulfillment_txid: Option<Txid>,
+    challenge_deadline: BitcoinBlockHeight,
+) -> Result<(), ContractManagerErr> {
+    let claim_txid = claim_tx.compute_txid();
+    // the WOTS signature itself is checked by the kickoff connector so a confirmed claim always
+    // commits to *some* txid.
+    let committed_txid = ClaimTx::parse_witness(&claim_tx)
+        .map_err(TxGraphError::from)?
+        .map(|signature| Txid::from_byte_array(wots_to_byte_array(signature)));
+
+    match (committed_txid, withdrawal_fulfillment_txid) {
+        (Some(committed_txid), Some(withdrawal_fulfillment_txid))
+            if committed_txid == withdrawal_fulfillment_txid =>
+        {
+            info!(%deposit_txid, %claim_txid, %withdrawal_fulfillment_txid, "claim is valid");
+
+            Ok(())
+        }
+        (committed_txid, withdrawal_fulfillment_txid) => {
+            warn!(%deposit_txid, %claim_txid, ?committed_txid, ?withdrawal_fulfillment_txid, "claim is fraudulent, challenging it");
+
+            handle_publish_challenge(
+                cfg,
+                output_handles,
+                deposit_txid,
+                graph_input,
+                graph_sigs,
+                challenge_deadline,
+            )
+            .await
+        }
+    }
+}
+
+async fn handle_publish_challenge(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_input: PegOutGraphInput,
+    graph_sigs: Vec<schnorr::Signature>,
+    deadline: BitcoinBlockHeight,
+) -> Result<(), ContractManagerErr> {
+    let (graph, connectors) = PegOutGraph::generate(
+        graph_input,
+        &cfg.operator_table.tx_build_context(cfg.network),
+        deposit_txid,
+        cfg.pegout_graph_params.clone(),
+        cfg.connector_params,
+        cfg.stake_chain_params,
+        Vec::new(),
+    )?;
+    let [challenge_sig] = graph_tx_sigs(&graph, &graph_sigs, graph.challenge_tx.compute_txid())?;
+    let challenge_leaf = ConnectorC1Path::Challenge(taproot::Signature {
+        signature: challenge_sig,
+        sighash_type: ConnectorC1Path::Challenge(()).get_sighash_type(),
+    });
+
+    let mut challenge_tx = graph.challenge_tx;
+    let challenge_prevout = challenge_tx.psbt().inputs[0]
+        .witness_utxo
+        .clone()
+        .expect("challenge input must have a witness utxo");
+    let challenge_cost = cfg.pegout_graph_params.challenge_cost;
+
+    let mut wallet = output_handles.wallet.write().await;
+    info!("syncing wallet before funding the challenge");
+    match wallet.sync().await {
+        Ok(()) => info!("synced wallet successfully"),
+        Err(e) => error!(?e, "could not sync wallet but proceeding regardless"),
+    }
+
+    let funding_utxo = wallet
+        .general_utxos()
+        .into_iter()
+        .find(|utxo| utxo.txout.value > challenge_cost)
+        .ok_or(TransitionErr(format!(
+            "no UTXO in the general wallet can fund a challenge of {challenge_cost}"
+        )))?;
+    let change_script = wallet.general_script_buf().clone();
+    drop(wallet);
+
+    // the presigned input only commits to the first output so we are free to add a change output
+    // for the excess funds. The fee is deducted from it once the size of the transaction is known.
+    challenge_tx.psbt_mut().unsigned_tx.output.push(TxOut {
+        value: funding_utxo.txout.value - challenge_cost,
+        script_pubkey: change_script,
+    });
+    let funded_challenge_tx = challenge_tx
+        .add_funding_input(funding_utxo.outpoint, funding_utxo.txout.clone())
+        .map_err(TxGraphError::from)?;
+    let mut signed_challenge_tx = funded_challenge_tx
+        .finalize(connectors.claim_out_1, challenge_leaf)
+        .map_err(TxGraphError::from)?;
+
+    const FUNDING_INPUT_INDEX: usize = 1;
+    const CHANGE_VOUT: usize = 1;
+
+    // a key spend witness is a single 64-byte signature when using the default sighash type.
+    signed_challenge_tx.input[FUNDING_INPUT_INDEX].witness = Witness::from_slice(&[[0u8; 64]]);
+    let fee_rate = output_handles
+        .wallet
+        .read()
+        .await
+        .fee_rate(FeeUrgency::DeadlineCritical)
+        .await;
+    let fee = fee_rate
+        .fee_vb(signed_challenge_tx.vsize() as u64)
+        .expect("fee must not overflow");
+    let change = signed_challenge_tx.output[CHANGE_VOUT]
+        .value
+        .checked_sub(fee)
+        .filter(|change| {
+            *change
+                >= signed_challenge_tx.output[CHANGE_VOUT]
+                    .script_pubkey
+                    .minimal_non_dust()
+        })
+        .ok_or(TransitionErr(format!(
+            "funding UTXO ({}) cannot cover the challenge cost ({challenge_cost}) and fee ({fee})",
+            funding_utxo.outpoint
+        )))?;
+    signed_challenge_tx.output[CHANGE_VOUT].value = change;
+
+    let request = SigningRequest {
+        tx: signed_challenge_tx.clone(),
+        prevouts: vec![challenge_prevout, funding_utxo.txout],
+        input_index: FUNDING_INPUT_INDEX,
+        spend_path: SpendPath::KeySpend { merkle_root: None },
+        sighash_type: TapSighashType::Default,
+    };
+    let funding_signature = output_handles
+        .s2_client
+        .general_wallet_signer()
+        .sign_tx(request)
+        .await??;
+    signed_challenge_tx.input[FUNDING_INPUT_INDEX].witness =
+        Witness::from_slice(&[funding_signature.to_vec()]);
+
+    info!(%deposit_txid, challenge_txid=%signed_challenge_tx.compute_txid(), "submitting challenge tx to the tx driver");
+    output_handles
+        .tx_driver
+        .drive(signed_challenge_tx, Some(deadline), FinalityPolicy::Buried)
+        .await?;
+
+    Ok(())
+}
+
+/// Validates the assertions committed to in the assert chain against the groth16 verification key
+/// and disproves them if they are invalid.
+#[expect(clippy::too_many_arguments)]
+async fn handle_verify_assertion(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_input: PegOutGraphInput,
+    graph_sigs: Vec<schnorr::Signature>,
+    claim_height: BitcoinBlockHeight,
+    post_assert_height: BitcoinBlockHeight,
+    disprove_deadline: BitcoinBlockHeight,
+) -> Result<(), ContractManagerErr> {
+    let wots_public_keys = graph_input.wots_public_keys.clone();
+    let (graph, connectors) = PegOutGraph::generate(
+        graph_input,
+        &cfg.operator_table.tx_build_context(cfg.network),
+        deposit_txid,
+        cfg.pegout_graph_params.clone(),
+        cfg.connector_params,
+        cfg.stake_chain_params,
+        Vec::new(),
+    )?;
+
+    // the claim and the assert data transactions carry the WOTS signatures of the asserter so
+    // we collect them from the blocks between the claim and the post-assert.
+    let claim_txid = graph.claim_tx.compute_txid();
+    let assert_data_txids = graph.assert_chain.assert_data.compute_txids();
+    let mut claim_tx = None;
+    let mut assert_data_txs: [Option<Transaction>; NUM_ASSERT_DATA_TX] =
+        std::array::from_fn(|_| None);
+    for height in claim_height..=post_assert_height {
+        let block = output_handles.rpc_client.get_block_at(height).await?;
+        for tx in block.txdata {
+            let txid = tx.compute_txid();
+            if txid == claim_txid {
+                claim_tx = Some(tx);
+            } else if let Some(index) = assert_data_txids.iter().position(|t| *t == txid) {
+                assert_data_txs[index] = Some(tx);
+            }
+        }
+    }
+
+    let claim_tx = claim_tx.ok_or(TransitionErr(format!(
+        "claim ({claim_txid}) not found between heights {claim_height} and {post_assert_height}"
+    )))?;
+    let assert_data_txs = assert_data_txs
+        .into_iter()
+        .zip(assert_data_txids)
+        .map(|(tx, txid)| {
+            tx.ok_or(TransitionErr(format!(
+                "assert data tx ({txid}) not found between heights {claim_height} and {post_assert_height}"
+            )))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .try_into()
+        .expect("must have the right number of assert data txs");
+
+    let withdrawal_fulfillment_sig = ClaimTx::parse_witness(&claim_tx)
+        .map_err(TxGraphError::from)?
+        .ok_or(TransitionErr(format!(
+            "claim ({claim_txid}) has no withdrawal fulfillment commitment"
+        )))?;
+    let groth16_sigs = AssertDataTxBatch::parse_witnesses(&assert_data_txs)
+        .map_err(TxGraphError::from)?
+        .ok_or(TransitionErr(format!(
+            "assert data txs for deposit ({deposit_txid}) have no assertions"
+        )))?;
+
+    let Some(disprove_leaf) = find_disprove_leaf(
+        deposit_txid,
+        withdrawal_fulfillment_sig,
+        groth16_sigs,
+        wots_public_keys,
+    ) else {
+        info!(%deposit_txid, %claim_txid, "assertions are valid");
+        return Ok(());
+    };
+
+    warn!(%deposit_txid, %claim_txid, "assertions are invalid, disproving them");
+    let [stake_sig] = graph_tx_sigs(&graph, &graph_sigs, graph.disprove_tx.compute_txid())?;
+    let stake_path = StakeSpendPath::Disprove(taproot::Signature {
+        signature: stake_sig,
+        sighash_type: TapSighashType::Single,
+    });
+
+    let disprove_tx = graph.disprove_tx;
+    let input_amount = parent_input_amount(disprove_tx.psbt());
+    let burn_amount = cfg.stake_chain_params.burn_amount;
+
+    let reward_script = output_handles
+        .wallet
+        .read()
+        .await
+        .general_script_buf()
+        .clone();
+    let mut signed_disprove_tx = disprove_tx.finalize(
+        TxOut {
+            value: input_amount - burn_amount,
+            script_pubkey: reward_script,
+        },
+        stake_path,
+        disprove_leaf,
+        connectors.stake,
+        connectors.post_assert_out_0,
+    );
+
+    // the presigned stake input only commits to the burn output so the reward can be reduced to
+    // pay for the fees.
+    let fee_rate = output_handles
+        .wallet
+        .read()
+        .await
+        .fee_rate(FeeUrgency::DeadlineCritical)
+        .await;
+    let fee = fee_rate
+        .fee_vb(signed_disprove_tx.vsize() as u64)
+        .expect("fee must not overflow");
+    let reward = signed_disprove_tx
+        .output
+        .last_mut()
+        .expect("disprove tx must have a reward output");
+    reward.value = reward.value.checked_sub(fee).ok_or(TransitionErr(format!(
+        "disprove reward ({}) cannot cover the fee ({fee})",
+        reward.value
+    )))?;
+
+    info!(%deposit_txid, disprove_txid=%signed_disprove_tx.compute_txid(), "submitting disprove tx to the tx driver");
+    output_handles
+        .tx_driver
+        .drive(
+            signed_disprove_tx,
+            Some(disprove_deadline),
+            FinalityPolicy::Buried,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Finds the leaf of the [`ConnectorA3`](strata_bridge_connectors::prelude::ConnectorA3) that can
+/// be used to disprove the assertions, if any.
+///
+/// The public inputs hash committed to in the assertions is checked first as it is much cheaper
+/// to verify than the groth16 proof itself.
+fn find_disprove_leaf(
+    deposit_txid: Txid,
+    withdrawal_fulfillment_sig: wots256::Signature,
+    groth16_sigs: g16Signatures,
+    wots_public_keys: wots::PublicKeys,
+) -> Option<ConnectorA3Leaf> {
+    let withdrawal_fulfillment_txid: [u8; 32] = wots_to_byte_array(withdrawal_fulfillment_sig);
+    let public_output = BridgeProofPublicOutput {
+        deposit_txid: deposit_txid.into(),
+        withdrawal_fulfillment_txid: withdrawal_fulfillment_txid.into(),
+    };
+
+    // NOTE: This is zkvm-specific logic
+    let serialized_public_output =
+        borsh::to_vec(&public_output).expect("must be able to serialize the public output");
+    let public_inputs_hash = hash_public_inputs(&serialized_public_output);
+
+    // FIXME: fix nibble flipping and remove this
+    let committed_public_inputs_hash =
+        wots_to_byte_array(groth16_sigs.0[0]).map(|b| ((b & 0xf0) >> 4) | ((b & 0x0f) << 4));
+
+    if public_inputs_hash != committed_public_inputs_hash {
+        warn!(expected = ?public_inputs_hash, committed = ?committed_public_inputs_hash, "public inputs hash mismatch");
+
+        return Some(ConnectorA3Leaf::DisprovePublicInputsCommitment {
+            deposit_txid,
+            witness: Some(DisprovePublicInputsCommitmentWitness {
+                sig_withdrawal_fulfillment_txid: withdrawal_fulfillment_sig,
+                sig_public_inputs_hash: groth16_sigs.0[0],
+            }),
+        });
+    }
+
+    let groth16_public_keys = *wots_public_keys.groth16;
+    let disprove_scripts =
+        api_generate_full_tapscripts(groth16_public_keys, &PARTIAL_VERIFIER_SCRIPTS);
+
+    validate_assertions(
+        &bridge_vk::GROTH16_VERIFICATION_KEY,
+        groth16_sigs,
+        groth16_public_keys,
+        &disprove_scripts,
+    )
+    .map(
+        |(tapleaf_index, witness_script)| ConnectorA3Leaf::DisproveProof {
+            disprove_script: disprove_scripts[tapleaf_index].clone(),
+            witness_script: Some(witness_script),
+        },
+    )
+}
+
+/// Splits a compact WOTS signature returned by the secret service into the preimages of each
+/// digit.
+fn wots_digits<const N_DIGITS: usize>(compact_signature: &[u8]) -> [[u8; 20]; N_DIGITS] {
+    std::array::from_fn(|i| {
+        compact_signature[i * 20..(i + 1) * 20]
+            .try_into()
+            .expect("must have 20 bytes per digit")
+    })
+}
+
+/// Looks up the aggregated N-of-N signatures for the inputs of the transaction with the given
+/// txid in the peg-out-graph, in the order of the inputs.
+fn graph_tx_sigs<const N_INPUTS: usize>(
+    graph: &PegOutGraph,
+    graph_sigs: &[schnorr::Signature],
+    txid: Txid,
+) -> Result<[schnorr::Signature; N_INPUTS], ContractManagerErr> {
+    let sigs = graph
+        .musig_inputs()
+        .into_iter()
+        .zip(graph_sigs)
+        .filter(|(input, _)| input.txid == txid)
+        .map(|(_, sig)| *sig)
+        .collect::<Vec<_>>();
+    let num_sigs = sigs.len();
+
+    sigs.try_into().map_err(|_| {
+        TransitionErr(format!(
+            "expected {N_INPUTS} graph signatures for tx ({txid}), found {num_sigs}"
+        ))
+        .into()
+    })
+}
+
+/// The total amount spent by the inputs of a presigned transaction.
+fn parent_input_amount(psbt: &Psbt) -> Amount {
+    psbt.inputs
+        .iter()
+        .filter_map(|input| input.witness_utxo.as_ref())
+        .map(|prevout| prevout.value)
+        .sum()
+}
+
+/// Submits a presigned transaction to the tx driver which pays for its fees with a child
+/// transaction that spends its CPFP output.
+///
+/// The tx driver bumps the fees of the child as the deadline approaches, if there is one.
+async fn drive_with_cpfp(
+    output_handles: &OutputHandles,
+    parent_tx: Transaction,
+    parent_input_amount: Amount,
+    cpfp_vout: u32,
+    connector_cpfp: ConnectorCpfp,
+    deadline: Option<BitcoinBlockHeight>,
+) -> Result<(), ContractManagerErr> {
+    let parent_txid = parent_tx.compute_txid();
+    // a retried duty must not spend another CPFP UTXO to bump a transaction that is already out so
+    // it is only tracked until it is buried.
+    if is_tx_published(&output_handles.rpc_client, parent_txid).await {
+        info!(%parent_txid, "tx already published, skipping cpfp");
+        output_handles
+            .tx_driver
+            .drive(parent_tx, None, FinalityPolicy::Buried)
+            .await?;
+
+        return Ok(());
+    }
+
+    let anchor = CpfpAnchor {
+        parent_input_amount,
+        vout: cpfp_vout,
+        connector: connector_cpfp,
+    };
+
+    info!(%parent_txid, ?deadline, "submitting tx to the tx driver with a cpfp anchor");
+    let mut updates = output_handles.tx_driver.drive_with_updates(
+        parent_tx,
+        deadline,
+        Some(anchor),
+        DriveOptions::default(),
+    )?;
+    while let Some(update) = updates.next().await {
+        match update? {
+            DriveUpdate::Mempool => debug!(%parent_txid, "tx entered the mempool"),
+            DriveUpdate::Mined {
+                height,
+                confirmations,
+                ..
+            } => debug!(%parent_txid, %height, %confirmations, "tx mined"),
+            DriveUpdate::Reorged => warn!(%parent_txid, "tx reorged out of the chain"),
+            DriveUpdate::Final => return Ok(()),
+        }
+    }
+
+    Err(DriveErr::DriverAborted.into())
+}
+
+/// Checks whether the transaction is already known to the bitcoin node, either in the mempool or
+/// on chain.
+///
+/// This is used to keep the duty handlers idempotent as the same duty may be executed more than
+/// once. Confirmed transactions are only found if the node maintains a transaction index, which is
+/// why the bridge node refuses to start against a bitcoin node that runs without `txindex=1`.
+async fn is_tx_published(rpc_client: &BitcoinClient, txid: Txid) -> bool {
+    rpc_client
+        .get_raw_transaction_verbosity_zero(&txid)
+        .await
+        .is_ok()
+}
+
+/// Regenerates the peg-out-graphs of all the operators and collects the inputs that need to be
+/// signed with the N-of-N MuSig2 key.
+///
+/// The graphs are traversed in the order of the operators' p2p keys so that all operators agree on
+/// the position of each input in the nonce and signature vectors exchanged over p2p.
+fn graph_musig_inputs(
+    cfg: &ExecutionConfig,
+    deposit_txid: Txid,
+    graph_inputs: BTreeMap<P2POperatorPubKey, PegOutGraphInput>,
+) -> Result<Vec<MusigInput>, ContractManagerErr> {
+    let context = cfg.operator_table.tx_build_context(cfg.network);
+
+    let mut musig_inputs = Vec::new();
+    for graph_input in graph_inputs.into_values() {
+        let (graph, _connectors) = PegOutGraph::generate(
+            graph_input,
+            &context,
+            deposit_txid,
+            cfg.pegout_graph_params.clone(),
+            cfg.connector_params,
+            cfg.stake_chain_params,
+            Vec::new(),
+        )?;
+
+        musig_inputs.extend(graph.musig_inputs());
+    }
+
+    Ok(musig_inputs)
+}
+
+/// The public keys of all the operators in the order used for MuSig2 key aggregation.
+fn musig_ordered_pubkeys(operator_table: &OperatorTable) -> Vec<XOnlyPublicKey> {
+    operator_table
+        .public_key_table()
+        .0
+        .values()
+        .map(|pubkey| pubkey.x_only_public_key().0)
+        .collect()
+}
+
+/// The key used for MuSig2 signing by the operator with the given p2p key.
+fn musig_peer_pubkey(
+    operator_table: &OperatorTable,
+    op_key: &P2POperatorPubKey,
+) -> Result<XOnlyPublicKey, TransitionErr> {
+    Ok(operator_table
+        .op_key_to_btc_key(op_key)
+        .ok_or(TransitionErr(format!(
+            "received musig2 data from unknown operator {}",
+            op_key
+        )))?
+        .x_only_public_key()
+        .0)
+}
+
+async fn handle_publish_graph_nonces(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_inputs: BTreeMap<P2POperatorPubKey, PegOutGraphInput>,
+) -> Result<(), ContractManagerErr> {
+    let musig_inputs = graph_musig_inputs(cfg, deposit_txid, graph_inputs)?;
+    let ordered_pubkeys = musig_ordered_pubkeys(&cfg.operator_table);
+
+    info!(%deposit_txid, num_inputs = %musig_inputs.len(), "generating graph nonces");
+    let musig_signer = output_handles.s2_client.musig2_signer();
+    let mut nonces = Vec::with_capacity(musig_inputs.len());
+    for MusigInput {
+        txid,
+        input_index,
+        witness,
+        ..
+    } in musig_inputs
+    {
+        // the secret service derives the nonce deterministically from the input being signed so
+        // opening the session again when nagged yields the same nonce.
+        let session = musig_signer
+            .new_session(ordered_pubkeys.clone(), witness, txid, input_index)
+            .await?
+            .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+
+        nonces.push(session.our_nonce().await?);
+    }
+
+    info!(%deposit_txid, "broadcasting graph nonces");
+    let session_id = SessionId::from_bytes(*deposit_txid.as_ref());
+    output_handles
+        .msg_handler
+        .send_musig2_nonces(session_id, nonces)
+        .await;
+
+    Ok(())
+}
+
+async fn handle_publish_graph_sigs(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_txid: Txid,
+    graph_inputs: BTreeMap<P2POperatorPubKey, PegOutGraphInput>,
+    graph_nonces: BTreeMap<P2POperatorPubKey, Vec<PubNonce>>,
+) -> Result<(), ContractManagerErr> {
+    let musig_inputs = graph_musig_inputs(cfg, deposit_txid, graph_inputs)?;
+    let ordered_pubkeys = musig_ordered_pubkeys(&cfg.operator_table);
+    let pov_key = cfg.operator_table.pov_op_key();
+
+    // the nonces from our peers along with the keys they are registered to.
+    let peer_nonces = graph_nonces
+        .into_iter()
+        .filter(|(op_key, _)| op_key != pov_key)
+        .map(|(op_key, nonces)| {
+            let pubkey = musig_peer_pubkey(&cfg.operator_table, &op_key)?;
+
+            if nonces.len() != musig_inputs.len() {
+                return Err(TransitionErr(format!(
+                    "operator {} sent {} graph nonces but {} are required",
+                    op_key,
+                    nonces.len(),
+                    musig_inputs.len()
+                )));
+            }
+
+            Ok((pubkey, nonces))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the secret service only finalizes the MuSig2 sessions of the transactions registered with
+    // it, which are forgotten once the contract is over.
+    let txids = musig_inputs
+        .iter()
+        .map(|musig_input| musig_input.txid)
+        .collect::<BTreeSet<_>>();
+    info!(%deposit_txid, num_txs = %txids.len(), "registering the graph transactions with the secret service");
+    output_handles
+        .s2_client
+        .policy_registry()
+        .allow_txids(deposit_txid, txids.into_iter().collect())
+        .await??;
+
+    info!(%deposit_txid, num_inputs = %musig_inputs.len(), "generating graph signatures");
+    let musig_signer = output_handles.s2_client.musig2_signer();
+    let mut partial_sigs = Vec::with_capacity(musig_inputs.len());
+    for (index, musig_input) in musig_inputs.into_iter().enumerate() {
+        let MusigInput {
+            txid,
+            input_index,
+            witness,
+            sighash_type,
+            tx,
+            prevouts,
+            ..
+        } = musig_input;
+        let request = SigningRequest {
+            tx,
+            prevouts,
+            input_index: input_index as usize,
+            spend_path: SpendPath::from(&witness),
+            sighash_type,
+        };
+
+        let mut session = musig_signer
+            .new_session(ordered_pubkeys.clone(), witness, txid, input_index)
+            .await?
+            .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+
+        for (pubkey, nonces) in &peer_nonces {
+            session
+                .receive_pub_nonce(*pubkey, nonces[index].clone())
+                .await?
+                .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+        }
+
+        let session = session
+            .finalize_tx(request)
+            .await?
+            .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+
+        partial_sigs.push(session.our_signature().await?);
+    }
+
+    info!(%deposit_txid, "broadcasting graph signatures");
+    let session_id = SessionId::from_bytes(*deposit_txid.as_ref());
+    output_handles
+        .msg_handler
+        .send_musig2_signatures(session_id, partial_sigs)
+        .await;
+
+    Ok(())
+}
+
+/// Registers the deposit transaction with the secret service and builds the request to sign its
+/// input that spends the deposit request.
+async fn deposit_signing_request(
+    output_handles: &OutputHandles,
+    deposit_tx: Transaction,
+    deposit_request_prevout: TxOut,
+    witness: &TaprootWitness,
+) -> Result<SigningRequest, ContractManagerErr> {
+    let deposit_txid = deposit_tx.compute_txid();
+    output_handles
+        .s2_client
+        .policy_registry()
+        .allow_txids(deposit_txid, vec![deposit_txid])
+        .await??;
+
+    Ok(SigningRequest {
+        tx: deposit_tx,
+        prevouts: vec![deposit_request_prevout],
+        input_index: 0,
+        spend_path: SpendPath::from(witness),
+        sighash_type: TapSighashType::Default,
+    })
+}
+
+/// Opens the MuSig2 session for the deposit transaction input that spends the deposit request
+/// and feeds it the nonces received from our peers.
+///
+/// The session is keyed by the deposit transaction input so that re-opening it (e.g. when nagged)
+/// yields the same nonce.
+async fn root_musig_session(
+    cfg: &ExecutionConfig,
+    output_handles: &OutputHandles,
+    deposit_txid: Txid,
+    witness: TaprootWitness,
+    root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
+) -> Result<Musig2FirstRound, ContractManagerErr> {
+    let ordered_pubkeys = musig_ordered_pubkeys(&cfg.operator_table);
+    let pov_key = cfg.operator_table.pov_op_key();
+
+    let mut session = output_handles
+        .s2_client
+        .musig2_signer()
+        .new_session(ordered_pubkeys, witness, deposit_txid, 0)
+        .await?
+        .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+
+    for (op_key, nonce) in root_nonces {
+        if &op_key == pov_key {
+            continue;
+        }
+
+        let pubkey = musig_peer_pubkey(&cfg.operator_table, &op_key)?;
+        session
+            .receive_pub_nonce(pubkey, nonce)
+            .await?
+            .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+    }
+
+    Ok(session)
+}
+
+async fn handle_publish_root_nonce(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_request_txid: Txid,
+    deposit_txid: Txid,
+    witness: TaprootWitness,
+) -> Result<(), ContractManagerErr> {
+    info!(%deposit_txid, "generating root nonce");
+    let session =
+        root_musig_session(cfg, &output_handles, deposit_txid, witness, BTreeMap::new()).await?;
+    let nonce = session.our_nonce().await?;
+
+    info!(%deposit_txid, "broadcasting root nonce");
+    let session_id = SessionId::from_bytes(*deposit_request_txid.as_ref());
+    output_handles
+        .msg_handler
+        .send_musig2_nonces(session_id, vec![nonce])
+        .await;
+
+    Ok(())
+}
+
+async fn handle_publish_root_signature(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_request_txid: Txid,
+    deposit_txid: Txid,
+    witness: TaprootWitness,
+    deposit_tx: Transaction,
+    deposit_request_prevout: TxOut,
+    root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
+) -> Result<(), ContractManagerErr> {
+    info!(%deposit_txid, "generating root signature");
+    let request = deposit_signing_request(
+        &output_handles,
+        deposit_tx,
+        deposit_request_prevout,
+        &witness,
+    )
+    .await?;
+    let session =
+        root_musig_session(cfg, &output_handles, deposit_txid, witness, root_nonces).await?;
+    let session = session
+        .finalize_tx(request)
+        .await?
+        .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+    let partial_sig = session.our_signature().await?;
+
+    info!(%deposit_txid, "broadcasting root signature");
+    let session_id = SessionId::from_bytes(*deposit_request_txid.as_ref());
+    output_handles
+        .msg_handler
+        .send_musig2_signatures(session_id, vec![partial_sig])
+        .await;
+
+    Ok(())
+}
+
+async fn handle_publish_deposit(
+    cfg: &ExecutionConfig,
+    output_handles: Arc<OutputHandles>,
+    deposit_tx: Transaction,
+    witness: TaprootWitness,
+    deposit_request_prevout: TxOut,
+    root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
+    root_sigs: BTreeMap<P2POperatorPubKey, PartialSignature>,
+) -> Result<(), ContractManagerErr> {
+    let deposit_txid = deposit_tx.compute_txid();
+    let pov_key = cfg.operator_table.pov_op_key();
+
+    info!(%deposit_txid, "aggregating root signatures");
+    let request = deposit_signing_request(
+        &output_handles,
+        deposit_tx.clone(),
+        deposit_request_prevout,
+        &witness,
+    )
+    .await?;
+    let session = root_musig_session(
+        cfg,
+        &output_handles,
+        deposit_txid,
+        witness.clone(),
+        root_nonces,
+    )
+    .await?;
+    let mut session = session
+        .finalize_tx(request)
+        .await?
+        .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+
+    for (op_key, partial_sig) in root_sigs {
+        if &op_key == pov_key {
+            continue;
+        }
+
+        let pubkey = musig_peer_pubkey(&cfg.operator_table, &op_key)?;
+        session
+            .receive_signature(pubkey, partial_sig)
+            .await?
+            .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+    }
+
+    let agg_sig = session
+        .finalize()
+        .await?
+        .map_err(|e| ContractManagerErr::Musig2Err(format!("{e:?}")))?;
+
+    let mut signed_deposit_tx = deposit_tx;
+    let deposit_witness = &mut signed_deposit_tx.input[0].witness;
+    deposit_witness.push(agg_sig.serialize());
+    if let TaprootWitness::Script {
+        script_buf,
+        control_block,
+    } = witness
+    {
+        deposit_witness.push(script_buf.to_bytes());
+        deposit_witness.push(control_block.serialize());
+    }
+
+    info!(%deposit_txid, "submitting deposit tx to the tx driver");
+    output_handles
+        .tx_driver
+        .drive(signed_deposit_tx, None, FinalityPolicy::Buried)
         .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn assertion_wots_keys_do_not_overlap_the_claim_key() {
+        // keys are identified by their (vout, index, message length in bytes).
+        let claim_key = (WOTS_VOUT, WITHDRAWAL_FULFILLMENT_WOTS_INDEX, 32);
+
+        let assertion_keys = (0..NUM_PUBS)
+            .map(|i| (WOTS_VOUT, public_input_wots_index(i), 32))
+            .chain((0..NUM_U256).map(|i| (WOTS_VOUT, fq_wots_index(i), 32)))
+            .chain((0..NUM_HASH).map(|i| (WOTS_VOUT, hash_wots_index(i), 16)))
+            .collect::<Vec<_>>();
+        let unique_keys = assertion_keys.iter().copied().collect::<BTreeSet<_>>();
+
+        assert_eq!(
+            unique_keys.len(),
+            assertion_keys.len(),
+            "every assertion must be signed with its own key"
+        );
+        assert!(
+            !unique_keys.contains(&claim_key),
+            "the claim key must not sign any assertion"
+        );
+    }
+}
@@ -1,16 +1,26 @@
 //! This module is responsible for being able to save the contents of the ContractSM to disk.
 
+use std::collections::BTreeMap;
+
 use alpen_bridge_params::prelude::{ConnectorParams, PegOutGraphParams, StakeChainParams};
 use bincode::ErrorKind;
 use bitcoin::{Network, Txid};
+use serde::{de::DeserializeOwned, Serialize};
 use sqlx::{
     sqlite::{SqliteQueryResult, SqliteRow},
-    Pool, Row, Sqlite,
+    Pool, Row, Sqlite, SqliteConnection, Transaction,
 };
 use thiserror::Error;
+use tracing::{error, info};
 
 use crate::contract_state_machine::{ContractCfg, ContractSM, MachineState};
 
+/// The version of the layout of the [`MachineState`]s that are written to the `contracts` table.
+///
+/// This must be bumped whenever the serialized layout of [`MachineState`] changes, along with a
+/// migration from the previous layout.
+const STATE_VERSION: i64 = 1;
+
 /// Error type for the [`ContractPersister`] methods.
 #[derive(Debug, Clone, Error)]
 pub enum ContractPersistErr {
@@ -47,6 +57,22 @@ impl ContractPersister {
                 deposit_idx INTEGER NOT NULL UNIQUE,
                 deposit_tx VARBINARY NOT NULL,
                 operator_table VARBINARY NOT NULL,
+                state VARBINARY NOT NULL,
+                state_version INTEGER NOT NULL DEFAULT 0,
+                deposit_request_height INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS legacy_contracts (
+                deposit_txid CHAR(64) PRIMARY KEY,
+                deposit_idx INTEGER NOT NULL,
+                deposit_tx VARBINARY NOT NULL,
+                operator_table VARBINARY NOT NULL,
                 state VARBINARY NOT NULL
             )
             "#,
@@ -54,12 +80,151 @@ impl ContractPersister {
         .execute(&pool)
         .await
         .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
-        Ok(ContractPersister { pool })
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS contract_cursor (
+                id INTEGER PRIMARY KEY CHECK (id = 0),
+                block_height INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS block_undos (
+                block_height INTEGER PRIMARY KEY,
+                undo VARBINARY NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+        let persister = ContractPersister { pool };
+        persister.migrate_states().await?;
+
+        Ok(persister)
+    }
+
+    /// Brings the states of the contracts that were persisted with an older layout up to date.
+    ///
+    /// Databases created before the states were versioned get the `state_version` column, with
+    /// their states marked as the unversioned layout. The contracts whose states cannot be carried
+    /// over because the data required by the current layout was never persisted are moved to the
+    /// `legacy_contracts` table, where they are kept for manual recovery.
+    ///
+    /// Contracts created before their deposit request height was persisted get a height of 0.
+    async fn migrate_states(&self) -> Result<(), ContractPersistErr> {
+        let mut db_tx = self.begin().await?;
+
+        add_missing_column(&mut db_tx, "state_version INTEGER NOT NULL DEFAULT 0").await?;
+        add_missing_column(
+            &mut db_tx,
+            "deposit_request_height INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+
+        let newer: Option<SqliteRow> = sqlx::query(
+            r#"
+            SELECT deposit_txid FROM contracts WHERE state_version > ?
+            "#,
+        )
+        .bind(STATE_VERSION)
+        .fetch_optional(&mut *db_tx)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        if newer.is_some() {
+            return Err(ContractPersistErr::Unexpected(format!(
+                "contract states were persisted by a newer version of the node (> {STATE_VERSION})"
+            )));
+        }
+
+        let rows = sqlx::query(
+            r#"
+            SELECT deposit_txid, state FROM contracts WHERE state_version = 0
+            "#,
+        )
+        .fetch_all(&mut *db_tx)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+        for row in rows {
+            let deposit_txid: String = row
+                .try_get("deposit_txid")
+                .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+            let state: Vec<u8> = row
+                .try_get("state")
+                .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+            let migrated = bincode::deserialize::<legacy::MachineState>(&state)
+                .ok()
+                .and_then(legacy::MachineState::migrate);
+
+            match migrated {
+                Some(state) => {
+                    info!(%deposit_txid, "migrating contract state to the current layout");
+                    let _: SqliteQueryResult = sqlx::query(
+                        r#"
+                        UPDATE contracts SET state = ?, state_version = ? WHERE deposit_txid = ?
+                        "#,
+                    )
+                    .bind(bincode::serialize(&state)?)
+                    .bind(STATE_VERSION)
+                    .bind(&deposit_txid)
+                    .execute(&mut *db_tx)
+                    .await
+                    .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+                }
+                None => {
+                    error!(%deposit_txid, "contract state cannot be migrated to the current layout, moving it to the legacy contracts");
+                    let _: SqliteQueryResult = sqlx::query(
+                        r#"
+                        INSERT OR REPLACE INTO legacy_contracts
+                        SELECT deposit_txid, deposit_idx, deposit_tx, operator_table, state
+                        FROM contracts WHERE deposit_txid = ?
+                        "#,
+                    )
+                    .bind(&deposit_txid)
+                    .execute(&mut *db_tx)
+                    .await
+                    .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+                    let _: SqliteQueryResult = sqlx::query(
+                        r#"
+                        DELETE FROM contracts WHERE deposit_txid = ?
+                        "#,
+                    )
+                    .bind(&deposit_txid)
+                    .execute(&mut *db_tx)
+                    .await
+                    .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+                }
+            }
+        }
+
+        db_tx
+            .commit()
+            .await
+            .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))
+    }
+
+    /// Begins a new database transaction.
+    ///
+    /// All the writes of the [`ContractPersister`] take a connection so that the changes caused by
+    /// a block can be committed atomically through the returned transaction.
+    pub async fn begin(&self) -> Result<Transaction<'static, Sqlite>, ContractPersistErr> {
+        self.pool
+            .begin()
+            .await
+            .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))
     }
 
     /// Initializes a new contract with the given [`ContractCfg`] and [`MachineState`].
     pub async fn init(
         &self,
+        conn: &mut SqliteConnection,
         cfg: &ContractCfg,
         state: &MachineState,
     ) -> Result<(), ContractPersistErr> {
@@ -70,8 +235,10 @@ impl ContractPersister {
                 deposit_idx,
                 deposit_tx,
                 operator_table,
-                state
-            ) VALUES (?, ?, ?, ?, ?)
+                state,
+                state_version,
+                deposit_request_height
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(cfg.deposit_tx.compute_txid().to_string())
@@ -79,7 +246,9 @@ impl ContractPersister {
         .bind(bincode::serialize(&cfg.deposit_tx)?)
         .bind(bincode::serialize(&cfg.operator_table)?)
         .bind(bincode::serialize(&state)?)
-        .execute(&self.pool)
+        .bind(STATE_VERSION)
+        .bind(cfg.deposit_request_height as i64)
+        .execute(conn)
         .await
         .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
         Ok(())
@@ -88,17 +257,37 @@ impl ContractPersister {
     /// Updates the [`MachineState`] for a contract.
     pub async fn commit(
         &self,
+        conn: &mut SqliteConnection,
         deposit_txid: &Txid,
         state: &MachineState,
     ) -> Result<(), ContractPersistErr> {
         let _: SqliteQueryResult = sqlx::query(
             r#"
-            UPDATE contracts SET state = ? WHERE deposit_txid = ?
+            UPDATE contracts SET state = ?, state_version = ? WHERE deposit_txid = ?
             "#,
         )
         .bind(bincode::serialize(state)?)
+        .bind(STATE_VERSION)
+        .bind(deposit_txid.to_string())
+        .execute(conn)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Deletes the contract with the given deposit [`Txid`] from the persistence layer.
+    pub async fn delete(
+        &self,
+        conn: &mut SqliteConnection,
+        deposit_txid: &Txid,
+    ) -> Result<(), ContractPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            DELETE FROM contracts WHERE deposit_txid = ?
+            "#,
+        )
         .bind(deposit_txid.to_string())
-        .execute(&self.pool)
+        .execute(conn)
         .await
         .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
         Ok(())
@@ -107,17 +296,137 @@ impl ContractPersister {
     /// Commits all the machine state in the give contract into the persistence layer.
     pub async fn commit_all(
         &self,
+        conn: &mut SqliteConnection,
         active_contracts: impl Iterator<Item = (&Txid, &ContractSM)>,
     ) -> Result<(), ContractPersistErr> {
         for (txid, contract_sm) in active_contracts {
             let machine_state = contract_sm.state();
-            // FIXME: (@Rajil1213) wrap all commits into a single db transaction.
-            self.commit(txid, machine_state).await?;
+            self.commit(conn, txid, machine_state).await?;
         }
 
         Ok(())
     }
 
+    /// Updates the height of the last block whose effects on the contracts have been persisted.
+    pub async fn commit_cursor(
+        &self,
+        conn: &mut SqliteConnection,
+        block_height: u64,
+    ) -> Result<(), ContractPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO contract_cursor (id, block_height) VALUES (0, ?)
+            "#,
+        )
+        .bind(block_height as i64)
+        .execute(conn)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Loads the height of the last block whose effects on the contracts have been persisted, if
+    /// any.
+    pub async fn load_cursor(&self) -> Result<Option<u64>, ContractPersistErr> {
+        let row: Option<SqliteRow> = sqlx::query(
+            r#"
+            SELECT block_height FROM contract_cursor WHERE id = 0
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+        row.map(|row| {
+            row.try_get::<i64, _>("block_height")
+                .map(|height| height as u64)
+                .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))
+        })
+        .transpose()
+    }
+
+    /// Saves the information required to undo the effects of the block at the given height.
+    pub async fn commit_undo(
+        &self,
+        conn: &mut SqliteConnection,
+        block_height: u64,
+        undo: &impl Serialize,
+    ) -> Result<(), ContractPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO block_undos (block_height, undo) VALUES (?, ?)
+            "#,
+        )
+        .bind(block_height as i64)
+        .bind(bincode::serialize(undo)?)
+        .execute(conn)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Deletes the undo information for the block at the given height.
+    pub async fn delete_undo(
+        &self,
+        conn: &mut SqliteConnection,
+        block_height: u64,
+    ) -> Result<(), ContractPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            DELETE FROM block_undos WHERE block_height = ?
+            "#,
+        )
+        .bind(block_height as i64)
+        .execute(conn)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Deletes the undo information for all the blocks up to and including the given height.
+    pub async fn prune_undos(
+        &self,
+        conn: &mut SqliteConnection,
+        block_height: u64,
+    ) -> Result<(), ContractPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            DELETE FROM block_undos WHERE block_height <= ?
+            "#,
+        )
+        .bind(block_height as i64)
+        .execute(conn)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Loads the undo information for all the blocks that it is retained for, keyed by height.
+    pub async fn load_undos<U: DeserializeOwned>(
+        &self,
+    ) -> Result<BTreeMap<u64, U>, ContractPersistErr> {
+        let rows = sqlx::query(
+            r#"
+            SELECT block_height, undo FROM block_undos
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        rows.into_iter()
+            .map(|row| {
+                let block_height = row
+                    .try_get::<i64, _>("block_height")
+                    .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+                let undo = bincode::deserialize(
+                    row.try_get("undo")
+                        .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?,
+                )?;
+                Ok((block_height as u64, undo))
+            })
+            .collect()
+    }
+
     /// Loads both the [`ContractCfg`] and [`MachineState`] from disk for a given [`Txid`].
     pub async fn load(
         &self,
@@ -131,6 +440,7 @@ impl ContractPersister {
             r#"
             SELECT
                 deposit_idx,
+                deposit_request_height,
                 deposit_tx,
                 operator_table,
                 state
@@ -144,6 +454,9 @@ impl ContractPersister {
         let deposit_idx = row
             .try_get("deposit_idx")
             .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+        let deposit_request_height =
+            row.try_get::<i64, _>("deposit_request_height")
+                .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))? as u64;
         let deposit_tx = bincode::deserialize(
             row.try_get("deposit_tx")
                 .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?,
@@ -161,6 +474,7 @@ impl ContractPersister {
             ContractCfg {
                 operator_table,
                 deposit_idx,
+                deposit_request_height,
                 deposit_tx,
                 network,
                 connector_params,
@@ -184,6 +498,7 @@ impl ContractPersister {
             r#"
             SELECT
                 deposit_idx,
+                deposit_request_height,
                 deposit_tx,
                 operator_table,
                 state
@@ -198,6 +513,10 @@ impl ContractPersister {
                 let deposit_idx = row
                     .try_get("deposit_idx")
                     .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+                let deposit_request_height = row
+                    .try_get::<i64, _>("deposit_request_height")
+                    .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?
+                    as u64;
                 let deposit_tx = bincode::deserialize(
                     row.try_get("deposit_tx")
                         .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?,
@@ -219,6 +538,7 @@ impl ContractPersister {
                         stake_chain_params,
                         // later
                         deposit_idx,
+                        deposit_request_height,
                         deposit_tx,
                     },
                     state,
@@ -227,3 +547,264 @@ impl ContractPersister {
             .collect::<Result<Vec<_>, _>>()
     }
 }
+
+/// Adds the column with the given definition to the `contracts` table unless it already has it.
+async fn add_missing_column(
+    conn: &mut SqliteConnection,
+    definition: &str,
+) -> Result<(), ContractPersistErr> {
+    let name = definition
+        .split_whitespace()
+        .next()
+        .expect("column definition must start with its name");
+
+    let exists: Option<SqliteRow> = sqlx::query(
+        r#"
+        SELECT 1 FROM pragma_table_info('contracts') WHERE name = ?
+        "#,
+    )
+    .bind(name)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+    if exists.is_some() {
+        return Ok(());
+    }
+
+    info!(%name, "adding column to the contracts table");
+    let _: SqliteQueryResult =
+        sqlx::query(&format!("ALTER TABLE contracts ADD COLUMN {definition}"))
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| ContractPersistErr::Unexpected(e.to_string()))?;
+
+    Ok(())
+}
+
+/// The layout of the contract states before they were versioned.
+mod legacy {
+    use std::collections::BTreeMap;
+
+    use bitcoin::Txid;
+    use bitcoin_bosd::Descriptor;
+    use musig2::{PartialSignature, PubNonce};
+    use serde::{Deserialize, Serialize};
+    use strata_bridge_primitives::types::{BitcoinBlockHeight, OperatorIdx};
+    use strata_bridge_stake_chain::prelude::StakeTx;
+    use strata_bridge_tx_graph::peg_out_graph::PegOutGraphSummary;
+    use strata_p2p_types::{P2POperatorPubKey, WotsPublicKeys};
+
+    use crate::contract_state_machine::{self, ContractState as CurrentContractState};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) struct MachineState {
+        pub(super) block_height: BitcoinBlockHeight,
+        pub(super) state: ContractState,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) enum ContractState {
+        Requested {
+            deposit_request_txid: Txid,
+            abort_deadline: BitcoinBlockHeight,
+            stake_txs: BTreeMap<P2POperatorPubKey, StakeTx>,
+            wots_keys: BTreeMap<P2POperatorPubKey, WotsPublicKeys>,
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            graph_nonces: BTreeMap<P2POperatorPubKey, Vec<PubNonce>>,
+            graph_sigs: BTreeMap<P2POperatorPubKey, Vec<PartialSignature>>,
+            root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
+            root_sigs: BTreeMap<P2POperatorPubKey, PartialSignature>,
+        },
+        Deposited {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+        },
+        Assigned {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            fulfiller: OperatorIdx,
+            recipient: Descriptor,
+            deadline: BitcoinBlockHeight,
+            active_graph: PegOutGraphSummary,
+        },
+        StakeTxReady {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            fulfiller: OperatorIdx,
+            recipient: Descriptor,
+            deadline: BitcoinBlockHeight,
+            active_graph: PegOutGraphSummary,
+        },
+        Fulfilled {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            fulfiller: OperatorIdx,
+            active_graph: PegOutGraphSummary,
+        },
+        Claimed {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            claim_height: BitcoinBlockHeight,
+            fulfiller: OperatorIdx,
+            active_graph: PegOutGraphSummary,
+        },
+        Challenged {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            fulfiller: OperatorIdx,
+            active_graph: PegOutGraphSummary,
+        },
+        Asserted {
+            peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+            post_assert_height: BitcoinBlockHeight,
+            fulfiller: OperatorIdx,
+            active_graph: PegOutGraphSummary,
+        },
+        Disproved {},
+        Resolved {},
+    }
+
+    impl MachineState {
+        /// Converts the state to the current layout.
+        ///
+        /// Only the contracts that have reached a final state can be converted. The others lack
+        /// the deposit request prevout and the signed graphs that the current layout requires,
+        /// which were never persisted.
+        pub(super) fn migrate(self) -> Option<contract_state_machine::MachineState> {
+            let state = match self.state {
+                ContractState::Disproved {} => CurrentContractState::Disproved {},
+                ContractState::Resolved {} => CurrentContractState::Resolved {},
+                _ => return None,
+            };
+
+            Some(contract_state_machine::MachineState {
+                block_height: self.block_height,
+                state,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use strata_bridge_primitives::operator_table::OperatorTable;
+    use strata_bridge_test_utils::prelude::{generate_keypair, generate_tx};
+    use strata_p2p_types::P2POperatorPubKey;
+
+    use super::*;
+    use crate::contract_state_machine::ContractState;
+
+    fn operator_table() -> OperatorTable {
+        let btc_key = generate_keypair().public_key();
+        let p2p_key = P2POperatorPubKey::from(btc_key.serialize().to_vec());
+        OperatorTable::new(vec![(0, p2p_key, btc_key)], 0).expect("operator table must be valid")
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn unversioned_states_are_migrated(pool: SqlitePool) {
+        // the layout of the contracts table before the states were versioned.
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE contracts (
+                deposit_txid CHAR(64) PRIMARY KEY,
+                deposit_idx INTEGER NOT NULL UNIQUE,
+                deposit_tx VARBINARY NOT NULL,
+                operator_table VARBINARY NOT NULL,
+                state VARBINARY NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let resolved_tx = generate_tx(1, 1);
+        let deposited_tx = generate_tx(1, 1);
+        let legacy_states = [
+            (0u32, &resolved_tx, legacy::ContractState::Resolved {}),
+            (
+                1u32,
+                &deposited_tx,
+                legacy::ContractState::Deposited {
+                    peg_out_graphs: BTreeMap::new(),
+                },
+            ),
+        ];
+        for (deposit_idx, deposit_tx, state) in legacy_states {
+            let state = legacy::MachineState {
+                block_height: 100,
+                state,
+            };
+            let _: SqliteQueryResult = sqlx::query(
+                r#"
+                INSERT INTO contracts (deposit_txid, deposit_idx, deposit_tx, operator_table, state)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(deposit_tx.compute_txid().to_string())
+            .bind(deposit_idx)
+            .bind(bincode::serialize(deposit_tx).unwrap())
+            .bind(bincode::serialize(&operator_table()).unwrap())
+            .bind(bincode::serialize(&state).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        // migrating an up to date database leaves it as is.
+        for _ in 0..2 {
+            let persister = ContractPersister::new(pool.clone()).await.unwrap();
+            let contracts = persister
+                .load_all(
+                    Network::Regtest,
+                    ConnectorParams::default(),
+                    PegOutGraphParams::default(),
+                    StakeChainParams::default(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(contracts.len(), 1);
+            let (cfg, state) = &contracts[0];
+            assert_eq!(cfg.deposit_tx, resolved_tx);
+            assert_eq!(cfg.deposit_request_height, 0);
+            assert_eq!(state.block_height, 100);
+            assert!(matches!(state.state, ContractState::Resolved {}));
+        }
+
+        let legacy_contracts: Vec<String> =
+            sqlx::query_scalar("SELECT deposit_txid FROM legacy_contracts")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            legacy_contracts,
+            vec![deposited_tx.compute_txid().to_string()]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn undos_round_trip_and_are_pruned(pool: SqlitePool) {
+        let persister = ContractPersister::new(pool).await.unwrap();
+
+        let mut db_tx = persister.begin().await.unwrap();
+        for height in 100..105u64 {
+            persister
+                .commit_undo(&mut db_tx, height, &vec![height as u8])
+                .await
+                .unwrap();
+        }
+        db_tx.commit().await.unwrap();
+
+        let undos = persister.load_undos::<Vec<u8>>().await.unwrap();
+        assert_eq!(
+            undos.into_iter().collect::<Vec<_>>(),
+            (100..105u64)
+                .map(|h| (h, vec![h as u8]))
+                .collect::<Vec<_>>()
+        );
+
+        let mut db_tx = persister.begin().await.unwrap();
+        persister.prune_undos(&mut db_tx, 101).await.unwrap();
+        persister.delete_undo(&mut db_tx, 104).await.unwrap();
+        db_tx.commit().await.unwrap();
+
+        let undos = persister.load_undos::<Vec<u8>>().await.unwrap();
+        assert_eq!(undos.keys().copied().collect::<Vec<_>>(), vec![102, 103]);
+    }
+}
@@ -9,14 +9,17 @@ use bitcoin::{
         serde::{Deserialize, Serialize},
         sha256,
     },
-    Network, OutPoint, Transaction, Txid, XOnlyPublicKey,
+    key::Parity,
+    secp256k1::schnorr,
+    Network, OutPoint, Transaction, TxOut, Txid, XOnlyPublicKey,
 };
 use bitcoin_bosd::Descriptor;
 use bitvm::chunk::api::{NUM_HASH, NUM_PUBS, NUM_U256};
-use musig2::{PartialSignature, PubNonce};
+use musig2::{aggregate_partial_signatures, AggNonce, KeyAggContext, PartialSignature, PubNonce};
 use strata_bridge_primitives::{
     operator_table::OperatorTable,
-    types::{BitcoinBlockHeight, OperatorIdx},
+    scripts::taproot::TaprootWitness,
+    types::{BitcoinBlockHeight, OperatorIdx, TxSigningData},
     wots::{Groth16PublicKeys, PublicKeys, Wots256PublicKey},
 };
 use strata_bridge_stake_chain::{
@@ -25,7 +28,7 @@ use strata_bridge_stake_chain::{
     transactions::stake::StakeTxData,
 };
 use strata_bridge_tx_graph::{
-    peg_out_graph::{PegOutGraph, PegOutGraphInput, PegOutGraphSummary},
+    peg_out_graph::{MusigInput, PegOutGraph, PegOutGraphInput, PegOutGraphSummary},
     transactions::prelude::WithdrawalMetadata,
 };
 use strata_p2p_types::{P2POperatorPubKey, WotsPublicKeys};
@@ -69,6 +72,27 @@ impl DepositSetup {
     }
 }
 
+/// The data needed to regenerate an operator's peg-out-graph along with the aggregated N-of-N
+/// signatures for its presigned transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedGraph {
+    /// The stake transaction that holds the stake corresponding to the graph.
+    pub stake_tx: StakeTx,
+
+    /// The hash used in the hashlock of the stake transaction.
+    pub stake_hash: sha256::Hash,
+
+    /// The WOTS public keys of the operator for this contract.
+    pub wots_keys: WotsPublicKeys,
+
+    /// The operator's public key used for CPFP outputs and receiving reimbursements.
+    pub operator_pubkey: XOnlyPublicKey,
+
+    /// The aggregated N-of-N signatures for the graph in the order given by
+    /// [`PegOutGraph::musig_inputs`].
+    pub signatures: Vec<schnorr::Signature>,
+}
+
 /// This is the unified event type for this state machine.
 ///
 /// Events of this type will be repeatedly fed to the state machine until it terminates.
@@ -117,6 +141,16 @@ pub enum ContractEvent {
     /// Signifies that a new block has been connected to the chain tip.
     Block(BitcoinBlockHeight),
 
+    /// Signifies that the block at the given height has been disconnected from the chain tip.
+    BlockDisconnected {
+        /// The height of the disconnected block.
+        height: BitcoinBlockHeight,
+
+        /// The state of the contract before any of the transactions in the disconnected block
+        /// were applied to it, if any of them were.
+        checkpoint: Option<Box<MachineState>>,
+    },
+
     /// Signifies that the claim transaction for this contract has failed verification.
     ClaimFailure,
 
@@ -145,6 +179,13 @@ pub enum ContractState {
         /// The txid of the deposit request transaction that kicked off this contract.
         deposit_request_txid: Txid,
 
+        /// The output of the deposit request transaction that is spent by the deposit
+        /// transaction.
+        deposit_request_prevout: TxOut,
+
+        /// The spend path used by the deposit transaction to spend the deposit request output.
+        deposit_request_witness: TaprootWitness,
+
         /// This is the height where the requester can reclaim the request output if it has not yet
         /// been converted to a deposit.
         abort_deadline: BitcoinBlockHeight,
@@ -153,10 +194,18 @@ pub enum ContractState {
         /// used to eventually construct the peg-out-graphs.
         stake_txs: BTreeMap<P2POperatorPubKey, StakeTx>,
 
+        /// This is a collection of the hashes used in the stake transactions on a per-operator
+        /// basis. This is used to regenerate the peg-out-graphs when signing them.
+        stake_hashes: BTreeMap<P2POperatorPubKey, sha256::Hash>,
+
         /// This is a collection of the WOTS public keys needed to generate the peg-out-graphs on
         /// a per-operator basis.
         wots_keys: BTreeMap<P2POperatorPubKey, WotsPublicKeys>,
 
+        /// This is a collection of the public keys used for CPFP outputs and reimbursements in
+        /// the peg-out-graphs on a per-operator basis.
+        operator_pubkeys: BTreeMap<P2POperatorPubKey, XOnlyPublicKey>,
+
         /// These are the actual peg-out-graph summaries for each operator. This will be stored so
         /// we can monitor the transactions relevant to advancing the contract through its
         /// lifecycle.
@@ -168,6 +217,10 @@ pub enum ContractState {
         /// This is the collection of signatures for the peg-out graph on a per-operator basis.
         graph_sigs: BTreeMap<P2POperatorPubKey, Vec<PartialSignature>>,
 
+        /// These are the aggregated signatures for each operator's peg-out-graph. These are only
+        /// available once every operator has sent us their graph signatures.
+        aggregated_graph_sigs: BTreeMap<P2POperatorPubKey, Vec<schnorr::Signature>>,
+
         /// This is a collection of the nonces for the final musig2 signature needed to sweep the
         /// deposit request transaction to the deposit transaction.
         root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
@@ -184,6 +237,9 @@ pub enum ContractState {
         /// we can monitor the transactions relevant to advancing the contract through its
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
     },
 
     /// This state describes everything from the moment the strata state commitment corresponding
@@ -195,6 +251,9 @@ pub enum ContractState {
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
 
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+
         /// The operator responsible for fulfilling the withdrawal.
         fulfiller: OperatorIdx,
 
@@ -217,6 +276,9 @@ pub enum ContractState {
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
 
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+
         /// The operator responsible for fulfilling the withdrawal.
         fulfiller: OperatorIdx,
 
@@ -238,11 +300,23 @@ pub enum ContractState {
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
 
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+
         /// The operator responsible for fulfilling the withdrawal.
         fulfiller: OperatorIdx,
 
         /// The graph that belongs to the assigned operator.
         active_graph: PegOutGraphSummary,
+
+        /// The txid of the withdrawal fulfillment transaction.
+        ///
+        /// The fulfiller commits to this txid in the claim transaction with its WOTS-256
+        /// `withdrawal_fulfillment` key.
+        withdrawal_fulfillment_txid: Txid,
+
+        /// The height at which the withdrawal fulfillment transaction was confirmed.
+        withdrawal_fulfillment_height: BitcoinBlockHeight,
     },
 
     /// This state describes everything from the moment the claim transaction confirms, to the
@@ -254,6 +328,9 @@ pub enum ContractState {
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
 
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+
         /// The height at which the claim transaction was confirmed.
         claim_height: BitcoinBlockHeight,
 
@@ -262,6 +339,12 @@ pub enum ContractState {
 
         /// The graph that belongs to the assigned operator.
         active_graph: PegOutGraphSummary,
+
+        /// The txid of the withdrawal fulfillment transaction.
+        withdrawal_fulfillment_txid: Txid,
+
+        /// The height at which the withdrawal fulfillment transaction was confirmed.
+        withdrawal_fulfillment_height: BitcoinBlockHeight,
     },
 
     /// This state describes everything from the moment the challenge transaction confirms, to the
@@ -272,11 +355,26 @@ pub enum ContractState {
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
 
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+
+        /// The height at which the claim transaction was confirmed.
+        ///
+        /// The pre-assert transaction can only be published once its relative timelock on the
+        /// claim output expires.
+        claim_height: BitcoinBlockHeight,
+
         /// The operator responsible for fulfilling the withdrawal.
         fulfiller: OperatorIdx,
 
         /// The graph that belongs to the assigned operator.
         active_graph: PegOutGraphSummary,
+
+        /// The txid of the withdrawal fulfillment transaction.
+        withdrawal_fulfillment_txid: Txid,
+
+        /// The height at which the withdrawal fulfillment transaction was confirmed.
+        withdrawal_fulfillment_height: BitcoinBlockHeight,
     },
 
     /// This state describes everything from the moment the post-assert transaction confirms, to
@@ -287,6 +385,12 @@ pub enum ContractState {
         /// lifecycle.
         peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
 
+        /// The data required to regenerate and finalize each operator's peg-out-graph.
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+
+        /// The height at which the claim transaction was confirmed.
+        claim_height: BitcoinBlockHeight,
+
         /// The height at which the post-assert transaction was confirmed.
         post_assert_height: BitcoinBlockHeight,
 
@@ -306,7 +410,7 @@ pub enum ContractState {
 }
 
 /// This is the superset of all possible operator duties.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[expect(clippy::large_enum_variant)]
 pub enum OperatorDuty {
     /// Instructs us to terminate this contract.
@@ -325,29 +429,146 @@ pub enum OperatorDuty {
     },
 
     /// Instructs us to publish our graph nonces for this contract.
-    PublishGraphNonces,
+    PublishGraphNonces {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The inputs required to regenerate the peg-out-graph of each operator.
+        graph_inputs: BTreeMap<P2POperatorPubKey, PegOutGraphInput>,
+    },
 
     /// Instructs us to send out signatures for the peg out graph.
-    PublishGraphSignatures,
+    PublishGraphSignatures {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The inputs required to regenerate the peg-out-graph of each operator.
+        graph_inputs: BTreeMap<P2POperatorPubKey, PegOutGraphInput>,
+
+        /// The nonces received from each operator for every input in the peg-out-graphs.
+        graph_nonces: BTreeMap<P2POperatorPubKey, Vec<PubNonce>>,
+    },
 
     /// Instructs us to send out our nonce for the deposit transaction signature.
-    PublishRootNonce,
+    PublishRootNonce {
+        /// Transaction ID of the DRT
+        deposit_request_txid: Txid,
+
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The spend path used by the DT to spend the DRT output.
+        witness: TaprootWitness,
+    },
 
     /// Instructs us to send out signatures for the deposit transaction.
-    PublishRootSignature,
+    PublishRootSignature {
+        /// Transaction ID of the DRT
+        deposit_request_txid: Txid,
+
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The spend path used by the DT to spend the DRT output.
+        witness: TaprootWitness,
+
+        /// The unsigned deposit transaction.
+        deposit_tx: Transaction,
+
+        /// The DRT output spent by the DT.
+        deposit_request_prevout: TxOut,
+
+        /// The nonces received from each operator for the deposit transaction signature.
+        root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
+    },
 
     /// Instructs us to submit the deposit transaction to the network.
-    PublishDeposit,
+    PublishDeposit {
+        /// The unsigned deposit transaction.
+        deposit_tx: Transaction,
+
+        /// The spend path used by the DT to spend the DRT output.
+        witness: TaprootWitness,
+
+        /// The DRT output spent by the DT.
+        deposit_request_prevout: TxOut,
+
+        /// The nonces received from each operator for the deposit transaction signature.
+        root_nonces: BTreeMap<P2POperatorPubKey, PubNonce>,
+
+        /// The partial signatures received from each operator for the deposit transaction.
+        root_sigs: BTreeMap<P2POperatorPubKey, PartialSignature>,
+    },
 
     /// Injection function for a FulfillerDuty.
     FulfillerDuty(FulfillerDuty),
 
     /// Injection function for a VerifierDuty.
     VerifierDuty(VerifierDuty),
+
+    /// Instructs us to stop letting the secret service sign the transactions of the peg-out
+    /// graphs of this contract, which is over.
+    ForgetGraphs {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+    },
+}
+
+/// Identifies the contract on whose behalf an [`OperatorDuty`] was emitted.
+enum DutyContract {
+    /// The contract centered on the deposit with the given txid.
+    DepositTxid(Txid),
+
+    /// The contract with the given deposit index.
+    DepositIdx(u32),
+}
+
+impl OperatorDuty {
+    /// The contract on whose behalf the duty was emitted if the relevance of the duty depends on
+    /// the progress of that contract.
+    fn contract(&self) -> Option<DutyContract> {
+        match self {
+            OperatorDuty::PublishDepositSetup { deposit_txid, .. }
+            | OperatorDuty::PublishGraphNonces { deposit_txid, .. }
+            | OperatorDuty::PublishGraphSignatures { deposit_txid, .. }
+            | OperatorDuty::PublishRootNonce { deposit_txid, .. }
+            | OperatorDuty::PublishRootSignature { deposit_txid, .. }
+            | OperatorDuty::FulfillerDuty(
+                FulfillerDuty::PublishClaim { deposit_txid, .. }
+                | FulfillerDuty::PublishPayoutOptimistic { deposit_txid, .. }
+                | FulfillerDuty::PublishAssertChain { deposit_txid, .. }
+                | FulfillerDuty::PublishPayout { deposit_txid, .. },
+            )
+            | OperatorDuty::VerifierDuty(
+                VerifierDuty::VerifyClaim { deposit_txid, .. }
+                | VerifierDuty::VerifyAssertion { deposit_txid, .. }
+                | VerifierDuty::PublishChallenge { deposit_txid, .. }
+                | VerifierDuty::PublishDisprove { deposit_txid, .. },
+            ) => Some(DutyContract::DepositTxid(*deposit_txid)),
+            OperatorDuty::PublishDeposit { deposit_tx, .. } => {
+                Some(DutyContract::DepositTxid(deposit_tx.compute_txid()))
+            }
+            OperatorDuty::FulfillerDuty(FulfillerDuty::PublishFulfillment {
+                withdrawal_metadata,
+                ..
+            }) => Some(DutyContract::DepositIdx(withdrawal_metadata.deposit_idx)),
+            // aborting and forgetting graphs are the last duties of a contract and the stake
+            // chain duties are not bound to any contract.
+            OperatorDuty::Abort
+            | OperatorDuty::ForgetGraphs { .. }
+            | OperatorDuty::FulfillerDuty(FulfillerDuty::AdvanceStakeChain { .. })
+            | OperatorDuty::VerifierDuty(VerifierDuty::VerifyStake) => None,
+        }
+    }
+
+    /// Whether the relevance of the duty depends on the progress of the contract that emitted it.
+    pub fn is_contract_bound(&self) -> bool {
+        self.contract().is_some()
+    }
 }
 
 /// This is a duty that has to be carried out if we are the assigned operator.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FulfillerDuty {
     /// Originates when strata state on L1 is published.
     AdvanceStakeChain {
@@ -368,36 +589,163 @@ pub enum FulfillerDuty {
     },
 
     /// Originates when Fulfillment confirms (is buried?)
-    PublishClaim,
+    PublishClaim {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The input required to regenerate our peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// Transaction ID of the withdrawal fulfillment that the claim commits to.
+        withdrawal_fulfillment_txid: Txid,
+    },
 
     /// Originates after reaching timelock expiry for Claim transaction
-    PublishPayoutOptimistic,
+    PublishPayoutOptimistic {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
 
-    /// Originates once challenge transaction is issued
-    PublishAssertChain,
+        /// The input required to regenerate our peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for our peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// The height at which the payout becomes valid. From then on, it races against any
+        /// challenge of the claim.
+        deadline: BitcoinBlockHeight,
+    },
+
+    /// Originates once challenge transaction is issued and the pre-assert timelock expires
+    PublishAssertChain {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The index of the deposit
+        deposit_idx: u32,
+
+        /// The height at which the deposit request was confirmed, which bounds the search for the
+        /// checkpoint that dispatches the deposit.
+        deposit_request_height: BitcoinBlockHeight,
+
+        /// The input required to regenerate our peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for our peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// Transaction ID of the withdrawal fulfillment that is proven in the assertions.
+        withdrawal_fulfillment_txid: Txid,
+
+        /// The height at which the withdrawal fulfillment transaction was confirmed.
+        withdrawal_fulfillment_height: BitcoinBlockHeight,
+    },
 
     /// Originates after post-assert timelock expires
-    PublishPayout,
+    PublishPayout {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The input required to regenerate our peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for our peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// The height at which the payout becomes valid. From then on, it races against any
+        /// disprove of the assertions.
+        deadline: BitcoinBlockHeight,
+    },
 }
 
 /// This is a duty that must be carried out as a Verifier.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VerifierDuty {
     /// Originates when *other* operator Claim transaction is issued
-    VerifyClaim,
+    VerifyClaim {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The claim transaction that needs to be verified.
+        claim_tx: Transaction,
+
+        /// The input required to regenerate the claimer's peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for the claimer's peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// The txid of the withdrawal fulfillment that the claimer is expected to commit to.
+        ///
+        /// This is `None` if the claimer has not fulfilled the withdrawal assigned to it in the
+        /// strata checkpoint, in which case any claim is fraudulent.
+        withdrawal_fulfillment_txid: Option<Txid>,
+
+        /// The height by which the challenge must be mined if the claim is fraudulent.
+        challenge_deadline: BitcoinBlockHeight,
+    },
 
     /// Originates when *other* operator PostAssert transaction is issued
-    VerifyAssertion,
+    VerifyAssertion {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The input required to regenerate the asserter's peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for the asserter's peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// The height at which the claim transaction was confirmed.
+        claim_height: BitcoinBlockHeight,
+
+        /// The height at which the post-assert transaction was confirmed.
+        post_assert_height: BitcoinBlockHeight,
+
+        /// The height by which the disprove must be mined if the assertions are invalid.
+        disprove_deadline: BitcoinBlockHeight,
+    },
 
     /// Originates when any of other operator's Claim, PreAssert, Assert, or Post-Assert are
     /// issued.
     VerifyStake,
 
     /// Originates when fraudulent Claim transaction is issued
-    PublishChallenge,
+    PublishChallenge {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The input required to regenerate the claimer's peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for the claimer's peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// The height by which the challenge must be mined, before the optimistic payout becomes
+        /// valid.
+        deadline: BitcoinBlockHeight,
+    },
 
     /// Originates after Post-Assert is issued if Disprove script is satisfiable
-    PublishDisprove,
+    PublishDisprove {
+        /// Transaction ID of the DT
+        deposit_txid: Txid,
+
+        /// The input required to regenerate the asserter's peg-out-graph.
+        graph_input: PegOutGraphInput,
+
+        /// The aggregated N-of-N signatures for the asserter's peg-out-graph.
+        graph_sigs: Vec<schnorr::Signature>,
+
+        /// The height at which the claim transaction was confirmed.
+        claim_height: BitcoinBlockHeight,
+
+        /// The height at which the post-assert transaction was confirmed.
+        post_assert_height: BitcoinBlockHeight,
+
+        /// The height by which the disprove must be mined, before the payout becomes valid.
+        deadline: BitcoinBlockHeight,
+    },
 }
 
 /// Error representing an invalid state transition.
@@ -432,6 +780,11 @@ pub struct ContractCfg {
     /// a deposit request.
     pub deposit_idx: u32,
 
+    /// The height at which the deposit request was confirmed.
+    ///
+    /// Nothing that concerns this contract can be found in the chain before this height.
+    pub deposit_request_height: BitcoinBlockHeight,
+
     /// The predetermined deposit transaction that the rest of the graph is built from.
     pub deposit_tx: Transaction,
 }
@@ -468,9 +821,18 @@ impl ContractSM {
         abort_deadline: BitcoinBlockHeight,
         deposit_idx: u32,
         deposit_request_txid: Txid,
-        deposit_tx: Transaction,
+        deposit_signing_data: TxSigningData,
         stake_chain_inputs: StakeChainInputs,
     ) -> (Self, OperatorDuty) {
+        let TxSigningData {
+            psbt,
+            spend_path: deposit_request_witness,
+        } = deposit_signing_data;
+        let deposit_request_prevout = psbt.inputs[0]
+            .witness_utxo
+            .clone()
+            .expect("deposit signing data must have the deposit request prevout");
+        let deposit_tx = psbt.unsigned_tx;
         let deposit_txid = deposit_tx.compute_txid();
         let cfg = ContractCfg {
             network,
@@ -479,16 +841,22 @@ impl ContractSM {
             peg_out_graph_params,
             stake_chain_params,
             deposit_idx,
+            deposit_request_height: block_height,
             deposit_tx,
         };
         let state = ContractState::Requested {
             deposit_request_txid,
+            deposit_request_prevout,
+            deposit_request_witness,
             abort_deadline,
             stake_txs: BTreeMap::new(),
+            stake_hashes: BTreeMap::new(),
             wots_keys: BTreeMap::new(),
+            operator_pubkeys: BTreeMap::new(),
             peg_out_graphs: BTreeMap::new(),
             graph_nonces: BTreeMap::new(),
             graph_sigs: BTreeMap::new(),
+            aggregated_graph_sigs: BTreeMap::new(),
             root_nonces: BTreeMap::new(),
             root_sigs: BTreeMap::new(),
         };
@@ -545,7 +913,7 @@ impl ContractSM {
         let txid = tx.compute_txid();
 
         let operator_ids = cfg.operator_table.operator_idxs();
-        if let ContractState::Assigned { recipient, .. } = &self.state.state {
+        if let ContractState::StakeTxReady { recipient, .. } = &self.state.state {
             if operator_ids.iter().any(|operator_idx| {
                 is_fulfillment_tx(
                     cfg.network,
@@ -602,6 +970,9 @@ impl ContractSM {
                 self.process_peg_out_graph_tx_confirmation(height, &tx)
             }
             ContractEvent::Block(height) => self.notify_new_block(height),
+            ContractEvent::BlockDisconnected { height, checkpoint } => {
+                self.process_block_disconnect(height, checkpoint.map(|c| *c))
+            }
             ContractEvent::ClaimFailure => self.process_claim_verification_failure(),
             ContractEvent::AssertionFailure => self.process_assertion_verification_failure(),
             ContractEvent::Assignment(deposit_entry, stake_tx) => {
@@ -622,9 +993,71 @@ impl ContractSM {
             )));
         }
 
+        // the deposit can only be signed once every operator's graph has been signed so missing
+        // data means that this contract cannot track the graphs that may be used to withdraw.
+        if let ContractState::Requested {
+            stake_txs,
+            stake_hashes,
+            wots_keys,
+            operator_pubkeys,
+            aggregated_graph_sigs,
+            ..
+        } = &self.state.state
+        {
+            let incomplete = self
+                .cfg
+                .operator_table
+                .p2p_keys()
+                .into_iter()
+                .filter(|op_key| {
+                    !(stake_txs.contains_key(op_key)
+                        && stake_hashes.contains_key(op_key)
+                        && wots_keys.contains_key(op_key)
+                        && operator_pubkeys.contains_key(op_key)
+                        && aggregated_graph_sigs.contains_key(op_key))
+                })
+                .collect::<Vec<_>>();
+
+            if !incomplete.is_empty() {
+                return Err(TransitionErr(format!(
+                    "deposit ({}) confirmed without the signed graphs of operators {:?}",
+                    tx.compute_txid(),
+                    incomplete
+                )));
+            }
+        }
+
         let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
-        if let ContractState::Requested { peg_out_graphs, .. } = current {
-            self.state.state = ContractState::Deposited { peg_out_graphs }
+        if let ContractState::Requested {
+            peg_out_graphs,
+            mut stake_txs,
+            mut stake_hashes,
+            mut wots_keys,
+            mut operator_pubkeys,
+            aggregated_graph_sigs,
+            ..
+        } = current
+        {
+            let signed_graphs = aggregated_graph_sigs
+                .into_iter()
+                .filter_map(|(op_key, signatures)| {
+                    Some((
+                        op_key.clone(),
+                        SignedGraph {
+                            stake_tx: stake_txs.remove(&op_key)?,
+                            stake_hash: stake_hashes.remove(&op_key)?,
+                            wots_keys: wots_keys.remove(&op_key)?,
+                            operator_pubkey: operator_pubkeys.remove(&op_key)?,
+                            signatures,
+                        },
+                    ))
+                })
+                .collect();
+
+            self.state.state = ContractState::Deposited {
+                peg_out_graphs,
+                signed_graphs,
+            }
         } else {
             self.state.state = current;
             return Err(TransitionErr(format!(
@@ -649,17 +1082,22 @@ impl ContractSM {
                 tx.compute_txid(),
                 self.state.state
             ))),
-            ContractState::Deposited { .. } => Err(TransitionErr(format!(
-                "peg out graph confirmation ({}) delivered to CSM in Deposited state ({:?})",
-                tx.compute_txid(),
-                self.state.state
-            ))),
-            ContractState::Assigned { .. } => self.process_stake_chain_advancement(tx),
-            ContractState::StakeTxReady { .. } => self.process_fulfillment_confirmation(tx),
-            ContractState::Fulfilled { .. } => self.process_claim_confirmation(height, tx),
+            ContractState::Deposited { .. } => {
+                self.process_unexpected_claim_confirmation(height, tx)
+            }
+            ContractState::Assigned { .. } => self
+                .process_stake_chain_advancement(tx)
+                .or_else(|_| self.process_unexpected_claim_confirmation(height, tx)),
+            ContractState::StakeTxReady { .. } => self
+                .process_fulfillment_confirmation(height, tx)
+                .or_else(|_| self.process_unexpected_claim_confirmation(height, tx)),
+            ContractState::Fulfilled { .. } => self
+                .process_claim_confirmation(height, tx)
+                .or_else(|_| self.process_unexpected_claim_confirmation(height, tx)),
             ContractState::Claimed { .. } => self
-                .process_challenge_confirmation(tx)
-                .or_else(|_| self.process_optimistic_payout_confirmation(tx)),
+                .process_challenge_confirmation(height, tx)
+                .or_else(|_| self.process_optimistic_payout_confirmation(tx))
+                .or_else(|_| self.process_unexpected_claim_confirmation(height, tx)),
             ContractState::Challenged { .. } => self.process_assert_chain_confirmation(height, tx),
             ContractState::Asserted { .. } => self
                 .process_disprove_confirmation(tx)
@@ -738,26 +1176,18 @@ impl ContractSM {
         match &mut self.state.state {
             ContractState::Requested {
                 stake_txs,
+                stake_hashes,
                 wots_keys,
+                operator_pubkeys,
                 peg_out_graphs,
                 ..
             } => {
-                let wots_key_record = PublicKeys {
-                    withdrawal_fulfillment: Wots256PublicKey(
-                        new_wots_keys.withdrawal_fulfillment.0,
-                    ),
-                    groth16: Self::convert_g16_keys(new_wots_keys.groth16.clone())?,
-                };
-                let pog_input = PegOutGraphInput {
-                    stake_outpoint: OutPoint::new(new_stake_tx.compute_txid(), STAKE_VOUT),
-                    withdrawal_fulfillment_outpoint: OutPoint::new(
-                        new_stake_tx.compute_txid(),
-                        WITHDRAWAL_FULFILLMENT_VOUT,
-                    ),
-                    stake_hash: new_stake_hash,
-                    wots_public_keys: wots_key_record,
+                let pog_input = Self::graph_input(
+                    &new_stake_tx,
+                    new_stake_hash,
+                    new_wots_keys.clone(),
                     operator_pubkey,
-                };
+                )?;
                 let pog_summary = PegOutGraph::generate(
                     pog_input,
                     &self.cfg.operator_table.tx_build_context(self.cfg.network),
@@ -776,26 +1206,149 @@ impl ContractSM {
                 .summarize();
 
                 stake_txs.insert(signer.clone(), new_stake_tx);
+                stake_hashes.insert(signer.clone(), new_stake_hash);
                 wots_keys.insert(signer.clone(), new_wots_keys);
+                operator_pubkeys.insert(signer.clone(), operator_pubkey);
                 peg_out_graphs.insert(signer, pog_summary);
 
-                Ok(
-                    // FIXME: (@Rajil1213) update this condition when multi stake chain is
-                    // implemented.
-                    if stake_txs.len() == self.cfg.operator_table.cardinality() {
-                        Some(OperatorDuty::PublishGraphNonces)
-                    } else {
-                        None
-                    },
-                )
+                // FIXME: (@Rajil1213) update this condition when multi stake chain is
+                // implemented.
+                if stake_txs.len() != self.cfg.operator_table.cardinality() {
+                    return Ok(None);
+                }
+            }
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_deposit_setup ({:?})",
+                    self.state.state
+                )))
             }
+        }
+
+        Ok(Some(self.graph_nonces_duty()?))
+    }
+
+    /// Constructs the input required to generate the peg-out-graph for an operator.
+    fn graph_input(
+        stake_tx: &StakeTx,
+        stake_hash: sha256::Hash,
+        wots_keys: WotsPublicKeys,
+        operator_pubkey: XOnlyPublicKey,
+    ) -> Result<PegOutGraphInput, TransitionErr> {
+        let wots_public_keys = PublicKeys {
+            withdrawal_fulfillment: Wots256PublicKey(wots_keys.withdrawal_fulfillment.0),
+            groth16: Self::convert_g16_keys(wots_keys.groth16)?,
+        };
+
+        Ok(PegOutGraphInput {
+            stake_outpoint: OutPoint::new(stake_tx.compute_txid(), STAKE_VOUT),
+            withdrawal_fulfillment_outpoint: OutPoint::new(
+                stake_tx.compute_txid(),
+                WITHDRAWAL_FULFILLMENT_VOUT,
+            ),
+            stake_hash,
+            wots_public_keys,
+            operator_pubkey,
+        })
+    }
+
+    /// Collects the inputs required to regenerate the peg-out-graphs of all the operators that
+    /// have completed their deposit setup so far.
+    fn graph_inputs(&self) -> Result<BTreeMap<P2POperatorPubKey, PegOutGraphInput>, TransitionErr> {
+        match &self.state.state {
+            ContractState::Requested {
+                stake_txs,
+                stake_hashes,
+                wots_keys,
+                operator_pubkeys,
+                ..
+            } => stake_txs
+                .iter()
+                .map(|(op_key, stake_tx)| {
+                    let stake_hash = stake_hashes.get(op_key).ok_or(TransitionErr(format!(
+                        "missing stake hash for operator {}",
+                        op_key
+                    )))?;
+                    let wots_keys = wots_keys.get(op_key).ok_or(TransitionErr(format!(
+                        "missing wots keys for operator {}",
+                        op_key
+                    )))?;
+                    let operator_pubkey = operator_pubkeys.get(op_key).ok_or(TransitionErr(
+                        format!("missing operator pubkey for operator {}", op_key),
+                    ))?;
+
+                    let input = Self::graph_input(
+                        stake_tx,
+                        *stake_hash,
+                        wots_keys.clone(),
+                        *operator_pubkey,
+                    )?;
+
+                    Ok((op_key.clone(), input))
+                })
+                .collect(),
             _ => Err(TransitionErr(format!(
-                "unexpected state in process_deposit_setup ({:?})",
+                "peg out graph inputs requested for CSM not in Requested state ({:?})",
                 self.state.state
             ))),
         }
     }
 
+    /// Collects the inputs required to regenerate the peg-out-graphs of all the operators.
+    ///
+    /// This fails if we have yet to receive the deposit setup from every operator.
+    fn complete_graph_inputs(
+        &self,
+    ) -> Result<BTreeMap<P2POperatorPubKey, PegOutGraphInput>, TransitionErr> {
+        let graph_inputs = self.graph_inputs()?;
+        if graph_inputs.len() != self.cfg.operator_table.cardinality() {
+            return Err(TransitionErr(format!(
+                "deposit setup incomplete for CSM ({}), have {} of {} graphs",
+                self.deposit_txid(),
+                graph_inputs.len(),
+                self.cfg.operator_table.cardinality()
+            )));
+        }
+
+        Ok(graph_inputs)
+    }
+
+    /// Constructs the duty to publish our nonces for the peg-out-graphs of all the operators.
+    ///
+    /// This fails if the contract is not in the [`Requested`](ContractState::Requested) state or
+    /// if we have yet to receive the deposit setup from every operator.
+    pub fn graph_nonces_duty(&self) -> Result<OperatorDuty, TransitionErr> {
+        Ok(OperatorDuty::PublishGraphNonces {
+            deposit_txid: self.deposit_txid(),
+            graph_inputs: self.complete_graph_inputs()?,
+        })
+    }
+
+    /// Constructs the duty to publish our partial signatures for the peg-out-graphs of all the
+    /// operators.
+    ///
+    /// This fails if the contract is not in the [`Requested`](ContractState::Requested) state or
+    /// if we have yet to receive the graph nonces from every operator.
+    pub fn graph_sigs_duty(&self) -> Result<OperatorDuty, TransitionErr> {
+        let graph_inputs = self.complete_graph_inputs()?;
+
+        match &self.state.state {
+            ContractState::Requested { graph_nonces, .. }
+                if graph_nonces.len() == self.cfg.operator_table.cardinality() =>
+            {
+                Ok(OperatorDuty::PublishGraphSignatures {
+                    deposit_txid: self.deposit_txid(),
+                    graph_inputs,
+                    graph_nonces: graph_nonces.clone(),
+                })
+            }
+            _ => Err(TransitionErr(format!(
+                "graph nonces incomplete for CSM ({})",
+                self.deposit_txid()
+            ))),
+        }
+    }
+
     fn process_graph_nonces(
         &mut self,
         signer: P2POperatorPubKey,
@@ -804,19 +1357,19 @@ impl ContractSM {
         match &mut self.state.state {
             ContractState::Requested { graph_nonces, .. } => {
                 graph_nonces.insert(signer, nonces);
-                Ok(
-                    if graph_nonces.len() == self.cfg.operator_table.cardinality() {
-                        Some(OperatorDuty::PublishGraphSignatures)
-                    } else {
-                        None
-                    },
-                )
+                if graph_nonces.len() != self.cfg.operator_table.cardinality() {
+                    return Ok(None);
+                }
+            }
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_graph_nonces ({:?})",
+                    self.state.state
+                )))
             }
-            _ => Err(TransitionErr(format!(
-                "unexpected state in process_graph_nonces ({:?})",
-                self.state.state
-            ))),
         }
+
+        Ok(Some(self.graph_sigs_duty()?))
     }
 
     /// Processes a graph signature payload from our peer.
@@ -827,47 +1380,187 @@ impl ContractSM {
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
         match &mut self.state.state {
             ContractState::Requested { graph_sigs, .. } => {
-                graph_sigs.insert(signer, sig);
-                Ok(
-                    if graph_sigs.len() == self.cfg.operator_table.cardinality() {
-                        // we have all the sigs now
-                        // issue deposit signature
-                        Some(OperatorDuty::PublishRootNonce)
-                    } else {
-                        None
-                    },
-                )
+                graph_sigs.insert(signer.clone(), sig);
+                if graph_sigs.len() != self.cfg.operator_table.cardinality() {
+                    return Ok(None);
+                }
+            }
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_graph_signatures ({:?})",
+                    self.state.state
+                )))
             }
-            _ => Err(TransitionErr(format!(
-                "unexpected state in process_graph_signatures ({:?})",
-                self.state.state
-            ))),
         }
-    }
 
-    fn process_root_nonce(
-        &mut self,
-        signer: P2POperatorPubKey,
-        nonce: PubNonce,
-    ) -> Result<Option<OperatorDuty>, TransitionErr> {
-        match &mut self.state.state {
-            ContractState::Requested { root_nonces, .. } => {
-                root_nonces.insert(signer, nonce);
-                Ok(
-                    if root_nonces.len() == self.cfg.operator_table.cardinality() {
-                        // we have all the sigs now
-                        // issue deposit signature
-                        Some(OperatorDuty::PublishRootSignature)
-                    } else {
-                        None
-                    },
-                )
+        // we have all the sigs now so we aggregate them to make sure that the graphs are fully
+        // signed before we ever sign the deposit.
+        let aggregated = self.aggregate_graph_sigs();
+        if let ContractState::Requested {
+            graph_sigs,
+            aggregated_graph_sigs,
+            ..
+        } = &mut self.state.state
+        {
+            match aggregated {
+                Ok(aggregated) => *aggregated_graph_sigs = aggregated,
+                Err(e) => {
+                    // drop the signatures that completed the set so that they can be requested
+                    // again.
+                    graph_sigs.remove(&signer);
+                    return Err(e);
+                }
+            }
+        }
+
+        // issue deposit signature
+        Ok(Some(self.root_nonce_duty()?))
+    }
+
+    /// Collects the inputs that need to be signed with the N-of-N MuSig2 key in each operator's
+    /// peg-out-graph.
+    fn graph_musig_inputs(
+        &self,
+    ) -> Result<BTreeMap<P2POperatorPubKey, Vec<MusigInput>>, TransitionErr> {
+        let context = self.cfg.operator_table.tx_build_context(self.cfg.network);
+
+        self.complete_graph_inputs()?
+            .into_iter()
+            .map(|(op_key, graph_input)| {
+                let (graph, _connectors) = PegOutGraph::generate(
+                    graph_input,
+                    &context,
+                    self.deposit_txid(),
+                    self.cfg.peg_out_graph_params.clone(),
+                    self.cfg.connector_params,
+                    self.cfg.stake_chain_params,
+                    Vec::new(),
+                )
+                .map_err(|e| {
+                    TransitionErr(format!(
+                        "could not generate peg out graph for operator {}: {e}",
+                        op_key
+                    ))
+                })?;
+
+                Ok((op_key, graph.musig_inputs()))
+            })
+            .collect()
+    }
+
+    /// Aggregates the graph signatures received from every operator.
+    ///
+    /// The nonces and partial signatures sent by each operator cover the inputs of all the
+    /// peg-out-graphs, traversed in the order of the operators' p2p keys.
+    fn aggregate_graph_sigs(
+        &self,
+    ) -> Result<BTreeMap<P2POperatorPubKey, Vec<schnorr::Signature>>, TransitionErr> {
+        let (graph_nonces, graph_sigs) = match &self.state.state {
+            ContractState::Requested {
+                graph_nonces,
+                graph_sigs,
+                ..
+            } => (graph_nonces, graph_sigs),
+            _ => {
+                return Err(TransitionErr(format!(
+                    "graph signatures aggregated for CSM not in Requested state ({:?})",
+                    self.state.state
+                )))
+            }
+        };
+
+        let musig_inputs = self.graph_musig_inputs()?;
+        let num_inputs = musig_inputs.values().map(Vec::len).sum::<usize>();
+        if graph_nonces.len() != self.cfg.operator_table.cardinality()
+            || graph_nonces
+                .values()
+                .any(|nonces| nonces.len() != num_inputs)
+            || graph_sigs.values().any(|sigs| sigs.len() != num_inputs)
+        {
+            return Err(TransitionErr(format!(
+                "graph nonces or signatures incomplete for CSM ({}), {} inputs required",
+                self.deposit_txid(),
+                num_inputs
+            )));
+        }
+
+        let key_agg_ctx = KeyAggContext::new(
+            self.cfg
+                .operator_table
+                .public_key_table()
+                .0
+                .values()
+                .map(|pubkey| pubkey.x_only_public_key().0.public_key(Parity::Even)),
+        )
+        .map_err(|e| TransitionErr(format!("could not aggregate operator keys: {e}")))?;
+
+        let mut index = 0;
+        musig_inputs
+            .into_iter()
+            .map(|(op_key, inputs)| {
+                let signatures = inputs
+                    .into_iter()
+                    .map(|input| {
+                        let agg_nonce: AggNonce =
+                            graph_nonces.values().map(|nonces| &nonces[index]).sum();
+                        let partial_sigs = graph_sigs.values().map(|sigs| sigs[index]);
+                        index += 1;
+
+                        let key_agg_ctx = match &input.witness {
+                            TaprootWitness::Key => key_agg_ctx
+                                .clone()
+                                .with_unspendable_taproot_tweak()
+                                .expect("must be able to tweak the key agg context"),
+                            TaprootWitness::Tweaked { tweak } => key_agg_ctx
+                                .clone()
+                                .with_taproot_tweak(tweak.as_ref())
+                                .expect("must be able to tweak the key agg context"),
+                            TaprootWitness::Script { .. } => key_agg_ctx.clone(),
+                        };
+
+                        aggregate_partial_signatures(
+                            &key_agg_ctx,
+                            &agg_nonce,
+                            partial_sigs,
+                            input.sighash.as_ref(),
+                        )
+                        .map_err(|e| {
+                            TransitionErr(format!(
+                                "invalid graph signatures for input {} of {}: {e}",
+                                input.input_index, input.txid
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok((op_key, signatures))
+            })
+            .collect()
+    }
+
+    fn process_root_nonce(
+        &mut self,
+        signer: P2POperatorPubKey,
+        nonce: PubNonce,
+    ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        match &mut self.state.state {
+            ContractState::Requested { root_nonces, .. } => {
+                root_nonces.insert(signer, nonce);
+                if root_nonces.len() != self.cfg.operator_table.cardinality() {
+                    return Ok(None);
+                }
+            }
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_root_nonce ({:?})",
+                    self.state.state
+                )))
             }
-            _ => Err(TransitionErr(format!(
-                "unexpected state in process_root_nonce ({:?})",
-                self.state.state
-            ))),
         }
+
+        // we have all the nonces now
+        // issue deposit signature
+        Ok(Some(self.root_sig_duty()?))
     }
 
     /// Processes a signature for the deposit transaction from our peer.
@@ -879,23 +1572,174 @@ impl ContractSM {
         match &mut self.state.state {
             ContractState::Requested { root_sigs, .. } => {
                 root_sigs.insert(signer, sig);
-                Ok(
-                    if root_sigs.len() == self.cfg.operator_table.cardinality() {
-                        // we have all the deposit sigs now
-                        // we can publish the deposit
-                        Some(OperatorDuty::PublishDeposit)
-                    } else {
-                        None
+                if root_sigs.len() != self.cfg.operator_table.cardinality() {
+                    return Ok(None);
+                }
+            }
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_root_signature ({:?})",
+                    self.state.state
+                )))
+            }
+        }
+
+        // we have all the deposit sigs now
+        // we can publish the deposit
+        Ok(Some(self.deposit_duty()?))
+    }
+
+    /// Constructs the duty to publish our nonce for the deposit transaction signature.
+    ///
+    /// This fails if the contract is not in the [`Requested`](ContractState::Requested) state.
+    pub fn root_nonce_duty(&self) -> Result<OperatorDuty, TransitionErr> {
+        match &self.state.state {
+            ContractState::Requested {
+                deposit_request_txid,
+                deposit_request_witness,
+                ..
+            } => Ok(OperatorDuty::PublishRootNonce {
+                deposit_request_txid: *deposit_request_txid,
+                deposit_txid: self.deposit_txid(),
+                witness: deposit_request_witness.clone(),
+            }),
+            _ => Err(TransitionErr(format!(
+                "root nonce requested for CSM not in Requested state ({:?})",
+                self.state.state
+            ))),
+        }
+    }
+
+    /// Constructs the duty to publish our partial signature for the deposit transaction.
+    ///
+    /// This fails if the contract is not in the [`Requested`](ContractState::Requested) state or
+    /// if we have yet to receive all the graph signatures or the root nonces. The graph
+    /// signatures are required so that we never sign the deposit before the peg-out-graphs are
+    /// fully signed.
+    pub fn root_sig_duty(&self) -> Result<OperatorDuty, TransitionErr> {
+        match &self.state.state {
+            ContractState::Requested {
+                deposit_request_txid,
+                deposit_request_prevout,
+                deposit_request_witness,
+                aggregated_graph_sigs,
+                root_nonces,
+                ..
+            } if aggregated_graph_sigs.len() == self.cfg.operator_table.cardinality()
+                && root_nonces.len() == self.cfg.operator_table.cardinality() =>
+            {
+                Ok(OperatorDuty::PublishRootSignature {
+                    deposit_request_txid: *deposit_request_txid,
+                    deposit_txid: self.deposit_txid(),
+                    witness: deposit_request_witness.clone(),
+                    deposit_tx: self.cfg.deposit_tx.clone(),
+                    deposit_request_prevout: deposit_request_prevout.clone(),
+                    root_nonces: root_nonces.clone(),
+                })
+            }
+            _ => Err(TransitionErr(format!(
+                "graph signatures or root nonces incomplete for CSM ({})",
+                self.deposit_txid()
+            ))),
+        }
+    }
+
+    /// Looks up the data needed to regenerate and finalize the peg-out-graph of the given operator.
+    fn signed_graph(
+        &self,
+        signed_graphs: &BTreeMap<P2POperatorPubKey, SignedGraph>,
+        operator_idx: OperatorIdx,
+    ) -> Result<(PegOutGraphInput, Vec<schnorr::Signature>), TransitionErr> {
+        let op_key = self
+            .cfg
+            .operator_table
+            .idx_to_op_key(&operator_idx)
+            .ok_or(TransitionErr(format!(
+                "could not convert operator index {} to operator key",
+                operator_idx
+            )))?;
+        let signed_graph = signed_graphs.get(op_key).ok_or(TransitionErr(format!(
+            "missing signed peg out graph for operator {}",
+            op_key
+        )))?;
+
+        let graph_input = Self::graph_input(
+            &signed_graph.stake_tx,
+            signed_graph.stake_hash,
+            signed_graph.wots_keys.clone(),
+            signed_graph.operator_pubkey,
+        )?;
+
+        Ok((graph_input, signed_graph.signatures.clone()))
+    }
+
+    /// Constructs the duty to prove the withdrawal fulfillment and publish the assert chain if we
+    /// are the fulfiller.
+    fn assert_chain_duty(&self) -> Result<Option<OperatorDuty>, TransitionErr> {
+        match &self.state.state {
+            ContractState::Challenged {
+                signed_graphs,
+                fulfiller,
+                withdrawal_fulfillment_txid,
+                withdrawal_fulfillment_height,
+                ..
+            } => {
+                if *fulfiller != self.cfg.operator_table.pov_idx() {
+                    return Ok(None);
+                }
+
+                let (graph_input, graph_sigs) = self.signed_graph(signed_graphs, *fulfiller)?;
+
+                Ok(Some(OperatorDuty::FulfillerDuty(
+                    FulfillerDuty::PublishAssertChain {
+                        deposit_txid: self.deposit_txid(),
+                        deposit_idx: self.cfg.deposit_idx,
+                        deposit_request_height: self.cfg.deposit_request_height,
+                        graph_input,
+                        graph_sigs,
+                        withdrawal_fulfillment_txid: *withdrawal_fulfillment_txid,
+                        withdrawal_fulfillment_height: *withdrawal_fulfillment_height,
                     },
-                )
+                )))
             }
             _ => Err(TransitionErr(format!(
-                "unexpected state in process_root_signature ({:?})",
+                "assert chain duty requested for CSM not in Challenged state ({:?})",
                 self.state.state
             ))),
         }
     }
 
+    /// Constructs the duty to aggregate the deposit transaction signatures and publish the
+    /// deposit transaction.
+    ///
+    /// This fails if the contract is not in the [`Requested`](ContractState::Requested) state or
+    /// if we have yet to receive the root signatures from every operator.
+    pub fn deposit_duty(&self) -> Result<OperatorDuty, TransitionErr> {
+        match &self.state.state {
+            ContractState::Requested {
+                deposit_request_prevout,
+                deposit_request_witness,
+                root_nonces,
+                root_sigs,
+                ..
+            } if root_nonces.len() == self.cfg.operator_table.cardinality()
+                && root_sigs.len() == self.cfg.operator_table.cardinality() =>
+            {
+                Ok(OperatorDuty::PublishDeposit {
+                    deposit_tx: self.cfg.deposit_tx.clone(),
+                    witness: deposit_request_witness.clone(),
+                    deposit_request_prevout: deposit_request_prevout.clone(),
+                    root_nonces: root_nonces.clone(),
+                    root_sigs: root_sigs.clone(),
+                })
+            }
+            _ => Err(TransitionErr(format!(
+                "root signatures incomplete for CSM ({})",
+                self.deposit_txid()
+            ))),
+        }
+    }
+
     /// Increment the internally tracked block height.
     fn notify_new_block(
         &mut self,
@@ -910,11 +1754,9 @@ impl ContractSM {
                 height
             )));
         }
-        let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
-
-        let duty = match current {
+        let duty = match &self.state.state {
             ContractState::Requested { abort_deadline, .. } => {
-                if self.state.block_height >= abort_deadline {
+                if self.state.block_height >= *abort_deadline {
                     Some(OperatorDuty::Abort)
                 } else {
                     None
@@ -927,32 +1769,59 @@ impl ContractSM {
             ContractState::StakeTxReady { .. } => None,
             ContractState::Fulfilled { .. } => None,
             ContractState::Claimed {
+                signed_graphs,
                 fulfiller,
                 claim_height,
                 ..
             } => {
-                if self.state.block_height
-                    >= claim_height + self.cfg.connector_params.payout_optimistic_timelock as u64
-                    && fulfiller == self.cfg.operator_table.pov_idx()
+                let deadline = self.challenge_deadline(*claim_height);
+                if self.state.block_height >= deadline
+                    && *fulfiller == self.cfg.operator_table.pov_idx()
                 {
+                    let (graph_input, graph_sigs) = self.signed_graph(signed_graphs, *fulfiller)?;
+
                     Some(OperatorDuty::FulfillerDuty(
-                        FulfillerDuty::PublishPayoutOptimistic,
+                        FulfillerDuty::PublishPayoutOptimistic {
+                            deposit_txid: self.deposit_txid(),
+                            graph_input,
+                            graph_sigs,
+                            deadline,
+                        },
                     ))
                 } else {
                     None
                 }
             }
-            ContractState::Challenged { .. } => None,
+            ContractState::Challenged { claim_height, .. } => {
+                // the assert chain is scheduled on every block once the pre-assert timelock
+                // expires so that missed or reorged blocks do not prevent it from firing. The
+                // duty is the same every time so it is deduplicated by its id.
+                if self.state.block_height
+                    >= claim_height + self.cfg.connector_params.pre_assert_timelock as u64
+                {
+                    self.assert_chain_duty()?
+                } else {
+                    None
+                }
+            }
             ContractState::Asserted {
+                signed_graphs,
                 post_assert_height,
                 fulfiller,
                 ..
             } => {
-                if self.state.block_height
-                    >= post_assert_height + self.cfg.connector_params.payout_timelock as u64
-                    && fulfiller == self.cfg.operator_table.pov_idx()
+                let deadline = self.disprove_deadline(*post_assert_height);
+                if self.state.block_height >= deadline
+                    && *fulfiller == self.cfg.operator_table.pov_idx()
                 {
-                    Some(OperatorDuty::FulfillerDuty(FulfillerDuty::PublishPayout))
+                    let (graph_input, graph_sigs) = self.signed_graph(signed_graphs, *fulfiller)?;
+
+                    Some(OperatorDuty::FulfillerDuty(FulfillerDuty::PublishPayout {
+                        deposit_txid: self.deposit_txid(),
+                        graph_input,
+                        graph_sigs,
+                        deadline,
+                    }))
                 } else {
                     None
                 }
@@ -961,12 +1830,46 @@ impl ContractSM {
             ContractState::Resolved {} => None,
         };
 
-        // restore state
-        self.state.state = current;
-
         Ok(duty)
     }
 
+    /// Rolls the state machine back to the last state consistent with the chain tip that precedes
+    /// the disconnected block.
+    ///
+    /// If the disconnected block contained transactions relevant to this contract, the
+    /// `checkpoint` taken before they were applied is restored, otherwise only the block height is
+    /// rewound.
+    fn process_block_disconnect(
+        &mut self,
+        height: BitcoinBlockHeight,
+        checkpoint: Option<MachineState>,
+    ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        match checkpoint {
+            Some(checkpoint) => {
+                if checkpoint.block_height + 1 != height {
+                    return Err(TransitionErr(format!(
+                        "checkpoint at height {} is not consistent with the disconnected block at {}",
+                        checkpoint.block_height, height
+                    )));
+                }
+
+                self.state = checkpoint;
+            }
+            None => {
+                if self.state.block_height != height {
+                    return Err(TransitionErr(format!(
+                        "received unexpected block disconnect notification, wanted {}, got {}",
+                        self.state.block_height, height
+                    )));
+                }
+
+                self.state.block_height = height - 1;
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Processes an assignment from the strata state commitment.
     pub fn process_assignment(
         &mut self,
@@ -982,7 +1885,10 @@ impl ContractSM {
         }
 
         match std::mem::replace(&mut self.state.state, ContractState::Resolved {}) {
-            ContractState::Deposited { peg_out_graphs } => match assignment.deposit_state() {
+            ContractState::Deposited {
+                peg_out_graphs,
+                signed_graphs,
+            } => match assignment.deposit_state() {
                 DepositState::Dispatched(dispatched_state) => {
                     let fulfiller = dispatched_state.assignee();
                     let fulfiller_key = match self.cfg.operator_table.idx_to_op_key(&fulfiller) {
@@ -1012,6 +1918,7 @@ impl ContractSM {
                     if let Some(recipient) = recipient {
                         self.state.state = ContractState::Assigned {
                             peg_out_graphs,
+                            signed_graphs,
                             fulfiller,
                             deadline,
                             active_graph,
@@ -1046,23 +1953,27 @@ impl ContractSM {
         &mut self,
         tx: &Transaction,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        if let ContractState::Assigned { active_graph, .. } = &self.state.state {
+            if tx.compute_txid() != active_graph.stake_txid {
+                return Err(TransitionErr(format!(
+                    "stake chain advancement txid ({}) doesn't match the stake txid of the active graph ({})", tx.compute_txid(), active_graph.stake_txid,
+                )));
+            }
+        }
+
         let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
         match current {
             ContractState::Assigned {
                 peg_out_graphs,
+                signed_graphs,
                 fulfiller,
                 recipient,
                 deadline,
                 active_graph,
             } => {
-                if tx.compute_txid() != active_graph.stake_txid {
-                    return Err(TransitionErr(format!(
-                        "stake chain advancement txid ({}) doesn't match the stake txid of the active graph ({})", tx.compute_txid(), active_graph.stake_txid,
-                    )));
-                }
-
                 self.state.state = ContractState::StakeTxReady {
                     peg_out_graphs,
+                    signed_graphs,
                     fulfiller,
                     recipient: recipient.clone(),
                     deadline,
@@ -1100,24 +2011,25 @@ impl ContractSM {
     fn process_fulfillment_confirmation(
         // Analyze fulfillment transaction to determine
         &mut self,
+        height: BitcoinBlockHeight,
         tx: &Transaction,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
         let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
         match current {
             ContractState::StakeTxReady {
                 peg_out_graphs,
+                signed_graphs,
                 fulfiller,
                 active_graph,
                 recipient,
                 ..
             } => {
-                // TODO(proofofkeags): we need to verify that this is bound properly to the correct
-                // operator.
+                // the fulfillment must be bound to the operator that was assigned the withdrawal.
                 let cfg = self.cfg();
                 if !is_fulfillment_tx(
                     cfg.network,
                     &cfg.peg_out_graph_params,
-                    cfg.operator_table.pov_idx(),
+                    fulfiller,
                     cfg.deposit_idx,
                     cfg.deposit_tx.compute_txid(),
                     recipient,
@@ -1130,19 +2042,31 @@ impl ContractSM {
                     )));
                 }
 
+                let withdrawal_fulfillment_txid = tx.compute_txid();
                 let duty = if fulfiller == self.cfg.operator_table.pov_idx() {
-                    Some(OperatorDuty::FulfillerDuty(FulfillerDuty::PublishClaim))
+                    self.signed_graph(&signed_graphs, fulfiller)
+                        .map(|(graph_input, _)| {
+                            Some(OperatorDuty::FulfillerDuty(FulfillerDuty::PublishClaim {
+                                deposit_txid: self.deposit_txid(),
+                                graph_input,
+                                withdrawal_fulfillment_txid,
+                            }))
+                        })
                 } else {
-                    None
+                    Ok(None)
                 };
 
+                // the fulfillment is recorded regardless of whether we can construct the duty.
                 self.state.state = ContractState::Fulfilled {
                     peg_out_graphs,
+                    signed_graphs,
                     fulfiller,
                     active_graph,
+                    withdrawal_fulfillment_txid,
+                    withdrawal_fulfillment_height: height,
                 };
 
-                Ok(duty)
+                duty
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_fulfillment_confirmation ({:?})",
@@ -1156,35 +2080,52 @@ impl ContractSM {
         height: BitcoinBlockHeight,
         tx: &Transaction,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        if let ContractState::Fulfilled { active_graph, .. } = &self.state.state {
+            if tx.compute_txid() != active_graph.claim_txid {
+                return Err(TransitionErr(format!(
+                    "invalid claim confirmation ({})",
+                    tx.compute_txid()
+                )));
+            }
+        }
+
         let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
         match current {
             ContractState::Fulfilled {
                 peg_out_graphs,
+                signed_graphs,
                 fulfiller,
                 active_graph,
-                ..
+                withdrawal_fulfillment_txid,
+                withdrawal_fulfillment_height,
             } => {
-                if tx.compute_txid() != active_graph.claim_txid {
-                    return Err(TransitionErr(format!(
-                        "invalid claim confirmation ({})",
-                        tx.compute_txid()
-                    )));
-                }
-
                 let duty = if fulfiller != self.cfg.operator_table.pov_idx() {
-                    Some(OperatorDuty::VerifierDuty(VerifierDuty::VerifyClaim))
+                    self.signed_graph(&signed_graphs, fulfiller)
+                        .map(|(graph_input, graph_sigs)| {
+                            Some(OperatorDuty::VerifierDuty(VerifierDuty::VerifyClaim {
+                                deposit_txid: self.deposit_txid(),
+                                claim_tx: tx.clone(),
+                                graph_input,
+                                graph_sigs,
+                                withdrawal_fulfillment_txid: Some(withdrawal_fulfillment_txid),
+                                challenge_deadline: self.challenge_deadline(height),
+                            }))
+                        })
                 } else {
-                    None
+                    Ok(None)
                 };
 
                 self.state.state = ContractState::Claimed {
                     peg_out_graphs,
+                    signed_graphs,
                     claim_height: height,
                     fulfiller,
                     active_graph,
+                    withdrawal_fulfillment_txid,
+                    withdrawal_fulfillment_height,
                 };
 
-                Ok(duty)
+                duty
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_claim_confirmation ({:?})",
@@ -1193,14 +2134,106 @@ impl ContractSM {
         }
     }
 
+    /// Processes the confirmation of a claim that does not belong to the operator that fulfilled
+    /// the withdrawal, i.e., a claim made by an operator that was not assigned the withdrawal or
+    /// that was made before the withdrawal was fulfilled.
+    ///
+    /// Such a claim does not advance the contract but it must be verified (and challenged) by
+    /// everyone else.
+    fn process_unexpected_claim_confirmation(
+        &self,
+        height: BitcoinBlockHeight,
+        tx: &Transaction,
+    ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        let (peg_out_graphs, signed_graphs) = match &self.state.state {
+            ContractState::Deposited {
+                peg_out_graphs,
+                signed_graphs,
+            }
+            | ContractState::Assigned {
+                peg_out_graphs,
+                signed_graphs,
+                ..
+            }
+            | ContractState::StakeTxReady {
+                peg_out_graphs,
+                signed_graphs,
+                ..
+            }
+            | ContractState::Fulfilled {
+                peg_out_graphs,
+                signed_graphs,
+                ..
+            }
+            | ContractState::Claimed {
+                peg_out_graphs,
+                signed_graphs,
+                ..
+            } => (peg_out_graphs, signed_graphs),
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_unexpected_claim_confirmation ({:?})",
+                    self.state.state
+                )))
+            }
+        };
+
+        let txid = tx.compute_txid();
+        let claimer_key = peg_out_graphs
+            .iter()
+            .find_map(|(op_key, graph)| (graph.claim_txid == txid).then_some(op_key))
+            .ok_or(TransitionErr(format!(
+                "transaction ({txid}) is not a claim in any of the peg out graphs"
+            )))?;
+        let claimer = self
+            .cfg
+            .operator_table
+            .op_key_to_idx(claimer_key)
+            .ok_or(TransitionErr(format!(
+                "could not convert operator key {claimer_key} to operator index"
+            )))?;
+
+        // we never verify our own claims.
+        if claimer == self.cfg.operator_table.pov_idx() {
+            return Ok(None);
+        }
+
+        let (graph_input, graph_sigs) = self.signed_graph(signed_graphs, claimer)?;
+
+        Ok(Some(OperatorDuty::VerifierDuty(
+            VerifierDuty::VerifyClaim {
+                deposit_txid: self.deposit_txid(),
+                claim_tx: tx.clone(),
+                graph_input,
+                graph_sigs,
+                withdrawal_fulfillment_txid: None,
+                challenge_deadline: self.challenge_deadline(height),
+            },
+        )))
+    }
+
     /// Tells the state machine that the claim was assessed to be fraudulent.
     fn process_claim_verification_failure(
         &mut self,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
         match &self.state.state {
-            ContractState::Claimed { .. } => Ok(Some(OperatorDuty::VerifierDuty(
-                VerifierDuty::PublishChallenge,
-            ))),
+            ContractState::Claimed {
+                signed_graphs,
+                fulfiller,
+                claim_height,
+                ..
+            } => {
+                let (graph_input, graph_sigs) = self.signed_graph(signed_graphs, *fulfiller)?;
+
+                Ok(Some(OperatorDuty::VerifierDuty(
+                    VerifierDuty::PublishChallenge {
+                        deposit_txid: self.deposit_txid(),
+                        graph_input,
+                        graph_sigs,
+                        deadline: self.challenge_deadline(*claim_height),
+                    },
+                )))
+            }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_claim_verification_failure ({:?})",
                 self.state.state
@@ -1210,38 +2243,54 @@ impl ContractSM {
 
     fn process_challenge_confirmation(
         &mut self,
+        height: BitcoinBlockHeight,
         tx: &Transaction,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
-        let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
-        match current {
-            ContractState::Claimed {
-                peg_out_graphs,
-                fulfiller,
-                active_graph,
-                ..
-            } => {
+        match &self.state.state {
+            ContractState::Claimed { active_graph, .. } => {
                 if !is_challenge(active_graph.claim_txid)(tx) {
                     return Err(TransitionErr(format!(
                         "invalid challenge transaction ({}) in process_challenge_confirmation",
                         tx.compute_txid()
                     )));
                 }
+            }
+            _ => {
+                return Err(TransitionErr(format!(
+                    "unexpected state in process_challenge_confirmation ({:?})",
+                    self.state.state
+                )))
+            }
+        }
 
-                let duty = if fulfiller == self.cfg.operator_table.pov_idx() {
-                    Some(OperatorDuty::FulfillerDuty(
-                        FulfillerDuty::PublishAssertChain,
-                    ))
-                } else {
-                    None
-                };
-
+        let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
+        match current {
+            ContractState::Claimed {
+                peg_out_graphs,
+                signed_graphs,
+                claim_height,
+                fulfiller,
+                active_graph,
+                withdrawal_fulfillment_txid,
+                withdrawal_fulfillment_height,
+            } => {
                 self.state.state = ContractState::Challenged {
                     peg_out_graphs,
+                    signed_graphs,
+                    claim_height,
                     fulfiller,
                     active_graph,
+                    withdrawal_fulfillment_txid,
+                    withdrawal_fulfillment_height,
                 };
 
-                Ok(duty)
+                // the assert chain is scheduled at the block where the pre-assert timelock
+                // expires. If the challenge confirms later than that, we have to do it now.
+                if height > claim_height + self.cfg.connector_params.pre_assert_timelock as u64 {
+                    self.assert_chain_duty()
+                } else {
+                    Ok(None)
+                }
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_challenge_confirmation ({:?})",
@@ -1255,35 +2304,51 @@ impl ContractSM {
         post_assert_height: BitcoinBlockHeight,
         tx: &Transaction,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        if let ContractState::Challenged { active_graph, .. } = &self.state.state {
+            if tx.compute_txid() != active_graph.post_assert_txid {
+                return Err(TransitionErr(format!(
+                    "invalid post assert transaction ({}) in process_assert_chain_confirmation",
+                    tx.compute_txid()
+                )));
+            }
+        }
+
         let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
         match current {
             ContractState::Challenged {
                 peg_out_graphs,
+                signed_graphs,
+                claim_height,
                 fulfiller,
                 active_graph,
                 ..
             } => {
-                if tx.compute_txid() != active_graph.post_assert_txid {
-                    return Err(TransitionErr(format!(
-                        "invalid post assert transaction ({}) in process_assert_chain_confirmation",
-                        tx.compute_txid()
-                    )));
-                }
-
                 let duty = if fulfiller != self.cfg.operator_table.pov_idx() {
-                    Some(OperatorDuty::VerifierDuty(VerifierDuty::VerifyAssertion))
+                    self.signed_graph(&signed_graphs, fulfiller)
+                        .map(|(graph_input, graph_sigs)| {
+                            Some(OperatorDuty::VerifierDuty(VerifierDuty::VerifyAssertion {
+                                deposit_txid: self.deposit_txid(),
+                                graph_input,
+                                graph_sigs,
+                                claim_height,
+                                post_assert_height,
+                                disprove_deadline: self.disprove_deadline(post_assert_height),
+                            }))
+                        })
                 } else {
-                    None
+                    Ok(None)
                 };
 
                 self.state.state = ContractState::Asserted {
                     peg_out_graphs,
+                    signed_graphs,
+                    claim_height,
                     post_assert_height,
                     fulfiller,
                     active_graph,
                 };
 
-                Ok(duty)
+                duty
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_assert_chain_confirmation ({:?})",
@@ -1296,14 +2361,30 @@ impl ContractSM {
     fn process_assertion_verification_failure(
         &mut self,
     ) -> Result<Option<OperatorDuty>, TransitionErr> {
-        let current = std::mem::replace(&mut self.state.state, ContractState::Resolved {});
-        match current {
-            ContractState::Asserted { .. } => Ok(Some(OperatorDuty::VerifierDuty(
-                VerifierDuty::PublishDisprove,
-            ))),
+        match &self.state.state {
+            ContractState::Asserted {
+                signed_graphs,
+                claim_height,
+                post_assert_height,
+                fulfiller,
+                ..
+            } => {
+                let (graph_input, graph_sigs) = self.signed_graph(signed_graphs, *fulfiller)?;
+
+                Ok(Some(OperatorDuty::VerifierDuty(
+                    VerifierDuty::PublishDisprove {
+                        deposit_txid: self.deposit_txid(),
+                        graph_input,
+                        graph_sigs,
+                        claim_height: *claim_height,
+                        post_assert_height: *post_assert_height,
+                        deadline: self.disprove_deadline(*post_assert_height),
+                    },
+                )))
+            }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_assert_verification_failure ({:?})",
-                current
+                self.state.state
             ))),
         }
     }
@@ -1323,7 +2404,9 @@ impl ContractSM {
 
                 self.state.state = ContractState::Disproved {};
 
-                Ok(None)
+                Ok(Some(OperatorDuty::ForgetGraphs {
+                    deposit_txid: self.deposit_txid(),
+                }))
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_disprove_confirmation ({:?})",
@@ -1344,7 +2427,9 @@ impl ContractSM {
 
                 self.state.state = ContractState::Resolved {};
 
-                Ok(None)
+                Ok(Some(OperatorDuty::ForgetGraphs {
+                    deposit_txid: self.deposit_txid(),
+                }))
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_optimistic_payout_confirmation ({:?})",
@@ -1367,7 +2452,9 @@ impl ContractSM {
 
                 self.state.state = ContractState::Resolved {};
 
-                Ok(None)
+                Ok(Some(OperatorDuty::ForgetGraphs {
+                    deposit_txid: self.deposit_txid(),
+                }))
             }
             _ => Err(TransitionErr(format!(
                 "unexpected state in process_defended_payout_confirmation ({:?})",
@@ -1401,4 +2488,512 @@ impl ContractSM {
             .previous_output
             .txid
     }
+
+    /// The height at which the optimistic payout of the claim confirmed at the given height becomes
+    /// valid, i.e., the height by which a challenge of the claim must be mined.
+    fn challenge_deadline(&self, claim_height: BitcoinBlockHeight) -> BitcoinBlockHeight {
+        claim_height + self.cfg.connector_params.payout_optimistic_timelock as BitcoinBlockHeight
+    }
+
+    /// The height at which the payout of the assertions whose post-assert transaction confirmed at
+    /// the given height becomes valid, i.e., the height by which a disprove must be mined.
+    fn disprove_deadline(&self, post_assert_height: BitcoinBlockHeight) -> BitcoinBlockHeight {
+        post_assert_height + self.cfg.connector_params.payout_timelock as BitcoinBlockHeight
+    }
+
+    /// Whether the duty was emitted on behalf of this contract.
+    pub fn emitted(&self, duty: &OperatorDuty) -> bool {
+        match duty.contract() {
+            Some(DutyContract::DepositTxid(deposit_txid)) => deposit_txid == self.deposit_txid(),
+            Some(DutyContract::DepositIdx(deposit_idx)) => deposit_idx == self.cfg.deposit_idx,
+            None => false,
+        }
+    }
+
+    /// Whether this contract has progressed past the states in which the duty it emitted can
+    /// still be of use, in which case the duty should be cancelled.
+    ///
+    /// Duties emitted on behalf of other contracts are never considered to be moved past.
+    pub fn has_moved_past(&self, duty: &OperatorDuty) -> bool {
+        if !self.emitted(duty) {
+            return false;
+        }
+
+        let state = &self.state.state;
+        let still_useful = match duty {
+            OperatorDuty::PublishDepositSetup { .. }
+            | OperatorDuty::PublishGraphNonces { .. }
+            | OperatorDuty::PublishGraphSignatures { .. }
+            | OperatorDuty::PublishRootNonce { .. }
+            | OperatorDuty::PublishRootSignature { .. }
+            | OperatorDuty::PublishDeposit { .. } => {
+                matches!(state, ContractState::Requested { .. })
+            }
+            OperatorDuty::FulfillerDuty(FulfillerDuty::PublishFulfillment { .. }) => matches!(
+                state,
+                ContractState::Deposited { .. }
+                    | ContractState::Assigned { .. }
+                    | ContractState::StakeTxReady { .. }
+            ),
+            OperatorDuty::FulfillerDuty(FulfillerDuty::PublishClaim { .. }) => {
+                matches!(state, ContractState::Fulfilled { .. })
+            }
+            OperatorDuty::FulfillerDuty(FulfillerDuty::PublishPayoutOptimistic { .. }) => {
+                matches!(state, ContractState::Claimed { .. })
+            }
+            // claims by operators other than the fulfiller can confirm before the contract is
+            // claimed and must be challenged all the same.
+            OperatorDuty::VerifierDuty(
+                VerifierDuty::VerifyClaim { .. } | VerifierDuty::PublishChallenge { .. },
+            ) => matches!(
+                state,
+                ContractState::Deposited { .. }
+                    | ContractState::Assigned { .. }
+                    | ContractState::StakeTxReady { .. }
+                    | ContractState::Fulfilled { .. }
+                    | ContractState::Claimed { .. }
+            ),
+            OperatorDuty::FulfillerDuty(FulfillerDuty::PublishAssertChain { .. }) => {
+                matches!(state, ContractState::Challenged { .. })
+            }
+            OperatorDuty::FulfillerDuty(FulfillerDuty::PublishPayout { .. })
+            | OperatorDuty::VerifierDuty(
+                VerifierDuty::VerifyAssertion { .. } | VerifierDuty::PublishDisprove { .. },
+            ) => matches!(state, ContractState::Asserted { .. }),
+            OperatorDuty::Abort
+            | OperatorDuty::ForgetGraphs { .. }
+            | OperatorDuty::FulfillerDuty(FulfillerDuty::AdvanceStakeChain { .. })
+            | OperatorDuty::VerifierDuty(VerifierDuty::VerifyStake) => true,
+        };
+
+        !still_useful
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{Amount, ScriptBuf};
+    use strata_bridge_connectors::prelude::ConnectorCpfp;
+    use strata_bridge_test_utils::prelude::{generate_keypair, generate_tx, generate_txid};
+
+    use super::*;
+
+    /// Builds the config of a contract between `num_operators` operators as seen by `pov_idx`.
+    fn contract_cfg(
+        num_operators: OperatorIdx,
+        pov_idx: OperatorIdx,
+        deposit_request_height: BitcoinBlockHeight,
+    ) -> ContractCfg {
+        let entries = (0..num_operators)
+            .map(|idx| {
+                let btc_key = generate_keypair().public_key();
+                let p2p_key = P2POperatorPubKey::from(btc_key.serialize().to_vec());
+                (idx, p2p_key, btc_key)
+            })
+            .collect();
+        let operator_table =
+            OperatorTable::new(entries, pov_idx).expect("operator table must be valid");
+
+        ContractCfg {
+            network: Network::Regtest,
+            operator_table,
+            connector_params: ConnectorParams::default(),
+            peg_out_graph_params: PegOutGraphParams::default(),
+            stake_chain_params: StakeChainParams::default(),
+            deposit_idx: 0,
+            deposit_request_height,
+            deposit_tx: generate_tx(1, 1),
+        }
+    }
+
+    /// Builds a contract with a single operator that is still waiting for its deposit.
+    fn requested_contract(
+        block_height: BitcoinBlockHeight,
+        abort_deadline: BitcoinBlockHeight,
+    ) -> ContractSM {
+        let cfg = contract_cfg(1, 0, block_height);
+        let state = MachineState {
+            block_height,
+            state: ContractState::Requested {
+                deposit_request_txid: generate_txid(),
+                deposit_request_prevout: TxOut {
+                    value: Amount::from_sat(1_000_000),
+                    script_pubkey: ScriptBuf::new(),
+                },
+                deposit_request_witness: TaprootWitness::Key,
+                abort_deadline,
+                stake_txs: BTreeMap::new(),
+                stake_hashes: BTreeMap::new(),
+                wots_keys: BTreeMap::new(),
+                operator_pubkeys: BTreeMap::new(),
+                peg_out_graphs: BTreeMap::new(),
+                graph_nonces: BTreeMap::new(),
+                graph_sigs: BTreeMap::new(),
+                aggregated_graph_sigs: BTreeMap::new(),
+                root_nonces: BTreeMap::new(),
+                root_sigs: BTreeMap::new(),
+            },
+        };
+
+        ContractSM::restore(cfg, state)
+    }
+
+    /// The peg-out graphs of every operator in a contract along with the claim and post-assert
+    /// transactions of each graph, indexed by operator.
+    struct Graphs {
+        peg_out_graphs: BTreeMap<P2POperatorPubKey, PegOutGraphSummary>,
+        signed_graphs: BTreeMap<P2POperatorPubKey, SignedGraph>,
+        claim_txs: Vec<Transaction>,
+        post_assert_txs: Vec<Transaction>,
+    }
+
+    impl Graphs {
+        fn new(cfg: &ContractCfg) -> Self {
+            let context = cfg.operator_table.tx_build_context(cfg.network);
+            let mut graphs = Self {
+                peg_out_graphs: BTreeMap::new(),
+                signed_graphs: BTreeMap::new(),
+                claim_txs: Vec::new(),
+                post_assert_txs: Vec::new(),
+            };
+
+            for idx in cfg.operator_table.operator_idxs() {
+                let op_key = cfg.operator_table.idx_to_op_key(&idx).unwrap().clone();
+                let operator_pubkey = cfg
+                    .operator_table
+                    .idx_to_btc_key(&idx)
+                    .unwrap()
+                    .x_only_public_key()
+                    .0;
+
+                // the keys are never checked by the transitions.
+                let wots_keys = WotsPublicKeys::from_flattened_bytes(&vec![0u8; 1_360 + 362_960]);
+                let stake_hash = sha256::Hash::const_hash(b"stake preimage");
+                let stake_tx = StakeTx::create_initial(
+                    &context,
+                    &cfg.stake_chain_params,
+                    stake_hash,
+                    Wots256PublicKey(wots_keys.withdrawal_fulfillment.0),
+                    OutPoint::new(generate_txid(), 0),
+                    OutPoint::new(generate_txid(), 0),
+                    operator_pubkey,
+                    ConnectorCpfp::new(operator_pubkey, cfg.network),
+                );
+
+                let claim_tx = generate_tx(1, 1);
+                let post_assert_tx = generate_tx(1, 1);
+                let summary = PegOutGraphSummary {
+                    stake_txid: stake_tx.compute_txid(),
+                    claim_txid: claim_tx.compute_txid(),
+                    payout_optimistic_txid: generate_txid(),
+                    pre_assert_txid: generate_txid(),
+                    assert_data_txids: std::array::from_fn(|_| generate_txid()),
+                    post_assert_txid: post_assert_tx.compute_txid(),
+                    payout_txid: generate_txid(),
+                    slash_stake_txids: Vec::new(),
+                };
+
+                graphs.peg_out_graphs.insert(op_key.clone(), summary);
+                graphs.signed_graphs.insert(
+                    op_key,
+                    SignedGraph {
+                        stake_tx,
+                        stake_hash,
+                        wots_keys,
+                        operator_pubkey,
+                        signatures: Vec::new(),
+                    },
+                );
+                graphs.claim_txs.push(claim_tx);
+                graphs.post_assert_txs.push(post_assert_tx);
+            }
+
+            graphs
+        }
+
+        fn active_graph(&self, cfg: &ContractCfg, fulfiller: OperatorIdx) -> PegOutGraphSummary {
+            let op_key = cfg.operator_table.idx_to_op_key(&fulfiller).unwrap();
+            self.peg_out_graphs[op_key].clone()
+        }
+    }
+
+    fn disconnect(
+        sm: &mut ContractSM,
+        height: BitcoinBlockHeight,
+        checkpoint: Option<MachineState>,
+    ) -> Result<Option<OperatorDuty>, TransitionErr> {
+        sm.process_contract_event(ContractEvent::BlockDisconnected {
+            height,
+            checkpoint: checkpoint.map(Box::new),
+        })
+    }
+
+    #[test]
+    fn blocks_without_events_round_trip() {
+        let mut sm = requested_contract(100, 102);
+
+        assert!(sm
+            .process_contract_event(ContractEvent::Block(101))
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            sm.process_contract_event(ContractEvent::Block(102)),
+            Ok(Some(OperatorDuty::Abort))
+        ));
+
+        disconnect(&mut sm, 102, None).unwrap();
+        assert_eq!(sm.state().block_height, 101);
+
+        // the duties of a reconnected block are emitted again.
+        assert!(matches!(
+            sm.process_contract_event(ContractEvent::Block(102)),
+            Ok(Some(OperatorDuty::Abort))
+        ));
+
+        disconnect(&mut sm, 102, None).unwrap();
+        disconnect(&mut sm, 101, None).unwrap();
+        assert_eq!(sm.state().block_height, 100);
+        assert!(matches!(sm.state().state, ContractState::Requested { .. }));
+    }
+
+    #[test]
+    fn disconnect_restores_the_checkpoint() {
+        let requested = requested_contract(100, 200);
+        let checkpoint = requested.state().clone();
+
+        // the contract as it would be after its deposit was confirmed in the next block.
+        let mut sm = ContractSM::restore(
+            requested.cfg().clone(),
+            MachineState {
+                block_height: 101,
+                state: ContractState::Deposited {
+                    peg_out_graphs: BTreeMap::new(),
+                    signed_graphs: BTreeMap::new(),
+                },
+            },
+        );
+
+        disconnect(&mut sm, 101, Some(checkpoint)).unwrap();
+        assert_eq!(sm.state().block_height, 100);
+        assert!(matches!(sm.state().state, ContractState::Requested { .. }));
+
+        // the contract follows the chain again once the block is replaced.
+        sm.process_contract_event(ContractEvent::Block(101))
+            .unwrap();
+        assert_eq!(sm.state().block_height, 101);
+    }
+
+    #[test]
+    fn deposit_confirmation_requires_every_signed_graph() {
+        let mut sm = requested_contract(100, 200);
+        let deposit_tx = sm.cfg().deposit_tx.clone();
+
+        assert!(sm
+            .process_contract_event(ContractEvent::DepositConfirmation(deposit_tx))
+            .is_err());
+        assert!(matches!(sm.state().state, ContractState::Requested { .. }));
+    }
+
+    #[test]
+    fn disconnect_rejects_unknown_blocks() {
+        let mut sm = requested_contract(100, 200);
+
+        assert!(disconnect(&mut sm, 105, None).is_err());
+
+        let stale_checkpoint = requested_contract(90, 200).state().clone();
+        assert!(disconnect(&mut sm, 100, Some(stale_checkpoint)).is_err());
+        assert_eq!(sm.state().block_height, 100);
+    }
+
+    #[test]
+    fn unexpected_claims_are_verified_by_everyone_else() {
+        let cfg = contract_cfg(2, 0, 100);
+        let graphs = Graphs::new(&cfg);
+        let challenge_deadline =
+            150 + cfg.connector_params.payout_optimistic_timelock as BitcoinBlockHeight;
+        let mut sm = ContractSM::restore(
+            cfg,
+            MachineState {
+                block_height: 150,
+                state: ContractState::Deposited {
+                    peg_out_graphs: graphs.peg_out_graphs.clone(),
+                    signed_graphs: graphs.signed_graphs.clone(),
+                },
+            },
+        );
+
+        let duty = sm
+            .process_contract_event(ContractEvent::PegOutGraphConfirmation(
+                graphs.claim_txs[1].clone(),
+                150,
+            ))
+            .unwrap();
+        assert!(matches!(
+            duty,
+            Some(OperatorDuty::VerifierDuty(VerifierDuty::VerifyClaim {
+                withdrawal_fulfillment_txid: None,
+                challenge_deadline: deadline,
+                ..
+            })) if deadline == challenge_deadline
+        ));
+        assert!(matches!(sm.state().state, ContractState::Deposited { .. }));
+
+        // we never verify our own claims.
+        assert!(sm
+            .process_contract_event(ContractEvent::PegOutGraphConfirmation(
+                graphs.claim_txs[0].clone(),
+                150,
+            ))
+            .unwrap()
+            .is_none());
+
+        assert!(sm
+            .process_contract_event(ContractEvent::PegOutGraphConfirmation(
+                generate_tx(1, 1),
+                150
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn claim_verification_failure_publishes_challenge() {
+        let cfg = contract_cfg(2, 0, 100);
+        let graphs = Graphs::new(&cfg);
+        let challenge_deadline =
+            150 + cfg.connector_params.payout_optimistic_timelock as BitcoinBlockHeight;
+        let active_graph = graphs.active_graph(&cfg, 1);
+        let mut sm = ContractSM::restore(
+            cfg,
+            MachineState {
+                block_height: 151,
+                state: ContractState::Claimed {
+                    peg_out_graphs: graphs.peg_out_graphs,
+                    signed_graphs: graphs.signed_graphs,
+                    claim_height: 150,
+                    fulfiller: 1,
+                    active_graph,
+                    withdrawal_fulfillment_txid: generate_txid(),
+                    withdrawal_fulfillment_height: 140,
+                },
+            },
+        );
+
+        assert!(matches!(
+            sm.process_contract_event(ContractEvent::ClaimFailure),
+            Ok(Some(OperatorDuty::VerifierDuty(VerifierDuty::PublishChallenge {
+                deadline,
+                ..
+            }))) if deadline == challenge_deadline
+        ));
+        // the contract only advances once the challenge confirms.
+        assert!(matches!(sm.state().state, ContractState::Claimed { .. }));
+
+        assert!(requested_contract(100, 200)
+            .process_contract_event(ContractEvent::ClaimFailure)
+            .is_err());
+    }
+
+    #[test]
+    fn assert_chain_confirmation_is_verified_by_everyone_else() {
+        for (fulfiller, verifies) in [(1, true), (0, false)] {
+            let cfg = contract_cfg(2, 0, 100);
+            let graphs = Graphs::new(&cfg);
+            let disprove_deadline =
+                170 + cfg.connector_params.payout_timelock as BitcoinBlockHeight;
+            let active_graph = graphs.active_graph(&cfg, fulfiller);
+            let mut sm = ContractSM::restore(
+                cfg,
+                MachineState {
+                    block_height: 169,
+                    state: ContractState::Challenged {
+                        peg_out_graphs: graphs.peg_out_graphs,
+                        signed_graphs: graphs.signed_graphs,
+                        claim_height: 150,
+                        fulfiller,
+                        active_graph,
+                        withdrawal_fulfillment_txid: generate_txid(),
+                        withdrawal_fulfillment_height: 140,
+                    },
+                },
+            );
+
+            // only the post-assert of the fulfiller's graph advances the contract.
+            let other_post_assert = graphs.post_assert_txs[1 - fulfiller as usize].clone();
+            assert!(sm
+                .process_contract_event(ContractEvent::PegOutGraphConfirmation(
+                    other_post_assert,
+                    170
+                ))
+                .is_err());
+            assert!(matches!(sm.state().state, ContractState::Challenged { .. }));
+
+            let post_assert = graphs.post_assert_txs[fulfiller as usize].clone();
+            let duty = sm
+                .process_contract_event(ContractEvent::PegOutGraphConfirmation(post_assert, 170))
+                .unwrap();
+            if verifies {
+                assert!(matches!(
+                    duty,
+                    Some(OperatorDuty::VerifierDuty(VerifierDuty::VerifyAssertion {
+                        claim_height: 150,
+                        post_assert_height: 170,
+                        disprove_deadline: deadline,
+                        ..
+                    })) if deadline == disprove_deadline
+                ));
+            } else {
+                assert!(duty.is_none());
+            }
+            assert!(matches!(
+                sm.state().state,
+                ContractState::Asserted {
+                    claim_height: 150,
+                    post_assert_height: 170,
+                    ..
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn assertion_verification_failure_publishes_disprove() {
+        let cfg = contract_cfg(2, 0, 100);
+        let graphs = Graphs::new(&cfg);
+        let disprove_deadline = 170 + cfg.connector_params.payout_timelock as BitcoinBlockHeight;
+        let active_graph = graphs.active_graph(&cfg, 1);
+        let post_assert_txid = active_graph.post_assert_txid;
+        let mut sm = ContractSM::restore(
+            cfg,
+            MachineState {
+                block_height: 171,
+                state: ContractState::Asserted {
+                    peg_out_graphs: graphs.peg_out_graphs,
+                    signed_graphs: graphs.signed_graphs,
+                    claim_height: 150,
+                    post_assert_height: 170,
+                    fulfiller: 1,
+                    active_graph,
+                },
+            },
+        );
+
+        assert!(matches!(
+            sm.process_contract_event(ContractEvent::AssertionFailure),
+            Ok(Some(OperatorDuty::VerifierDuty(VerifierDuty::PublishDisprove {
+                claim_height: 150,
+                post_assert_height: 170,
+                deadline,
+                ..
+            }))) if deadline == disprove_deadline
+        ));
+        assert!(matches!(sm.state().state, ContractState::Asserted { .. }));
+
+        let mut disprove_tx = generate_tx(2, 2);
+        disprove_tx.input[0].previous_output = OutPoint::new(post_assert_txid, 0);
+        assert!(matches!(
+            sm.process_contract_event(ContractEvent::PegOutGraphConfirmation(disprove_tx, 172)),
+            Ok(Some(OperatorDuty::ForgetGraphs { .. }))
+        ));
+        assert!(matches!(sm.state().state, ContractState::Disproved {}));
+    }
 }
@@ -0,0 +1,409 @@
+//! This module is responsible for persisting the [`OperatorDuty`]s emitted by the
+//! [`crate::contract_state_machine::ContractSM`]s so that they survive crashes and can be retried
+//! until they succeed.
+
+use std::{fmt::Display, str::FromStr};
+
+use bincode::ErrorKind;
+use bitcoin::{
+    hashes::{sha256, Hash},
+    Transaction,
+};
+use sqlx::{
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Pool, Row, Sqlite,
+};
+use thiserror::Error;
+use tracing::error;
+
+use crate::contract_state_machine::OperatorDuty;
+
+/// Error type for the [`DutyPersister`] methods.
+#[derive(Debug, Clone, Error)]
+pub enum DutyPersistErr {
+    /// Unexpected error.
+    #[error("Unexpected error: {0}")]
+    Unexpected(String),
+}
+impl From<Box<ErrorKind>> for DutyPersistErr {
+    fn from(e: Box<ErrorKind>) -> Self {
+        DutyPersistErr::Unexpected(e.to_string())
+    }
+}
+impl From<sqlx::Error> for DutyPersistErr {
+    fn from(e: sqlx::Error) -> Self {
+        DutyPersistErr::Unexpected(e.to_string())
+    }
+}
+
+/// Stable identifier of an [`OperatorDuty`].
+///
+/// The id is derived from the contents of the duty so the same duty emitted more than once (for
+/// example, when it is re-emitted on every block until its transaction confirms) maps to the same
+/// entry in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DutyId(sha256::Hash);
+
+impl DutyId {
+    /// Computes the [`DutyId`] of the given duty.
+    pub fn of(duty: &OperatorDuty) -> Result<Self, DutyPersistErr> {
+        Ok(DutyId(sha256::Hash::hash(&bincode::serialize(duty)?)))
+    }
+}
+
+impl Display for DutyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for DutyId {
+    type Err = DutyPersistErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        sha256::Hash::from_str(s)
+            .map(DutyId)
+            .map_err(|e| DutyPersistErr::Unexpected(e.to_string()))
+    }
+}
+
+/// A duty that was given up on, as recorded in the dead letter table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadDuty {
+    /// The id of the duty.
+    pub duty_id: DutyId,
+
+    /// The number of times the execution of the duty was attempted.
+    pub attempts: u32,
+
+    /// Why the duty was given up on.
+    pub reason: String,
+}
+
+/// System for persisting the queue of [`OperatorDuty`]s that are yet to be executed successfully.
+///
+/// Duties are removed from the queue once they are completed or cancelled. Duties that cannot be
+/// executed are moved to a dead letter table instead so that they can be inspected by the
+/// operator.
+#[derive(Debug, Clone)]
+pub struct DutyPersister {
+    pool: Pool<Sqlite>,
+}
+
+impl DutyPersister {
+    /// Initializes the [`DutyPersister`]
+    pub async fn new(pool: Pool<Sqlite>) -> Result<Self, DutyPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS duties (
+                duty_id CHAR(64) PRIMARY KEY,
+                duty VARBINARY NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS duty_txs (
+                duty_id CHAR(64) PRIMARY KEY,
+                tx VARBINARY NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS dead_duties (
+                duty_id CHAR(64) PRIMARY KEY,
+                duty VARBINARY NOT NULL,
+                attempts INTEGER NOT NULL,
+                reason TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        Ok(DutyPersister { pool })
+    }
+
+    /// Adds the duty to the queue of pending duties and returns its [`DutyId`].
+    ///
+    /// Enqueuing a duty that is already pending leaves it as is.
+    pub async fn enqueue(&self, duty: &OperatorDuty) -> Result<DutyId, DutyPersistErr> {
+        let duty_id = DutyId::of(duty)?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT INTO duties (duty_id, duty) VALUES (?, ?)
+            ON CONFLICT (duty_id) DO NOTHING
+            "#,
+        )
+        .bind(duty_id.to_string())
+        .bind(bincode::serialize(duty)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(duty_id)
+    }
+
+    /// Records a new attempt at executing the duty and returns the total number of attempts.
+    pub async fn record_attempt(&self, duty_id: &DutyId) -> Result<u32, DutyPersistErr> {
+        let row: SqliteRow = sqlx::query(
+            r#"
+            UPDATE duties SET attempts = attempts + 1 WHERE duty_id = ? RETURNING attempts
+            "#,
+        )
+        .bind(duty_id.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.try_get("attempts")?)
+    }
+
+    /// Removes the successfully executed duty from the queue along with its transaction.
+    pub async fn complete(&self, duty_id: &DutyId) -> Result<(), DutyPersistErr> {
+        self.remove(duty_id).await
+    }
+
+    /// Removes the duty that is no longer needed from the queue along with its transaction.
+    pub async fn cancel(&self, duty_id: &DutyId) -> Result<(), DutyPersistErr> {
+        self.remove(duty_id).await
+    }
+
+    /// Moves the duty from the queue to the dead letter table.
+    pub async fn dead_letter(&self, duty_id: &DutyId, reason: &str) -> Result<(), DutyPersistErr> {
+        let mut db_tx = self.pool.begin().await?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO dead_duties (duty_id, duty, attempts, reason)
+            SELECT duty_id, duty, attempts, ? FROM duties WHERE duty_id = ?
+            "#,
+        )
+        .bind(reason)
+        .bind(duty_id.to_string())
+        .execute(&mut *db_tx)
+        .await?;
+        Self::delete(&mut db_tx, duty_id).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Whether the duty with the given [`DutyId`] was given up on.
+    pub async fn is_dead(&self, duty_id: &DutyId) -> Result<bool, DutyPersistErr> {
+        let row: Option<SqliteRow> = sqlx::query(
+            r#"
+            SELECT 1 FROM dead_duties WHERE duty_id = ?
+            "#,
+        )
+        .bind(duty_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+
+    /// Loads the duties that were given up on.
+    pub async fn load_dead(&self) -> Result<Vec<DeadDuty>, DutyPersistErr> {
+        let rows = sqlx::query(
+            r#"
+            SELECT duty_id, attempts, reason FROM dead_duties
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(DeadDuty {
+                    duty_id: row.try_get::<String, _>("duty_id")?.parse()?,
+                    attempts: row.try_get("attempts")?,
+                    reason: row.try_get("reason")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Loads all the duties that have not been executed successfully yet.
+    ///
+    /// Duties that cannot be decoded, for example because they were persisted by a version of the
+    /// node with a different duty format, are moved to the dead letter table.
+    pub async fn load_pending(&self) -> Result<Vec<(DutyId, OperatorDuty)>, DutyPersistErr> {
+        let rows = sqlx::query(
+            r#"
+            SELECT duty_id, duty FROM duties
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut pending = Vec::with_capacity(rows.len());
+        for row in rows {
+            let duty_id: DutyId = row.try_get::<String, _>("duty_id")?.parse()?;
+            match bincode::deserialize::<OperatorDuty>(row.try_get("duty")?) {
+                Ok(duty) => pending.push((duty_id, duty)),
+                Err(e) => {
+                    error!(%duty_id, %e, "could not decode persisted duty, moving it to the dead letter table");
+                    self.dead_letter(&duty_id, &format!("could not decode duty: {e}"))
+                        .await?;
+                }
+            }
+        }
+
+        Ok(pending)
+    }
+
+    /// Records the transaction produced by the duty so that subsequent attempts can resubmit it
+    /// instead of building a new, conflicting one.
+    pub async fn save_tx(&self, duty_id: &DutyId, tx: &Transaction) -> Result<(), DutyPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO duty_txs (duty_id, tx) VALUES (?, ?)
+            "#,
+        )
+        .bind(duty_id.to_string())
+        .bind(bincode::serialize(tx)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Loads the transaction previously produced by the duty, if any.
+    pub async fn load_tx(&self, duty_id: &DutyId) -> Result<Option<Transaction>, DutyPersistErr> {
+        let row: Option<SqliteRow> = sqlx::query(
+            r#"
+            SELECT tx FROM duty_txs WHERE duty_id = ?
+            "#,
+        )
+        .bind(duty_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(|row| Ok(bincode::deserialize(row.try_get("tx")?)?))
+            .transpose()
+    }
+
+    /// Removes the duty from the queue along with its transaction.
+    async fn remove(&self, duty_id: &DutyId) -> Result<(), DutyPersistErr> {
+        let mut db_tx = self.pool.begin().await?;
+        Self::delete(&mut db_tx, duty_id).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes the rows of the duty from the queue as part of the given database transaction.
+    async fn delete(
+        db_tx: &mut sqlx::Transaction<'_, Sqlite>,
+        duty_id: &DutyId,
+    ) -> Result<(), DutyPersistErr> {
+        for query in [
+            "DELETE FROM duties WHERE duty_id = ?",
+            "DELETE FROM duty_txs WHERE duty_id = ?",
+        ] {
+            let _: SqliteQueryResult = sqlx::query(query)
+                .bind(duty_id.to_string())
+                .execute(&mut **db_tx)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{absolute, transaction, Txid};
+    use sqlx::SqlitePool;
+
+    use super::*;
+
+    fn forget_graphs(seed: u8) -> OperatorDuty {
+        OperatorDuty::ForgetGraphs {
+            deposit_txid: Txid::from_byte_array([seed; 32]),
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn enqueued_duties_round_trip(pool: SqlitePool) {
+        let persister = DutyPersister::new(pool).await.unwrap();
+        let duty = forget_graphs(1);
+
+        let duty_id = persister.enqueue(&duty).await.unwrap();
+        assert_eq!(duty_id, DutyId::of(&duty).unwrap());
+        assert_eq!(persister.record_attempt(&duty_id).await.unwrap(), 1);
+
+        // re-enqueuing a pending duty keeps its attempts.
+        assert_eq!(persister.enqueue(&duty).await.unwrap(), duty_id);
+        assert_eq!(persister.record_attempt(&duty_id).await.unwrap(), 2);
+
+        let pending = persister.load_pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, duty_id);
+        assert_eq!(DutyId::of(&pending[0].1).unwrap(), duty_id);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn completed_and_cancelled_duties_are_deleted(pool: SqlitePool) {
+        let persister = DutyPersister::new(pool).await.unwrap();
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+
+        let completed = persister.enqueue(&forget_graphs(1)).await.unwrap();
+        persister.save_tx(&completed, &tx).await.unwrap();
+        assert_eq!(persister.load_tx(&completed).await.unwrap(), Some(tx));
+        let cancelled = persister.enqueue(&forget_graphs(2)).await.unwrap();
+
+        persister.complete(&completed).await.unwrap();
+        persister.cancel(&cancelled).await.unwrap();
+
+        assert!(persister.load_pending().await.unwrap().is_empty());
+        assert_eq!(persister.load_tx(&completed).await.unwrap(), None);
+        assert!(persister.load_dead().await.unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn dead_lettered_duties_leave_the_queue(pool: SqlitePool) {
+        let persister = DutyPersister::new(pool).await.unwrap();
+        let duty_id = persister.enqueue(&forget_graphs(1)).await.unwrap();
+        persister.record_attempt(&duty_id).await.unwrap();
+
+        persister.dead_letter(&duty_id, "boom").await.unwrap();
+
+        assert!(persister.is_dead(&duty_id).await.unwrap());
+        assert!(!persister
+            .is_dead(&DutyId::of(&forget_graphs(2)).unwrap())
+            .await
+            .unwrap());
+        assert!(persister.load_pending().await.unwrap().is_empty());
+        assert_eq!(
+            persister.load_dead().await.unwrap(),
+            vec![DeadDuty {
+                duty_id,
+                attempts: 1,
+                reason: "boom".to_string(),
+            }]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn undecodable_duties_are_dead_lettered(pool: SqlitePool) {
+        let persister = DutyPersister::new(pool.clone()).await.unwrap();
+        let valid = persister.enqueue(&forget_graphs(1)).await.unwrap();
+        let garbage = DutyId(sha256::Hash::hash(b"garbage"));
+        let _: SqliteQueryResult = sqlx::query("INSERT INTO duties (duty_id, duty) VALUES (?, ?)")
+            .bind(garbage.to_string())
+            .bind(vec![0xffu8; 4])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let pending = persister.load_pending().await.unwrap();
+        assert_eq!(
+            pending.into_iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![valid]
+        );
+
+        let dead = persister.load_dead().await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].duty_id, garbage);
+    }
+}
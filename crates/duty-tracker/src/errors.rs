@@ -12,7 +12,7 @@ use thiserror::Error;
 
 use crate::{
     contract_persister::ContractPersistErr, contract_state_machine::TransitionErr,
-    tx_driver::DriveErr,
+    duty_persister::DutyPersistErr, tx_driver::DriveErr,
 };
 
 /// Unified error type for everything that can happen in the ContractManager.
@@ -22,6 +22,10 @@ pub enum ContractManagerErr {
     #[error("failed to commit contract state to disk: {0}")]
     ContractPersistErr(#[from] ContractPersistErr),
 
+    /// Errors related to writing the duty queue to disk.
+    #[error("failed to persist duty: {0}")]
+    DutyPersistErr(#[from] DutyPersistErr),
+
     /// Errors related to state machines being unable to process ContractEvents
     #[error("contract state machine received an invalid event: {0}")]
     TransitionErr(#[from] TransitionErr),
@@ -46,6 +50,14 @@ pub enum ContractManagerErr {
     #[error("secret service request failed with {0:?}")]
     SecretServiceErr(#[from] secret_service_proto::v1::traits::ClientError),
 
+    /// Errors from the secret service refusing to produce a WOTS signature.
+    #[error("secret service refused to produce a WOTS signature: {0:?}")]
+    WotsSignErr(#[from] secret_service_proto::v1::traits::WotsSignError),
+
+    /// Errors from the secret service refusing to sign a transaction that violates its policy.
+    #[error("secret service refused to sign a transaction: {0}")]
+    PolicyViolation(#[from] secret_service_proto::v2::traits::PolicyViolation),
+
     /// Errors from the bridge db
     #[error("database error: {0:?}")]
     DbErr(#[from] DbError),
@@ -58,9 +70,33 @@ pub enum ContractManagerErr {
     #[error("error: {0}")]
     FatalErr(#[from] Box<dyn Error + 'static>),
 
+    /// Errors from MuSig2 signing sessions opened with the secret service.
+    #[error("musig2 signing session failed: {0}")]
+    Musig2Err(String),
+
     /// Error from the tx driver while submitting/tracking transaction on chain.
     #[error("failed to submit or track transaction: {0:?}")]
     TxDriverErr(#[from] DriveErr),
+
+    /// Errors from generating the bridge proof, for example because the prover is not configured.
+    #[error("failed to generate bridge proof: {0}")]
+    ProverErr(String),
+}
+
+impl ContractManagerErr {
+    /// Whether the error is bound to happen again if the failed operation is retried, as opposed
+    /// to errors caused by dependencies that are unavailable or lagging behind for now.
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            ContractManagerErr::TransitionErr(_)
+                | ContractManagerErr::TxGraphError(_)
+                | ContractManagerErr::InvalidP2PMessage(_)
+                | ContractManagerErr::WotsSignErr(_)
+                | ContractManagerErr::PolicyViolation(_)
+                | ContractManagerErr::FatalErr(_)
+        )
+    }
 }
 
 /// Error type for problems arising in maintaining or querying stake chain data.
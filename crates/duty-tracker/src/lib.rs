@@ -5,8 +5,10 @@
 pub mod contract_manager;
 pub mod contract_persister;
 pub mod contract_state_machine;
+pub mod duty_persister;
 pub mod errors;
 pub mod predicates;
 pub mod stake_chain_persister;
 pub mod stake_chain_state_machine;
 pub mod tx_driver;
+pub mod tx_driver_persister;
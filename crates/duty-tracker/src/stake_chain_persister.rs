@@ -3,7 +3,12 @@
 use std::collections::BTreeMap;
 
 use bitcoin::{OutPoint, XOnlyPublicKey};
-use strata_bridge_db::{errors::DbError, persistent::sqlite::SqliteDb, public::PublicDb};
+use sqlx::SqliteConnection;
+use strata_bridge_db::{
+    errors::DbError,
+    persistent::{errors::StorageError, sqlite::SqliteDb},
+    public::PublicDb,
+};
 use strata_bridge_primitives::operator_table::OperatorTable;
 use strata_bridge_stake_chain::stake_chain::StakeChainInputs;
 use strata_p2p_types::P2POperatorPubKey;
@@ -35,6 +40,25 @@ impl StakeChainPersister {
         &self,
         cfg: &OperatorTable,
         state: BTreeMap<P2POperatorPubKey, StakeChainInputs>,
+    ) -> Result<(), DbError> {
+        let mut tx = self.db.pool().begin().await.map_err(StorageError::from)?;
+
+        self.commit_stake_data_with(&mut tx, cfg, state).await?;
+
+        tx.commit().await.map_err(StorageError::from)?;
+
+        Ok(())
+    }
+
+    /// Writes the stake chain inputs to the database using the supplied connection.
+    ///
+    /// This is meant to be used with a connection that is in the middle of a transaction so that
+    /// the stake chain inputs are committed atomically with other state.
+    pub async fn commit_stake_data_with(
+        &self,
+        conn: &mut SqliteConnection,
+        cfg: &OperatorTable,
+        state: BTreeMap<P2POperatorPubKey, StakeChainInputs>,
     ) -> Result<(), DbError> {
         let op_id_and_chain_inputs = state.iter().filter_map(|(p2p_key, chain_inputs)| {
             // extract only those with valid operator ids
@@ -51,9 +75,13 @@ impl StakeChainPersister {
                     ?stake_input,
                     "committing stake data to disk"
                 );
-                self.db
-                    .add_stake_data(operator_id, stake_index as u32, stake_input.to_owned())
-                    .await?;
+                SqliteDb::add_stake_data_with(
+                    conn,
+                    operator_id,
+                    stake_index as u32,
+                    stake_input.to_owned(),
+                )
+                .await?;
             }
         }
         Ok(())
@@ -1,110 +1,486 @@
 //! This module implements a system that will accept signed transactions and ensure they are posted
 //! to the blockchain within a reasonable time.
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
-use bitcoin::Transaction;
+use bitcoin::{
+    Address, Amount, BlockHash, FeeRate, Network, OutPoint, TapSighashType, Transaction, TxOut,
+    Txid, Witness,
+};
 use btc_notify::{
-    client::{BtcZmqClient, TxEvent},
+    client::{BtcZmqClient, ChainEvent, TxEvent, TxStatus},
     subscription::Subscription,
 };
-use futures::{channel::oneshot, stream::SelectAll, FutureExt, StreamExt};
-use strata_btcio::rpc::{traits::BroadcasterRpc, BitcoinClient};
+use futures::{
+    stream::{self, SelectAll},
+    FutureExt, StreamExt,
+};
+use operator_wallet::{
+    fee_estimator::{FeeOracle, FeeUrgency},
+    OperatorWallet,
+};
+use secret_service_client::SecretServiceClient;
+use secret_service_proto::{
+    v1::traits::*,
+    v2::traits::{SigningRequest, SpendPath, TxSigner},
+};
+use strata_bridge_connectors::prelude::ConnectorCpfp;
+use strata_bridge_primitives::types::BitcoinBlockHeight;
+use strata_bridge_tx_graph::transactions::prelude::{Cpfp, CpfpInput};
+use strata_btcio::rpc::{
+    error::ClientError,
+    traits::{BroadcasterRpc, ReaderRpc},
+    BitcoinClient,
+};
 use thiserror::Error;
 use tokio::{
     select,
-    sync::mpsc::{unbounded_channel, UnboundedSender},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedSender},
+        RwLock,
+    },
     task::JoinHandle,
 };
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::{wrappers::UnboundedReceiverStream, StreamMap};
+use tracing::{debug, error, info, warn};
+
+use crate::tx_driver_persister::{PersistedTxJob, TxDriverPersister};
+
+/// The number of blocks before the deadline of a transaction at which the TxDriver starts bumping
+/// its fees if it still hasn't been mined.
+const CPFP_ESCALATION_WINDOW: BitcoinBlockHeight = 6;
+
+/// The factor by which the fee rate of a CPFP child is multiplied every time it is re-bumped.
+const CPFP_FEE_RATE_MULTIPLIER: u64 = 2;
+
+/// The error code returned by bitcoin core when a transaction is rejected by its mempool policy.
+const RPC_VERIFY_REJECTED: i32 = -26;
+
+/// The error code returned by bitcoin core when a transaction fails consensus validation, e.g. if
+/// its inputs are missing or already spent.
+const RPC_VERIFY_ERROR: i32 = -25;
+
+/// The error code returned by bitcoin core when a transaction is already in the chain.
+const RPC_VERIFY_ALREADY_IN_CHAIN: i32 = -27;
+
+/// The error code returned by bitcoin core for RPC methods that it does not know of.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+
+/// The reasons given by bitcoin core for rejecting a transaction that does not pay enough fees,
+/// either to enter its mempool or to replace the transactions it conflicts with.
+const INSUFFICIENT_FEE_REASONS: [&str; 3] = [
+    "min relay fee not met",
+    "mempool min fee not met",
+    "insufficient fee",
+];
 
 /// Error type for the TxDriver.
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum DriveErr {
     /// Indicates that the TxDriver has been dropped and no more events should be expected.
     #[error("tx driver has been aborted, no more events should be expected")]
     DriverAborted,
+
+    /// Indicates that a CPFP child could not be built for the transaction.
+    #[error("could not fee bump tx {0}: {1}")]
+    FeeBumpFailed(Txid, String),
+
+    /// Indicates that one or more of the inputs of the transaction have been spent by another
+    /// transaction so it can never be confirmed.
+    #[error("inputs of the tx have been spent by {conflicting_txid}")]
+    InputsSpent {
+        /// The mined transaction that spends the inputs.
+        conflicting_txid: Txid,
+    },
+
+    /// Indicates that there is no active job for the transaction to attach to.
+    #[error("no active job for tx {0}")]
+    UnknownJob(Txid),
+}
+
+/// The reasons for which bitcoin core may refuse to broadcast a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastFailure {
+    /// One or more of the inputs of the transaction are spent by another transaction in the
+    /// mempool.
+    InputsSpent,
+
+    /// One or more of the inputs of the transaction are not in the UTXO set.
+    ///
+    /// The parent of an input may simply not have been broadcasted yet but the input may also have
+    /// been spent by a transaction in the chain. Bitcoin core reports the same for a transaction
+    /// that is already in the chain if all of its outputs have been spent.
+    MissingInputs,
+
+    /// The transaction does not pay enough fees to enter the mempool or to replace a conflicting
+    /// transaction.
+    InsufficientFee,
+
+    /// The transaction is already in the mempool.
+    AlreadyInMempool,
+
+    /// The transaction is already in the chain.
+    AlreadyInChain,
+
+    /// The transaction does not pay enough fees on its own and the node does not support package
+    /// relay so it cannot be submitted along with its CPFP child.
+    PackageRelayUnsupported,
+
+    /// Any other failure, along with the error reported by bitcoin core.
+    Other(String),
+}
+
+impl BroadcastFailure {
+    /// Classifies the error returned by bitcoin core when broadcasting a transaction.
+    pub fn classify(err: &ClientError) -> Self {
+        let ClientError::Server(code, msg) = err else {
+            return BroadcastFailure::Other(err.to_string());
+        };
+
+        match *code {
+            RPC_VERIFY_ALREADY_IN_CHAIN => BroadcastFailure::AlreadyInChain,
+            _ if msg.contains("txn-already-in-mempool") || msg.contains("txn-already-known") => {
+                BroadcastFailure::AlreadyInMempool
+            }
+            RPC_VERIFY_REJECTED if msg.contains("txn-mempool-conflict") => {
+                BroadcastFailure::InputsSpent
+            }
+            // the reason is followed by the details of the rejection, e.g.
+            // "mempool min fee not met, 1000 < 1500".
+            RPC_VERIFY_REJECTED
+                if INSUFFICIENT_FEE_REASONS
+                    .iter()
+                    .any(|reason| msg.starts_with(reason)) =>
+            {
+                BroadcastFailure::InsufficientFee
+            }
+            RPC_VERIFY_ERROR
+                if msg.contains("bad-txns-inputs-missingorspent")
+                    || msg.contains("missing-inputs") =>
+            {
+                BroadcastFailure::MissingInputs
+            }
+            _ => BroadcastFailure::Other(format!("{code}: {msg}")),
+        }
+    }
+}
+
+/// The point at which the TxDriver considers a transaction final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FinalityPolicy {
+    /// The transaction is final as soon as it is included in a block.
+    Inclusion,
+
+    /// The transaction is final once it has the given number of confirmations.
+    ///
+    /// A transaction is always considered final once the [`BtcZmqClient`] reports it as buried so
+    /// depths beyond its bury depth are never waited for.
+    Depth(BitcoinBlockHeight),
+
+    /// The transaction is final once the [`BtcZmqClient`] reports it as buried.
+    #[default]
+    Buried,
+}
+
+impl FinalityPolicy {
+    /// The number of confirmations after which the transaction is final or `None` if it is only
+    /// final once it is buried.
+    pub(crate) fn required_confirmations(&self) -> Option<BitcoinBlockHeight> {
+        match self {
+            FinalityPolicy::Inclusion => Some(1),
+            FinalityPolicy::Depth(depth) => Some((*depth).max(1)),
+            FinalityPolicy::Buried => None,
+        }
+    }
+
+    /// Whether a transaction with the given number of confirmations is final.
+    ///
+    /// Transactions that are only final once buried are never final by their confirmations alone.
+    pub(crate) fn is_final(&self, confirmations: BitcoinBlockHeight) -> bool {
+        self.required_confirmations()
+            .is_some_and(|required| confirmations >= required)
+    }
+
+    /// The inverse of [`FinalityPolicy::required_confirmations`].
+    pub(crate) fn from_required_confirmations(confirmations: Option<BitcoinBlockHeight>) -> Self {
+        match confirmations {
+            None => FinalityPolicy::Buried,
+            Some(0 | 1) => FinalityPolicy::Inclusion,
+            Some(depth) => FinalityPolicy::Depth(depth),
+        }
+    }
+}
+
+/// The options that control how long the TxDriver keeps driving a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DriveOptions {
+    /// The point at which the transaction is reported as final.
+    pub finality: FinalityPolicy,
+
+    /// Whether to keep watching the transaction after it is final until it is buried.
+    ///
+    /// While the transaction is being watched, it is rebroadcasted if a reorg takes it out of the
+    /// chain and it is reported as final again once it satisfies the [`FinalityPolicy`] in the new
+    /// chain.
+    pub watch_until_final: bool,
+}
+
+/// The updates on the status of a transaction that is being driven.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriveUpdate {
+    /// The transaction has entered the mempool.
+    Mempool,
+
+    /// The transaction has been included in a block.
+    ///
+    /// This is emitted once for every new block that confirms the transaction until it is final.
+    Mined {
+        /// The hash of the block that includes the transaction.
+        blockhash: BlockHash,
+
+        /// The height of the block that includes the transaction.
+        height: BitcoinBlockHeight,
+
+        /// The number of blocks, including the one that includes the transaction, on top of
+        /// which the transaction has been confirmed.
+        confirmations: BitcoinBlockHeight,
+    },
+
+    /// The transaction has been taken out of the chain by a reorg and has been rebroadcasted.
+    Reorged,
+
+    /// The transaction satisfies the [`FinalityPolicy`] of its job.
+    ///
+    /// Unless the transaction is watched until it is buried, this is the last update.
+    Final,
+}
+
+/// The stream of updates on the status of a transaction that is being driven.
+///
+/// The stream ends once the job driving the transaction is resolved, either right after a
+/// [`DriveUpdate::Final`] or an error or, for jobs that watch their transaction until it is
+/// final, once the transaction is buried.
+pub type DriveUpdates = UnboundedReceiverStream<Result<DriveUpdate, DriveErr>>;
+
+/// The sending half of [`DriveUpdates`].
+type DriveUpdatesSender = UnboundedSender<Result<DriveUpdate, DriveErr>>;
+
+/// The output of a transaction that can be spent by a CPFP child to bump its fees.
+#[derive(Debug, Clone, Copy)]
+pub struct CpfpAnchor {
+    /// The total amount of the inputs of the parent transaction.
+    ///
+    /// This is used to compute the fees already paid by the parent transaction.
+    pub parent_input_amount: Amount,
+
+    /// The index of the CPFP output in the parent transaction.
+    pub vout: u32,
+
+    /// The connector that locks the CPFP output.
+    pub connector: ConnectorCpfp,
+}
+
+/// The latest CPFP child broadcasted for a transaction.
+#[derive(Debug)]
+pub(crate) struct CpfpBump {
+    /// The UTXO that funds the child.
+    ///
+    /// Subsequent bumps reuse the same UTXO so that they replace the previous child.
+    pub(crate) funding_outpoint: OutPoint,
+
+    /// The output being spent by `funding_outpoint`.
+    pub(crate) funding_prevout: TxOut,
+
+    /// The fee rate paid by the package.
+    pub(crate) fee_rate: FeeRate,
+
+    /// The signed child transaction.
+    pub(crate) child_tx: Transaction,
 }
 
 struct TxDriveJob {
     tx: Transaction,
-    _deadline: usize,
-    respond_on: oneshot::Sender<Result<(), DriveErr>>,
+    deadline: Option<BitcoinBlockHeight>,
+    anchor: Option<CpfpAnchor>,
+    options: DriveOptions,
+    updates: DriveUpdatesSender,
+}
+
+/// The requests that can be sent to the task that drives the jobs.
+enum DriverRequest {
+    /// Drives a new transaction, or attaches to the job already driving it.
+    Drive(TxDriveJob),
+
+    /// Attaches to the job driving the transaction with the given txid.
+    Attach {
+        txid: Txid,
+        updates: DriveUpdatesSender,
+    },
+}
+
+/// The bookkeeping of a [`TxDriveJob`] while it is being driven.
+struct ActiveJob {
+    tx: Transaction,
+    deadline: Option<BitcoinBlockHeight>,
+    anchor: Option<CpfpAnchor>,
+    options: DriveOptions,
+
+    /// The callers following the updates on the job.
+    ///
+    /// This is empty for jobs that were resumed after a restart until a caller re-attaches to
+    /// them.
+    subscribers: Vec<DriveUpdatesSender>,
+
+    /// The hash and height of the block that includes the transaction, if any.
+    mined_in: Option<(BlockHash, BitcoinBlockHeight)>,
+
+    /// Whether the transaction has been reported as final.
+    ///
+    /// This is only ever set for jobs that watch their transaction until it is buried.
+    finalized: bool,
+    bump: Option<CpfpBump>,
+
+    /// A transaction in the mempool that spends some of the inputs of the job's transaction.
+    conflicting_txid: Option<Txid>,
+}
+
+/// The handles the TxDriver uses to build and sign CPFP children.
+struct CpfpFunder {
+    network: Network,
+    wallet: Arc<RwLock<OperatorWallet>>,
+    s2_client: SecretServiceClient,
+    fee_oracle: FeeOracle,
 }
+
+/// The state of the task that drives all the jobs submitted to the TxDriver.
+struct DriverState {
+    rpc_client: BitcoinClient,
+    funder: CpfpFunder,
+    persister: TxDriverPersister,
+    active_jobs: BTreeMap<Txid, ActiveJob>,
+
+    /// The height of the latest block that has been observed.
+    tip_height: BitcoinBlockHeight,
+}
+
 /// System for driving a signed transaction to confirmation.
 #[derive(Debug)]
 pub struct TxDriver {
-    new_jobs_sender: UnboundedSender<TxDriveJob>,
+    new_jobs_sender: UnboundedSender<DriverRequest>,
     driver: JoinHandle<()>,
 }
 impl TxDriver {
     /// Initializes the TxDriver.
-    pub async fn new(zmq_client: BtcZmqClient, rpc_client: BitcoinClient) -> Self {
-        let new_jobs = unbounded_channel::<TxDriveJob>();
+    ///
+    /// The `wallet` and the `s2_client` are used to fund and sign the CPFP children of the
+    /// transactions that are driven with [`TxDriver::drive_with_cpfp`] while the `fee_oracle`
+    /// decides the fee rates that they pay.
+    ///
+    /// The jobs that were persisted before the last shutdown are resumed right away. Callers can
+    /// re-attach to them with [`TxDriver::attach`].
+    pub async fn new(
+        zmq_client: BtcZmqClient,
+        rpc_client: BitcoinClient,
+        network: Network,
+        wallet: Arc<RwLock<OperatorWallet>>,
+        s2_client: SecretServiceClient,
+        fee_oracle: FeeOracle,
+        persister: TxDriverPersister,
+    ) -> Self {
+        let new_jobs = unbounded_channel::<DriverRequest>();
         let new_jobs_sender = new_jobs.0;
-        let mut block_subscription = zmq_client.subscribe_blocks().await;
+        let mut chain_subscription = zmq_client.subscribe_chain_events().await;
+        let mut state = DriverState {
+            rpc_client,
+            funder: CpfpFunder {
+                network,
+                wallet,
+                s2_client,
+                fee_oracle,
+            },
+            persister,
+            active_jobs: BTreeMap::new(),
+            tip_height: 0,
+        };
 
         let driver = tokio::task::spawn(async move {
             let mut new_jobs_receiver_stream = UnboundedReceiverStream::new(new_jobs.1);
-            let mut active_tx_subs = SelectAll::<Subscription<TxEvent>>::new();
-            let mut active_jobs = BTreeMap::new();
+            // The subscriptions are keyed by the txid of their job so that they can be dropped, and
+            // released by the zmq client, once the job is resolved.
+            let mut active_tx_subs = StreamMap::<Txid, Subscription<TxEvent>>::new();
+            let mut conflict_subs = StreamMap::<Txid, SelectAll<Subscription<TxEvent>>>::new();
+
+            match state.persister.load_jobs(state.funder.network).await {
+                Ok(persisted_jobs) => {
+                    for persisted_job in persisted_jobs {
+                        let txid = persisted_job.tx.compute_txid();
+                        info!(%txid, "resuming tx drive job");
+                        let (tx_sub, conflict_sub) =
+                            subscribe_job(&zmq_client, &persisted_job.tx).await;
+                        active_tx_subs.insert(txid, tx_sub);
+                        conflict_subs.insert(txid, conflict_sub);
+
+                        state.resume_job(persisted_job).await;
+                    }
+                }
+                Err(e) => error!(%e, "could not load tx drive jobs, they will not be resumed"),
+            }
+
             loop {
+                let resolved = active_tx_subs
+                    .keys()
+                    .chain(conflict_subs.keys())
+                    .filter(|txid| !state.active_jobs.contains_key(txid))
+                    .copied()
+                    .collect::<BTreeSet<_>>();
+                for txid in resolved {
+                    active_tx_subs.remove(&txid);
+                    conflict_subs.remove(&txid);
+                }
+
                 select! {
-                    Some(job) = new_jobs_receiver_stream.next().fuse() => {
-                        let rawtx_filter = job.tx.clone();
-                        let rawtx_rpc_client = job.tx.clone();
-                        let txid = job.tx.compute_txid();
-                        let tx_sub = zmq_client.subscribe_transactions(
-                            move |tx| tx == &rawtx_filter
-                        ).await;
-                        active_tx_subs.push(tx_sub);
-                        active_jobs.insert(txid, job);
-                        match rpc_client.send_raw_transaction(&rawtx_rpc_client).await {
-                            Ok(_txid) => { /* NOOP, we good fam */ }
-                            Err(_err) => {
-                                // TODO(proofofkeags): in this case we may have not hit the mempool
-                                // purge rate and then we have to probably CPFP using anchor from
-                                // the getgo and try again via submit package.
-                            }
-                        }
-                    }
-                    Some(event) = active_tx_subs.next().fuse() => {
-                        match event.status {
-                            btc_notify::client::TxStatus::Unknown => {
-                                // Transaction has been evicted, resubmit and see what happens
-                                match rpc_client.send_raw_transaction(&event.rawtx).await {
-                                    Ok(_txid) => { /* NOOP, we good fam */ }
-                                    Err(_err) => {
-                                        // TODO(proofofkeags): in this case we need to analyze the
-                                        // reported error. There are a few scenarios that can play
-                                        // out here.
-                                        //
-                                        // 1. It failed because one or more of the inputs is double
-                                        // spent.
-                                        // 2. It failed because the fee didn't exceed the purge
-                                        // rate.
-                                        // 3. If failed because the transaction has already
-                                        // re-entered the mempool automatically upon reorg.
-                                    }
+                    Some(request) = new_jobs_receiver_stream.next().fuse() => {
+                        match request {
+                            DriverRequest::Drive(job) => {
+                                let txid = job.tx.compute_txid();
+                                if state.active_jobs.contains_key(&txid) {
+                                    debug!(%txid, "tx is already being driven, attaching to its job");
+                                    state.attach(txid, job.updates);
+                                    continue;
                                 }
+
+                                let (tx_sub, conflict_sub) = subscribe_job(&zmq_client, &job.tx).await;
+                                active_tx_subs.insert(txid, tx_sub);
+                                conflict_subs.insert(txid, conflict_sub);
+
+                                state.start_job(job).await;
                             }
-                            btc_notify::client::TxStatus::Buried { .. } => {
-                                // Since our responsibility ends at block inclusion we will send an
-                                // answer on the response channel now. It is the API caller's
-                                // responsibility for handling reorgs after inclusion.
-                                match active_jobs.remove(&event.rawtx.compute_txid()) {
-                                    Some(job) => {
-                                        let _ = job.respond_on.send(Ok(()));
-                                    }
-                                    None => {
-                                        debug_assert!(false, "invariant violated: no job record");
-                                    }
-                                }
+                            DriverRequest::Attach { txid, updates } => {
+                                state.attach(txid, updates);
                             }
-                            _ => {}
                         }
                     }
-                    _block = block_subscription.next().fuse() => {
-                        // TODO(proofofkeags): Compare against deadlines and CPFP using anchor.
+                    Some((_, event)) = active_tx_subs.next().fuse() => {
+                        state.process_tx_event(event).await;
+                    }
+                    Some((_, event)) = conflict_subs.next().fuse() => {
+                        state.process_conflict_event(event).await;
+                    }
+                    Some(event) = chain_subscription.next().fuse() => match event {
+                        ChainEvent::Connected(block) => {
+                            let Ok(height) = block.bip34_block_height() else {
+                                warn!(blockhash=%block.block_hash(), "could not get block height, skipping fee bumps");
+                                continue;
+                            };
+
+                            state.process_block(height).await;
+                        }
+                        ChainEvent::Disconnected { hash, height, reorg_depth } => {
+                            debug!(blockhash=%hash, %height, %reorg_depth, "block disconnected");
+                            state.process_block_disconnect(height);
+                        }
                     }
                 }
             }
@@ -116,20 +492,87 @@ impl TxDriver {
         }
     }
 
-    /// Instructs the TxDriver to drive a new transaction to confirmation by the supplied deadline.
-    pub async fn drive(&self, tx: Transaction, _deadline: usize) -> Result<(), DriveErr> {
-        let (sender, receiver) = oneshot::channel();
+    /// Instructs the TxDriver to drive a new transaction to confirmation by the supplied deadline,
+    /// if any.
+    ///
+    /// This resolves once the transaction satisfies the given [`FinalityPolicy`].
+    pub async fn drive(
+        &self,
+        tx: Transaction,
+        deadline: Option<BitcoinBlockHeight>,
+        finality: FinalityPolicy,
+    ) -> Result<(), DriveErr> {
+        let options = DriveOptions {
+            finality,
+            watch_until_final: false,
+        };
+        wait_for_finality(self.drive_with_updates(tx, deadline, None, options)?).await
+    }
+
+    /// Instructs the TxDriver to drive a new transaction to confirmation by the supplied deadline,
+    /// paying its fees with a CPFP child that spends the given anchor.
+    ///
+    /// The child is re-bumped with increasing fee rates in every block that falls within
+    /// [`CPFP_ESCALATION_WINDOW`] blocks of the deadline until the transaction is mined. Without
+    /// a deadline, the child is only re-bumped if it is rejected for paying too little. This
+    /// resolves once the transaction satisfies the given [`FinalityPolicy`].
+    pub async fn drive_with_cpfp(
+        &self,
+        tx: Transaction,
+        anchor: CpfpAnchor,
+        deadline: Option<BitcoinBlockHeight>,
+        finality: FinalityPolicy,
+    ) -> Result<(), DriveErr> {
+        let options = DriveOptions {
+            finality,
+            watch_until_final: false,
+        };
+        wait_for_finality(self.drive_with_updates(tx, deadline, Some(anchor), options)?).await
+    }
+
+    /// Instructs the TxDriver to drive a new transaction to confirmation by the supplied deadline
+    /// and returns the stream of updates on its status.
+    ///
+    /// If an anchor is supplied, the fees of the transaction are paid with a CPFP child that
+    /// spends it as in [`TxDriver::drive_with_cpfp`].
+    ///
+    /// If the transaction is already being driven, the returned stream follows the existing job
+    /// and the supplied deadline, anchor and options are ignored.
+    pub fn drive_with_updates(
+        &self,
+        tx: Transaction,
+        deadline: Option<BitcoinBlockHeight>,
+        anchor: Option<CpfpAnchor>,
+        options: DriveOptions,
+    ) -> Result<DriveUpdates, DriveErr> {
+        let (sender, receiver) = unbounded_channel();
         self.new_jobs_sender
-            .send(TxDriveJob {
+            .send(DriverRequest::Drive(TxDriveJob {
                 tx,
-                _deadline,
-                respond_on: sender,
+                deadline,
+                anchor,
+                options,
+                updates: sender,
+            }))
+            .map_err(|_| DriveErr::DriverAborted)?;
+
+        Ok(UnboundedReceiverStream::new(receiver))
+    }
+
+    /// Waits for the job driving the transaction with the given txid to be resolved.
+    ///
+    /// This is meant to re-attach to the jobs that were resumed after a restart. It fails with
+    /// [`DriveErr::UnknownJob`] if no such job is active.
+    pub async fn attach(&self, txid: Txid) -> Result<(), DriveErr> {
+        let (sender, receiver) = unbounded_channel();
+        self.new_jobs_sender
+            .send(DriverRequest::Attach {
+                txid,
+                updates: sender,
             })
             .map_err(|_| DriveErr::DriverAborted)?;
-        receiver
-            .await
-            .map_err(|_| DriveErr::DriverAborted)
-            .flatten()
+
+        wait_for_finality(UnboundedReceiverStream::new(receiver)).await
     }
 }
 impl Drop for TxDriver {
@@ -137,3 +580,949 @@ impl Drop for TxDriver {
         self.driver.abort();
     }
 }
+
+impl DriverState {
+    /// Starts driving a new job.
+    async fn start_job(&mut self, job: TxDriveJob) {
+        let txid = job.tx.compute_txid();
+
+        // the first child is built before anything is broadcasted so that a job that cannot be
+        // fee bumped is rejected right away.
+        let bump = match job.anchor {
+            Some(anchor) => {
+                let reserved = self.reserved_funding_outpoints();
+                let fee_rate = self
+                    .funder
+                    .fee_oracle
+                    .fee_rate(FeeUrgency::Background)
+                    .await;
+                match self
+                    .funder
+                    .bump(&job.tx, anchor, None, fee_rate, &reserved)
+                    .await
+                {
+                    Ok(bump) => Some(bump),
+                    Err(e) => {
+                        let _ = job.updates.send(Err(e));
+                        return;
+                    }
+                }
+            }
+            None => None,
+        };
+
+        if let Err(e) = self
+            .persister
+            .save_job(&job.tx, job.deadline, job.anchor.as_ref(), job.options)
+            .await
+        {
+            error!(%txid, %e, "could not persist tx drive job, it will not be resumed on restart");
+        }
+        if let Some(bump) = &bump {
+            if let Err(e) = self.persister.save_bump(txid, bump).await {
+                error!(%txid, %e, "could not persist fee bump");
+            }
+        }
+
+        self.active_jobs.insert(
+            txid,
+            ActiveJob {
+                tx: job.tx,
+                deadline: job.deadline,
+                anchor: job.anchor,
+                options: job.options,
+                subscribers: vec![job.updates],
+                mined_in: None,
+                finalized: false,
+                bump,
+                conflicting_txid: None,
+            },
+        );
+        self.broadcast(txid).await;
+    }
+
+    /// Resumes a job that was persisted before the last shutdown and rebroadcasts its transaction
+    /// in case it is in neither the mempool nor the chain anymore.
+    async fn resume_job(&mut self, persisted_job: PersistedTxJob) {
+        let txid = persisted_job.tx.compute_txid();
+        self.active_jobs.insert(
+            txid,
+            ActiveJob {
+                tx: persisted_job.tx,
+                deadline: persisted_job.deadline,
+                anchor: persisted_job.anchor,
+                options: persisted_job.options,
+                subscribers: Vec::new(),
+                mined_in: None,
+                finalized: false,
+                bump: persisted_job.bump,
+                conflicting_txid: None,
+            },
+        );
+        self.broadcast(txid).await;
+    }
+
+    /// Registers a caller to follow the updates on the job driving the transaction.
+    fn attach(&mut self, txid: Txid, updates: DriveUpdatesSender) {
+        match self.active_jobs.get_mut(&txid) {
+            Some(active_job) => {
+                if active_job.finalized {
+                    let _ = updates.send(Ok(DriveUpdate::Final));
+                }
+                active_job.subscribers.push(updates);
+            }
+            None => {
+                let _ = updates.send(Err(DriveErr::UnknownJob(txid)));
+            }
+        }
+    }
+
+    /// Reacts to a change in the status of the transaction of an active job.
+    async fn process_tx_event(&mut self, event: TxEvent) {
+        let txid = event.rawtx.compute_txid();
+        let Some(active_job) = self.active_jobs.get_mut(&txid) else {
+            // the job may have been resolved before its transaction was buried, for example, if
+            // it was already in the chain when it was submitted.
+            debug!(%txid, status=?event.status, "ignoring event for a resolved job");
+            return;
+        };
+
+        match event.status {
+            TxStatus::Unknown => {
+                if active_job.mined_in.take().is_some() {
+                    warn!(%txid, "tx has been reorged out of the chain");
+                    active_job.finalized = false;
+                    active_job.notify(DriveUpdate::Reorged);
+                }
+
+                // Transaction has been evicted, resubmit and see what happens
+                self.broadcast(txid).await;
+            }
+            TxStatus::Mempool => {
+                if active_job.mined_in.take().is_some() {
+                    // the transaction re-enters the mempool after a reorg but its CPFP child may
+                    // not so the whole package is rebroadcasted.
+                    warn!(%txid, "tx has been reorged out of the chain");
+                    active_job.finalized = false;
+                    active_job.notify(DriveUpdate::Reorged);
+                    self.broadcast(txid).await;
+                } else {
+                    active_job.notify(DriveUpdate::Mempool);
+                }
+            }
+            TxStatus::Mined { blockhash, height } => {
+                active_job.mined_in = Some((blockhash, height));
+                self.check_finality(txid).await;
+            }
+            TxStatus::Buried { .. } => {
+                // reorgs are not expected past the bury depth so there is nothing left to watch.
+                self.resolve(txid, Ok(())).await;
+            }
+        }
+    }
+
+    /// Reports the confirmations of the transaction of the job and reports it as final if it
+    /// satisfies the [`FinalityPolicy`] of the job.
+    ///
+    /// Jobs that do not watch their transaction until it is buried are resolved right away once it
+    /// is final.
+    async fn check_finality(&mut self, txid: Txid) {
+        let tip_height = self.tip_height;
+        let Some(active_job) = self.active_jobs.get_mut(&txid) else {
+            return;
+        };
+        let Some((blockhash, height)) = active_job.mined_in else {
+            return;
+        };
+        if active_job.finalized {
+            return;
+        }
+
+        let confirmations = tip_height.max(height) - height + 1;
+        active_job.notify(DriveUpdate::Mined {
+            blockhash,
+            height,
+            confirmations,
+        });
+
+        if !active_job.options.finality.is_final(confirmations) {
+            return;
+        }
+
+        info!(%txid, %confirmations, "tx is final");
+        if active_job.options.watch_until_final {
+            active_job.finalized = true;
+            active_job.notify(DriveUpdate::Final);
+        } else {
+            self.resolve(txid, Ok(())).await;
+        }
+    }
+
+    /// Reacts to a change in the status of a transaction that spends some of the inputs of one or
+    /// more active jobs.
+    async fn process_conflict_event(&mut self, event: TxEvent) {
+        let conflicting_txid = event.rawtx.compute_txid();
+        let spent = event
+            .rawtx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<BTreeSet<_>>();
+        let conflicted_jobs = self
+            .active_jobs
+            .iter()
+            .filter(|(txid, active_job)| {
+                **txid != conflicting_txid
+                    && active_job
+                        .tx
+                        .input
+                        .iter()
+                        .any(|txin| spent.contains(&txin.previous_output))
+            })
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+
+        for txid in conflicted_jobs {
+            match event.status {
+                TxStatus::Mempool => {
+                    warn!(%txid, %conflicting_txid, "conflicting tx entered the mempool");
+                    if let Some(active_job) = self.active_jobs.get_mut(&txid) {
+                        active_job.conflicting_txid = Some(conflicting_txid);
+                    }
+                }
+                TxStatus::Unknown => {
+                    if let Some(active_job) = self.active_jobs.get_mut(&txid) {
+                        if active_job.conflicting_txid == Some(conflicting_txid) {
+                            active_job.conflicting_txid = None;
+                        }
+                    }
+                }
+                TxStatus::Mined { .. } | TxStatus::Buried { .. } => {
+                    error!(%txid, %conflicting_txid, "inputs of tx have been spent by a mined tx");
+                    self.resolve(txid, Err(DriveErr::InputsSpent { conflicting_txid }))
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Bumps the fees of all the jobs that haven't been mined yet and whose deadline is
+    /// approaching and rebroadcasts the transactions of the other jobs that haven't been mined.
+    ///
+    /// The transactions of the jobs that have already been mined get one more confirmation.
+    async fn process_block(&mut self, height: BitcoinBlockHeight) {
+        self.tip_height = height;
+
+        let due = self
+            .active_jobs
+            .iter()
+            .filter(|(_, active_job)| {
+                active_job.anchor.is_some()
+                    && active_job.mined_in.is_none()
+                    && escalation_due(height, active_job.deadline)
+            })
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+
+        for txid in &due {
+            info!(%txid, %height, "fee bumping tx as its deadline approaches");
+            self.escalate(*txid).await;
+        }
+
+        // the transactions that were rejected may be accepted now, e.g., because their timelocks
+        // expired, their parents got in or the minimum fee rate of the mempool dropped.
+        let unconfirmed = self
+            .active_jobs
+            .iter()
+            .filter(|(txid, active_job)| active_job.mined_in.is_none() && !due.contains(txid))
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+
+        for txid in unconfirmed {
+            self.broadcast(txid).await;
+        }
+
+        let mined = self
+            .active_jobs
+            .iter()
+            .filter(|(_, active_job)| active_job.mined_in.is_some())
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<_>>();
+
+        for txid in mined {
+            self.check_finality(txid).await;
+        }
+    }
+
+    /// Moves the tip back below the disconnected block.
+    ///
+    /// The jobs whose transactions were mined in the disconnected block are reset by the
+    /// [`TxStatus`] updates that follow.
+    fn process_block_disconnect(&mut self, height: BitcoinBlockHeight) {
+        self.tip_height = height.saturating_sub(1);
+    }
+
+    /// Broadcasts the transaction of the job along with its CPFP child, if any, and reacts to the
+    /// failure if it is rejected.
+    async fn broadcast(&mut self, txid: Txid) {
+        let Some(active_job) = self.active_jobs.get_mut(&txid) else {
+            return;
+        };
+        let Err(failure) = submit(&self.rpc_client, &active_job.tx, active_job.bump.as_ref()).await
+        else {
+            return;
+        };
+
+        warn!(%txid, ?failure, "could not broadcast tx");
+        match failure {
+            BroadcastFailure::AlreadyInMempool => {}
+            BroadcastFailure::AlreadyInChain => self.resolve(txid, Ok(())).await,
+            BroadcastFailure::InputsSpent => {
+                // the conflicting transaction may still be evicted or replaced. The job only fails
+                // once a conflicting transaction is mined.
+                let conflicting_txid = active_job.conflicting_txid;
+                warn!(%txid, ?conflicting_txid, "inputs of tx are spent by a tx in the mempool");
+            }
+            BroadcastFailure::MissingInputs => {
+                if is_in_chain(&self.rpc_client, txid).await {
+                    info!(%txid, "tx is already in the chain");
+                    self.resolve(txid, Ok(())).await;
+                }
+                // otherwise, the parents may not have been broadcasted yet. The job only fails once
+                // a conflicting transaction is mined.
+            }
+            BroadcastFailure::InsufficientFee => self.escalate(txid).await,
+            BroadcastFailure::PackageRelayUnsupported => {
+                // bumping the child cannot help as long as the parent does not get in on its own.
+                error!(%txid, "bitcoin node does not support package relay, waiting for the mempool minimum fee rate to drop below the one of the tx");
+            }
+            BroadcastFailure::Other(_) => {
+                // the transaction is rebroadcasted with every new block, e.g., until its timelocks
+                // expire.
+            }
+        }
+    }
+
+    /// Replaces the CPFP child of the job with one that pays a higher fee rate and broadcasts it.
+    ///
+    /// The new fee rate is the highest between the deadline-critical estimate and a multiple of the
+    /// previous one, capped by the maximum fee rate of the [`FeeOracle`].
+    async fn escalate(&mut self, txid: Txid) {
+        let reserved = self.reserved_funding_outpoints();
+        let estimate = self
+            .funder
+            .fee_oracle
+            .fee_rate(FeeUrgency::DeadlineCritical)
+            .await;
+        let Some(active_job) = self.active_jobs.get_mut(&txid) else {
+            return;
+        };
+        let Some(anchor) = active_job.anchor else {
+            warn!(%txid, "tx has no cpfp anchor, cannot bump its fees");
+            return;
+        };
+
+        let fee_rate = escalated_fee_rate(
+            active_job.bump.as_ref().map(|bump| bump.fee_rate),
+            estimate,
+            self.funder.fee_oracle.max_fee_rate(),
+        );
+
+        if active_job
+            .bump
+            .as_ref()
+            .is_none_or(|bump| bump.fee_rate < fee_rate)
+        {
+            info!(%txid, deadline=?active_job.deadline, %fee_rate, "fee bumping tx");
+            match self
+                .funder
+                .bump(
+                    &active_job.tx,
+                    anchor,
+                    active_job.bump.as_ref(),
+                    fee_rate,
+                    &reserved,
+                )
+                .await
+            {
+                Ok(bump) => {
+                    if let Err(e) = self.persister.save_bump(txid, &bump).await {
+                        error!(%txid, %e, "could not persist fee bump");
+                    }
+                    active_job.bump = Some(bump);
+                }
+                Err(e) => {
+                    error!(%txid, %e, "could not fee bump tx, will retry in the next block");
+                    return;
+                }
+            }
+        }
+
+        // if we are already paying the maximum fee rate, this just makes sure that the package is
+        // still out there.
+        if let Err(failure) =
+            submit(&self.rpc_client, &active_job.tx, active_job.bump.as_ref()).await
+        {
+            warn!(%txid, ?failure, "could not broadcast fee bumped tx");
+        }
+    }
+
+    /// Removes the job and sends the final update to the callers that are following it.
+    ///
+    /// A successful result is reported as [`DriveUpdate::Final`] unless the transaction has already
+    /// been reported as final.
+    async fn resolve(&mut self, txid: Txid, result: Result<(), DriveErr>) {
+        let Some(active_job) = self.active_jobs.remove(&txid) else {
+            return;
+        };
+        if let Err(e) = self.persister.delete_job(txid).await {
+            error!(%txid, %e, "could not delete persisted tx drive job");
+        }
+
+        let update = match result {
+            Ok(()) if active_job.finalized => return,
+            Ok(()) => Ok(DriveUpdate::Final),
+            Err(e) => Err(e),
+        };
+        for subscriber in active_job.subscribers {
+            let _ = subscriber.send(update.clone());
+        }
+    }
+
+    /// Collects the UTXOs that fund the CPFP children of the active jobs so that they are not used
+    /// to fund another one.
+    fn reserved_funding_outpoints(&self) -> BTreeSet<OutPoint> {
+        self.active_jobs
+            .values()
+            .filter_map(|active_job| active_job.bump.as_ref())
+            .map(|bump| bump.funding_outpoint)
+            .collect()
+    }
+}
+
+impl ActiveJob {
+    /// Sends the update to the callers that are following the job and forgets the ones that
+    /// stopped listening.
+    fn notify(&mut self, update: DriveUpdate) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(Ok(update.clone())).is_ok());
+    }
+}
+
+impl CpfpFunder {
+    /// Builds and signs a CPFP child for the parent transaction that pays the given fee rate for
+    /// the whole package.
+    ///
+    /// If a previous bump is supplied, its funding UTXO is reused so that the new child replaces
+    /// the old one. Otherwise, a fresh CPFP UTXO that is not in the `reserved` set is selected from
+    /// the wallet.
+    async fn bump(
+        &self,
+        parent_tx: &Transaction,
+        anchor: CpfpAnchor,
+        prev_bump: Option<&CpfpBump>,
+        fee_rate: FeeRate,
+        reserved: &BTreeSet<OutPoint>,
+    ) -> Result<CpfpBump, DriveErr> {
+        let parent_txid = parent_tx.compute_txid();
+        let bump_err = |e: String| DriveErr::FeeBumpFailed(parent_txid, e);
+
+        let cpfp = Cpfp::new(
+            CpfpInput::new(parent_tx, anchor.parent_input_amount, anchor.vout)
+                .map_err(|e| bump_err(e.to_string()))?,
+            anchor.connector,
+        );
+
+        let mut wallet = self.wallet.write().await;
+        let (funding_outpoint, funding_prevout) = match prev_bump {
+            Some(prev_bump) => (
+                prev_bump.funding_outpoint,
+                prev_bump.funding_prevout.clone(),
+            ),
+            None => {
+                // sync so that we do not select a CPFP UTXO that has already been spent by a
+                // previous child.
+                if let Err(e) = wallet.sync().await {
+                    error!(?e, "could not sync wallet but proceeding regardless");
+                }
+                let funding_utxo = wallet
+                    .cpfp_utxos()
+                    .into_iter()
+                    .find(|utxo| !reserved.contains(&utxo.outpoint))
+                    .ok_or(bump_err("no CPFP UTXOs available".to_string()))?;
+                (funding_utxo.outpoint, funding_utxo.txout)
+            }
+        };
+        let change_address = Address::from_script(wallet.general_script_buf(), self.network)
+            .expect("general wallet must have a valid address");
+        drop(wallet);
+
+        let package_fee = cpfp
+            .estimate_package_fee(fee_rate)
+            .map_err(|e| bump_err(e.to_string()))?;
+        if package_fee >= funding_prevout.value {
+            return Err(bump_err(format!(
+                "CPFP UTXO {funding_outpoint} cannot pay {package_fee} in fees"
+            )));
+        }
+
+        let cpfp = cpfp
+            .add_funding(
+                funding_prevout.clone(),
+                funding_outpoint,
+                change_address,
+                fee_rate,
+            )
+            .map_err(|e| bump_err(e.to_string()))?;
+
+        let prevouts = cpfp
+            .psbt()
+            .inputs
+            .iter()
+            .map(|input| {
+                input
+                    .witness_utxo
+                    .clone()
+                    .expect("cpfp inputs must have witness utxos")
+            })
+            .collect::<Vec<_>>();
+        let request = |input_index, spend_path| SigningRequest {
+            tx: cpfp.psbt().unsigned_tx.clone(),
+            prevouts: prevouts.clone(),
+            input_index,
+            spend_path,
+            sighash_type: TapSighashType::Default,
+        };
+
+        let signer = self.s2_client.general_wallet_signer();
+        // the CPFP connector locks funds with the general wallet key without any taproot tweak.
+        let parent_signature = signer
+            .sign_tx(request(
+                Cpfp::PARENT_INPUT_INDEX,
+                SpendPath::UntweakedKeySpend,
+            ))
+            .await
+            .map_err(|e| bump_err(format!("{e:?}")))?
+            .map_err(|e| bump_err(e.to_string()))?;
+        let funding_signature = signer
+            .sign_tx(request(
+                Cpfp::FUNDING_INPUT_INDEX,
+                SpendPath::KeySpend { merkle_root: None },
+            ))
+            .await
+            .map_err(|e| bump_err(format!("{e:?}")))?
+            .map_err(|e| bump_err(e.to_string()))?;
+
+        let child_tx = cpfp
+            .finalize(
+                anchor.connector,
+                Witness::from_slice(&[funding_signature.to_vec()]),
+                parent_signature.signature,
+            )
+            .map_err(|e| bump_err(e.to_string()))?;
+
+        Ok(CpfpBump {
+            funding_outpoint,
+            funding_prevout,
+            fee_rate,
+            child_tx,
+        })
+    }
+}
+
+/// Whether the CPFP child of a transaction that is still not mined at the given height must be
+/// re-bumped in order to meet the deadline.
+///
+/// Transactions without a deadline are never re-bumped on account of the passing blocks.
+fn escalation_due(height: BitcoinBlockHeight, deadline: Option<BitcoinBlockHeight>) -> bool {
+    deadline.is_some_and(|deadline| height + CPFP_ESCALATION_WINDOW >= deadline)
+}
+
+/// The fee rate with which to re-bump a CPFP child that previously paid the given fee rate, if
+/// any.
+///
+/// This is the highest between the estimate and a multiple of the previous fee rate, capped by
+/// the maximum fee rate.
+fn escalated_fee_rate(prev: Option<FeeRate>, estimate: FeeRate, max: FeeRate) -> FeeRate {
+    let prev = prev.unwrap_or(FeeRate::BROADCAST_MIN);
+    FeeRate::from_sat_per_kwu(prev.to_sat_per_kwu() * CPFP_FEE_RATE_MULTIPLIER)
+        .max(estimate)
+        .min(max)
+}
+
+/// Consumes the updates on a transaction until it is reported as final.
+async fn wait_for_finality(mut updates: DriveUpdates) -> Result<(), DriveErr> {
+    while let Some(update) = updates.next().await {
+        if update? == DriveUpdate::Final {
+            return Ok(());
+        }
+    }
+
+    Err(DriveErr::DriverAborted)
+}
+
+/// Subscribes to the status of the transaction and to the transactions that conflict with it.
+async fn subscribe_job(
+    zmq_client: &BtcZmqClient,
+    tx: &Transaction,
+) -> (Subscription<TxEvent>, SelectAll<Subscription<TxEvent>>) {
+    let tx_sub = zmq_client.subscribe_txid(tx.compute_txid()).await;
+
+    // The spend subscriptions also report the transaction itself, which is ignored when
+    // processing the conflicts.
+    let mut conflict_subs = Vec::with_capacity(tx.input.len());
+    for txin in &tx.input {
+        conflict_subs.push(zmq_client.subscribe_spend(txin.previous_output).await);
+    }
+
+    (tx_sub, stream::select_all(conflict_subs))
+}
+
+/// Broadcasts the transaction along with its CPFP child, if any.
+///
+/// Presigned transactions are signed long before the fee rates at which they are broadcasted are
+/// known so they may be rejected on their own. In that case, the transaction and its child are
+/// submitted together as a package so that the fees paid by the child count towards the parent.
+/// The child is never broadcasted on its own since it would be an orphan without its parent. Nodes
+/// that do not support package relay are reported with
+/// [`BroadcastFailure::PackageRelayUnsupported`] and the transaction is retried once the mempool
+/// has room for it on its own.
+///
+/// Otherwise, the returned failure is the one that was reported when broadcasting the transaction
+/// on its own.
+async fn submit(
+    rpc_client: &BitcoinClient,
+    tx: &Transaction,
+    bump: Option<&CpfpBump>,
+) -> Result<(), BroadcastFailure> {
+    let txid = tx.compute_txid();
+    let failure = match rpc_client.send_raw_transaction(tx).await {
+        Ok(_txid) => {
+            if let Some(bump) = bump {
+                submit_child(rpc_client, txid, bump).await;
+            }
+
+            return Ok(());
+        }
+        Err(err) => BroadcastFailure::classify(&err),
+    };
+
+    let Some(bump) = bump.filter(|_| failure == BroadcastFailure::InsufficientFee) else {
+        return Err(failure);
+    };
+
+    let child_txid = bump.child_tx.compute_txid();
+    info!(%txid, %child_txid, fee_rate=%bump.fee_rate, "tx does not pay enough fees on its own, submitting it as a package");
+    let result = rpc_client
+        .submit_package(&[tx.clone(), bump.child_tx.clone()])
+        .await;
+    match &result {
+        Ok(result) => {
+            debug!(%txid, %child_txid, package_msg=%result.package_msg, "package submitted")
+        }
+        Err(e) => warn!(%txid, %child_txid, ?e, "could not submit package"),
+    }
+
+    classify_package_submission(
+        result.as_ref().map(|result| result.package_msg.as_str()),
+        failure,
+    )
+}
+
+/// Interprets the outcome of submitting a transaction as a package along with its CPFP child,
+/// i.e., the `package_msg` reported by bitcoin core or the error it returned, after the
+/// transaction was rejected on its own because of the given failure.
+///
+/// The child is never submitted on its own as it would be an orphan without its parent.
+fn classify_package_submission(
+    outcome: Result<&str, &ClientError>,
+    failure: BroadcastFailure,
+) -> Result<(), BroadcastFailure> {
+    match outcome {
+        Ok("success") => Ok(()),
+        Ok(_) => Err(failure),
+        Err(ClientError::Server(RPC_METHOD_NOT_FOUND, _)) => {
+            Err(BroadcastFailure::PackageRelayUnsupported)
+        }
+        Err(_) => Err(failure),
+    }
+}
+
+/// Checks whether the transaction with the given txid is in the chain.
+///
+/// This relies on the bitcoin node maintaining a transaction index. The transaction is looked up
+/// after it was rejected so it cannot be in the mempool.
+async fn is_in_chain(rpc_client: &BitcoinClient, txid: Txid) -> bool {
+    rpc_client
+        .get_raw_transaction_verbosity_zero(&txid)
+        .await
+        .is_ok()
+}
+
+/// Broadcasts the CPFP child of the given parent transaction.
+async fn submit_child(rpc_client: &BitcoinClient, parent_txid: Txid, bump: &CpfpBump) {
+    let child_txid = bump.child_tx.compute_txid();
+    info!(%parent_txid, %child_txid, fee_rate=%bump.fee_rate, "submitting cpfp child tx");
+    if let Err(e) = rpc_client.send_raw_transaction(&bump.child_tx).await {
+        warn!(%parent_txid, %child_txid, ?e, "could not submit cpfp child tx");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+
+    use super::*;
+
+    #[test]
+    fn classify_bitcoin_core_rejections() {
+        let cases = [
+            (
+                RPC_VERIFY_REJECTED,
+                "min relay fee not met, 0 < 141",
+                BroadcastFailure::InsufficientFee,
+            ),
+            (
+                RPC_VERIFY_REJECTED,
+                "mempool min fee not met, 1000 < 1500",
+                BroadcastFailure::InsufficientFee,
+            ),
+            (
+                RPC_VERIFY_REJECTED,
+                "insufficient fee, rejecting replacement \
+                 9d9a1fa2b0bd6e7c6b1e9b1a4d3f1e0c7d6b5a4f3e2d1c0b9a8f7e6d5c4b3a21; \
+                 new feerate 0.00001000 BTC/kvB <= old feerate 0.00002000 BTC/kvB",
+                BroadcastFailure::InsufficientFee,
+            ),
+            (
+                RPC_VERIFY_REJECTED,
+                "txn-mempool-conflict",
+                BroadcastFailure::InputsSpent,
+            ),
+            (
+                RPC_VERIFY_REJECTED,
+                "txn-already-in-mempool",
+                BroadcastFailure::AlreadyInMempool,
+            ),
+            (
+                RPC_VERIFY_ERROR,
+                "bad-txns-inputs-missingorspent",
+                BroadcastFailure::MissingInputs,
+            ),
+            (
+                RPC_VERIFY_ERROR,
+                "missing-inputs",
+                BroadcastFailure::MissingInputs,
+            ),
+            (
+                RPC_VERIFY_REJECTED,
+                "non-BIP68-final",
+                BroadcastFailure::Other("-26: non-BIP68-final".to_string()),
+            ),
+            (
+                RPC_VERIFY_ALREADY_IN_CHAIN,
+                "Transaction outputs already in utxo set",
+                BroadcastFailure::AlreadyInChain,
+            ),
+            // rejections that mention fees but that paying more cannot fix.
+            (
+                RPC_VERIFY_REJECTED,
+                "absurdly-high-fee, 100000000 > 10000000",
+                BroadcastFailure::Other("-26: absurdly-high-fee, 100000000 > 10000000".to_string()),
+            ),
+            (
+                RPC_VERIFY_ERROR,
+                "Fee exceeds maximum configured by user (e.g. -maxtxfee, maxfeerate)",
+                BroadcastFailure::Other(
+                    "-25: Fee exceeds maximum configured by user (e.g. -maxtxfee, maxfeerate)"
+                        .to_string(),
+                ),
+            ),
+            (
+                RPC_VERIFY_REJECTED,
+                "dust",
+                BroadcastFailure::Other("-26: dust".to_string()),
+            ),
+        ];
+
+        for (code, msg, expected) in cases {
+            let err = ClientError::Server(code, msg.to_string());
+            assert_eq!(BroadcastFailure::classify(&err), expected, "{msg}");
+        }
+    }
+
+    #[test]
+    fn successful_package_submission() {
+        assert_eq!(
+            classify_package_submission(Ok("success"), BroadcastFailure::InsufficientFee),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejected_package_keeps_the_original_failure() {
+        assert_eq!(
+            classify_package_submission(
+                Ok("transaction failed"),
+                BroadcastFailure::InsufficientFee
+            ),
+            Err(BroadcastFailure::InsufficientFee)
+        );
+
+        let err = ClientError::Server(-22, "TX decode failed".to_string());
+        assert_eq!(
+            classify_package_submission(Err(&err), BroadcastFailure::InsufficientFee),
+            Err(BroadcastFailure::InsufficientFee)
+        );
+    }
+
+    #[test]
+    fn missing_package_relay_is_reported() {
+        let err = ClientError::Server(RPC_METHOD_NOT_FOUND, "Method not found".to_string());
+        assert_eq!(
+            classify_package_submission(Err(&err), BroadcastFailure::InsufficientFee),
+            Err(BroadcastFailure::PackageRelayUnsupported)
+        );
+    }
+
+    #[test]
+    fn escalation_starts_within_the_window_of_the_deadline() {
+        let deadline = 100;
+
+        assert!(!escalation_due(
+            deadline - CPFP_ESCALATION_WINDOW - 1,
+            Some(deadline)
+        ));
+        assert!(escalation_due(
+            deadline - CPFP_ESCALATION_WINDOW,
+            Some(deadline)
+        ));
+        assert!(escalation_due(deadline, Some(deadline)));
+        // a missed deadline keeps the tx escalating until it is mined.
+        assert!(escalation_due(deadline + 10, Some(deadline)));
+    }
+
+    #[test]
+    fn no_escalation_without_deadline() {
+        assert!(!escalation_due(0, None));
+        assert!(!escalation_due(BitcoinBlockHeight::MAX / 2, None));
+    }
+
+    #[test]
+    fn escalated_fee_rate_follows_the_schedule() {
+        let estimate = FeeRate::from_sat_per_vb_unchecked(5);
+        let max = FeeRate::from_sat_per_vb_unchecked(100);
+
+        // the first bump pays at least the estimate.
+        let mut fee_rate = escalated_fee_rate(None, estimate, max);
+        assert_eq!(fee_rate, estimate);
+
+        // every subsequent bump multiplies the previous fee rate until it reaches the maximum.
+        let mut schedule = vec![fee_rate];
+        while fee_rate < max {
+            fee_rate = escalated_fee_rate(Some(fee_rate), estimate, max);
+            schedule.push(fee_rate);
+        }
+        assert_eq!(
+            schedule,
+            [5, 10, 20, 40, 80, 100]
+                .map(FeeRate::from_sat_per_vb_unchecked)
+                .to_vec()
+        );
+
+        // bumps at the maximum stay there.
+        assert_eq!(escalated_fee_rate(Some(max), estimate, max), max);
+    }
+
+    #[test]
+    fn escalated_fee_rate_catches_up_with_a_rising_estimate() {
+        let prev = FeeRate::from_sat_per_vb_unchecked(2);
+        let estimate = FeeRate::from_sat_per_vb_unchecked(30);
+        let max = FeeRate::from_sat_per_vb_unchecked(100);
+
+        assert_eq!(escalated_fee_rate(Some(prev), estimate, max), estimate);
+        // the estimate is capped as well.
+        assert_eq!(
+            escalated_fee_rate(Some(prev), FeeRate::from_sat_per_vb_unchecked(500), max),
+            max
+        );
+    }
+
+    #[test]
+    fn finality_policies_resolve_by_confirmations() {
+        assert!(!FinalityPolicy::Inclusion.is_final(0));
+        assert!(FinalityPolicy::Inclusion.is_final(1));
+
+        assert!(!FinalityPolicy::Depth(6).is_final(5));
+        assert!(FinalityPolicy::Depth(6).is_final(6));
+        assert!(FinalityPolicy::Depth(6).is_final(7));
+        // a depth of zero still needs the transaction to be mined.
+        assert!(!FinalityPolicy::Depth(0).is_final(0));
+        assert!(FinalityPolicy::Depth(0).is_final(1));
+
+        // buried transactions are reported by the zmq client, not by their confirmations.
+        assert!(!FinalityPolicy::Buried.is_final(1_000));
+    }
+
+    #[test]
+    fn finality_policies_round_trip_through_required_confirmations() {
+        for policy in [
+            FinalityPolicy::Inclusion,
+            FinalityPolicy::Depth(6),
+            FinalityPolicy::Buried,
+        ] {
+            assert_eq!(
+                FinalityPolicy::from_required_confirmations(policy.required_confirmations()),
+                policy
+            );
+        }
+
+        // shallow depths are the same as inclusion.
+        assert_eq!(
+            FinalityPolicy::from_required_confirmations(
+                FinalityPolicy::Depth(1).required_confirmations()
+            ),
+            FinalityPolicy::Inclusion
+        );
+        assert_eq!(FinalityPolicy::default(), FinalityPolicy::Buried);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_finality_follows_the_updates() {
+        let (sender, receiver) = unbounded_channel();
+        sender.send(Ok(DriveUpdate::Mempool)).unwrap();
+        sender
+            .send(Ok(DriveUpdate::Mined {
+                blockhash: BlockHash::all_zeros(),
+                height: 100,
+                confirmations: 1,
+            }))
+            .unwrap();
+        sender.send(Ok(DriveUpdate::Reorged)).unwrap();
+        sender.send(Ok(DriveUpdate::Final)).unwrap();
+        assert!(wait_for_finality(UnboundedReceiverStream::new(receiver))
+            .await
+            .is_ok());
+
+        let (sender, receiver) = unbounded_channel();
+        sender.send(Ok(DriveUpdate::Mempool)).unwrap();
+        sender
+            .send(Err(DriveErr::InputsSpent {
+                conflicting_txid: Txid::all_zeros(),
+            }))
+            .unwrap();
+        assert!(matches!(
+            wait_for_finality(UnboundedReceiverStream::new(receiver)).await,
+            Err(DriveErr::InputsSpent { .. })
+        ));
+
+        // the driver going away before the transaction is final is an error.
+        let (sender, receiver) = unbounded_channel();
+        sender.send(Ok(DriveUpdate::Mempool)).unwrap();
+        drop(sender);
+        assert!(matches!(
+            wait_for_finality(UnboundedReceiverStream::new(receiver)).await,
+            Err(DriveErr::DriverAborted)
+        ));
+    }
+}
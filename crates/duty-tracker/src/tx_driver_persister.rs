@@ -0,0 +1,372 @@
+//! This module is responsible for persisting the jobs of the [`crate::tx_driver::TxDriver`] so that
+//! the transactions being driven survive restarts.
+
+use bitcoin::{
+    consensus, Amount, FeeRate, Network, OutPoint, Transaction, TxOut, Txid, XOnlyPublicKey,
+};
+use sqlx::{
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Pool, Row, Sqlite,
+};
+use strata_bridge_connectors::prelude::ConnectorCpfp;
+use strata_bridge_primitives::types::BitcoinBlockHeight;
+use thiserror::Error;
+
+use crate::tx_driver::{CpfpAnchor, CpfpBump, DriveOptions, FinalityPolicy};
+
+/// Error type for the [`TxDriverPersister`] methods.
+#[derive(Debug, Clone, Error)]
+pub enum TxDriverPersistErr {
+    /// Unexpected error.
+    #[error("Unexpected error: {0}")]
+    Unexpected(String),
+}
+impl From<consensus::encode::Error> for TxDriverPersistErr {
+    fn from(e: consensus::encode::Error) -> Self {
+        TxDriverPersistErr::Unexpected(e.to_string())
+    }
+}
+impl From<sqlx::Error> for TxDriverPersistErr {
+    fn from(e: sqlx::Error) -> Self {
+        TxDriverPersistErr::Unexpected(e.to_string())
+    }
+}
+
+/// A job of the [`crate::tx_driver::TxDriver`] as it was persisted.
+pub(crate) struct PersistedTxJob {
+    pub(crate) tx: Transaction,
+    pub(crate) deadline: Option<BitcoinBlockHeight>,
+    pub(crate) anchor: Option<CpfpAnchor>,
+    pub(crate) options: DriveOptions,
+
+    /// The latest CPFP child that was broadcasted for the transaction, if any.
+    pub(crate) bump: Option<CpfpBump>,
+}
+
+/// System for persisting the jobs of the [`crate::tx_driver::TxDriver`] along with the history of
+/// the fee bumps of their transactions.
+#[derive(Debug, Clone)]
+pub struct TxDriverPersister {
+    pool: Pool<Sqlite>,
+}
+
+impl TxDriverPersister {
+    /// Initializes the [`TxDriverPersister`]
+    pub async fn new(pool: Pool<Sqlite>) -> Result<Self, TxDriverPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tx_driver_jobs (
+                txid CHAR(64) PRIMARY KEY,
+                tx VARBINARY NOT NULL,
+                deadline INTEGER,
+                anchor_input_amount INTEGER,
+                anchor_vout INTEGER,
+                anchor_pubkey VARBINARY,
+                finality_depth INTEGER,
+                watch_until_final BOOLEAN NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tx_driver_bumps (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                txid CHAR(64) NOT NULL,
+                fee_rate INTEGER NOT NULL,
+                funding_outpoint VARBINARY NOT NULL,
+                funding_prevout VARBINARY NOT NULL,
+                child_tx VARBINARY NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        Ok(TxDriverPersister { pool })
+    }
+
+    /// Records a new job.
+    pub(crate) async fn save_job(
+        &self,
+        tx: &Transaction,
+        deadline: Option<BitcoinBlockHeight>,
+        anchor: Option<&CpfpAnchor>,
+        options: DriveOptions,
+    ) -> Result<(), TxDriverPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO tx_driver_jobs
+                (txid, tx, deadline, anchor_input_amount, anchor_vout, anchor_pubkey,
+                 finality_depth, watch_until_final)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(tx.compute_txid().to_string())
+        .bind(consensus::serialize(tx))
+        // a deadline of `NULL` means that the transaction has no deadline.
+        .bind(deadline.map(|deadline| deadline as i64))
+        .bind(anchor.map(|anchor| anchor.parent_input_amount.to_sat() as i64))
+        .bind(anchor.map(|anchor| anchor.vout))
+        .bind(anchor.map(|anchor| anchor.connector.public_key().serialize().to_vec()))
+        // a depth of `NULL` means that the transaction is only final once it is buried.
+        .bind(
+            options
+                .finality
+                .required_confirmations()
+                .map(|depth| depth as i64),
+        )
+        .bind(options.watch_until_final)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Appends a fee bump to the history of the job driving the transaction with the given txid.
+    pub(crate) async fn save_bump(
+        &self,
+        txid: Txid,
+        bump: &CpfpBump,
+    ) -> Result<(), TxDriverPersistErr> {
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            INSERT INTO tx_driver_bumps
+                (txid, fee_rate, funding_outpoint, funding_prevout, child_tx)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(txid.to_string())
+        .bind(bump.fee_rate.to_sat_per_kwu() as i64)
+        .bind(consensus::serialize(&bump.funding_outpoint))
+        .bind(consensus::serialize(&bump.funding_prevout))
+        .bind(consensus::serialize(&bump.child_tx))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes the job driving the transaction with the given txid along with its fee bumps.
+    pub(crate) async fn delete_job(&self, txid: Txid) -> Result<(), TxDriverPersistErr> {
+        let mut db_tx = self.pool.begin().await?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            DELETE FROM tx_driver_bumps WHERE txid = ?
+            "#,
+        )
+        .bind(txid.to_string())
+        .execute(&mut *db_tx)
+        .await?;
+        let _: SqliteQueryResult = sqlx::query(
+            r#"
+            DELETE FROM tx_driver_jobs WHERE txid = ?
+            "#,
+        )
+        .bind(txid.to_string())
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Loads all the jobs along with their latest fee bump.
+    ///
+    /// The `network` is the one for which the CPFP connectors of the anchors were generated.
+    pub(crate) async fn load_jobs(
+        &self,
+        network: Network,
+    ) -> Result<Vec<PersistedTxJob>, TxDriverPersistErr> {
+        let rows: Vec<SqliteRow> = sqlx::query(
+            r#"
+            SELECT txid, tx, deadline, anchor_input_amount, anchor_vout, anchor_pubkey,
+                finality_depth, watch_until_final
+            FROM tx_driver_jobs
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut jobs = Vec::with_capacity(rows.len());
+        for row in rows {
+            let tx: Transaction = consensus::deserialize(row.try_get("tx")?)?;
+            let deadline = row
+                .try_get::<Option<i64>, _>("deadline")?
+                .map(|deadline| deadline as BitcoinBlockHeight);
+
+            let anchor_input_amount: Option<i64> = row.try_get("anchor_input_amount")?;
+            let anchor_vout: Option<u32> = row.try_get("anchor_vout")?;
+            let anchor_pubkey: Option<Vec<u8>> = row.try_get("anchor_pubkey")?;
+            let anchor = match (anchor_input_amount, anchor_vout, anchor_pubkey) {
+                (Some(input_amount), Some(vout), Some(pubkey)) => {
+                    let public_key = XOnlyPublicKey::from_slice(&pubkey)
+                        .map_err(|e| TxDriverPersistErr::Unexpected(e.to_string()))?;
+                    Some(CpfpAnchor {
+                        parent_input_amount: Amount::from_sat(input_amount as u64),
+                        vout,
+                        connector: ConnectorCpfp::new(public_key, network),
+                    })
+                }
+                _ => None,
+            };
+
+            let finality_depth: Option<i64> = row.try_get("finality_depth")?;
+            let options = DriveOptions {
+                finality: FinalityPolicy::from_required_confirmations(
+                    finality_depth.map(|depth| depth as BitcoinBlockHeight),
+                ),
+                watch_until_final: row.try_get("watch_until_final")?,
+            };
+
+            let bump_row: Option<SqliteRow> = sqlx::query(
+                r#"
+                SELECT fee_rate, funding_outpoint, funding_prevout, child_tx
+                FROM tx_driver_bumps WHERE txid = ?
+                ORDER BY id DESC LIMIT 1
+                "#,
+            )
+            .bind(tx.compute_txid().to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+            let bump = bump_row
+                .map(|row| -> Result<CpfpBump, TxDriverPersistErr> {
+                    let funding_outpoint: OutPoint =
+                        consensus::deserialize(row.try_get("funding_outpoint")?)?;
+                    let funding_prevout: TxOut =
+                        consensus::deserialize(row.try_get("funding_prevout")?)?;
+                    let fee_rate =
+                        FeeRate::from_sat_per_kwu(row.try_get::<i64, _>("fee_rate")? as u64);
+                    let child_tx: Transaction = consensus::deserialize(row.try_get("child_tx")?)?;
+                    Ok(CpfpBump {
+                        funding_outpoint,
+                        funding_prevout,
+                        fee_rate,
+                        child_tx,
+                    })
+                })
+                .transpose()?;
+
+            jobs.push(PersistedTxJob {
+                tx,
+                deadline,
+                anchor,
+                options,
+                bump,
+            });
+        }
+
+        Ok(jobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use strata_bridge_test_utils::prelude::{generate_keypair, generate_tx};
+
+    use super::*;
+
+    fn bump(fee_rate: u64) -> CpfpBump {
+        let child_tx = generate_tx(2, 1);
+        CpfpBump {
+            funding_outpoint: child_tx.input[1].previous_output,
+            funding_prevout: TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: child_tx.output[0].script_pubkey.clone(),
+            },
+            fee_rate: FeeRate::from_sat_per_kwu(fee_rate),
+            child_tx,
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn jobs_round_trip_with_their_latest_bump(pool: SqlitePool) {
+        let persister = TxDriverPersister::new(pool).await.unwrap();
+        let network = Network::Regtest;
+
+        let anchored_tx = generate_tx(1, 2);
+        let anchor_key = generate_keypair().x_only_public_key().0;
+        let anchor = CpfpAnchor {
+            parent_input_amount: Amount::from_sat(50_000),
+            vout: 1,
+            connector: ConnectorCpfp::new(anchor_key, network),
+        };
+        let anchored_options = DriveOptions {
+            finality: FinalityPolicy::Depth(6),
+            watch_until_final: true,
+        };
+        persister
+            .save_job(&anchored_tx, Some(120), Some(&anchor), anchored_options)
+            .await
+            .unwrap();
+
+        let plain_tx = generate_tx(1, 1);
+        persister
+            .save_job(&plain_tx, None, None, DriveOptions::default())
+            .await
+            .unwrap();
+
+        let anchored_txid = anchored_tx.compute_txid();
+        let first_bump = bump(1_000);
+        let latest_bump = bump(2_000);
+        persister
+            .save_bump(anchored_txid, &first_bump)
+            .await
+            .unwrap();
+        persister
+            .save_bump(anchored_txid, &latest_bump)
+            .await
+            .unwrap();
+
+        let jobs = persister.load_jobs(network).await.unwrap();
+        assert_eq!(jobs.len(), 2);
+
+        let anchored = jobs.iter().find(|job| job.tx == anchored_tx).unwrap();
+        assert_eq!(anchored.deadline, Some(120));
+        assert_eq!(anchored.options, anchored_options);
+        let loaded_anchor = anchored.anchor.unwrap();
+        assert_eq!(
+            loaded_anchor.parent_input_amount,
+            anchor.parent_input_amount
+        );
+        assert_eq!(loaded_anchor.vout, anchor.vout);
+        assert_eq!(loaded_anchor.connector.public_key(), anchor_key);
+        let loaded_bump = anchored.bump.as_ref().unwrap();
+        assert_eq!(loaded_bump.fee_rate, latest_bump.fee_rate);
+        assert_eq!(loaded_bump.funding_outpoint, latest_bump.funding_outpoint);
+        assert_eq!(loaded_bump.funding_prevout, latest_bump.funding_prevout);
+        assert_eq!(loaded_bump.child_tx, latest_bump.child_tx);
+
+        let plain = jobs.iter().find(|job| job.tx == plain_tx).unwrap();
+        assert_eq!(plain.deadline, None);
+        assert!(plain.anchor.is_none());
+        assert_eq!(plain.options, DriveOptions::default());
+        assert!(plain.bump.is_none());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn deleted_jobs_forget_their_bumps(pool: SqlitePool) {
+        let persister = TxDriverPersister::new(pool).await.unwrap();
+        let tx = generate_tx(1, 1);
+        let txid = tx.compute_txid();
+
+        persister
+            .save_job(&tx, None, None, DriveOptions::default())
+            .await
+            .unwrap();
+        persister.save_bump(txid, &bump(1_000)).await.unwrap();
+        persister.delete_job(txid).await.unwrap();
+        assert!(persister
+            .load_jobs(Network::Regtest)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // a job that is driven again starts without the bumps of its previous run.
+        persister
+            .save_job(&tx, None, None, DriveOptions::default())
+            .await
+            .unwrap();
+        let jobs = persister.load_jobs(Network::Regtest).await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert!(jobs[0].bump.is_none());
+    }
+}
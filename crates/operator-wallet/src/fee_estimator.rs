@@ -0,0 +1,425 @@
+//! Fee estimation for the transactions that the operator funds and fee bumps.
+//!
+//! Fee rates are obtained from one or more [`FeeEstimator`] backends through a [`FeeOracle`] that
+//! maps the urgency of a transaction to a confirmation target and caps the resulting estimate.
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bdk_bitcoind_rpc::bitcoincore_rpc::{self, json::GetMempoolEntryResult, RpcApi};
+use bdk_wallet::bitcoin::{FeeRate, Weight};
+use strata_btcio::rpc::{traits::ReaderRpc, BitcoinClient};
+use thiserror::Error;
+use tokio::{
+    sync::RwLock,
+    task::JoinHandle,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{debug, warn};
+
+/// Error type for the [`FeeEstimator`]s.
+#[derive(Debug, Clone, Error)]
+pub enum FeeEstimationError {
+    /// Error returned by the bitcoin node.
+    #[error("rpc error: {0}")]
+    Rpc(String),
+
+    /// The estimator does not have enough data to produce an estimate.
+    #[error("not enough data to estimate fees: {0}")]
+    InsufficientData(String),
+}
+
+/// How urgently a transaction needs to be confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeUrgency {
+    /// The transaction must be confirmed before a protocol deadline.
+    DeadlineCritical,
+
+    /// The transaction can wait for fee rates to come down.
+    Background,
+}
+
+/// Source of fee rate estimates.
+#[async_trait]
+pub trait FeeEstimator: Debug + Send + Sync {
+    /// Estimates the fee rate that a transaction needs to pay to be confirmed within
+    /// `target_blocks` blocks.
+    async fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError>;
+}
+
+/// A [`FeeEstimator`] that always returns the same fee rate.
+///
+/// This is meant to be used as the last resort when the other estimators fail.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticFeeEstimator {
+    fee_rate: FeeRate,
+}
+
+impl StaticFeeEstimator {
+    /// Creates a new [`StaticFeeEstimator`] that returns the given fee rate.
+    pub fn new(fee_rate: FeeRate) -> Self {
+        Self { fee_rate }
+    }
+}
+
+#[async_trait]
+impl FeeEstimator for StaticFeeEstimator {
+    async fn estimate_fee_rate(&self, _target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        Ok(self.fee_rate)
+    }
+}
+
+/// A [`FeeEstimator`] backed by the `estimatesmartfee` RPC of bitcoin core.
+#[derive(Debug, Clone)]
+pub struct BitcoinCoreFeeEstimator {
+    rpc_client: BitcoinClient,
+}
+
+impl BitcoinCoreFeeEstimator {
+    /// Creates a new [`BitcoinCoreFeeEstimator`].
+    pub fn new(rpc_client: BitcoinClient) -> Self {
+        Self { rpc_client }
+    }
+}
+
+#[async_trait]
+impl FeeEstimator for BitcoinCoreFeeEstimator {
+    async fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        let sat_per_vb = self
+            .rpc_client
+            .estimate_smart_fee(target_blocks)
+            .await
+            .map_err(|e| FeeEstimationError::Rpc(e.to_string()))?;
+
+        FeeRate::from_sat_per_vb(sat_per_vb).ok_or(FeeEstimationError::InsufficientData(format!(
+            "fee rate of {sat_per_vb} sat/vB is out of range"
+        )))
+    }
+}
+
+/// A transaction in the mempool as seen by the [`MempoolFeeEstimator`].
+#[derive(Debug, Clone, Copy)]
+struct MempoolEntry {
+    fee_rate: FeeRate,
+    weight: Weight,
+}
+
+impl MempoolEntry {
+    /// Creates a [`MempoolEntry`] from the fees and size reported by bitcoin core.
+    fn from_rpc(entry: &GetMempoolEntryResult) -> Self {
+        let weight = Weight::from_wu(entry.weight.unwrap_or(entry.vsize * 4).max(1));
+        let fee_rate = FeeRate::from_sat_per_kwu(entry.fees.base.to_sat() * 1_000 / weight.to_wu());
+
+        Self { fee_rate, weight }
+    }
+}
+
+/// A [`FeeEstimator`] that estimates fee rates from the transactions in the mempool.
+///
+/// The mempool is read from the node with `getrawmempool true` at a regular interval, which
+/// reports the fees and size of every transaction so neither a transaction index nor a lookup per
+/// transaction is needed. The estimates are based on the latest snapshot that could be taken.
+///
+/// A target of `n` blocks is estimated as the fee rate of the transaction at which the
+/// transactions that pay higher fee rates fill `n` blocks, i.e., the percentile of the mempool,
+/// by weight, that would be mined in the next `n` blocks if no more transactions arrived.
+#[derive(Debug)]
+pub struct MempoolFeeEstimator {
+    mempool: Arc<RwLock<Vec<MempoolEntry>>>,
+    tracker: JoinHandle<()>,
+}
+
+impl MempoolFeeEstimator {
+    /// Creates a new [`MempoolFeeEstimator`] that takes a snapshot of the mempool right away and
+    /// then every `refresh_interval`.
+    pub fn new(rpc_client: Arc<bitcoincore_rpc::Client>, refresh_interval: Duration) -> Self {
+        let mempool = Arc::new(RwLock::new(Vec::new()));
+
+        let tracked_mempool = mempool.clone();
+        let tracker = tokio::task::spawn(async move {
+            let mut interval = time::interval(refresh_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+
+                let rpc_client = rpc_client.clone();
+                let snapshot =
+                    tokio::task::spawn_blocking(move || rpc_client.get_raw_mempool_verbose()).await;
+                match snapshot {
+                    Ok(Ok(entries)) => {
+                        let entries = entries.values().map(MempoolEntry::from_rpc).collect();
+                        *tracked_mempool.write().await = entries;
+                    }
+                    Ok(Err(e)) => warn!(%e, "could not take a snapshot of the mempool"),
+                    Err(e) => warn!(%e, "mempool snapshot task failed"),
+                }
+            }
+        });
+
+        Self { mempool, tracker }
+    }
+}
+
+impl Drop for MempoolFeeEstimator {
+    fn drop(&mut self) {
+        self.tracker.abort();
+    }
+}
+
+#[async_trait]
+impl FeeEstimator for MempoolFeeEstimator {
+    async fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        let mempool = self.mempool.read().await;
+        if mempool.is_empty() {
+            return Err(FeeEstimationError::InsufficientData(
+                "no transactions in the mempool".to_string(),
+            ));
+        }
+
+        let mut entries = mempool.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.fee_rate.cmp(&a.fee_rate));
+
+        let target_weight = Weight::MAX_BLOCK * u64::from(target_blocks.max(1));
+        let mut cumulative_weight = Weight::ZERO;
+        for entry in entries {
+            cumulative_weight += entry.weight;
+            if cumulative_weight >= target_weight {
+                return Ok(entry.fee_rate);
+            }
+        }
+
+        // the whole mempool fits within the target so any fee rate that can be relayed will do.
+        Ok(FeeRate::BROADCAST_MIN)
+    }
+}
+
+/// The confirmation targets and caps that the [`FeeOracle`] applies to the estimates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeOracleConfig {
+    /// The number of blocks within which [`FeeUrgency::DeadlineCritical`] transactions should be
+    /// confirmed.
+    pub deadline_critical_target: u16,
+
+    /// The number of blocks within which [`FeeUrgency::Background`] transactions should be
+    /// confirmed.
+    pub background_target: u16,
+
+    /// The minimum fee rate that is ever returned.
+    pub min_fee_rate: FeeRate,
+
+    /// The maximum fee rate that is ever returned.
+    pub max_fee_rate: FeeRate,
+}
+
+/// Produces the fee rates for the transactions of the operator from a list of [`FeeEstimator`]s.
+///
+/// The estimators are consulted in order until one of them produces an estimate so the more
+/// accurate ones should come first and a [`StaticFeeEstimator`] last.
+#[derive(Debug, Clone)]
+pub struct FeeOracle {
+    estimators: Vec<Arc<dyn FeeEstimator>>,
+    config: FeeOracleConfig,
+}
+
+impl FeeOracle {
+    /// Creates a new [`FeeOracle`].
+    pub fn new(estimators: Vec<Arc<dyn FeeEstimator>>, config: FeeOracleConfig) -> Self {
+        Self { estimators, config }
+    }
+
+    /// Returns the fee rate that a transaction with the given urgency should pay, clamped between
+    /// [`FeeOracleConfig::min_fee_rate`] and [`FeeOracleConfig::max_fee_rate`].
+    ///
+    /// If none of the estimators can produce an estimate, the minimum fee rate is returned.
+    pub async fn fee_rate(&self, urgency: FeeUrgency) -> FeeRate {
+        let target_blocks = match urgency {
+            FeeUrgency::DeadlineCritical => self.config.deadline_critical_target,
+            FeeUrgency::Background => self.config.background_target,
+        };
+
+        for estimator in &self.estimators {
+            match estimator.estimate_fee_rate(target_blocks).await {
+                Ok(fee_rate) => {
+                    debug!(?estimator, ?urgency, %target_blocks, %fee_rate, "estimated fee rate");
+                    return fee_rate
+                        .max(self.config.min_fee_rate)
+                        .min(self.config.max_fee_rate);
+                }
+                Err(e) => warn!(?estimator, %e, "could not estimate fee rate"),
+            }
+        }
+
+        warn!(
+            ?urgency,
+            "no fee estimates available, falling back to the minimum fee rate"
+        );
+        self.config.min_fee_rate
+    }
+
+    /// Returns the maximum fee rate that the operator is willing to pay.
+    pub fn max_fee_rate(&self) -> FeeRate {
+        self.config.max_fee_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An estimator that fails like `estimatesmartfee` does when the node has no estimates.
+    #[derive(Debug)]
+    struct FailingEstimator;
+
+    #[async_trait]
+    impl FeeEstimator for FailingEstimator {
+        async fn estimate_fee_rate(
+            &self,
+            _target_blocks: u16,
+        ) -> Result<FeeRate, FeeEstimationError> {
+            Err(FeeEstimationError::Rpc(
+                "Insufficient data or no feerate found".to_string(),
+            ))
+        }
+    }
+
+    /// An estimator that returns the target it was asked for as a fee rate in sat/vB.
+    #[derive(Debug)]
+    struct TargetEchoEstimator;
+
+    #[async_trait]
+    impl FeeEstimator for TargetEchoEstimator {
+        async fn estimate_fee_rate(
+            &self,
+            target_blocks: u16,
+        ) -> Result<FeeRate, FeeEstimationError> {
+            Ok(FeeRate::from_sat_per_vb_unchecked(u64::from(target_blocks)))
+        }
+    }
+
+    fn sat_per_vb(rate: u64) -> FeeRate {
+        FeeRate::from_sat_per_vb_unchecked(rate)
+    }
+
+    fn config() -> FeeOracleConfig {
+        FeeOracleConfig {
+            deadline_critical_target: 2,
+            background_target: 20,
+            min_fee_rate: sat_per_vb(2),
+            max_fee_rate: sat_per_vb(50),
+        }
+    }
+
+    fn mempool_estimator(entries: &[(u64, Weight)]) -> MempoolFeeEstimator {
+        let mempool = entries
+            .iter()
+            .map(|(rate, weight)| MempoolEntry {
+                fee_rate: sat_per_vb(*rate),
+                weight: *weight,
+            })
+            .collect();
+
+        MempoolFeeEstimator {
+            mempool: Arc::new(RwLock::new(mempool)),
+            tracker: tokio::spawn(async {}),
+        }
+    }
+
+    #[tokio::test]
+    async fn mempool_estimates_are_the_percentile_that_fills_the_target() {
+        let half_block = Weight::from_wu(Weight::MAX_BLOCK.to_wu() / 2);
+        let estimator = mempool_estimator(&[
+            (1, Weight::MAX_BLOCK),
+            (30, half_block),
+            (10, Weight::MAX_BLOCK),
+        ]);
+
+        // 30 sat/vB fills half a block so the next block is filled at 10 sat/vB.
+        assert_eq!(
+            estimator.estimate_fee_rate(1).await.unwrap(),
+            sat_per_vb(10)
+        );
+        // a target of zero blocks is treated as the next block.
+        assert_eq!(
+            estimator.estimate_fee_rate(0).await.unwrap(),
+            sat_per_vb(10)
+        );
+        assert_eq!(estimator.estimate_fee_rate(2).await.unwrap(), sat_per_vb(1));
+        // the whole mempool fits within three blocks.
+        assert_eq!(
+            estimator.estimate_fee_rate(3).await.unwrap(),
+            FeeRate::BROADCAST_MIN
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_mempool_has_no_estimates() {
+        let estimator = mempool_estimator(&[]);
+
+        assert!(matches!(
+            estimator.estimate_fee_rate(1).await,
+            Err(FeeEstimationError::InsufficientData(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn oracle_clamps_estimates() {
+        let too_high = FeeOracle::new(
+            vec![Arc::new(StaticFeeEstimator::new(sat_per_vb(500)))],
+            config(),
+        );
+        assert_eq!(
+            too_high.fee_rate(FeeUrgency::DeadlineCritical).await,
+            sat_per_vb(50)
+        );
+
+        let too_low = FeeOracle::new(
+            vec![Arc::new(StaticFeeEstimator::new(sat_per_vb(1)))],
+            config(),
+        );
+        assert_eq!(
+            too_low.fee_rate(FeeUrgency::Background).await,
+            sat_per_vb(2)
+        );
+
+        let within = FeeOracle::new(
+            vec![Arc::new(StaticFeeEstimator::new(sat_per_vb(7)))],
+            config(),
+        );
+        assert_eq!(within.fee_rate(FeeUrgency::Background).await, sat_per_vb(7));
+    }
+
+    #[tokio::test]
+    async fn oracle_uses_the_target_of_the_urgency() {
+        let oracle = FeeOracle::new(vec![Arc::new(TargetEchoEstimator)], config());
+
+        assert_eq!(
+            oracle.fee_rate(FeeUrgency::DeadlineCritical).await,
+            sat_per_vb(2)
+        );
+        assert_eq!(
+            oracle.fee_rate(FeeUrgency::Background).await,
+            sat_per_vb(20)
+        );
+    }
+
+    #[tokio::test]
+    async fn oracle_falls_back_when_estimators_fail() {
+        let oracle = FeeOracle::new(
+            vec![
+                Arc::new(FailingEstimator),
+                Arc::new(mempool_estimator(&[])),
+                Arc::new(StaticFeeEstimator::new(sat_per_vb(12))),
+            ],
+            config(),
+        );
+        assert_eq!(
+            oracle.fee_rate(FeeUrgency::DeadlineCritical).await,
+            sat_per_vb(12)
+        );
+
+        let no_estimates = FeeOracle::new(vec![Arc::new(FailingEstimator)], config());
+        assert_eq!(
+            no_estimates.fee_rate(FeeUrgency::DeadlineCritical).await,
+            sat_per_vb(2)
+        );
+    }
+}
@@ -1,4 +1,5 @@
 //! Operator wallet
+pub mod fee_estimator;
 pub mod sync;
 
 use bdk_wallet::{
@@ -8,8 +9,9 @@ use bdk_wallet::{
     },
     descriptor,
     error::CreateTxError,
-    KeychainKind, LocalOutput, Wallet,
+    KeychainKind, LocalOutput, TxOrdering, Wallet,
 };
+use fee_estimator::{FeeOracle, FeeUrgency};
 use sync::{Backend, SyncError};
 use tracing::{debug, info};
 
@@ -65,6 +67,7 @@ pub struct OperatorWallet {
     stakechain_addr_script_buf: ScriptBuf,
     general_addr_script_buf: ScriptBuf,
     sync_backend: Backend,
+    fee_oracle: FeeOracle,
 }
 
 impl OperatorWallet {
@@ -74,6 +77,7 @@ impl OperatorWallet {
         stakechain: XOnlyPublicKey,
         config: OperatorWalletConfig,
         sync_backend: Backend,
+        fee_oracle: FeeOracle,
     ) -> Self {
         let (general_desc, ..) = descriptor!(tr(general)).unwrap();
         let (stakechain_desc, ..) = descriptor!(tr(stakechain)).unwrap();
@@ -100,6 +104,7 @@ impl OperatorWallet {
             general_wallet,
             stakechain_wallet,
             sync_backend,
+            fee_oracle,
         }
     }
 
@@ -154,6 +159,9 @@ impl OperatorWallet {
         // DON'T spend any of the cpfp outputs
         tx_builder.unspendable(cpfp_utxos);
         tx_builder.fee_rate(fee_rate);
+        // the user output and the `OP_RETURN` must be the first and second outputs respectively
+        // for the withdrawal fulfillment to be recognized.
+        tx_builder.ordering(TxOrdering::Untouched);
         tx_builder.add_recipient(user_p2tr_address.script_pubkey(), amount);
         tx_builder.add_data(&push_data);
         tx_builder.finish()
@@ -240,6 +248,17 @@ impl OperatorWallet {
         &self.stakechain_wallet
     }
 
+    /// Returns the fee rate that a transaction funded by the wallet should pay given how urgently
+    /// it needs to be confirmed.
+    pub async fn fee_rate(&self, urgency: FeeUrgency) -> FeeRate {
+        self.fee_oracle.fee_rate(urgency).await
+    }
+
+    /// Returns the [`FeeOracle`] used by the wallet.
+    pub fn fee_oracle(&self) -> &FeeOracle {
+        &self.fee_oracle
+    }
+
     /// Syncs the wallet using the backend provided on construction
     pub async fn sync(&mut self) -> Result<(), SyncError> {
         self.sync_backend
@@ -268,3 +287,147 @@ pub enum FundingUtxo {
     /// Really bad if this happens because you should've refilled when we told you too.
     Empty,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bdk_bitcoind_rpc::bitcoincore_rpc::{self, Auth};
+    use bdk_wallet::bitcoin::{
+        absolute,
+        hashes::Hash,
+        secp256k1::{Keypair, Secp256k1},
+        transaction, CompressedPublicKey, Transaction, TxIn, TxOut, Txid,
+    };
+
+    use super::*;
+    use crate::fee_estimator::{FeeOracleConfig, StaticFeeEstimator};
+
+    const CPFP_VALUE: Amount = Amount::from_sat(330);
+    const FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_unchecked(2);
+
+    fn keypair(seed: u8) -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[seed; 32])
+            .expect("must be a valid secret key")
+    }
+
+    fn x_only_pubkey(seed: u8) -> XOnlyPublicKey {
+        keypair(seed).x_only_public_key().0
+    }
+
+    fn operator_wallet() -> OperatorWallet {
+        let config = OperatorWalletConfig::new(
+            Amount::from_sat(100_000),
+            2,
+            CPFP_VALUE,
+            Amount::from_int_btc(1),
+            Network::Regtest,
+        );
+        // the backend is never used since the wallet is funded by hand.
+        let rpc_client = bitcoincore_rpc::Client::new("http://127.0.0.1:18443", Auth::None)
+            .expect("must be able to create rpc client");
+        let fee_oracle = FeeOracle::new(
+            vec![Arc::new(StaticFeeEstimator::new(FEE_RATE))],
+            FeeOracleConfig {
+                deadline_critical_target: 1,
+                background_target: 6,
+                min_fee_rate: FEE_RATE,
+                max_fee_rate: FEE_RATE,
+            },
+        );
+
+        OperatorWallet::new(
+            x_only_pubkey(1),
+            x_only_pubkey(2),
+            config,
+            Backend::BitcoinCore(Arc::new(rpc_client)),
+            fee_oracle,
+        )
+    }
+
+    /// Pays `value` to the general wallet in an unconfirmed transaction and returns the outpoint.
+    fn fund(wallet: &mut OperatorWallet, seed: u8, value: Amount) -> OutPoint {
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array([seed; 32]), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: wallet.general_script_buf().clone(),
+            }],
+        };
+        let outpoint = OutPoint::new(tx.compute_txid(), 0);
+        wallet.general_wallet.apply_unconfirmed_txs([(tx, 1)]);
+
+        outpoint
+    }
+
+    fn user_address() -> Address {
+        Address::p2tr(&Secp256k1::new(), x_only_pubkey(3), None, Network::Regtest)
+    }
+
+    #[test]
+    fn front_withdrawal_keeps_the_output_order() {
+        let mut wallet = operator_wallet();
+        fund(&mut wallet, 1, Amount::from_int_btc(2));
+        fund(&mut wallet, 2, Amount::from_int_btc(3));
+
+        let amount = Amount::from_int_btc(4);
+        let op_return_data = [0xab; 40];
+        let psbt = wallet
+            .front_withdrawal(FEE_RATE, user_address(), amount, &op_return_data)
+            .expect("must be able to front withdrawal");
+
+        let outputs = &psbt.unsigned_tx.output;
+        assert_eq!(
+            outputs.len(),
+            3,
+            "must have user, OP_RETURN and change outputs"
+        );
+        assert_eq!(outputs[0].script_pubkey, user_address().script_pubkey());
+        assert_eq!(outputs[0].value, amount);
+        assert!(outputs[1].script_pubkey.is_op_return());
+        assert_eq!(outputs[1].value, Amount::ZERO);
+        assert!(outputs[1]
+            .script_pubkey
+            .as_bytes()
+            .ends_with(&op_return_data));
+        assert_eq!(outputs[2].script_pubkey, *wallet.general_script_buf());
+    }
+
+    #[test]
+    fn front_withdrawal_does_not_spend_cpfp_utxos() {
+        let mut wallet = operator_wallet();
+        let cpfp_outpoint = fund(&mut wallet, 1, CPFP_VALUE);
+        fund(&mut wallet, 2, Amount::from_int_btc(1));
+
+        let psbt = wallet
+            .front_withdrawal(FEE_RATE, user_address(), Amount::from_sat(99_000_000), &[])
+            .expect("must be able to front withdrawal");
+
+        assert!(psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .all(|txin| txin.previous_output != cpfp_outpoint));
+    }
+
+    #[test]
+    fn front_withdrawal_requires_p2tr_address() {
+        let mut wallet = operator_wallet();
+        fund(&mut wallet, 1, Amount::from_int_btc(1));
+
+        let p2wpkh_address = Address::p2wpkh(
+            &CompressedPublicKey(keypair(3).public_key()),
+            Network::Regtest,
+        );
+
+        assert!(matches!(
+            wallet.front_withdrawal(FEE_RATE, p2wpkh_address, Amount::from_sat(10_000), &[]),
+            Err(CreateTxError::NoRecipients)
+        ));
+    }
+}
@@ -123,6 +123,46 @@ where
         .expect("must have the correct size due to the computation above")
 }
 
+/// Reconstructs a WOTS signature from its compact form i.e., from the preimages of each digit.
+///
+/// The digits of the signature are recomputed from the signed `message` in the same layout that is
+/// expected by [`wots_to_byte_array`]: the nibbles of the message in reverse order (i.e., the MSB
+/// first) followed by the 4-digit checksum.
+///
+/// # Panics
+///
+/// If the number of message nibbles does not match the size of the signature.
+pub fn wots_from_compact<const TOTAL_SIZE: usize>(
+    message: &[u8],
+    compact_signature: [[u8; 20]; TOTAL_SIZE],
+) -> [([u8; 20], u8); TOTAL_SIZE] {
+    const CHECKSUM_SIZE: usize = 4;
+    const MAX_DIGIT: u32 = 0xf;
+
+    assert_eq!(
+        message.len() * 2 + CHECKSUM_SIZE,
+        TOTAL_SIZE,
+        "message size must match the signature size"
+    );
+
+    // [LSB, MSB, LSB, MSB, ...]
+    let nibs = message
+        .iter()
+        .flat_map(|byte| [byte & 0xf, byte >> 4])
+        .collect::<Vec<u8>>();
+
+    let checksum = MAX_DIGIT * nibs.len() as u32 - nibs.iter().map(|nib| *nib as u32).sum::<u32>();
+    // the checksum digits are in little-endian order, which is reversed along with the message.
+    let checksum_nibs = (0..CHECKSUM_SIZE)
+        .rev()
+        .map(|i| ((checksum >> (4 * i)) & MAX_DIGIT) as u8);
+
+    // [MSB, LSB, MSB, LSB, ..., checksum]
+    let digits = nibs.into_iter().rev().chain(checksum_nibs).collect::<Vec<u8>>();
+
+    std::array::from_fn(|i| (compact_signature[i], digits[i]))
+}
+
 #[cfg(test)]
 mod tests {
     use bitcoin::{
@@ -181,4 +221,32 @@ mod tests {
             "committed and extracted 256-bit data must match"
         );
     }
+
+    #[test]
+    fn test_wots_from_compact() {
+        let secret_str = "test_wots_from_compact".to_string();
+        let secret = hashes::sha256::Hash::hash(secret_str.as_bytes())
+            .to_byte_array()
+            .to_lower_hex_string();
+
+        let message_bytes = OsRng.gen::<[u8; HASH_LEN as usize]>();
+        let signature = wots_hash::get_signature(&secret, &message_bytes);
+        let compact_signature = signature.map(|(preimage, _)| preimage);
+
+        assert_eq!(
+            wots_from_compact(&message_bytes, compact_signature),
+            signature,
+            "reconstructed hash signature must match"
+        );
+
+        let message_bytes = OsRng.gen::<[u8; MSG_LEN as usize]>();
+        let signature = wots256::get_signature(&secret, &message_bytes);
+        let compact_signature = signature.map(|(preimage, _)| preimage);
+
+        assert_eq!(
+            wots_from_compact(&message_bytes, compact_signature),
+            signature,
+            "reconstructed 256-bit signature must match"
+        );
+    }
 }
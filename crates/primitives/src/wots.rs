@@ -8,6 +8,7 @@ use bitvm::{
     },
     signatures::wots_api::{wots256, wots_hash},
 };
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::scripts::{
     commitments::{
@@ -30,6 +31,25 @@ impl Wots256PublicKey {
     }
 }
 
+// serde does not support arrays of arbitrary length so the key is (de)serialized as a sequence of
+// its digits.
+impl Serialize for Wots256PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.as_slice().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Wots256PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let digits = Vec::<[u8; 20]>::deserialize(deserializer)?;
+        let num_digits = digits.len();
+
+        digits.try_into().map(Self).map_err(|_| {
+            de::Error::invalid_length(num_digits, &"the number of digits in a wots256 key")
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct WotsHashPublicKey(pub wots_hash::PublicKey);
 
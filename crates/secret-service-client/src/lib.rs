@@ -5,6 +5,7 @@
 
 pub mod musig2;
 pub mod p2p;
+pub mod policy;
 pub mod stakechain;
 pub mod wallet;
 
@@ -18,6 +19,7 @@ use std::{
 
 use musig2::{Musig2Client, Musig2FirstRound, Musig2SecondRound};
 use p2p::P2PClient;
+use policy::PolicyClient;
 pub use quinn::rustls;
 use quinn::{
     crypto::rustls::{NoInitialCipherSuite, QuicClientConfig},
@@ -29,6 +31,7 @@ use secret_service_proto::{
         traits::{Client, ClientError, SecretService},
         wire::{ClientMessage, ServerMessage},
     },
+    v2,
     wire::{
         ArchivedVersionedServerMessage, LengthUint, VersionedClientMessage, VersionedServerMessage,
         WireMessage,
@@ -114,6 +117,12 @@ impl SecretServiceClient {
             conn,
         })
     }
+
+    /// Creates an instance of the [`PolicyClient`] to register the transactions the secret
+    /// service may sign.
+    pub fn policy_registry(&self) -> PolicyClient {
+        PolicyClient::new(self.conn.clone(), self.config.clone())
+    }
 }
 
 impl SecretService<Client, Musig2FirstRound, Musig2SecondRound> for SecretServiceClient {
@@ -159,10 +168,32 @@ pub async fn make_v1_req(
     msg: ClientMessage,
     timeout_dur: Duration,
 ) -> Result<ServerMessage, ClientError> {
+    match make_req(conn, VersionedClientMessage::V1(msg), timeout_dur).await? {
+        VersionedServerMessage::V1(msg) => Ok(msg),
+        _ => Err(ClientError::WrongVersion),
+    }
+}
+
+/// Makes a v2 secret service request via QUIC.
+pub async fn make_v2_req(
+    conn: &Connection,
+    msg: v2::wire::ClientMessage,
+    timeout_dur: Duration,
+) -> Result<v2::wire::ServerMessage, ClientError> {
+    match make_req(conn, VersionedClientMessage::V2(msg), timeout_dur).await? {
+        VersionedServerMessage::V2(msg) => Ok(msg),
+        _ => Err(ClientError::WrongVersion),
+    }
+}
+
+/// Makes a secret service request of any version via QUIC.
+async fn make_req(
+    conn: &Connection,
+    msg: VersionedClientMessage,
+    timeout_dur: Duration,
+) -> Result<VersionedServerMessage, ClientError> {
     let (mut tx, mut rx) = conn.open_bi().await.map_err(ClientError::ConnectionError)?;
-    let (len_bytes, msg_bytes) = VersionedClientMessage::V1(msg)
-        .serialize()
-        .map_err(ClientError::SerializationError)?;
+    let (len_bytes, msg_bytes) = msg.serialize().map_err(ClientError::SerializationError)?;
     timeout(timeout_dur, tx.write_all(&len_bytes))
         .await
         .map_err(|_| ClientError::Timeout)?
@@ -191,8 +222,5 @@ pub async fn make_v1_req(
     let archived = rkyv::access::<ArchivedVersionedServerMessage, rancor::Error>(&buf)
         .map_err(ClientError::DeserializationError)?;
 
-    let VersionedServerMessage::V1(msg) =
-        deserialize(archived).map_err(ClientError::DeserializationError)?;
-
-    Ok(msg)
+    deserialize(archived).map_err(ClientError::DeserializationError)
 }
@@ -8,16 +8,22 @@ use musig2::{
     AggNonce, LiftedSignature, PubNonce,
 };
 use quinn::Connection;
-use secret_service_proto::v1::{
-    traits::{
-        Client, ClientError, Musig2SessionId, Musig2Signer, Musig2SignerFirstRound,
-        Musig2SignerSecondRound, Origin, SignerIdxOutOfBounds,
+use secret_service_proto::{
+    v1::{
+        traits::{
+            Client, ClientError, Musig2SessionId, Musig2Signer, Musig2SignerFirstRound,
+            Musig2SignerSecondRound, Origin, SignerIdxOutOfBounds,
+        },
+        wire::{ClientMessage, ServerMessage},
+    },
+    v2::{
+        self,
+        traits::{Musig2FinalizeTxError, Musig2TxSignerFirstRound, SigningRequest},
     },
-    wire::{ClientMessage, ServerMessage},
 };
 use strata_bridge_primitives::scripts::taproot::TaprootWitness;
 
-use crate::{make_v1_req, Config};
+use crate::{make_v1_req, make_v2_req, Config};
 
 /// MuSig2 client.
 #[derive(Debug, Clone)]
@@ -167,6 +173,30 @@ impl Musig2SignerFirstRound<Client, Musig2SecondRound> for Musig2FirstRound {
     }
 }
 
+impl Musig2TxSignerFirstRound<Client, Musig2SecondRound> for Musig2FirstRound {
+    async fn finalize_tx(
+        self,
+        request: SigningRequest,
+    ) -> <Client as Origin>::Container<Result<Musig2SecondRound, Musig2FinalizeTxError>> {
+        let msg = v2::wire::ClientMessage::Musig2FirstRoundFinalizeTx {
+            session_id: self.session_id,
+            request: request.into(),
+        };
+        let res = make_v2_req(&self.connection, msg, self.config.timeout).await?;
+        let v2::wire::ServerMessage::Musig2FirstRoundFinalizeTx(maybe_err) = res else {
+            return Err(ClientError::WrongV2Message(res.into()));
+        };
+        Ok(match maybe_err {
+            Some(e) => Err(e),
+            None => Ok(Musig2SecondRound {
+                session_id: self.session_id,
+                connection: self.connection,
+                config: self.config,
+            }),
+        })
+    }
+}
+
 /// The second round of the MuSig2 protocol.
 #[derive(Debug, Clone)]
 pub struct Musig2SecondRound {
@@ -0,0 +1,61 @@
+//! Signing policy registry client
+
+use std::sync::Arc;
+
+use bitcoin::{hashes::Hash, Txid};
+use quinn::Connection;
+use secret_service_proto::{
+    v1::traits::{Client, ClientError, Origin},
+    v2::{
+        self,
+        traits::{PolicyRegistry, PolicyViolation},
+    },
+};
+
+use crate::{make_v2_req, Config};
+
+/// Signing policy registry client.
+#[derive(Debug, Clone)]
+pub struct PolicyClient {
+    /// QUIC connection to the server.
+    conn: Connection,
+
+    /// Configuration for the client.
+    config: Arc<Config>,
+}
+
+impl PolicyClient {
+    /// Creates a new policy client with an existing QUIC connection and configuration.
+    pub fn new(conn: Connection, config: Arc<Config>) -> Self {
+        Self { conn, config }
+    }
+}
+
+impl PolicyRegistry<Client> for PolicyClient {
+    async fn allow_txids(
+        &self,
+        deposit_txid: Txid,
+        txids: Vec<Txid>,
+    ) -> <Client as Origin>::Container<Result<(), PolicyViolation>> {
+        let msg = v2::wire::ClientMessage::PolicyAllowTxids {
+            deposit_txid: deposit_txid.to_byte_array(),
+            txids: txids.iter().map(|txid| txid.to_byte_array()).collect(),
+        };
+        let res = make_v2_req(&self.conn, msg, self.config.timeout).await?;
+        let v2::wire::ServerMessage::PolicyAllowTxids(registered) = res else {
+            return Err(ClientError::WrongV2Message(res.into()));
+        };
+        Ok(registered)
+    }
+
+    async fn forget_deposit(&self, deposit_txid: Txid) -> <Client as Origin>::Container<()> {
+        let msg = v2::wire::ClientMessage::PolicyForgetDeposit {
+            deposit_txid: deposit_txid.to_byte_array(),
+        };
+        let res = make_v2_req(&self.conn, msg, self.config.timeout).await?;
+        let v2::wire::ServerMessage::PolicyForgetDeposit = res else {
+            return Err(ClientError::WrongV2Message(res.into()));
+        };
+        Ok(())
+    }
+}
@@ -2,15 +2,21 @@
 
 use std::sync::Arc;
 
-use bitcoin::{hashes::Hash, TapNodeHash, XOnlyPublicKey};
+use bitcoin::{consensus, hashes::Hash, taproot, TapNodeHash, Transaction, XOnlyPublicKey};
 use musig2::secp256k1::schnorr::Signature;
 use quinn::Connection;
-use secret_service_proto::v1::{
-    traits::{Client, ClientError, Origin, WalletSigner},
-    wire::{ClientMessage, ServerMessage},
+use secret_service_proto::{
+    v1::{
+        traits::{Client, ClientError, Origin, WalletSigner},
+        wire::{ClientMessage, ServerMessage},
+    },
+    v2::{
+        self,
+        traits::{PolicyViolation, SigningRequest, TxSigner, TxidSigner},
+    },
 };
 
-use crate::{make_v1_req, Config};
+use crate::{make_v1_req, make_v2_req, Config};
 
 /// General wallet signer client.
 #[derive(Debug, Clone)]
@@ -71,6 +77,44 @@ impl WalletSigner<Client> for GeneralWalletClient {
     }
 }
 
+impl TxSigner<Client> for GeneralWalletClient {
+    async fn sign_tx(
+        &self,
+        request: SigningRequest,
+    ) -> <Client as Origin>::Container<Result<taproot::Signature, PolicyViolation>> {
+        let sighash_type = request.sighash_type;
+        let msg = v2::wire::ClientMessage::GeneralWalletSignTx {
+            request: request.into(),
+        };
+        let res = make_v2_req(&self.conn, msg, self.config.timeout).await?;
+        let v2::wire::ServerMessage::GeneralWalletSignTx(res) = res else {
+            return Err(ClientError::WrongV2Message(res.into()));
+        };
+        Ok(match res {
+            Ok(sig) => Ok(taproot::Signature {
+                signature: Signature::from_slice(&sig).map_err(|_| ClientError::BadData)?,
+                sighash_type,
+            }),
+            Err(violation) => Err(violation),
+        })
+    }
+}
+
+impl TxidSigner<Client> for GeneralWalletClient {
+    async fn sign_txid(&self, tx: Transaction) -> <Client as Origin>::Container<Signature> {
+        let msg = v2::wire::ClientMessage::GeneralWalletSignTxid {
+            tx: consensus::serialize(&tx),
+        };
+        let res = make_v2_req(&self.conn, msg, self.config.timeout).await?;
+        match res {
+            v2::wire::ServerMessage::GeneralWalletSignTxid { sig } => {
+                Signature::from_slice(&sig).map_err(|_| ClientError::BadData)
+            }
+            _ => Err(ClientError::WrongV2Message(res.into())),
+        }
+    }
+}
+
 /// Stakechain wallet signer client.
 #[derive(Debug, Clone)]
 pub struct StakechainWalletClient {
@@ -129,3 +173,26 @@ impl WalletSigner<Client> for StakechainWalletClient {
         }
     }
 }
+
+impl TxSigner<Client> for StakechainWalletClient {
+    async fn sign_tx(
+        &self,
+        request: SigningRequest,
+    ) -> <Client as Origin>::Container<Result<taproot::Signature, PolicyViolation>> {
+        let sighash_type = request.sighash_type;
+        let msg = v2::wire::ClientMessage::StakechainWalletSignTx {
+            request: request.into(),
+        };
+        let res = make_v2_req(&self.conn, msg, self.config.timeout).await?;
+        let v2::wire::ServerMessage::StakechainWalletSignTx(res) = res else {
+            return Err(ClientError::WrongV2Message(res.into()));
+        };
+        Ok(match res {
+            Ok(sig) => Ok(taproot::Signature {
+                signature: Signature::from_slice(&sig).map_err(|_| ClientError::BadData)?,
+                sighash_type,
+            }),
+            Err(violation) => Err(violation),
+        })
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use bitcoin::{hashes::Hash, Txid};
 use quinn::Connection;
 use secret_service_proto::v1::{
-    traits::{Client, ClientError, Origin, WotsSigner},
+    traits::{Client, ClientError, Origin, WotsSignError, WotsSigner},
     wire::{ClientMessage, ServerMessage},
 };
 
@@ -106,7 +106,7 @@ impl WotsSigner<Client> for WotsClient {
         vout: u32,
         index: u32,
         msg: &[u8; 16],
-    ) -> <Client as Origin>::Container<[u8; 20 * 36]> {
+    ) -> <Client as Origin>::Container<Result<[u8; 20 * 36], WotsSignError>> {
         let wire_msg = ClientMessage::WotsGet128Signature {
             index,
             prestake_vout: vout,
@@ -114,7 +114,7 @@ impl WotsSigner<Client> for WotsClient {
             msg: *msg,
         };
         let res = make_v1_req(&self.conn, wire_msg, self.config.timeout).await?;
-        let ServerMessage::WotsGet128Signature { sig } = res else {
+        let ServerMessage::WotsGet128Signature(sig) = res else {
             return Err(ClientError::WrongMessage(res.into()));
         };
         Ok(sig)
@@ -126,7 +126,7 @@ impl WotsSigner<Client> for WotsClient {
         vout: u32,
         index: u32,
         msg: &[u8; 32],
-    ) -> <Client as Origin>::Container<[u8; 20 * 68]> {
+    ) -> <Client as Origin>::Container<Result<[u8; 20 * 68], WotsSignError>> {
         let wire_msg = ClientMessage::WotsGet256Signature {
             index,
             prestake_vout: vout,
@@ -134,7 +134,7 @@ impl WotsSigner<Client> for WotsClient {
             msg: *msg,
         };
         let res = make_v1_req(&self.conn, wire_msg, self.config.timeout).await?;
-        let ServerMessage::WotsGet256Signature { sig } = res else {
+        let ServerMessage::WotsGet256Signature(sig) = res else {
             return Err(ClientError::WrongMessage(res.into()));
         };
         Ok(sig)
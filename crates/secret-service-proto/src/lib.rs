@@ -2,4 +2,6 @@
 
 #[allow(missing_docs)] // because lints wouldn't shut up about rkyv's Archive proc macro
 pub mod v1;
+#[allow(missing_docs)] // same as above
+pub mod v2;
 pub mod wire;
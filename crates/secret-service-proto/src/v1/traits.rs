@@ -286,21 +286,42 @@ pub trait WotsSigner<O: Origin>: Send {
         index: u32,
     ) -> impl Future<Output = O::Container<[u8; 20 * 68]>> + Send;
 
+    /// Signs a 128-bit message with the WOTS key for a given prestake transaction ID, prestake
+    /// vout, and WOTS index.
+    ///
+    /// A WOTS key must only ever sign a single message, so this fails if the key already signed a
+    /// different one.
     fn get_128_signature(
         &self,
         prestake_txid: Txid,
         prestake_vout: u32,
         index: u32,
         msg: &[u8; 16],
-    ) -> impl Future<Output = O::Container<[u8; 20 * 36]>> + Send;
+    ) -> impl Future<Output = O::Container<Result<[u8; 20 * 36], WotsSignError>>> + Send;
 
+    /// Signs a 256-bit message with the WOTS key for a given prestake transaction ID, prestake
+    /// vout, and WOTS index.
+    ///
+    /// A WOTS key must only ever sign a single message, so this fails if the key already signed a
+    /// different one.
     fn get_256_signature(
         &self,
         prestake_txid: Txid,
         prestake_vout: u32,
         index: u32,
         msg: &[u8; 32],
-    ) -> impl Future<Output = O::Container<[u8; 20 * 68]>> + Send;
+    ) -> impl Future<Output = O::Container<Result<[u8; 20 * 68], WotsSignError>>> + Send;
+}
+
+/// Error returned when the secret service refuses to produce a WOTS signature.
+#[derive(Debug, Archive, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum WotsSignError {
+    /// The key already signed a different message. Signing another one would allow anyone to
+    /// forge signatures with this key.
+    KeyAlreadyUsed,
+
+    /// The use of the key could not be durably recorded, so the signature was withheld.
+    RecordFailed,
 }
 
 /// The Stake Chain preimages are used to generate deterministic preimages for the Stake Chain
@@ -370,6 +391,9 @@ pub enum ClientError {
     /// The server sent a message that was not expected.
     WrongMessage(Box<ServerMessage>),
 
+    /// The server sent a v2 message that was not expected.
+    WrongV2Message(Box<crate::v2::wire::ServerMessage>),
+
     /// The server sent a message with an unexpected protocol version.
     WrongVersion,
 }
@@ -9,7 +9,7 @@ use musig2::errors::{RoundContributionError, RoundFinalizeError};
 use rkyv::{with::Map, Archive, Deserialize, Serialize};
 use strata_bridge_primitives::scripts::taproot::TaprootWitness;
 
-use super::traits::{Musig2SessionId, SignerIdxOutOfBounds};
+use super::traits::{Musig2SessionId, SignerIdxOutOfBounds, WotsSignError};
 
 /// Various messages the server can send to the client.
 #[derive(Debug, Clone, Archive, Serialize, Deserialize)]
@@ -24,6 +24,10 @@ pub enum ServerMessage {
     /// Check the server logs for debugging details.
     OpaqueServerError,
 
+    /// The server is configured to reject signing raw digests, use the policy-checked v2
+    /// signing requests instead.
+    RawDigestSigningDisabled,
+
     /// Response for [`WalletSigner::sign`](super::traits::WalletSigner::sign).
     GeneralWalletSign {
         /// Schnorr signature for a certain message.
@@ -171,11 +175,17 @@ pub enum ServerMessage {
 
     /// Response for
     /// [`WotsSigner::get_128_signature`](super::traits::WotsSigner::get_128_signature).
-    WotsGet128Signature { sig: [u8; 20 * 36] },
+    WotsGet128Signature(
+        /// The signature, or the reason the server refused to produce it.
+        Result<[u8; 20 * 36], WotsSignError>,
+    ),
 
     /// Response for
     /// [`WotsSigner::get_256_signature`](super::traits::WotsSigner::get_256_signature).
-    WotsGet256Signature { sig: [u8; 20 * 68] },
+    WotsGet256Signature(
+        /// The signature, or the reason the server refused to produce it.
+        Result<[u8; 20 * 68], WotsSignError>,
+    ),
 
     /// Response for
     /// [`StakeChainPreimages::get_preimg`](super::traits::StakeChainPreimages::get_preimg).
@@ -0,0 +1,8 @@
+//! V2 secret service
+//!
+//! Instead of signing arbitrary digests, clients of the v2 signing API submit the unsigned
+//! transaction along with the outputs it spends. The secret service recomputes the sighash itself
+//! and checks the transaction against its signing policy before producing a signature.
+
+pub mod traits;
+pub mod wire;
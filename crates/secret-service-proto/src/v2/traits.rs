@@ -0,0 +1,330 @@
+//! The traits that make up the secret service's v2 signing interfaces
+
+use std::{fmt, future::Future};
+
+use bitcoin::{
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, LeafVersion},
+    Amount, TapLeafHash, TapNodeHash, TapSighash, TapSighashType, Transaction, TxOut, Txid,
+};
+use musig2::{errors::RoundFinalizeError, secp256k1::schnorr};
+use rkyv::{Archive, Deserialize, Serialize};
+use strata_bridge_primitives::scripts::taproot::TaprootWitness;
+
+use crate::v1::traits::Origin;
+
+/// Wallet signers that sign inputs of transactions instead of arbitrary digests.
+///
+/// This is the policy-checked counterpart of
+/// [`WalletSigner`](crate::v1::traits::WalletSigner).
+pub trait TxSigner<O: Origin>: Send {
+    /// Signs the input of the transaction described by the `request` with the operator's
+    /// [`SecretKey`](musig2::secp256k1::SecretKey).
+    ///
+    /// Returns a [`PolicyViolation`] if the secret service refuses to sign the transaction.
+    fn sign_tx(
+        &self,
+        request: SigningRequest,
+    ) -> impl Future<Output = O::Container<Result<taproot::Signature, PolicyViolation>>> + Send;
+}
+
+/// Wallet signers that sign the txids of transactions.
+///
+/// Unlike a raw digest, a txid is the hash of an actual transaction and thus can't be the sighash
+/// of an input, so its signature can't be used to spend the wallet's funds.
+pub trait TxidSigner<O: Origin>: Send {
+    /// Signs the txid of the `tx` with the operator's untweaked
+    /// [`SecretKey`](musig2::secp256k1::SecretKey).
+    fn sign_txid(
+        &self,
+        tx: Transaction,
+    ) -> impl Future<Output = O::Container<schnorr::Signature>> + Send;
+}
+
+/// The policy-checked counterpart of
+/// [`Musig2SignerFirstRound::finalize`](crate::v1::traits::Musig2SignerFirstRound::finalize).
+pub trait Musig2TxSignerFirstRound<O: Origin, SecondRound>: Send + Sync {
+    /// Finishes the first round once all nonces are received, creating a partial signature for the
+    /// input of the transaction described by the `request`.
+    ///
+    /// The tweak used is the one the session was created with, so the
+    /// [`SpendPath::KeySpend::merkle_root`] of the `request` is ignored.
+    ///
+    /// Like its v1 counterpart, this method consumes the `FirstRound` to avoid accidentally
+    /// reusing a secret nonce.
+    fn finalize_tx(
+        self,
+        request: SigningRequest,
+    ) -> impl Future<Output = O::Container<Result<SecondRound, Musig2FinalizeTxError>>> + Send;
+}
+
+/// Registers the transactions the secret service may sign with the N-of-N key.
+///
+/// This is how the transactions of the peg-out graphs, which are only known at runtime, are
+/// allowed by the signing policy. MuSig2 sessions are only ever finalized for registered
+/// transactions. Registering a transaction doesn't exempt it from the scripts the wallet signers
+/// are allowed to pay to.
+///
+/// Transactions are registered on behalf of the contract of a deposit so that they can all be
+/// forgotten at once when the contract is over. The server caps how many transactions can be
+/// registered at once.
+pub trait PolicyRegistry<O: Origin>: Send {
+    /// Allows signing the transactions with the given txids for the contract of the given
+    /// deposit.
+    ///
+    /// None of the transactions are registered if that would exceed the cap of the server.
+    fn allow_txids(
+        &self,
+        deposit_txid: Txid,
+        txids: Vec<Txid>,
+    ) -> impl Future<Output = O::Container<Result<(), PolicyViolation>>> + Send;
+
+    /// Stops allowing signing the transactions registered for the contract of the given deposit.
+    fn forget_deposit(&self, deposit_txid: Txid) -> impl Future<Output = O::Container<()>> + Send;
+}
+
+/// How the input being signed for is spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendPath {
+    /// Key path spend, signed with the key tweaked with the given merkle root.
+    KeySpend {
+        /// Merkle root of the script tree of the output, if any.
+        merkle_root: Option<TapNodeHash>,
+    },
+
+    /// Key path spend of an output locked with the untweaked key, signed with the untweaked key.
+    UntweakedKeySpend,
+
+    /// Script path spend, signed with the untweaked key.
+    ScriptSpend {
+        /// Hash of the leaf being spent.
+        leaf_hash: TapLeafHash,
+    },
+}
+
+impl From<&TaprootWitness> for SpendPath {
+    fn from(witness: &TaprootWitness) -> Self {
+        match witness {
+            TaprootWitness::Key => SpendPath::KeySpend { merkle_root: None },
+            TaprootWitness::Tweaked { tweak } => SpendPath::KeySpend {
+                merkle_root: Some(*tweak),
+            },
+            TaprootWitness::Script { script_buf, .. } => SpendPath::ScriptSpend {
+                leaf_hash: TapLeafHash::from_script(script_buf, LeafVersion::TapScript),
+            },
+        }
+    }
+}
+
+/// A request to sign one of the inputs of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningRequest {
+    /// The unsigned transaction.
+    pub tx: Transaction,
+
+    /// The outputs spent by the inputs of the transaction, in the same order as the inputs.
+    pub prevouts: Vec<TxOut>,
+
+    /// Index of the input to sign.
+    pub input_index: usize,
+
+    /// How the input is spent.
+    pub spend_path: SpendPath,
+
+    /// The sighash type the signature commits to.
+    pub sighash_type: TapSighashType,
+}
+
+impl SigningRequest {
+    /// Computes the BIP 341 sighash of the input to sign.
+    ///
+    /// The sighash commits to the amounts and scripts of every prevout, so a client lying about
+    /// them only gets a signature that is invalid for the transaction it actually spends.
+    pub fn sighash(&self) -> Result<TapSighash, PolicyViolation> {
+        let n_inputs = self.tx.input.len() as u32;
+        if self.input_index >= self.tx.input.len() {
+            return Err(PolicyViolation::InputIndexOutOfBounds {
+                index: self.input_index as u32,
+                n_inputs,
+            });
+        }
+        if self.prevouts.len() != self.tx.input.len() {
+            return Err(PolicyViolation::PrevoutsMismatch {
+                n_prevouts: self.prevouts.len() as u32,
+                n_inputs,
+            });
+        }
+
+        let mut cache = SighashCache::new(&self.tx);
+        let prevouts = Prevouts::All(&self.prevouts);
+        match self.spend_path {
+            SpendPath::KeySpend { .. } | SpendPath::UntweakedKeySpend => cache
+                .taproot_key_spend_signature_hash(self.input_index, &prevouts, self.sighash_type),
+            SpendPath::ScriptSpend { leaf_hash } => cache.taproot_script_spend_signature_hash(
+                self.input_index,
+                &prevouts,
+                leaf_hash,
+                self.sighash_type,
+            ),
+        }
+        // the input index and the prevouts are checked above, so this can only fail because of a
+        // `SIGHASH_SINGLE` without a matching output.
+        .map_err(|_| PolicyViolation::UnsupportedSighashType {
+            sighash_type: self.sighash_type as u8,
+        })
+    }
+
+    /// Returns the fee the signature commits the transaction to pay, or `None` if its outputs
+    /// spend more than its prevouts.
+    ///
+    /// With `ANYONECANPAY`, the signature only commits to the amount of the input being signed,
+    /// so the amounts the client claims for the other prevouts can't be trusted. The fee is then
+    /// the part of the signed input's amount that isn't paid to the outputs, which is what the
+    /// signature lets go to fees no matter which other inputs end up in the transaction.
+    pub fn fee(&self) -> Option<Amount> {
+        let total = |txouts: &[TxOut]| {
+            txouts
+                .iter()
+                .try_fold(Amount::ZERO, |acc, txout| acc.checked_add(txout.value))
+        };
+        let outputs = total(&self.tx.output)?;
+
+        if self.is_anyone_can_pay() {
+            let signed_prevout = self.prevouts.get(self.input_index)?;
+            // the other inputs may cover the outputs, in which case no amount of the signed input
+            // goes to fees.
+            Some(
+                signed_prevout
+                    .value
+                    .checked_sub(outputs)
+                    .unwrap_or(Amount::ZERO),
+            )
+        } else {
+            total(&self.prevouts)?.checked_sub(outputs)
+        }
+    }
+
+    /// Whether the signature only commits to the input being signed.
+    fn is_anyone_can_pay(&self) -> bool {
+        matches!(
+            self.sighash_type,
+            TapSighashType::AllPlusAnyoneCanPay
+                | TapSighashType::NonePlusAnyoneCanPay
+                | TapSighashType::SinglePlusAnyoneCanPay
+        )
+    }
+}
+
+/// Reasons for the secret service to refuse signing a [`SigningRequest`].
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Serialize, Deserialize)]
+pub enum PolicyViolation {
+    /// The input to sign is not part of the transaction.
+    InputIndexOutOfBounds {
+        /// Index of the input to sign.
+        index: u32,
+
+        /// Number of inputs of the transaction.
+        n_inputs: u32,
+    },
+
+    /// The number of prevouts doesn't match the number of inputs of the transaction.
+    PrevoutsMismatch {
+        /// Number of prevouts in the request.
+        n_prevouts: u32,
+
+        /// Number of inputs of the transaction.
+        n_inputs: u32,
+    },
+
+    /// The sighash type doesn't commit to every output of the transaction.
+    UnsupportedSighashType {
+        /// Consensus encoding of the sighash type.
+        sighash_type: u8,
+    },
+
+    /// The outputs of the transaction spend more than its prevouts.
+    OutputsExceedInputs,
+
+    /// The transaction must be registered through the [`PolicyRegistry`] to be signed, but
+    /// isn't.
+    UnknownTransaction,
+
+    /// An output pays to a script that isn't allowed.
+    DisallowedOutput {
+        /// Index of the offending output.
+        vout: u32,
+    },
+
+    /// Registering the transactions would exceed the number of transactions that can be
+    /// registered at once.
+    TooManyTxids {
+        /// Maximum number of registered transactions.
+        max_txids: u32,
+    },
+
+    /// The transaction pays more fees than allowed.
+    FeeTooHigh {
+        /// Fee paid by the transaction, in sats.
+        fee: u64,
+
+        /// Maximum fee allowed, in sats.
+        max_fee: u64,
+    },
+
+    /// The transaction pays a higher fee rate than allowed.
+    FeeRateTooHigh {
+        /// Fee rate paid by the transaction, in sat/vB.
+        fee_rate: u64,
+
+        /// Maximum fee rate allowed, in sat/vB.
+        max_fee_rate: u64,
+    },
+}
+
+impl std::error::Error for PolicyViolation {}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputIndexOutOfBounds { index, n_inputs } => {
+                write!(f, "input {index} is out of bounds of {n_inputs} inputs")
+            }
+            Self::PrevoutsMismatch {
+                n_prevouts,
+                n_inputs,
+            } => write!(f, "got {n_prevouts} prevouts for {n_inputs} inputs"),
+            Self::UnsupportedSighashType { sighash_type } => {
+                write!(f, "unsupported sighash type {sighash_type:#04x}")
+            }
+            Self::OutputsExceedInputs => f.write_str("outputs spend more than the prevouts"),
+            Self::UnknownTransaction => f.write_str("the transaction is not registered"),
+            Self::DisallowedOutput { vout } => {
+                write!(f, "output {vout} pays to a disallowed script")
+            }
+            Self::TooManyTxids { max_txids } => {
+                write!(f, "cannot register more than {max_txids} transactions")
+            }
+            Self::FeeTooHigh { fee, max_fee } => {
+                write!(f, "fee of {fee} sats exceeds the cap of {max_fee} sats")
+            }
+            Self::FeeRateTooHigh {
+                fee_rate,
+                max_fee_rate,
+            } => write!(
+                f,
+                "fee rate of {fee_rate} sat/vB exceeds the cap of {max_fee_rate} sat/vB"
+            ),
+        }
+    }
+}
+
+/// Errors that may occur when finalizing the first round of a MuSig2 session over a
+/// [`SigningRequest`].
+#[derive(Debug, Clone, Archive, Serialize, Deserialize)]
+pub enum Musig2FinalizeTxError {
+    /// The secret service refused to sign the transaction.
+    PolicyViolation(PolicyViolation),
+
+    /// The first round could not be finalized.
+    RoundFinalize(#[rkyv(with = crate::v1::rkyv_wrappers::RoundFinalizeError)] RoundFinalizeError),
+}
@@ -0,0 +1,207 @@
+//! V2 wire protocol
+
+use bitcoin::{
+    consensus::{self, encode},
+    hashes::Hash,
+    TapLeafHash, TapNodeHash, TapSighashType,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::traits::{Musig2FinalizeTxError, PolicyViolation, SigningRequest, SpendPath};
+
+/// Various messages the server can send to the client.
+#[derive(Debug, Clone, Archive, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// The message the client sent was invalid.
+    InvalidClientMessage,
+
+    /// The server experienced an unexpected internal error while handling the
+    /// request.
+    ///
+    /// Check the server logs for debugging details.
+    OpaqueServerError,
+
+    /// Response for [`TxSigner::sign_tx`](super::traits::TxSigner::sign_tx).
+    GeneralWalletSignTx(
+        /// Schnorr signature for the input, or the reason the server refused to sign it.
+        Result<[u8; 64], PolicyViolation>,
+    ),
+
+    /// Response for [`TxSigner::sign_tx`](super::traits::TxSigner::sign_tx).
+    StakechainWalletSignTx(
+        /// Schnorr signature for the input, or the reason the server refused to sign it.
+        Result<[u8; 64], PolicyViolation>,
+    ),
+
+    /// Response for [`TxidSigner::sign_txid`](super::traits::TxidSigner::sign_txid).
+    GeneralWalletSignTxid {
+        /// Schnorr signature for the txid.
+        sig: [u8; 64],
+    },
+
+    /// Response for
+    /// [`Musig2TxSignerFirstRound::finalize_tx`](super::traits::Musig2TxSignerFirstRound::finalize_tx).
+    Musig2FirstRoundFinalizeTx(
+        /// Error indicating whether the server was unable to process the request.
+        Option<Musig2FinalizeTxError>,
+    ),
+
+    /// Response for [`PolicyRegistry::allow_txids`](super::traits::PolicyRegistry::allow_txids).
+    PolicyAllowTxids(
+        /// The reason the server refused to register the transactions, if it did.
+        Result<(), PolicyViolation>,
+    ),
+
+    /// Response for
+    /// [`PolicyRegistry::forget_deposit`](super::traits::PolicyRegistry::forget_deposit).
+    PolicyForgetDeposit,
+}
+
+/// Various messages the client can send to the server.
+#[derive(Debug, Clone, Archive, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Request for [`TxSigner::sign_tx`](super::traits::TxSigner::sign_tx).
+    GeneralWalletSignTx {
+        /// The input the client wants signed.
+        request: SerializableSigningRequest,
+    },
+
+    /// Request for [`TxSigner::sign_tx`](super::traits::TxSigner::sign_tx).
+    StakechainWalletSignTx {
+        /// The input the client wants signed.
+        request: SerializableSigningRequest,
+    },
+
+    /// Request for [`TxidSigner::sign_txid`](super::traits::TxidSigner::sign_txid).
+    GeneralWalletSignTxid {
+        /// Consensus encoding of the [`Transaction`](bitcoin::Transaction) whose txid is signed.
+        tx: Vec<u8>,
+    },
+
+    /// Request for
+    /// [`Musig2TxSignerFirstRound::finalize_tx`](super::traits::Musig2TxSignerFirstRound::finalize_tx).
+    Musig2FirstRoundFinalizeTx {
+        /// Session that this server is requesting for.
+        session_id: usize,
+
+        /// The input the client is signing.
+        request: SerializableSigningRequest,
+    },
+
+    /// Request for [`PolicyRegistry::allow_txids`](super::traits::PolicyRegistry::allow_txids).
+    PolicyAllowTxids {
+        /// Raw bytes of the [`Txid`](bitcoin::Txid) of the deposit the transactions belong to.
+        deposit_txid: [u8; 32],
+
+        /// Raw bytes of the [`Txid`](bitcoin::Txid)s to allow.
+        txids: Vec<[u8; 32]>,
+    },
+
+    /// Request for
+    /// [`PolicyRegistry::forget_deposit`](super::traits::PolicyRegistry::forget_deposit).
+    PolicyForgetDeposit {
+        /// Raw bytes of the [`Txid`](bitcoin::Txid) of the deposit to forget.
+        deposit_txid: [u8; 32],
+    },
+}
+
+/// Serializable version of [`SigningRequest`].
+#[derive(Debug, Clone, Archive, Serialize, Deserialize)]
+pub struct SerializableSigningRequest {
+    /// Consensus encoding of the unsigned [`Transaction`](bitcoin::Transaction).
+    pub tx: Vec<u8>,
+
+    /// Consensus encoding of the [`TxOut`](bitcoin::TxOut)s spent by the transaction.
+    pub prevouts: Vec<Vec<u8>>,
+
+    /// Index of the input to sign.
+    pub input_index: u32,
+
+    /// How the input is spent.
+    pub spend_path: SerializableSpendPath,
+
+    /// Consensus encoding of the [`TapSighashType`].
+    pub sighash_type: u8,
+}
+
+/// Serializable version of [`SpendPath`].
+#[derive(Debug, Clone, Archive, Serialize, Deserialize)]
+pub enum SerializableSpendPath {
+    /// Key path spend.
+    KeySpend {
+        /// Raw bytes of the merkle root [`TapNodeHash`].
+        merkle_root: Option<[u8; 32]>,
+    },
+
+    /// Key path spend with the untweaked key.
+    UntweakedKeySpend,
+
+    /// Script path spend.
+    ScriptSpend {
+        /// Raw bytes of the [`TapLeafHash`].
+        leaf_hash: [u8; 32],
+    },
+}
+
+impl From<SigningRequest> for SerializableSigningRequest {
+    fn from(request: SigningRequest) -> Self {
+        Self {
+            tx: consensus::serialize(&request.tx),
+            prevouts: request.prevouts.iter().map(consensus::serialize).collect(),
+            input_index: request.input_index as u32,
+            spend_path: request.spend_path.into(),
+            sighash_type: request.sighash_type as u8,
+        }
+    }
+}
+
+impl TryFrom<SerializableSigningRequest> for SigningRequest {
+    type Error = encode::Error;
+
+    fn try_from(value: SerializableSigningRequest) -> Result<Self, Self::Error> {
+        let tx = consensus::deserialize(&value.tx)?;
+        let prevouts = value
+            .prevouts
+            .iter()
+            .map(|txout| consensus::deserialize(txout))
+            .collect::<Result<_, _>>()?;
+        let sighash_type = TapSighashType::from_consensus_u8(value.sighash_type)
+            .map_err(|_| encode::Error::ParseFailed("invalid sighash type"))?;
+
+        Ok(Self {
+            tx,
+            prevouts,
+            input_index: value.input_index as usize,
+            spend_path: value.spend_path.into(),
+            sighash_type,
+        })
+    }
+}
+
+impl From<SpendPath> for SerializableSpendPath {
+    fn from(spend_path: SpendPath) -> Self {
+        match spend_path {
+            SpendPath::KeySpend { merkle_root } => SerializableSpendPath::KeySpend {
+                merkle_root: merkle_root.map(|root| root.to_raw_hash().to_byte_array()),
+            },
+            SpendPath::UntweakedKeySpend => SerializableSpendPath::UntweakedKeySpend,
+            SpendPath::ScriptSpend { leaf_hash } => SerializableSpendPath::ScriptSpend {
+                leaf_hash: leaf_hash.to_raw_hash().to_byte_array(),
+            },
+        }
+    }
+}
+
+impl From<SerializableSpendPath> for SpendPath {
+    fn from(spend_path: SerializableSpendPath) -> Self {
+        match spend_path {
+            SerializableSpendPath::KeySpend { merkle_root } => SpendPath::KeySpend {
+                merkle_root: merkle_root.map(TapNodeHash::from_byte_array),
+            },
+            SerializableSpendPath::UntweakedKeySpend => SpendPath::UntweakedKeySpend,
+            SerializableSpendPath::ScriptSpend { leaf_hash } => SpendPath::ScriptSpend {
+                leaf_hash: TapLeafHash::from_byte_array(leaf_hash),
+            },
+        }
+    }
+}
@@ -5,7 +5,7 @@ use rkyv::{
     Archive, Deserialize, Serialize,
 };
 
-use crate::v1;
+use crate::{v1, v2};
 
 trait WireMessageMarker:
     for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>
@@ -27,6 +27,9 @@ pub type LengthUint = u16;
 pub enum VersionedClientMessage {
     /// Version 1 of the client message.
     V1(v1::wire::ClientMessage),
+
+    /// Version 2 of the client message.
+    V2(v2::wire::ClientMessage),
 }
 
 impl WireMessageMarker for VersionedClientMessage {}
@@ -37,6 +40,9 @@ impl WireMessageMarker for VersionedClientMessage {}
 pub enum VersionedServerMessage {
     /// Version 1 of the server message.
     V1(v1::wire::ServerMessage),
+
+    /// Version 2 of the server message.
+    V2(v2::wire::ServerMessage),
 }
 
 impl WireMessageMarker for VersionedServerMessage {}
@@ -6,12 +6,14 @@
 //! for the secret service protocol.
 
 pub mod musig2_session_mgr;
+pub mod policy;
 
 use std::{io, marker::Sync, net::SocketAddr, sync::Arc};
 
-use bitcoin::{hashes::Hash, TapNodeHash, Txid, XOnlyPublicKey};
+use bitcoin::{consensus, hashes::Hash, TapNodeHash, Transaction, Txid, XOnlyPublicKey};
 use musig2::{errors::RoundFinalizeError, PartialSignature, PubNonce};
 use musig2_session_mgr::Musig2SessionManager;
+use policy::SigningPolicy;
 pub use quinn::rustls;
 use quinn::{
     crypto::rustls::{NoInitialCipherSuite, QuicServerConfig},
@@ -32,6 +34,10 @@ use secret_service_proto::{
         },
         wire::{ArchivedClientMessage, ServerMessage},
     },
+    v2::{
+        traits::{Musig2FinalizeTxError, PolicyViolation, SigningRequest, SpendPath},
+        wire::{ClientMessage as V2ClientMessage, ServerMessage as V2ServerMessage},
+    },
     wire::{ArchivedVersionedClientMessage, LengthUint, VersionedServerMessage, WireMessage},
 };
 use strata_bridge_primitives::scripts::taproot::TaprootWitness;
@@ -50,6 +56,9 @@ pub struct Config {
 
     /// The TLS configuration for the server.
     pub tls_config: rustls::ServerConfig,
+
+    /// The policy the v2 signing requests are checked against.
+    pub signing_policy: Arc<SigningPolicy>,
 }
 
 /// Runs the secret service server given the service and a server configuration.
@@ -80,7 +89,13 @@ where
             incoming.refuse();
         } else {
             tokio::spawn(
-                conn_handler(incoming, service.clone(), musig2_sm.clone()).instrument(span),
+                conn_handler(
+                    incoming,
+                    service.clone(),
+                    musig2_sm.clone(),
+                    c.signing_policy.clone(),
+                )
+                .instrument(span),
             );
         }
     }
@@ -92,6 +107,7 @@ async fn conn_handler<FirstRound, SecondRound, Service>(
     incoming: Incoming,
     service: Arc<Service>,
     musig2_sm: Arc<Mutex<Musig2SessionManager<FirstRound, SecondRound>>>,
+    signing_policy: Arc<SigningPolicy>,
 ) where
     FirstRound: Musig2SignerFirstRound<Server, SecondRound> + 'static,
     SecondRound: Musig2SignerSecondRound<Server> + 'static,
@@ -124,8 +140,13 @@ async fn conn_handler<FirstRound, SecondRound, Service>(
             request_manager(
                 tx,
                 tokio::spawn(
-                    request_handler(rx, service.clone(), musig2_sm.clone())
-                        .instrument(handler_span),
+                    request_handler(
+                        rx,
+                        service.clone(),
+                        musig2_sm.clone(),
+                        signing_policy.clone(),
+                    )
+                    .instrument(handler_span),
                 ),
             )
             .instrument(manager_span),
@@ -136,7 +157,7 @@ async fn conn_handler<FirstRound, SecondRound, Service>(
 /// Manages the stream of requests.
 async fn request_manager(
     mut tx: SendStream,
-    handler: JoinHandle<Result<ServerMessage, ReadExactError>>,
+    handler: JoinHandle<Result<VersionedServerMessage, ReadExactError>>,
 ) {
     let handler_res = match handler.await {
         Ok(r) => r,
@@ -148,7 +169,7 @@ async fn request_manager(
 
     match handler_res {
         Ok(msg) => {
-            let (len_bytes, msg_bytes) = match VersionedServerMessage::V1(msg).serialize() {
+            let (len_bytes, msg_bytes) = match msg.serialize() {
                 Ok(r) => r,
                 Err(e) => {
                     error!("failed to serialize response: {e:?}");
@@ -173,7 +194,8 @@ async fn request_handler<Service, FirstRound, SecondRound>(
     mut rx: RecvStream,
     service: Arc<Service>,
     musig2_sm: Arc<Mutex<Musig2SessionManager<FirstRound, SecondRound>>>,
-) -> Result<ServerMessage, ReadExactError>
+    signing_policy: Arc<SigningPolicy>,
+) -> Result<VersionedServerMessage, ReadExactError>
 where
     FirstRound: Musig2SignerFirstRound<Server, SecondRound>,
     SecondRound: Musig2SignerSecondRound<Server>,
@@ -191,8 +213,15 @@ where
 
     let msg = rkyv::access::<ArchivedVersionedClientMessage, Error>(&buf).unwrap();
     Ok(match msg {
+        ArchivedVersionedClientMessage::V1(req)
+            if signing_policy.rejects_raw_digests() && signs_raw_digest(req) =>
+        {
+            warn!("refusing to sign a raw digest");
+            VersionedServerMessage::V1(ServerMessage::RawDigestSigningDisabled)
+        }
+
         // this would be a separate function but tokio would start whining because !Sync
-        ArchivedVersionedClientMessage::V1(req) => match req {
+        ArchivedVersionedClientMessage::V1(req) => VersionedServerMessage::V1(match req {
             ArchivedClientMessage::GeneralWalletSign { digest, tweak } => {
                 let tweak = match tweak {
                     ArchivedOption::None => None,
@@ -550,7 +579,7 @@ where
                     .wots_signer()
                     .get_128_signature(prestake_txid, prestake_vout.into(), index.into(), msg)
                     .await;
-                ServerMessage::WotsGet128Signature { sig }
+                ServerMessage::WotsGet128Signature(sig)
             }
 
             ArchivedClientMessage::WotsGet256Signature {
@@ -564,7 +593,7 @@ where
                     .wots_signer()
                     .get_256_signature(prestake_txid, prestake_vout.into(), index.into(), msg)
                     .await;
-                ServerMessage::WotsGet256Signature { sig }
+                ServerMessage::WotsGet256Signature(sig)
             }
 
             ArchivedClientMessage::StakeChainGetPreimage {
@@ -582,6 +611,151 @@ where
                     .await;
                 ServerMessage::StakeChainGetPreimage { preimg }
             }
-        },
+        }),
+
+        ArchivedVersionedClientMessage::V2(req) => {
+            VersionedServerMessage::V2(match deserialize::<V2ClientMessage, rancor::Error>(req) {
+                Ok(req) => v2_request_handler(req, service, musig2_sm, &signing_policy).await,
+                Err(_) => V2ServerMessage::InvalidClientMessage,
+            })
+        }
     })
 }
+
+/// Whether the v1 `req` signs a raw digest, which can't be checked against the signing policy.
+fn signs_raw_digest(req: &ArchivedClientMessage) -> bool {
+    matches!(
+        req,
+        ArchivedClientMessage::GeneralWalletSign { .. }
+            | ArchivedClientMessage::GeneralWalletSignNoTweak { .. }
+            | ArchivedClientMessage::StakechainWalletSign { .. }
+            | ArchivedClientMessage::StakechainWalletSignNoTweak { .. }
+            | ArchivedClientMessage::Musig2FirstRoundFinalize { .. }
+    )
+}
+
+/// Handles the v2 requests, which are checked against the signing policy before being signed.
+async fn v2_request_handler<Service, FirstRound, SecondRound>(
+    req: V2ClientMessage,
+    service: Arc<Service>,
+    musig2_sm: Arc<Mutex<Musig2SessionManager<FirstRound, SecondRound>>>,
+    signing_policy: &SigningPolicy,
+) -> V2ServerMessage
+where
+    FirstRound: Musig2SignerFirstRound<Server, SecondRound>,
+    SecondRound: Musig2SignerSecondRound<Server>,
+    Service: SecretService<Server, FirstRound, SecondRound>,
+{
+    match req {
+        V2ClientMessage::GeneralWalletSignTx { request } => {
+            let Ok(request) = SigningRequest::try_from(request) else {
+                return V2ServerMessage::InvalidClientMessage;
+            };
+            let signer = service.general_wallet_signer();
+            V2ServerMessage::GeneralWalletSignTx(sign_tx(&signer, &request, signing_policy).await)
+        }
+
+        V2ClientMessage::StakechainWalletSignTx { request } => {
+            let Ok(request) = SigningRequest::try_from(request) else {
+                return V2ServerMessage::InvalidClientMessage;
+            };
+            let signer = service.stakechain_wallet_signer();
+            V2ServerMessage::StakechainWalletSignTx(
+                sign_tx(&signer, &request, signing_policy).await,
+            )
+        }
+
+        V2ClientMessage::GeneralWalletSignTxid { tx } => {
+            let Ok(tx) = consensus::deserialize::<Transaction>(&tx) else {
+                return V2ServerMessage::InvalidClientMessage;
+            };
+            let txid = tx.compute_txid();
+            let sig = service
+                .general_wallet_signer()
+                .sign_no_tweak(txid.as_byte_array())
+                .await;
+            V2ServerMessage::GeneralWalletSignTxid {
+                sig: sig.serialize(),
+            }
+        }
+
+        V2ClientMessage::Musig2FirstRoundFinalizeTx {
+            session_id,
+            request,
+        } => {
+            let Ok(request) = SigningRequest::try_from(request) else {
+                return V2ServerMessage::InvalidClientMessage;
+            };
+            let sighash = match signing_policy.check_musig2(&request) {
+                Ok(sighash) => sighash,
+                Err(violation) => {
+                    warn!("refusing to finalize musig2 session {session_id}: {violation}");
+                    return V2ServerMessage::Musig2FirstRoundFinalizeTx(Some(
+                        Musig2FinalizeTxError::PolicyViolation(violation),
+                    ));
+                }
+            };
+
+            let mut sm = musig2_sm.lock().await;
+            let r = sm
+                .transition_first_to_second_round(session_id, sighash.to_byte_array())
+                .await;
+
+            if let Err(e) = r {
+                use terrors::E3::*;
+                match e.narrow::<RoundFinalizeError, _>() {
+                    Ok(e) => V2ServerMessage::Musig2FirstRoundFinalizeTx(Some(
+                        Musig2FinalizeTxError::RoundFinalize(e),
+                    )),
+                    Err(e) => match e.as_enum() {
+                        A(_not_in_first_round) => V2ServerMessage::InvalidClientMessage,
+                        B(_out_of_range) => V2ServerMessage::InvalidClientMessage,
+                        C(_other_refs_active) => V2ServerMessage::OpaqueServerError,
+                    },
+                }
+            } else {
+                V2ServerMessage::Musig2FirstRoundFinalizeTx(None)
+            }
+        }
+
+        V2ClientMessage::PolicyAllowTxids {
+            deposit_txid,
+            txids,
+        } => V2ServerMessage::PolicyAllowTxids(signing_policy.allow_txids(
+            Txid::from_byte_array(deposit_txid),
+            txids.into_iter().map(Txid::from_byte_array),
+        )),
+
+        V2ClientMessage::PolicyForgetDeposit { deposit_txid } => {
+            signing_policy.forget_deposit(Txid::from_byte_array(deposit_txid));
+            V2ServerMessage::PolicyForgetDeposit
+        }
+    }
+}
+
+/// Signs the input of a [`SigningRequest`] with a wallet signer if it complies with the signing
+/// policy.
+async fn sign_tx<Signer: WalletSigner<Server>>(
+    signer: &Signer,
+    request: &SigningRequest,
+    signing_policy: &SigningPolicy,
+) -> Result<[u8; 64], PolicyViolation> {
+    let digest = signing_policy
+        .check(request)
+        .inspect_err(|violation| {
+            warn!(
+                "refusing to sign input {} of {}: {violation}",
+                request.input_index,
+                request.tx.compute_txid()
+            )
+        })?
+        .to_byte_array();
+
+    let sig = match request.spend_path {
+        SpendPath::KeySpend { merkle_root } => signer.sign(&digest, merkle_root).await,
+        SpendPath::UntweakedKeySpend | SpendPath::ScriptSpend { .. } => {
+            signer.sign_no_tweak(&digest).await
+        }
+    };
+    Ok(sig.serialize())
+}
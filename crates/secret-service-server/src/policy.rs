@@ -0,0 +1,230 @@
+//! The policy the v2 signing requests are checked against before the server signs them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::RwLock,
+};
+
+use bitcoin::{Amount, FeeRate, ScriptBuf, TapSighash, TapSighashType, Txid};
+use secret_service_proto::v2::traits::{PolicyViolation, SigningRequest};
+
+/// Default maximum number of transactions that can be registered at once.
+///
+/// This leaves room for hundreds of concurrent contracts with the graphs of a few dozen operators.
+pub const DEFAULT_MAX_REGISTERED_TXIDS: usize = 100_000;
+
+/// Signing policy of the secret service.
+///
+/// The wallet signers sign a transaction if each of its outputs pays to one of the allowed scripts,
+/// whether or not it was registered. The N-of-N key only signs the registered transactions, as the
+/// transactions of the peg-out graphs are. Either way, the fees a transaction pays must stay within
+/// the configured caps.
+///
+/// The v1 requests sign raw digests, which can't be checked against the policy. They are only
+/// served if the policy doesn't [reject them](Self::rejecting_raw_digests).
+///
+/// Transactions can be registered and forgotten while the server is running, which is why this is
+/// shared with the server rather than moved into it.
+#[derive(Debug, Default)]
+pub struct SigningPolicy {
+    /// Scripts the transactions are allowed to pay to, [`None`] if they may pay to any script.
+    allowed_scripts: Option<BTreeSet<ScriptBuf>>,
+
+    /// Txids of the registered transactions, by the txid of the deposit they were registered
+    /// for.
+    known_txids: RwLock<BTreeMap<Txid, BTreeSet<Txid>>>,
+
+    /// Maximum number of transactions that can be registered at once, across all deposits.
+    ///
+    /// [`DEFAULT_MAX_REGISTERED_TXIDS`] if [`None`].
+    max_registered_txids: Option<usize>,
+
+    /// Maximum fee a transaction may pay.
+    max_fee: Option<Amount>,
+
+    /// Maximum fee rate a transaction may pay.
+    max_fee_rate: Option<FeeRate>,
+
+    /// Whether the v1 requests signing raw digests are rejected.
+    reject_raw_digests: bool,
+}
+
+impl SigningPolicy {
+    /// Allows the transactions to pay to the given scripts.
+    ///
+    /// Once called, the wallet signers only sign transactions that pay to the allowed scripts.
+    pub fn with_allowed_scripts(mut self, scripts: impl IntoIterator<Item = ScriptBuf>) -> Self {
+        self.allowed_scripts
+            .get_or_insert_with(BTreeSet::new)
+            .extend(scripts);
+        self
+    }
+
+    /// Caps the fee a transaction may pay.
+    pub fn with_max_fee(mut self, max_fee: Amount) -> Self {
+        self.max_fee = Some(max_fee);
+        self
+    }
+
+    /// Caps the fee rate a transaction may pay.
+    ///
+    /// The fee rate is computed over the weight of the unsigned transaction, so it is
+    /// overestimated by the weight of the witnesses.
+    pub fn with_max_fee_rate(mut self, max_fee_rate: FeeRate) -> Self {
+        self.max_fee_rate = Some(max_fee_rate);
+        self
+    }
+
+    /// Caps the number of transactions that can be registered at once, across all deposits.
+    pub fn with_max_registered_txids(mut self, max_registered_txids: usize) -> Self {
+        self.max_registered_txids = Some(max_registered_txids);
+        self
+    }
+
+    /// Rejects the v1 requests signing raw digests, which bypass the policy.
+    pub fn rejecting_raw_digests(mut self) -> Self {
+        self.reject_raw_digests = true;
+        self
+    }
+
+    /// Whether the v1 requests signing raw digests are rejected.
+    pub fn rejects_raw_digests(&self) -> bool {
+        self.reject_raw_digests
+    }
+
+    /// Registers the transactions with the given txids for the contract of the given deposit.
+    ///
+    /// None of them are registered if that would exceed the cap on the registered transactions.
+    pub fn allow_txids(
+        &self,
+        deposit_txid: Txid,
+        txids: impl IntoIterator<Item = Txid>,
+    ) -> Result<(), PolicyViolation> {
+        let max_txids = self
+            .max_registered_txids
+            .unwrap_or(DEFAULT_MAX_REGISTERED_TXIDS);
+        let mut known_txids = self.known_txids.write().expect("policy lock poisoned");
+
+        let registered = known_txids.values().map(BTreeSet::len).sum::<usize>();
+        let deposit_txids = known_txids.get(&deposit_txid);
+        let new_txids = txids
+            .into_iter()
+            .filter(|txid| deposit_txids.is_none_or(|known| !known.contains(txid)))
+            .collect::<BTreeSet<_>>();
+        if registered + new_txids.len() > max_txids {
+            return Err(PolicyViolation::TooManyTxids {
+                max_txids: max_txids.try_into().unwrap_or(u32::MAX),
+            });
+        }
+
+        known_txids
+            .entry(deposit_txid)
+            .or_default()
+            .extend(new_txids);
+        Ok(())
+    }
+
+    /// Forgets the transactions registered for the contract of the given deposit.
+    pub fn forget_deposit(&self, deposit_txid: Txid) {
+        self.known_txids
+            .write()
+            .expect("policy lock poisoned")
+            .remove(&deposit_txid);
+    }
+
+    /// Whether the transaction with the given txid is registered.
+    fn is_known(&self, txid: &Txid) -> bool {
+        self.known_txids
+            .read()
+            .expect("policy lock poisoned")
+            .values()
+            .any(|txids| txids.contains(txid))
+    }
+
+    /// Checks the `request` against the policy and returns the sighash to sign if it complies.
+    pub fn check(&self, request: &SigningRequest) -> Result<TapSighash, PolicyViolation> {
+        // the other sighash types would let the outputs be changed after signing. the fee of
+        // `ANYONECANPAY` signatures only accounts for the signed input's amount, see
+        // `SigningRequest::fee`.
+        if !matches!(
+            request.sighash_type,
+            TapSighashType::Default | TapSighashType::All | TapSighashType::AllPlusAnyoneCanPay
+        ) {
+            return Err(PolicyViolation::UnsupportedSighashType {
+                sighash_type: request.sighash_type as u8,
+            });
+        }
+
+        let sighash = request.sighash()?;
+
+        // registering a transaction only allows the N-of-N key to sign it, see `check_musig2`,
+        // since any client of the server can register any txid.
+        if let Some(allowed_scripts) = &self.allowed_scripts {
+            if let Some(vout) = request
+                .tx
+                .output
+                .iter()
+                .position(|txout| !allowed_scripts.contains(&txout.script_pubkey))
+            {
+                return Err(PolicyViolation::DisallowedOutput { vout: vout as u32 });
+            }
+        }
+
+        self.check_fee(request)?;
+
+        Ok(sighash)
+    }
+
+    /// Checks a `request` to finalize a MuSig2 session against the policy and returns the sighash
+    /// to sign if it complies.
+    ///
+    /// The N-of-N key locks the funds of every deposit, so only registered transactions are
+    /// signed. Unlike with the wallets, their signatures may use `SINGLE`, which the peg-out graphs
+    /// rely on to let the challenger and the disprover add their own inputs and outputs. The value
+    /// these signatures don't commit to is distributed by the protocol, so the fee caps don't apply
+    /// to them.
+    pub fn check_musig2(&self, request: &SigningRequest) -> Result<TapSighash, PolicyViolation> {
+        if !self.is_known(&request.tx.compute_txid()) {
+            return Err(PolicyViolation::UnknownTransaction);
+        }
+
+        match request.sighash_type {
+            TapSighashType::Default | TapSighashType::All | TapSighashType::AllPlusAnyoneCanPay => {
+                let sighash = request.sighash()?;
+                self.check_fee(request)?;
+                Ok(sighash)
+            }
+            TapSighashType::Single | TapSighashType::SinglePlusAnyoneCanPay => request.sighash(),
+            sighash_type => Err(PolicyViolation::UnsupportedSighashType {
+                sighash_type: sighash_type as u8,
+            }),
+        }
+    }
+
+    /// Checks the fee committed to by the `request` against the fee caps.
+    fn check_fee(&self, request: &SigningRequest) -> Result<(), PolicyViolation> {
+        let fee = request.fee().ok_or(PolicyViolation::OutputsExceedInputs)?;
+
+        if let Some(max_fee) = self.max_fee {
+            if fee > max_fee {
+                return Err(PolicyViolation::FeeTooHigh {
+                    fee: fee.to_sat(),
+                    max_fee: max_fee.to_sat(),
+                });
+            }
+        }
+
+        if let Some(max_fee_rate) = self.max_fee_rate {
+            let weight = request.tx.weight().to_wu().max(1);
+            let fee_rate = FeeRate::from_sat_per_kwu(fee.to_sat() * 1000 / weight);
+            if fee_rate > max_fee_rate {
+                return Err(PolicyViolation::FeeRateTooHigh {
+                    fee_rate: fee_rate.to_sat_per_vb_ceil(),
+                    max_fee_rate: max_fee_rate.to_sat_per_vb_ceil(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use std::ops::{Deref, DerefMut};
 use alpen_bridge_params::stake_chain::StakeChainParams;
 use bitcoin::{hashes::sha256, OutPoint, XOnlyPublicKey};
 use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
 use strata_bridge_connectors::prelude::ConnectorCpfp;
 use strata_bridge_primitives::build_context::BuildContext;
 
@@ -152,7 +153,7 @@ impl DerefMut for StakeChain {
 /// advancing the [`StakeChain`].
 ///
 /// The staking amount and `ΔS` relative timelock interval are consensus parameters.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StakeChainInputs {
     /// Operator's public key.
     pub operator_pubkey: XOnlyPublicKey,
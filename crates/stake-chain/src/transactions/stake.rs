@@ -28,7 +28,7 @@ use crate::{
 
 /// The metadata required to create a [`StakeTx`] transaction in the stake chain (except the first
 /// stake transaction).
-#[derive(Debug, Clone, Copy, Eq)]
+#[derive(Debug, Clone, Copy, Eq, Serialize, Deserialize)]
 pub struct StakeTxData {
     /// The [`OutPoint`] used to fund the dust outputs for the tx-graph for the given stake
     /// transaction.
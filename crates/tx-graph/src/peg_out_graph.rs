@@ -5,8 +5,13 @@ use core::fmt;
 use std::{marker::PhantomData, mem::MaybeUninit};
 
 use alpen_bridge_params::{connectors::*, prelude::StakeChainParams, tx_graph::PegOutGraphParams};
-use bitcoin::{hashes::sha256, relative, OutPoint, Txid};
-use secp256k1::XOnlyPublicKey;
+use bitcoin::{
+    hashes::sha256,
+    relative,
+    sighash::{Prevouts, SighashCache},
+    OutPoint, TapSighashType, Transaction, TxOut, Txid,
+};
+use secp256k1::{Message, XOnlyPublicKey};
 use serde::{
     de::{SeqAccess, Visitor},
     ser::SerializeTuple,
@@ -16,6 +21,7 @@ use strata_bridge_connectors::prelude::*;
 use strata_bridge_primitives::{
     build_context::BuildContext,
     constants::*,
+    scripts::taproot::{create_message_hash, TaprootWitness},
     wots::{self, Groth16PublicKeys},
 };
 use tracing::debug;
@@ -187,6 +193,12 @@ pub struct PegOutGraph {
     /// The transaction used to reimburse operators when no challenge occurs.
     pub payout_optimistic: PayoutOptimisticTx,
 
+    /// The unfunded transaction used to challenge the claim.
+    ///
+    /// The N-of-N input in this transaction is signed with `SINGLE|ANYONECANPAY` so that any
+    /// verifier can fund and publish it.
+    pub challenge_tx: ChallengeTx,
+
     /// The assert chain that commits to the proof of a valid claim.
     pub assert_chain: AssertChain,
 
@@ -290,6 +302,17 @@ impl PegOutGraph {
             connectors.connector_cpfp,
         );
 
+        let challenge_input = ChallengeTxInput {
+            claim_outpoint: OutPoint {
+                txid: claim_txid,
+                vout: 1,
+            },
+            challenge_amt: graph_params.challenge_cost,
+            operator_pubkey: input.operator_pubkey,
+            network: context.network(),
+        };
+        let challenge_tx = ChallengeTx::new(challenge_input, connectors.claim_out_1);
+
         let assert_chain_data = AssertChainData {
             pre_assert_data: PreAssertData {
                 claim_txid,
@@ -381,6 +404,7 @@ impl PegOutGraph {
             Self {
                 claim_tx,
                 payout_optimistic,
+                challenge_tx,
                 assert_chain,
                 payout_tx,
                 disprove_tx,
@@ -408,6 +432,119 @@ impl PegOutGraph {
                 .collect(),
         }
     }
+
+    /// Returns all the inputs in the graph that must be presigned with the N-of-N MuSig2 key.
+    ///
+    /// The inputs are returned in a deterministic order so that the nonces and partial signatures
+    /// exchanged between operators can be matched against the inputs they belong to.
+    pub fn musig_inputs(&self) -> Vec<MusigInput> {
+        let mut inputs = Vec::new();
+
+        let payout_optimistic_inputs = self.payout_optimistic.witnesses().len();
+        inputs.extend(musig_inputs(
+            &self.payout_optimistic,
+            &vec![TapSighashType::Default; payout_optimistic_inputs],
+        ));
+
+        inputs.extend(musig_inputs(
+            &self.challenge_tx,
+            &[ConnectorC1Path::Challenge(()).get_sighash_type()],
+        ));
+
+        inputs.extend(musig_inputs(
+            &self.assert_chain.pre_assert,
+            &[TapSighashType::Default],
+        ));
+
+        let post_assert_inputs = self.assert_chain.post_assert.witnesses().len();
+        inputs.extend(musig_inputs(
+            &self.assert_chain.post_assert,
+            &vec![TapSighashType::Default; post_assert_inputs],
+        ));
+
+        let payout_inputs = self.payout_tx.witnesses().len();
+        inputs.extend(musig_inputs(
+            &self.payout_tx,
+            &vec![TapSighashType::Default; payout_inputs],
+        ));
+
+        // only the stake input is presigned, the rest is only known at disprove time.
+        inputs.extend(musig_inputs(&self.disprove_tx, &[TapSighashType::Single]));
+
+        for slash_stake_tx in &self.slash_stake_txs {
+            inputs.extend(musig_inputs(
+                slash_stake_tx,
+                &[TapSighashType::Single, TapSighashType::Single],
+            ));
+        }
+
+        inputs
+    }
+}
+
+/// An input in the [`PegOutGraph`] that must be presigned with the N-of-N MuSig2 key.
+#[derive(Debug, Clone)]
+pub struct MusigInput {
+    /// The txid of the transaction that contains this input.
+    pub txid: Txid,
+
+    /// The index of this input in the transaction.
+    pub input_index: u32,
+
+    /// The witness type for this input which determines the tweak applied to the aggregated key.
+    pub witness: TaprootWitness,
+
+    /// The sighash type that the signature commits to.
+    pub sighash_type: TapSighashType,
+
+    /// The message that must be signed.
+    pub sighash: Message,
+
+    /// The unsigned transaction that contains this input.
+    pub tx: Transaction,
+
+    /// The outputs spent by the inputs of the transaction, in the same order as the inputs.
+    pub prevouts: Vec<TxOut>,
+}
+
+/// Computes the [`MusigInput`]s for the first `sighash_types.len()` inputs of a covenant
+/// transaction.
+fn musig_inputs(tx: &impl CovenantTx, sighash_types: &[TapSighashType]) -> Vec<MusigInput> {
+    let unsigned_tx = &tx.psbt().unsigned_tx;
+    let txid = unsigned_tx.compute_txid();
+    let mut sighash_cache = SighashCache::new(unsigned_tx);
+    let prevouts = match tx.prevouts() {
+        Prevouts::All(prevouts) => prevouts.to_vec(),
+        Prevouts::One(..) => {
+            unreachable!("presigned covenant transactions know all their prevouts")
+        }
+    };
+
+    tx.witnesses()
+        .iter()
+        .zip(sighash_types)
+        .enumerate()
+        .map(|(input_index, (witness, sighash_type))| {
+            let sighash = create_message_hash(
+                &mut sighash_cache,
+                tx.prevouts(),
+                witness,
+                *sighash_type,
+                input_index,
+            )
+            .expect("must be able to create message hash for covenant input");
+
+            MusigInput {
+                txid,
+                input_index: input_index as u32,
+                witness: witness.clone(),
+                sighash_type: *sighash_type,
+                sighash,
+                tx: unsigned_tx.clone(),
+                prevouts: prevouts.clone(),
+            }
+        })
+        .collect()
 }
 
 /// Connectors represent UTXOs in the peg-out graph.
@@ -628,6 +765,122 @@ mod tests {
         assert_eq!(assert_vector, deserialized.assert_vector);
     }
 
+    #[tokio::test]
+    async fn test_musig_inputs() {
+        let SetupOutput {
+            bitcoind,
+            n_of_n_keypair,
+            context,
+            deposit_txid,
+            public_db,
+        } = setup().await;
+
+        let btc_client = &bitcoind.client;
+        let wots_public_keys = public_db
+            .get_wots_public_keys(0, deposit_txid)
+            .await
+            .expect("must be able to get wots public keys")
+            .expect("must have wots public keys");
+
+        let (input, _, _) =
+            create_tx_graph_input(btc_client, &context, n_of_n_keypair, wots_public_keys);
+        let stake_chain_params = StakeChainParams::default();
+        let graph_params = PegOutGraphParams {
+            deposit_amount: DEPOSIT_AMOUNT,
+            ..Default::default()
+        };
+
+        let prev_claim_txids = vec![generate_txid(); stake_chain_params.slash_stake_count];
+        let (graph, _) = PegOutGraph::generate(
+            input,
+            &context,
+            deposit_txid,
+            graph_params,
+            ConnectorParams::default(),
+            stake_chain_params,
+            prev_claim_txids,
+        )
+        .expect("must be able to generate peg-out graph");
+
+        let musig_inputs = graph.musig_inputs();
+
+        let expected_len = graph.payout_optimistic.witnesses().len()
+            + 1 // challenge
+            + 1 // pre-assert
+            + graph.assert_chain.post_assert.witnesses().len()
+            + graph.payout_tx.witnesses().len()
+            + 1 // disprove
+            + 2 * graph.slash_stake_txs.len();
+        assert_eq!(
+            musig_inputs.len(),
+            expected_len,
+            "must have one musig input per presigned input"
+        );
+
+        let unique_inputs = musig_inputs
+            .iter()
+            .map(|input| (input.txid, input.input_index))
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            unique_inputs.len(),
+            musig_inputs.len(),
+            "musig inputs must not repeat"
+        );
+
+        let sighashes =
+            |inputs: &[MusigInput]| inputs.iter().map(|input| input.sighash).collect::<Vec<_>>();
+        assert_eq!(
+            sighashes(&musig_inputs),
+            sighashes(&graph.musig_inputs()),
+            "musig inputs must be returned in a deterministic order"
+        );
+
+        let payout_optimistic_txid = graph.payout_optimistic.compute_txid();
+        let mut sighash_cache = SighashCache::new(&graph.payout_optimistic.psbt().unsigned_tx);
+        for (i, witness) in graph.payout_optimistic.witnesses().iter().enumerate() {
+            let expected = create_message_hash(
+                &mut sighash_cache,
+                graph.payout_optimistic.prevouts(),
+                witness,
+                TapSighashType::Default,
+                i,
+            )
+            .expect("must be able to create a message hash");
+
+            let musig_input = &musig_inputs[i];
+            assert_eq!(musig_input.txid, payout_optimistic_txid);
+            assert_eq!(musig_input.input_index, i as u32);
+            assert_eq!(
+                musig_input.sighash, expected,
+                "sighash must match the one used to finalize the payout optimistic tx"
+            );
+        }
+
+        let disprove_txid = graph.disprove_tx.compute_txid();
+        let disprove_inputs = musig_inputs
+            .iter()
+            .filter(|input| input.txid == disprove_txid)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            disprove_inputs.len(),
+            1,
+            "only the stake input of the disprove tx must be presigned"
+        );
+        assert_eq!(disprove_inputs[0].input_index, 0);
+        assert_eq!(disprove_inputs[0].sighash_type, TapSighashType::Single);
+
+        for slash_stake_tx in &graph.slash_stake_txs {
+            let txid = slash_stake_tx.compute_txid();
+            assert!(
+                musig_inputs
+                    .iter()
+                    .filter(|input| input.txid == txid)
+                    .all(|input| input.sighash_type == TapSighashType::Single),
+                "slash stake inputs must be signed with SIGHASH_SINGLE"
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_payout_optimistic() {
         let SetupOutput {
@@ -1,5 +1,6 @@
 use bitcoin::{consensus, Amount, OutPoint, Transaction, TxOut, Txid};
 use bitcoin_bosd::Descriptor;
+use serde::{Deserialize, Serialize};
 use strata_bridge_primitives::{
     scripts::general::{create_tx, create_tx_ins, create_tx_outs, op_return_nonce},
     types::OperatorIdx,
@@ -12,7 +13,7 @@ pub struct WithdrawalFulfillment(Transaction);
 /// Metadata to be posted in the withdrawal transaction.
 ///
 /// This metadata is used to identify the operator and deposit index in the bridge withdrawal proof.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WithdrawalMetadata {
     /// The tag used to mark the withdrawal metadata transaction.
     pub tag: Vec<u8>,